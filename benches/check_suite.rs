@@ -0,0 +1,216 @@
+//! Timings of `check` on synthetic suites built from the fixtures under
+//! `tests/fixtures/check`, run with `cargo bench --bench check_suite`.
+//!
+//! - `parallel`: a suite of about 300 units checked once with
+//!   CHECK_THREADS=1 and once on the default pool, one thread per CPU. The
+//!   pool is read once per process, so each run is a child process of the
+//!   bench. Both runs must agree unit by unit.
+//! - `storage`: a unit whose pre state holds an account with many storage
+//!   slots, executing the same transfer in every test. Every test reads the
+//!   pre state through a shared database; the clone of the pre `CacheState`
+//!   every test paid before is timed next to the time of a test.
+
+use goat_prover::check::{self, CheckConfig, SpecFilter, SuiteReport};
+use revm::db::CacheState;
+use revm::primitives::{address, AccountInfo, HashMap, U256};
+use serde_json::{json, Value};
+use std::process::Command;
+use std::time::{Duration, Instant};
+
+/// Fixtures replicated into the `parallel` suite.
+const FIXTURES: [&str; 4] = ["transfer", "loop", "p256verify", "withdrawals"];
+const PARALLEL_UNITS: usize = 300;
+const STORAGE_SLOTS: [usize; 3] = [0, 10_000, 100_000];
+const STORAGE_TESTS: usize = 32;
+const RUNS: usize = 3;
+/// Set on the child processes of the `parallel` bench.
+const CHILD_ENV: &str = "CHECK_SUITE_BENCH_CHILD";
+
+fn fixture(name: &str) -> Value {
+    let path = format!(
+        "{}/tests/fixtures/check/{}.json",
+        env!("CARGO_MANIFEST_DIR"),
+        name
+    );
+    let json = std::fs::read_to_string(&path).unwrap_or_else(|e| panic!("{}: {}", path, e));
+    serde_json::from_str(&json).unwrap_or_else(|e| panic!("{}: {}", path, e))
+}
+
+/// Best wall-clock time of `RUNS` calls of `f`, with the last result.
+fn best_of<T>(mut f: impl FnMut() -> T) -> (Duration, T) {
+    let mut best = Duration::MAX;
+    let mut result = None;
+    for _ in 0..RUNS {
+        let start = Instant::now();
+        result = Some(f());
+        best = best.min(start.elapsed());
+    }
+    (best, result.expect("RUNS is not 0"))
+}
+
+fn parallel_suite() -> Vec<u8> {
+    let units: Vec<(String, Value)> = FIXTURES
+        .iter()
+        .flat_map(|name| match fixture(name) {
+            Value::Object(units) => units.into_iter().collect::<Vec<_>>(),
+            other => panic!("{}: not a suite: {}", name, other),
+        })
+        .collect();
+    let suite: serde_json::Map<String, Value> = (0..PARALLEL_UNITS)
+        .map(|i| {
+            let (name, unit) = &units[i % units.len()];
+            (format!("{}-{:03}", name, i), unit.clone())
+        })
+        .collect();
+    serde_json::to_vec(&suite).unwrap()
+}
+
+/// One line per unit, its outcome and the gas of every test, to compare the
+/// runs of the `parallel` bench.
+fn outcomes(report: &SuiteReport) -> String {
+    report
+        .units
+        .iter()
+        .map(|(name, unit)| match &unit.error {
+            Some(e) => format!("{} error {}\n", name, e),
+            None => {
+                let gas: Vec<_> = unit.per_test.iter().map(|t| t.gas_used).collect();
+                format!("{} ok {:?}\n", name, gas)
+            }
+        })
+        .collect()
+}
+
+/// Runs the `parallel` suite in this process and prints the best time in
+/// microseconds on the first line, the outcomes after it.
+fn parallel_child() {
+    let suite = parallel_suite();
+    let config = CheckConfig {
+        continue_on_error: true,
+        ..CheckConfig::default()
+    };
+    let (elapsed, report) =
+        best_of(|| check::run_suite_report(&suite, &config, None).expect("suite decodes"));
+    print!("{}\n{}", elapsed.as_micros(), outcomes(&report));
+}
+
+fn run_child(threads: &str) -> (Duration, String) {
+    let exe = std::env::current_exe().expect("bench executable");
+    let output = Command::new(exe)
+        .env(CHILD_ENV, "1")
+        .env("CHECK_THREADS", threads)
+        .output()
+        .expect("failed to run the bench child");
+    assert!(
+        output.status.success(),
+        "bench child failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let stdout = String::from_utf8(output.stdout).expect("bench child output");
+    let (micros, outcomes) = stdout.split_once('\n').expect("bench child timing");
+    let micros: u64 = micros.parse().expect("bench child timing");
+    (Duration::from_micros(micros), outcomes.to_string())
+}
+
+fn bench_parallel() {
+    let (sequential, sequential_outcomes) = run_child("1");
+    let (parallel, parallel_outcomes) = run_child("0");
+    assert_eq!(
+        sequential_outcomes, parallel_outcomes,
+        "the parallel check disagrees with the sequential one"
+    );
+    println!(
+        "parallel: {} units, CHECK_THREADS=1 {:?}, {} threads {:?}, speedup {:.2}x",
+        PARALLEL_UNITS,
+        sequential,
+        std::thread::available_parallelism().map_or(1, |n| n.get()),
+        parallel,
+        sequential.as_secs_f64() / parallel.as_secs_f64()
+    );
+}
+
+/// The transfer unit with `slots` storage slots on an account it does not
+/// touch and `STORAGE_TESTS` London tests, all expecting `root`.
+fn storage_suite(slots: usize, root: &str) -> Vec<u8> {
+    let Value::Object(mut suite) = fixture("transfer") else {
+        panic!("transfer: not a suite");
+    };
+    let mut unit = suite.remove("transfer").expect("transfer unit");
+    let storage: serde_json::Map<String, Value> = (1..=slots)
+        .map(|slot| (format!("{:#x}", slot), json!(format!("{:#x}", slot))))
+        .collect();
+    unit["pre"]["0x0000000000000000000000000000000000002000"] = json!({
+        "balance": "0x00",
+        "code": "0x",
+        "nonce": "0x01",
+        "storage": storage,
+    });
+    let mut test = unit["post"]["London"][0].clone();
+    test["hash"] = json!(root);
+    unit["post"] = json!({ "London": vec![test; STORAGE_TESTS] });
+    serde_json::to_vec(&json!({ "storage": unit })).unwrap()
+}
+
+/// Root of the post state of `storage_suite(slots, ..)`, from the mismatch
+/// of a run expecting a zero root.
+fn storage_root(slots: usize, config: &CheckConfig) -> String {
+    let zero = format!("{:#066x}", 0);
+    match check::execute_test_suite(&storage_suite(slots, &zero), config, None) {
+        Err(check::CheckError::PostStateMismatch { actual, .. }) => actual.to_string(),
+        other => panic!("expected a post state mismatch, got {:?}", other),
+    }
+}
+
+/// Time of cloning the pre `CacheState` of a unit with `slots` slots, which
+/// every test paid before the tests shared the pre state.
+fn cache_state_clone(slots: usize) -> Duration {
+    let mut cache_state = CacheState::new(false);
+    let storage: HashMap<U256, U256> = (1..=slots as u64)
+        .map(|slot| (U256::from(slot), U256::from(slot)))
+        .collect();
+    cache_state.insert_account_with_storage(
+        address!("0000000000000000000000000000000000002000"),
+        AccountInfo::default(),
+        storage,
+    );
+    best_of(|| cache_state.clone()).0
+}
+
+fn bench_storage() {
+    let config = CheckConfig {
+        specs: SpecFilter {
+            only: Some(vec!["london".to_string()]),
+            skip: Vec::new(),
+        },
+        ..CheckConfig::default()
+    };
+    for slots in STORAGE_SLOTS {
+        let suite = storage_suite(slots, &storage_root(slots, &config));
+        let (elapsed, report) = best_of(|| {
+            check::execute_test_suite(&suite, &config, None).expect("storage suite passes")
+        });
+        assert_eq!(report.units["storage"].per_test.len(), STORAGE_TESTS);
+        println!(
+            "storage: {} slots, {:?} per test, a CacheState clone took {:?}",
+            slots,
+            elapsed / STORAGE_TESTS as u32,
+            cache_state_clone(slots)
+        );
+    }
+}
+
+fn main() {
+    if std::env::var_os(CHILD_ENV).is_some() {
+        parallel_child();
+        return;
+    }
+    // `cargo bench --bench check_suite -- storage` runs one bench only.
+    let filter = std::env::args().skip(1).find(|arg| !arg.starts_with('-'));
+    let selected = |name: &str| filter.as_deref().map_or(true, |f| name.contains(f));
+    if selected("parallel") {
+        bench_parallel();
+    }
+    if selected("storage") {
+        bench_storage();
+    }
+}
@@ -0,0 +1,661 @@
+//! Webhook alerts of goat_prover and tx_transfer: typed events with one
+//! payload schema, digests of noisy event kinds, an hourly delivery cap and
+//! retried, best-effort delivery that never blocks the caller.
+
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::env;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex, MutexGuard, Weak};
+use std::time::{Duration, Instant};
+use tokio::sync::{mpsc, oneshot};
+
+/// Version of the payload schema, bumped with every change of the snapshots
+/// in tests/snapshots.
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// Upper bound of one delivery attempt, a dead webhook must not pile up
+/// deliveries.
+const DELIVERY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Attempts of a delivery, RETRY_BACKOFF apart and doubled after each one.
+const DELIVERY_ATTEMPTS: u32 = 3;
+const RETRY_BACKOFF: Duration = Duration::from_secs(1);
+
+/// Upper bound of [`Notifier::flush`] on exit.
+const FLUSH_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Span of the `max_per_hour` cap.
+const CAP_WINDOW: Duration = Duration::from_secs(3600);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    Info,
+    Warning,
+    Critical,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EventKind {
+    /// goat_prover proved a block.
+    BlockProved,
+    /// goat_prover gave up on a block after its retries.
+    BlockFailed,
+    /// A proof did not pass verification.
+    VerificationFailed,
+    /// Proving fell more than MAX_LAG_BLOCKS behind the head.
+    LagExceeded,
+    /// tx_transfer could not submit the blobs of a block to Celestia.
+    BlobFailed,
+    /// tx_transfer stopped following the source chain.
+    SourceFailed,
+    /// The local clock is off the Celestia network time.
+    ClockSkew,
+    /// The process exits gracefully.
+    ShuttingDown,
+}
+
+impl EventKind {
+    pub fn severity(self) -> Severity {
+        match self {
+            Self::BlockProved | Self::ShuttingDown => Severity::Info,
+            Self::BlockFailed | Self::VerificationFailed | Self::BlobFailed => Severity::Warning,
+            Self::LagExceeded | Self::SourceFailed | Self::ClockSkew => Severity::Critical,
+        }
+    }
+}
+
+/// Payload POSTed to the webhook, see tests/snapshots.
+#[derive(Debug, Clone, Serialize)]
+pub struct Event {
+    pub schema: u32,
+    pub event: EventKind,
+    pub severity: Severity,
+    /// Binary that raised the event, set by the [`Notifier`].
+    pub source: String,
+    /// Chain id the binary follows, set by the [`Notifier`].
+    pub chain: String,
+    /// Block of goat_prover, source chain height of tx_transfer.
+    pub block: Option<u64>,
+    pub error: Option<String>,
+    pub duration_secs: Option<u64>,
+    /// Blocks of a digest, which has no `block` of its own.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub blocks: Vec<u64>,
+    /// Events dropped by the hourly cap since the last delivery.
+    #[serde(skip_serializing_if = "is_zero")]
+    pub suppressed: u64,
+}
+
+fn is_zero(n: &u64) -> bool {
+    *n == 0
+}
+
+impl Event {
+    pub fn new(event: EventKind, block: Option<u64>) -> Self {
+        Self {
+            schema: SCHEMA_VERSION,
+            event,
+            severity: event.severity(),
+            source: String::new(),
+            chain: String::new(),
+            block,
+            error: None,
+            duration_secs: None,
+            blocks: Vec::new(),
+            suppressed: 0,
+        }
+    }
+
+    fn is_failure(&self) -> bool {
+        self.severity >= Severity::Warning
+    }
+
+    /// Delivered at once, never held back for a digest.
+    fn is_immediate(&self) -> bool {
+        self.severity == Severity::Critical || self.event == EventKind::ShuttingDown
+    }
+
+    /// One payload for `events` of the same kind, listing their blocks and
+    /// the most common error. A single event is delivered as it is.
+    fn digest(mut events: Vec<Event>) -> Self {
+        if events.len() == 1 {
+            return events.remove(0);
+        }
+        let mut errors: HashMap<&str, usize> = HashMap::new();
+        for error in events.iter().filter_map(|event| event.error.as_deref()) {
+            *errors.entry(error).or_default() += 1;
+        }
+        let error = errors
+            .into_iter()
+            .max_by(|(a, m), (b, n)| m.cmp(n).then(b.cmp(a)))
+            .map(|(error, _)| error.to_string());
+        let mut digest = Self::new(events[0].event, None);
+        digest.source = events[0].source.clone();
+        digest.chain = events[0].chain.clone();
+        digest.blocks = events.iter().filter_map(|event| event.block).collect();
+        digest.blocks.sort_unstable();
+        digest.error = error;
+        digest
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NotifyOn {
+    /// Events of at least [`Severity::Warning`].
+    #[default]
+    Failure,
+    All,
+}
+
+impl FromStr for NotifyOn {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s {
+            "failure" => Ok(Self::Failure),
+            "all" => Ok(Self::All),
+            other => anyhow::bail!("invalid notify_on {:?}, expected failure or all", other),
+        }
+    }
+}
+
+/// Webhook settings, the `[alerts]` table of tx_transfer's config.toml and
+/// the NOTIFY_* variables of goat_prover, with the same fields.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct AlertsConfig {
+    /// No alerts without it.
+    pub webhook_url: Option<String>,
+    /// Sent as a bearer token.
+    pub token: Option<String>,
+    pub notify_on: NotifyOn,
+    /// Window the events of one kind are coalesced over, 0 to deliver each
+    /// event on its own.
+    pub digest_secs: u64,
+    /// Deliveries per hour, 0 for no cap.
+    pub max_per_hour: usize,
+}
+
+impl AlertsConfig {
+    /// From NOTIFY_WEBHOOK_URL, NOTIFY_TOKEN, NOTIFY_ON, NOTIFY_DIGEST_SECS
+    /// and NOTIFY_MAX_PER_HOUR.
+    pub fn from_env() -> anyhow::Result<Self> {
+        let notify_on = env::var("NOTIFY_ON").unwrap_or("failure".to_string());
+        let digest_secs = env::var("NOTIFY_DIGEST_SECS").unwrap_or("0".to_string());
+        let max_per_hour = env::var("NOTIFY_MAX_PER_HOUR").unwrap_or("0".to_string());
+        Ok(Self {
+            webhook_url: env::var("NOTIFY_WEBHOOK_URL")
+                .ok()
+                .filter(|url| !url.is_empty()),
+            token: env::var("NOTIFY_TOKEN")
+                .ok()
+                .filter(|token| !token.is_empty()),
+            notify_on: notify_on
+                .parse()
+                .map_err(|e| anyhow::anyhow!("NOTIFY_ON: {}", e))?,
+            digest_secs: digest_secs.parse().unwrap_or(0),
+            max_per_hour: max_per_hour.parse().unwrap_or(0),
+        })
+    }
+}
+
+enum Delivery {
+    Event(Event),
+    /// Answered once the deliveries queued before it are done.
+    Flush(oneshot::Sender<()>),
+}
+
+#[derive(Debug, Default)]
+struct State {
+    /// Events held for a digest, with the arrival of the first one.
+    pending: BTreeMap<EventKind, (Instant, Vec<Event>)>,
+    /// Deliveries within the last CAP_WINDOW.
+    sent: VecDeque<Instant>,
+    suppressed: u64,
+}
+
+struct Shared {
+    max_per_hour: usize,
+    state: Mutex<State>,
+    queue: mpsc::UnboundedSender<Delivery>,
+}
+
+impl Shared {
+    fn state(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// The digests of the events held for at least `window`, of all of them
+    /// without a window.
+    fn take_due(&self, window: Option<Duration>) -> Vec<Event> {
+        let mut state = self.state();
+        let due: Vec<EventKind> = state
+            .pending
+            .iter()
+            .filter(|(_, (since, _))| window.map_or(true, |window| since.elapsed() >= window))
+            .map(|(kind, _)| *kind)
+            .collect();
+        due.into_iter()
+            .filter_map(|kind| state.pending.remove(&kind))
+            .map(|(_, events)| Event::digest(events))
+            .collect()
+    }
+
+    /// Queue `event` for delivery unless the hourly cap is reached.
+    fn send(&self, mut event: Event) {
+        {
+            let mut state = self.state();
+            while state
+                .sent
+                .front()
+                .is_some_and(|sent| sent.elapsed() >= CAP_WINDOW)
+            {
+                state.sent.pop_front();
+            }
+            if self.max_per_hour > 0 && state.sent.len() >= self.max_per_hour {
+                state.suppressed += event.blocks.len().max(1) as u64;
+                log::debug!("Webhook cap reached, suppressed {:?}", event.event);
+                return;
+            }
+            state.sent.push_back(Instant::now());
+            event.suppressed = std::mem::take(&mut state.suppressed);
+        }
+        let _ = self.queue.send(Delivery::Event(event));
+    }
+}
+
+struct Webhook {
+    client: reqwest::Client,
+    url: String,
+    token: Option<String>,
+    retry_backoff: Duration,
+}
+
+impl Webhook {
+    async fn post(&self, event: &Event) -> Result<(), reqwest::Error> {
+        let mut request = self.client.post(&self.url).json(event);
+        if let Some(token) = &self.token {
+            request = request.bearer_auth(token);
+        }
+        request.send().await?.error_for_status()?;
+        Ok(())
+    }
+
+    /// POST `event`, again after a connection failure, a 429 or a 5xx.
+    /// Delivery errors are logged and otherwise ignored.
+    async fn deliver(&self, event: &Event) {
+        let mut backoff = self.retry_backoff;
+        for attempt in 1..=DELIVERY_ATTEMPTS {
+            let Err(e) = self.post(event).await else {
+                log::debug!("Webhook notified: {:?}", event.event);
+                return;
+            };
+            let transient = e.status().map_or(true, |status| {
+                status.is_server_error() || status == reqwest::StatusCode::TOO_MANY_REQUESTS
+            });
+            if !transient || attempt == DELIVERY_ATTEMPTS {
+                log::warn!("Failed to notify webhook of {:?}: {}", event.event, e);
+                return;
+            }
+            tokio::time::sleep(backoff).await;
+            backoff *= 2;
+        }
+    }
+}
+
+/// Deliver the queued events in order until the notifier is dropped.
+async fn deliver(webhook: Webhook, mut deliveries: mpsc::UnboundedReceiver<Delivery>) {
+    while let Some(delivery) = deliveries.recv().await {
+        match delivery {
+            Delivery::Event(event) => webhook.deliver(&event).await,
+            Delivery::Flush(done) => {
+                let _ = done.send(());
+            }
+        }
+    }
+}
+
+/// Queue the digests whose window passed, until the notifier is dropped.
+async fn send_digests(shared: Weak<Shared>, window: Duration) {
+    let tick = (window / 4).max(Duration::from_secs(1));
+    loop {
+        tokio::time::sleep(tick).await;
+        let Some(shared) = shared.upgrade() else {
+            return;
+        };
+        for digest in shared.take_due(Some(window)) {
+            shared.send(digest);
+        }
+    }
+}
+
+/// Best-effort webhook alerts of the binary `source` following `chain`.
+///
+/// With `digest_secs`, events of one kind are coalesced over that window into
+/// one payload listing their blocks; critical events are delivered at once.
+/// `max_per_hour` caps the deliveries, the next one after the cap reports how
+/// many events were suppressed. Pending digests are delivered by
+/// [`Notifier::flush`] on exit.
+pub struct Notifier {
+    source: String,
+    chain: String,
+    all: bool,
+    digest_window: Option<Duration>,
+    shared: Arc<Shared>,
+}
+
+impl Notifier {
+    /// `None` without a webhook url. Spawns the delivery task, and with
+    /// `digest_secs` the digest task, on the current runtime.
+    pub fn new(config: &AlertsConfig, source: &str, chain: &str) -> anyhow::Result<Option<Self>> {
+        let Some(url) = &config.webhook_url else {
+            return Ok(None);
+        };
+        Ok(Some(Self::with_backoff(
+            config,
+            url,
+            source,
+            chain,
+            RETRY_BACKOFF,
+        )?))
+    }
+
+    fn with_backoff(
+        config: &AlertsConfig,
+        url: &str,
+        source: &str,
+        chain: &str,
+        retry_backoff: Duration,
+    ) -> anyhow::Result<Self> {
+        let webhook = Webhook {
+            client: reqwest::Client::builder()
+                .timeout(DELIVERY_TIMEOUT)
+                .build()?,
+            url: url.to_string(),
+            token: config.token.clone(),
+            retry_backoff,
+        };
+        let (queue, deliveries) = mpsc::unbounded_channel();
+        tokio::spawn(deliver(webhook, deliveries));
+        let shared = Arc::new(Shared {
+            max_per_hour: config.max_per_hour,
+            state: Mutex::default(),
+            queue,
+        });
+        let digest_window =
+            (config.digest_secs > 0).then(|| Duration::from_secs(config.digest_secs));
+        if let Some(window) = digest_window {
+            tokio::spawn(send_digests(Arc::downgrade(&shared), window));
+        }
+        Ok(Self {
+            source: source.to_string(),
+            chain: chain.to_string(),
+            all: config.notify_on == NotifyOn::All,
+            digest_window,
+            shared,
+        })
+    }
+
+    /// Queue `event` unless filtered by `notify_on`, or hold it for a digest.
+    /// Never waits for the delivery.
+    pub fn notify(&self, mut event: Event) {
+        if !self.all && !event.is_failure() {
+            return;
+        }
+        event.source = self.source.clone();
+        event.chain = self.chain.clone();
+        if event.event == EventKind::ShuttingDown {
+            // The pending digests go out before the process does.
+            for digest in self.shared.take_due(None) {
+                self.shared.send(digest);
+            }
+        }
+        if self.digest_window.is_some() && !event.is_immediate() {
+            let mut state = self.shared.state();
+            let (_, events) = state
+                .pending
+                .entry(event.event)
+                .or_insert_with(|| (Instant::now(), Vec::new()));
+            events.push(event);
+            return;
+        }
+        self.shared.send(event);
+    }
+
+    /// Deliver the pending digests and wait for the queued deliveries, on
+    /// exit.
+    pub async fn flush(&self) {
+        for digest in self.shared.take_due(None) {
+            self.shared.send(digest);
+        }
+        let (done, flushed) = oneshot::channel();
+        if self.shared.queue.send(Delivery::Flush(done)).is_ok()
+            && tokio::time::timeout(FLUSH_TIMEOUT, flushed).await.is_err()
+        {
+            log::warn!("Webhook deliveries still pending after {:?}", FLUSH_TIMEOUT);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::{HeaderMap, StatusCode};
+    use axum::{body::Bytes, Router};
+
+    /// Local webhook answering with `status`, recording the requests.
+    struct MockWebhook {
+        url: String,
+        requests: Arc<Mutex<Vec<(HeaderMap, Bytes)>>>,
+    }
+
+    impl MockWebhook {
+        fn start(status: StatusCode) -> Self {
+            let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+            let url = format!("http://{}", listener.local_addr().unwrap());
+            let requests = Arc::new(Mutex::new(Vec::new()));
+            let recorded = requests.clone();
+            let app = Router::new().fallback(move |headers: HeaderMap, body: Bytes| {
+                let recorded = recorded.clone();
+                async move {
+                    recorded.lock().unwrap().push((headers, body));
+                    status
+                }
+            });
+            let server = axum::Server::from_tcp(listener)
+                .unwrap()
+                .serve(app.into_make_service());
+            tokio::spawn(server);
+            Self { url, requests }
+        }
+
+        fn bodies(&self) -> Vec<serde_json::Value> {
+            let requests = self.requests.lock().unwrap();
+            requests
+                .iter()
+                .map(|(_, body)| serde_json::from_slice(body).unwrap())
+                .collect()
+        }
+
+        fn notifier(&self, config: AlertsConfig) -> Notifier {
+            Notifier::with_backoff(
+                &config,
+                &self.url,
+                "goat_prover",
+                "48816",
+                Duration::from_millis(1),
+            )
+            .unwrap()
+        }
+    }
+
+    fn all() -> AlertsConfig {
+        AlertsConfig {
+            notify_on: NotifyOn::All,
+            ..Default::default()
+        }
+    }
+
+    fn failed(block: u64, error: &str) -> Event {
+        let mut event = Event::new(EventKind::BlockFailed, Some(block));
+        event.error = Some(error.to_string());
+        event
+    }
+
+    #[test]
+    fn config_defaults() {
+        let config: AlertsConfig = serde_json::from_value(serde_json::json!({
+            "webhook_url": "https://hooks.example.com/prover",
+            "digest_secs": 300,
+        }))
+        .unwrap();
+        assert_eq!(config.notify_on, NotifyOn::Failure);
+        assert_eq!(config.digest_secs, 300);
+        assert_eq!(config.max_per_hour, 0);
+        assert!(config.token.is_none());
+        assert!("some".parse::<NotifyOn>().is_err());
+    }
+
+    #[test]
+    fn digest_lists_blocks_and_the_common_error() {
+        let events = vec![
+            failed(12, "rpc timed out"),
+            failed(10, "prover timed out"),
+            failed(11, "rpc timed out"),
+        ];
+        let digest = Event::digest(events);
+        assert_eq!(digest.block, None);
+        assert_eq!(digest.blocks, vec![10, 11, 12]);
+        assert_eq!(digest.error.as_deref(), Some("rpc timed out"));
+        // A single event is not wrapped.
+        let single = Event::digest(vec![failed(7, "e")]);
+        assert_eq!(single.block, Some(7));
+        assert!(single.blocks.is_empty());
+    }
+
+    #[tokio::test]
+    async fn failures_only_by_default() {
+        let webhook = MockWebhook::start(StatusCode::OK);
+        let notifier = webhook.notifier(AlertsConfig::default());
+        notifier.notify(Event::new(EventKind::BlockProved, Some(1)));
+        notifier.notify(Event::new(EventKind::VerificationFailed, Some(2)));
+        notifier.flush().await;
+        let bodies = webhook.bodies();
+        assert_eq!(bodies.len(), 1);
+        assert_eq!(bodies[0]["event"], "verification_failed");
+        assert_eq!(bodies[0]["block"], 2);
+        assert_eq!(bodies[0]["source"], "goat_prover");
+        assert_eq!(bodies[0]["chain"], "48816");
+    }
+
+    #[tokio::test]
+    async fn token_is_sent_as_bearer() {
+        let webhook = MockWebhook::start(StatusCode::OK);
+        let notifier = webhook.notifier(AlertsConfig {
+            token: Some("secret".to_string()),
+            ..all()
+        });
+        notifier.notify(Event::new(EventKind::ShuttingDown, None));
+        notifier.flush().await;
+        let requests = webhook.requests.lock().unwrap();
+        assert_eq!(requests[0].0["authorization"], "Bearer secret");
+    }
+
+    #[tokio::test]
+    async fn digests_until_flushed() {
+        let webhook = MockWebhook::start(StatusCode::OK);
+        let notifier = webhook.notifier(AlertsConfig {
+            digest_secs: 300,
+            ..all()
+        });
+        notifier.notify(failed(1, "rpc timed out"));
+        notifier.notify(failed(2, "rpc timed out"));
+        notifier.notify(Event::new(EventKind::BlockProved, Some(3)));
+        assert!(notifier
+            .shared
+            .take_due(Some(Duration::from_secs(300)))
+            .is_empty());
+        // Critical events are not held back.
+        notifier.notify(Event::new(EventKind::LagExceeded, Some(4)));
+
+        notifier.flush().await;
+        let bodies = webhook.bodies();
+        assert_eq!(bodies.len(), 3);
+        assert_eq!(bodies[0]["event"], "lag_exceeded");
+        assert_eq!(bodies[1]["event"], "block_proved");
+        assert_eq!(bodies[1]["block"], 3);
+        assert_eq!(bodies[2]["event"], "block_failed");
+        assert_eq!(bodies[2]["blocks"], serde_json::json!([1, 2]));
+        assert_eq!(bodies[2]["source"], "goat_prover");
+        notifier.flush().await;
+        assert_eq!(webhook.bodies().len(), 3);
+
+        // Shutting down delivers the digests first.
+        notifier.notify(failed(5, "e"));
+        notifier.notify(failed(6, "e"));
+        notifier.notify(Event::new(EventKind::ShuttingDown, None));
+        notifier.flush().await;
+        let events: Vec<_> = webhook.bodies()[3..]
+            .iter()
+            .map(|body| body["event"].clone())
+            .collect();
+        assert_eq!(events, ["block_failed", "shutting_down"]);
+    }
+
+    #[tokio::test]
+    async fn cap_reports_suppressed_events() {
+        let webhook = MockWebhook::start(StatusCode::OK);
+        let notifier = webhook.notifier(AlertsConfig {
+            max_per_hour: 2,
+            ..all()
+        });
+        for block in 1..=5 {
+            notifier.notify(failed(block, "e"));
+        }
+        notifier.flush().await;
+        assert_eq!(webhook.bodies().len(), 2);
+        // An hour later.
+        notifier.shared.state().sent.clear();
+        notifier.notify(failed(6, "e"));
+        notifier.flush().await;
+        let bodies = webhook.bodies();
+        assert_eq!(bodies.len(), 3);
+        assert_eq!(bodies[2]["block"], 6);
+        assert_eq!(bodies[2]["suppressed"], 3);
+        assert!(bodies[0].get("suppressed").is_none());
+    }
+
+    #[tokio::test]
+    async fn server_errors_are_retried() {
+        let webhook = MockWebhook::start(StatusCode::INTERNAL_SERVER_ERROR);
+        let notifier = webhook.notifier(all());
+        notifier.notify(failed(1, "e"));
+        notifier.flush().await;
+        assert_eq!(webhook.bodies().len(), DELIVERY_ATTEMPTS as usize);
+
+        let webhook = MockWebhook::start(StatusCode::BAD_REQUEST);
+        let notifier = webhook.notifier(all());
+        notifier.notify(failed(1, "e"));
+        notifier.flush().await;
+        assert_eq!(webhook.bodies().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn dead_webhook_is_ignored() {
+        // Nothing listens on the discard port.
+        let notifier = Notifier::with_backoff(
+            &all(),
+            "http://127.0.0.1:9",
+            "tx_transfer",
+            "48816",
+            Duration::from_millis(1),
+        )
+        .unwrap();
+        notifier.notify(failed(1, "e"));
+        notifier.flush().await;
+    }
+}
@@ -0,0 +1,56 @@
+//! Snapshots of the webhook payloads. The alert routing pipeline parses
+//! them, a change here must be deliberate and bump `SCHEMA_VERSION`.
+
+use alerts::{Event, EventKind, SCHEMA_VERSION};
+
+fn snapshot(name: &str) -> serde_json::Value {
+    let path = format!("{}/tests/snapshots/{}", env!("CARGO_MANIFEST_DIR"), name);
+    let buf = std::fs::read_to_string(&path).unwrap_or_else(|e| panic!("{}: {}", path, e));
+    serde_json::from_str(&buf).unwrap()
+}
+
+fn event(kind: EventKind, source: &str, block: Option<u64>) -> Event {
+    let mut event = Event::new(kind, block);
+    event.source = source.to_string();
+    event.chain = "48816".to_string();
+    event
+}
+
+#[test]
+fn block_failed() {
+    let mut event = event(EventKind::BlockFailed, "goat_prover", Some(1200));
+    event.error = Some("prover timed out after 7200 secs".to_string());
+    event.duration_secs = Some(7200);
+    assert_eq!(
+        serde_json::to_value(&event).unwrap(),
+        snapshot("block_failed.json")
+    );
+}
+
+#[test]
+fn digest() {
+    let mut event = event(EventKind::BlobFailed, "tx_transfer", None);
+    event.error = Some("celestia node unreachable".to_string());
+    event.blocks = vec![195899, 195900, 195903];
+    event.suppressed = 12;
+    assert_eq!(
+        serde_json::to_value(&event).unwrap(),
+        snapshot("digest.json")
+    );
+}
+
+#[test]
+fn shutting_down() {
+    let event = event(EventKind::ShuttingDown, "goat_prover", None);
+    assert_eq!(
+        serde_json::to_value(&event).unwrap(),
+        snapshot("shutting_down.json")
+    );
+}
+
+#[test]
+fn snapshots_carry_the_schema_version() {
+    for name in ["block_failed.json", "digest.json", "shutting_down.json"] {
+        assert_eq!(snapshot(name)["schema"], SCHEMA_VERSION, "{}", name);
+    }
+}
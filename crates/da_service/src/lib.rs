@@ -1,12 +1,20 @@
+use base64::Engine;
 use celestia_rpc::prelude::*;
-use celestia_types::blob::{Blob as JsonBlob, Commitment, SubmitOptions};
+use celestia_types::blob::{Blob as JsonBlob, SubmitOptions};
 use celestia_types::consts::appconsts::{
     CONTINUATION_SPARSE_SHARE_CONTENT_SIZE, FIRST_SPARSE_SHARE_CONTENT_SIZE, SHARE_SIZE,
 };
 use celestia_types::nmt::Namespace;
-use ethers::core::k256::sha2::digest::block_buffer::Error;
 use jsonrpsee::http_client::{HeaderMap, HttpClient};
-use log::{error, info};
+use log::info;
+
+/// Where a blob landed on Celestia.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlobSubmission {
+    pub height: u64,
+    /// Base64 encoded blob commitment, as shown by celestia-node.
+    pub commitment: String,
+}
 
 #[derive(Debug, Clone)]
 pub struct CelestiaService {
@@ -79,7 +87,7 @@ impl CelestiaService {
         Self::with_client(client, config.namespace)
     }
 
-    pub async fn send_transaction(&self, blob: &[u8]) -> Result<(), Box<dyn std::error::Error>> {
+    pub async fn send_transaction(&self, blob: &[u8]) -> anyhow::Result<BlobSubmission> {
         info!("Sending {} bytes of raw data to Celestia.", blob.len());
 
         let gas_limit = get_gas_limit_for_bytes(blob.len()) as u64;
@@ -87,6 +95,7 @@ impl CelestiaService {
 
         let blob = JsonBlob::new(self.rollup_namespace, blob.to_vec())?;
         info!("Submiting: {:?}", blob.commitment);
+        let commitment = base64::engine::general_purpose::STANDARD.encode(blob.commitment.0);
 
         let height = self
             .client
@@ -102,12 +111,23 @@ impl CelestiaService {
             "Blob has been submitted to Celestia. block-height={}",
             height,
         );
-        Ok(())
+        Ok(BlobSubmission { height, commitment })
+    }
+
+    /// Height and unix time of the newest header of the Celestia network
+    /// the node knows of.
+    pub async fn network_head(&self) -> anyhow::Result<(u64, i64)> {
+        let head = self.client.header_network_head().await?;
+        Ok((
+            head.header.height.value(),
+            head.header.time.unix_timestamp(),
+        ))
     }
 }
 
+/// Gas limit of submitting a blob of `n` bytes.
 // https://docs.celestia.org/learn/submit-data/#fees-and-gas-limits
-fn get_gas_limit_for_bytes(n: usize) -> usize {
+pub fn get_gas_limit_for_bytes(n: usize) -> usize {
     let fixed_cost = 75000;
 
     let continuation_shares_needed =
@@ -0,0 +1,162 @@
+use crate::atomic_file;
+use crate::error::ProverError;
+use crate::prover::SharedProver;
+use crate::schema::{self, SCHEMA_VERSION};
+use crate::verify::ProofVerifier;
+use crate::{has_valid_proof, naming, suite};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::ops::RangeInclusive;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use zkm_sdk::prover::ProverInput;
+
+/// Result of aggregating the proofs of `first..=last`, written to
+/// `{first}_{last}_agg_summary.json`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AggregateSummary {
+    #[serde(default = "schema::legacy_schema_version")]
+    pub schema_version: u32,
+    pub first: u64,
+    pub last: u64,
+    /// Member proofs in the order of the public input.
+    pub proofs: Vec<String>,
+    pub prove_secs: u64,
+    pub proof_bytes: u64,
+}
+
+pub fn proof_path(outdir: &str, first: u64, last: u64) -> PathBuf {
+    Path::new(outdir).join(format!("{}_{}_agg_proof.json", first, last))
+}
+
+fn summary_path(outdir: &str, first: u64, last: u64) -> PathBuf {
+    Path::new(outdir).join(format!("{}_{}_agg_summary.json", first, last))
+}
+
+/// Prove the per-block proofs of `blocks` in OUTPUT_DIR with the
+/// aggregation guest `agg_elf_path`. The public input is the list of the
+/// per-block public input hashes, the sha256 of each block's suite, and the
+/// member proofs are the private input.
+pub async fn aggregate(
+    prover: &SharedProver,
+    verifier: Option<&ProofVerifier>,
+    agg_elf_path: &str,
+    outdir: &str,
+    blocks: RangeInclusive<u64>,
+    seg_size: u32,
+    timeout: Duration,
+) -> anyhow::Result<PathBuf> {
+    let (first, last) = (*blocks.start(), *blocks.end());
+    if first > last {
+        return Err(
+            ProverError::Config(format!("aggregate: empty range {}..={}", first, last)).into(),
+        );
+    }
+    let mut proofs = Vec::new();
+    let mut hashes = Vec::new();
+    let mut offending = Vec::new();
+    let mut proof_paths = Vec::new();
+    for block_no in first..=last {
+        let proof_path = naming::find_proof(outdir, block_no)
+            .unwrap_or_else(|| naming::proof_path(outdir, block_no, block_no, None));
+        let member = match member_input(verifier, outdir, block_no, &proof_path).await {
+            Ok(member) => member,
+            Err(e) => {
+                log::error!(
+                    "Proof of block_no {} can not be aggregated: {}",
+                    block_no,
+                    e
+                );
+                offending.push(block_no);
+                continue;
+            }
+        };
+        hashes.push(member.0);
+        proofs.push(member.1);
+        proof_paths.push(proof_path.to_string_lossy().into_owned());
+    }
+    if !offending.is_empty() {
+        return Err(ProverError::Prove(format!(
+            "aggregate: missing or invalid proofs of blocks {:?}",
+            offending
+        ))
+        .into());
+    }
+
+    let input = ProverInput {
+        elf: std::fs::read(agg_elf_path).map_err(|e| ProverError::io(agg_elf_path, e))?,
+        public_inputstream: bincode::serialize(&hashes)?,
+        private_inputstream: bincode::serialize(&proofs)?,
+        seg_size,
+        execute_only: false,
+    };
+    log::info!(
+        "Aggregating {} proofs of block_no: {}..={}",
+        proofs.len(),
+        first,
+        last
+    );
+    let start = Instant::now();
+    let client = prover.client().await;
+    let result = match tokio::time::timeout(timeout, client.prover.prove(&input, None)).await {
+        Ok(Ok(Some(result))) if !result.proof_with_public_inputs.is_empty() => result,
+        Ok(Ok(_)) => {
+            return Err(
+                ProverError::Prove("aggregate: the prover returned no proof".to_string()).into(),
+            )
+        }
+        Ok(Err(e)) => return Err(ProverError::Prove(format!("aggregate: {}", e)).into()),
+        Err(_) => {
+            return Err(ProverError::Prove(format!(
+                "aggregate: prover timed out after {} secs",
+                timeout.as_secs()
+            ))
+            .into())
+        }
+    };
+    let prove_secs = start.elapsed().as_secs();
+
+    let agg_proof_path = proof_path(outdir, first, last);
+    atomic_file::write(&agg_proof_path, &result.proof_with_public_inputs)
+        .map_err(|e| ProverError::io(&agg_proof_path, e))?;
+    let summary = AggregateSummary {
+        schema_version: SCHEMA_VERSION,
+        first,
+        last,
+        proofs: proof_paths,
+        prove_secs,
+        proof_bytes: result.proof_with_public_inputs.len() as u64,
+    };
+    let summary_path = summary_path(outdir, first, last);
+    atomic_file::write(&summary_path, serde_json::to_vec_pretty(&summary)?)
+        .map_err(|e| ProverError::io(&summary_path, e))?;
+    log::info!(
+        "Aggregated block_no: {}..={} in {} secs: {}",
+        first,
+        last,
+        prove_secs,
+        agg_proof_path.display()
+    );
+    Ok(agg_proof_path)
+}
+
+/// Public input hash and proof of a member block.
+async fn member_input(
+    verifier: Option<&ProofVerifier>,
+    outdir: &str,
+    block_no: u64,
+    proof_path: &Path,
+) -> anyhow::Result<([u8; 32], Vec<u8>)> {
+    if !has_valid_proof(proof_path) {
+        anyhow::bail!("no valid proof at {}", proof_path.display());
+    }
+    if let Some(verifier) = verifier {
+        verifier.verify(proof_path).await?;
+    }
+    let suite_path = suite::file_paths(outdir, block_no)
+        .into_iter()
+        .find(|path| path.exists())
+        .ok_or_else(|| anyhow::anyhow!("no suite of block_no {} in {}", block_no, outdir))?;
+    let hash: [u8; 32] = Sha256::digest(suite::read(&suite_path)?).into();
+    Ok((hash, std::fs::read(proof_path)?))
+}
@@ -0,0 +1,202 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    routing::{get, post},
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
+
+/// Finished jobs kept for `/jobs`.
+const MAX_FINISHED_JOBS: usize = 1000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobStatus {
+    Queued,
+    Checking,
+    Proving,
+    Done,
+    Failed,
+}
+
+impl JobStatus {
+    fn is_finished(self) -> bool {
+        matches!(self, Self::Done | Self::Failed)
+    }
+}
+
+/// A proof requested through `POST /prove`.
+#[derive(Debug, Clone, Serialize)]
+pub struct Job {
+    pub id: u64,
+    pub block_no: u64,
+    pub status: JobStatus,
+    pub proof_path: Option<PathBuf>,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Default)]
+struct Jobs {
+    next_id: u64,
+    jobs: BTreeMap<u64, Job>,
+    /// Latest job of every block, requests for a block with a queued,
+    /// running or done job get that job.
+    by_block: HashMap<u64, u64>,
+}
+
+/// Jobs of the SERVE_ADDR API, worked off in order by the prover loop.
+#[derive(Debug, Clone)]
+pub struct JobQueue {
+    jobs: Arc<Mutex<Jobs>>,
+    tx: mpsc::Sender<u64>,
+}
+
+impl JobQueue {
+    /// A queue holding up to `depth` waiting jobs, and the receiver of the
+    /// job ids for the prover loop.
+    pub fn new(depth: usize) -> (Self, mpsc::Receiver<u64>) {
+        let (tx, rx) = mpsc::channel(depth.max(1));
+        let queue = Self {
+            jobs: Arc::new(Mutex::new(Jobs::default())),
+            tx,
+        };
+        (queue, rx)
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Jobs> {
+        self.jobs.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Enqueue a proof of `block_no`. Returns `None` if the queue is full.
+    pub fn submit(&self, block_no: u64) -> Option<Job> {
+        let mut jobs = self.lock();
+        if let Some(job) = jobs
+            .by_block
+            .get(&block_no)
+            .and_then(|id| jobs.jobs.get(id))
+            .filter(|job| job.status != JobStatus::Failed)
+        {
+            return Some(job.clone());
+        }
+        let id = jobs.next_id;
+        if self.tx.try_send(id).is_err() {
+            return None;
+        }
+        jobs.next_id += 1;
+        let job = Job {
+            id,
+            block_no,
+            status: JobStatus::Queued,
+            proof_path: None,
+            error: None,
+        };
+        jobs.jobs.insert(id, job.clone());
+        jobs.by_block.insert(block_no, id);
+        Some(job)
+    }
+
+    pub fn get(&self, id: u64) -> Option<Job> {
+        self.lock().jobs.get(&id).cloned()
+    }
+
+    pub fn set_status(&self, id: u64, status: JobStatus) {
+        if let Some(job) = self.lock().jobs.get_mut(&id) {
+            job.status = status;
+        }
+    }
+
+    /// Record the result of job `id`, the proof path or the error.
+    pub fn finish(&self, id: u64, result: Result<Option<PathBuf>, String>) {
+        let mut jobs = self.lock();
+        if let Some(job) = jobs.jobs.get_mut(&id) {
+            match result {
+                Ok(proof_path) => {
+                    job.status = JobStatus::Done;
+                    job.proof_path = proof_path;
+                }
+                Err(error) => {
+                    job.status = JobStatus::Failed;
+                    job.error = Some(error);
+                }
+            }
+        }
+        while jobs.jobs.len() > MAX_FINISHED_JOBS {
+            let Some(oldest) = jobs
+                .jobs
+                .values()
+                .find(|job| job.status.is_finished())
+                .map(|job| (job.id, job.block_no))
+            else {
+                break;
+            };
+            jobs.jobs.remove(&oldest.0);
+            if jobs.by_block.get(&oldest.1) == Some(&oldest.0) {
+                jobs.by_block.remove(&oldest.1);
+            }
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct ProveRequest {
+    block_no: u64,
+}
+
+async fn prove(
+    State(jobs): State<JobQueue>,
+    Json(request): Json<ProveRequest>,
+) -> Result<(StatusCode, Json<Job>), StatusCode> {
+    match jobs.submit(request.block_no) {
+        Some(job) => Ok((StatusCode::ACCEPTED, Json(job))),
+        None => Err(StatusCode::SERVICE_UNAVAILABLE),
+    }
+}
+
+async fn job(State(jobs): State<JobQueue>, Path(id): Path<u64>) -> Result<Json<Job>, StatusCode> {
+    jobs.get(id).map(Json).ok_or(StatusCode::NOT_FOUND)
+}
+
+/// The proof file of a done job.
+async fn job_proof(
+    State(jobs): State<JobQueue>,
+    Path(id): Path<u64>,
+) -> Result<Vec<u8>, StatusCode> {
+    let proof_path = jobs
+        .get(id)
+        .and_then(|job| job.proof_path)
+        .ok_or(StatusCode::NOT_FOUND)?;
+    tokio::fs::read(proof_path)
+        .await
+        .map_err(|_| StatusCode::NOT_FOUND)
+}
+
+/// Recent jobs, newest first.
+async fn list_jobs(State(jobs): State<JobQueue>) -> Json<Vec<Job>> {
+    Json(jobs.lock().jobs.values().rev().cloned().collect())
+}
+
+/// Serve the job API on `addr` until `shutdown` is cancelled.
+pub async fn serve(
+    addr: SocketAddr,
+    jobs: JobQueue,
+    shutdown: CancellationToken,
+) -> anyhow::Result<()> {
+    let app = Router::new()
+        .route("/prove", post(prove))
+        .route("/jobs", get(list_jobs))
+        .route("/jobs/:id", get(job))
+        .route("/jobs/:id/proof", get(job_proof))
+        .with_state(jobs);
+    log::info!("Job API listening on {}", addr);
+    axum::Server::bind(&addr)
+        .serve(app.into_make_service())
+        .with_graceful_shutdown(shutdown.cancelled_owned())
+        .await?;
+    Ok(())
+}
@@ -0,0 +1,113 @@
+use std::ffi::OsString;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+const TMP_SUFFIX: &str = ".tmp";
+
+/// `path` with `.tmp` appended to the file name.
+fn tmp_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().map(OsString::from).unwrap_or_default();
+    name.push(TMP_SUFFIX);
+    path.with_file_name(name)
+}
+
+/// Write `buf` to `<path>.tmp` in the same directory, fsync it and rename it
+/// to `path`. A crash or a full disk mid-write leaves at most the temp file
+/// behind, never a truncated artifact under the final name.
+pub fn write(path: impl AsRef<Path>, buf: impl AsRef<[u8]>) -> std::io::Result<()> {
+    let path = path.as_ref();
+    let _span = tracing::info_span!(
+        "write_artifact",
+        path = %path.display(),
+        bytes = buf.as_ref().len()
+    )
+    .entered();
+    let tmp_path = tmp_path(path);
+    let written = std::fs::File::create(&tmp_path).and_then(|mut file| {
+        file.write_all(buf.as_ref())?;
+        file.sync_all()
+    });
+    if let Err(e) = written.and_then(|_| std::fs::rename(&tmp_path, path)) {
+        let _ = std::fs::remove_file(&tmp_path);
+        return Err(e);
+    }
+    Ok(())
+}
+
+/// Delete the temp files of writes interrupted by a previous run in `dir`.
+pub fn remove_stale(dir: impl AsRef<Path>) -> std::io::Result<usize> {
+    let mut removed = 0;
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        let is_tmp = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .is_some_and(|name| name.ends_with(TMP_SUFFIX));
+        if is_tmp && path.is_file() {
+            log::warn!("Removing leftover partial write {}", path.display());
+            std::fs::remove_file(&path)?;
+            removed += 1;
+        }
+    }
+    Ok(removed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("atomic-file-{}-{}", std::process::id(), name));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn write_replaces_the_file() {
+        let dir = temp_dir("write");
+        let path = dir.join("1200_summary.json");
+        write(&path, "old").unwrap();
+        write(&path, "new").unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "new");
+        assert!(!tmp_path(&path).exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn failed_write_keeps_the_previous_artifact() {
+        let dir = temp_dir("failed");
+        let path = dir.join("1200.json");
+        write(&path, "complete").unwrap();
+        // The temp file can not be created.
+        std::fs::create_dir(tmp_path(&path)).unwrap();
+        assert!(write(&path, "partial").is_err());
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "complete");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn failed_rename_leaves_nothing_behind() {
+        let dir = temp_dir("rename");
+        let path = dir.join("1200.json");
+        // A non-empty directory under the final name can not be replaced.
+        std::fs::create_dir_all(path.join("occupied")).unwrap();
+        assert!(write(&path, "partial").is_err());
+        assert!(path.is_dir());
+        assert!(!tmp_path(&path).exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn remove_stale_deletes_only_temp_files() {
+        let dir = temp_dir("stale");
+        std::fs::write(dir.join("1200.json.tmp"), "partial").unwrap();
+        std::fs::write(dir.join("checkpoint.json.tmp"), "partial").unwrap();
+        std::fs::write(dir.join("1200.json"), "complete").unwrap();
+        std::fs::create_dir(dir.join("archive.tmp")).unwrap();
+        assert_eq!(remove_stale(&dir).unwrap(), 2);
+        assert!(dir.join("1200.json").exists());
+        assert!(dir.join("archive.tmp").is_dir());
+        assert!(!dir.join("1200.json.tmp").exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
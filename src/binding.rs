@@ -0,0 +1,137 @@
+use crate::error::ProverError;
+use revm::primitives::U256;
+use sha2::{Digest, Sha256};
+use std::env;
+use std::str::FromStr;
+
+/// Encoding of the public inputs in the proof JSON returned by the prover.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PublicInputsFormat {
+    /// plonky2 style, an array of numbers holding one byte of the committed
+    /// hash each.
+    Plonky2,
+    /// groth16 style, an array of field elements as decimal or 0x-hex
+    /// strings, one of them the committed hash with the top 3 bits cleared to
+    /// fit the bn254 scalar field.
+    Groth16,
+}
+
+/// Checks that a proof commits to the sha256 of the public input stream it
+/// was generated from, configured by PUBLIC_INPUTS_FORMAT (off, plonky2 or
+/// groth16), PUBLIC_INPUTS_POINTER (JSON pointer of the public inputs array)
+/// and PUBLIC_INPUTS_OFFSET (index of the committed hash in the array).
+#[derive(Debug, Clone)]
+pub struct PublicInputsBinding {
+    format: PublicInputsFormat,
+    pointer: String,
+    offset: usize,
+}
+
+impl PublicInputsBinding {
+    /// `None` when PUBLIC_INPUTS_FORMAT is off.
+    pub fn from_env() -> anyhow::Result<Option<Self>> {
+        let format = env::var("PUBLIC_INPUTS_FORMAT").unwrap_or("off".to_string());
+        let (format, default_pointer) = match format.as_str() {
+            "off" => return Ok(None),
+            "plonky2" => (PublicInputsFormat::Plonky2, "/public_inputs"),
+            "groth16" => (PublicInputsFormat::Groth16, "/PublicWitness"),
+            _ => {
+                return Err(ProverError::Config(format!(
+                    "invalid PUBLIC_INPUTS_FORMAT {:?}, expected off, plonky2 or groth16",
+                    format
+                ))
+                .into())
+            }
+        };
+        let pointer = env::var("PUBLIC_INPUTS_POINTER").unwrap_or(default_pointer.to_string());
+        let offset = env::var("PUBLIC_INPUTS_OFFSET").unwrap_or("0".to_string());
+        Ok(Some(Self {
+            format,
+            pointer,
+            offset: offset.parse().unwrap_or(0),
+        }))
+    }
+
+    /// Compare the hash committed by `proof` with the sha256 of
+    /// `public_inputstream`.
+    pub fn check(&self, proof: &[u8], public_inputstream: &[u8]) -> Result<(), ProverError> {
+        let mismatch = ProverError::PublicInputMismatch;
+        let proof: serde_json::Value = serde_json::from_slice(proof)
+            .map_err(|e| mismatch(format!("proof is not JSON: {}", e)))?;
+        let inputs = proof
+            .pointer(&self.pointer)
+            .and_then(|inputs| inputs.as_array())
+            .ok_or_else(|| mismatch(format!("no public inputs array at {}", self.pointer)))?;
+        let mut expected: [u8; 32] = Sha256::digest(public_inputstream).into();
+        let committed = match self.format {
+            PublicInputsFormat::Plonky2 => {
+                let bytes = inputs
+                    .iter()
+                    .skip(self.offset)
+                    .take(32)
+                    .map(|input| input.as_u64().and_then(|b| u8::try_from(b).ok()))
+                    .collect::<Option<Vec<u8>>>()
+                    .filter(|bytes| bytes.len() == 32)
+                    .ok_or_else(|| {
+                        mismatch(format!(
+                            "public inputs at {} hold no 32 byte hash at offset {}",
+                            self.pointer, self.offset
+                        ))
+                    })?;
+                <[u8; 32]>::try_from(bytes).unwrap_or_default()
+            }
+            PublicInputsFormat::Groth16 => {
+                let element = inputs
+                    .get(self.offset)
+                    .and_then(|input| input.as_str())
+                    .and_then(|input| U256::from_str(input).ok())
+                    .ok_or_else(|| {
+                        mismatch(format!(
+                            "public inputs at {} hold no field element at offset {}",
+                            self.pointer, self.offset
+                        ))
+                    })?;
+                expected[0] &= 0x1f;
+                element.to_be_bytes::<32>()
+            }
+        };
+        if committed != expected {
+            return Err(mismatch(format!(
+                "proof commits to 0x{}, the submitted suite hashes to 0x{}",
+                hex::encode(committed),
+                hex::encode(expected)
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Public inputs of `proof` for display, looked up at PUBLIC_INPUTS_POINTER
+/// or the default pointers of both formats. A plonky2 byte array is shown
+/// as 0x-hex, groth16 field elements as they are.
+pub fn describe_public_inputs(proof: &serde_json::Value) -> Option<String> {
+    let pointers = match env::var("PUBLIC_INPUTS_POINTER") {
+        Ok(pointer) => vec![pointer],
+        Err(_) => vec!["/public_inputs".to_string(), "/PublicWitness".to_string()],
+    };
+    let inputs = pointers
+        .iter()
+        .find_map(|pointer| proof.pointer(pointer).and_then(|inputs| inputs.as_array()))?;
+    let bytes = inputs
+        .iter()
+        .map(|input| input.as_u64().and_then(|b| u8::try_from(b).ok()))
+        .collect::<Option<Vec<u8>>>();
+    match bytes {
+        Some(bytes) => Some(format!("0x{}", hex::encode(bytes))),
+        None => Some(
+            inputs
+                .iter()
+                .map(|input| match input.as_str() {
+                    Some(input) => input.to_string(),
+                    None => input.to_string(),
+                })
+                .collect::<Vec<_>>()
+                .join(", "),
+        ),
+    }
+}
@@ -0,0 +1,224 @@
+use crate::rpc::FailoverClient;
+use ethers_providers::{Middleware, Provider};
+use std::collections::HashSet;
+use std::io::Write;
+use std::path::Path;
+
+/// Starting block given in BLOCK_NO: a number, `latest` or `latest-N`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlockSpec {
+    Number(u64),
+    /// `offset` blocks behind the chain head.
+    Latest {
+        offset: u64,
+    },
+}
+
+impl BlockSpec {
+    pub fn parse(spec: &str) -> anyhow::Result<Self> {
+        let spec = spec.trim();
+        let invalid = || {
+            anyhow::anyhow!(
+                "invalid BLOCK_NO {:?}, expected a block number, \"latest\" or \"latest-N\"",
+                spec
+            )
+        };
+        match spec.strip_prefix("latest") {
+            Some("") => Ok(Self::Latest { offset: 0 }),
+            Some(offset) => {
+                let offset = offset.strip_prefix('-').ok_or_else(invalid)?;
+                let offset = offset.trim().parse().map_err(|_| invalid())?;
+                Ok(Self::Latest { offset })
+            }
+            None => spec.parse().map(Self::Number).map_err(|_| invalid()),
+        }
+    }
+
+    pub async fn resolve(&self, client: &Provider<FailoverClient>) -> anyhow::Result<u64> {
+        match *self {
+            Self::Number(block_no) => Ok(block_no),
+            Self::Latest { offset } => {
+                let head = client.get_block_number().await?.as_u64();
+                let block_no = head.checked_sub(offset).ok_or_else(|| {
+                    anyhow::anyhow!(
+                        "BLOCK_NO latest-{} is before genesis, head is {}",
+                        offset,
+                        head
+                    )
+                })?;
+                log::info!(
+                    "Resolved BLOCK_NO latest-{} to block_no: {} (head: {})",
+                    offset,
+                    block_no,
+                    head
+                );
+                Ok(block_no)
+            }
+        }
+    }
+}
+
+/// The blocks a run works through: a sequential counter, or an explicit list
+/// read from BLOCKS_FILE.
+#[derive(Debug)]
+pub enum BlockQueue {
+    Sequential { next: u64 },
+    List { blocks: Vec<u64>, pos: usize },
+}
+
+impl BlockQueue {
+    /// The block to process next, `None` once a list is exhausted.
+    pub fn current(&self) -> Option<u64> {
+        match self {
+            Self::Sequential { next } => Some(*next),
+            Self::List { blocks, pos } => blocks.get(*pos).copied(),
+        }
+    }
+
+    pub fn advance(&mut self) {
+        match self {
+            Self::Sequential { next } => *next += 1,
+            Self::List { pos, .. } => *pos += 1,
+        }
+    }
+
+    /// Continue a sequential queue from `block_no`, again after a reorg or
+    /// ahead after a lag skip.
+    pub fn rewind(&mut self, block_no: u64) {
+        if let Self::Sequential { next } = self {
+            *next = block_no;
+        }
+    }
+
+    pub fn is_list(&self) -> bool {
+        matches!(self, Self::List { .. })
+    }
+}
+
+/// Parse a blocks file: one block number per line, `#` starts a comment.
+/// Duplicates are dropped, keeping the first occurrence. Every unparsable
+/// line is reported, not just the first one.
+pub fn parse_blocks_file(path: &str) -> anyhow::Result<Vec<u64>> {
+    let content = std::fs::read_to_string(path)
+        .map_err(|e| anyhow::anyhow!("failed to read blocks file {}: {}", path, e))?;
+    let mut blocks = Vec::new();
+    let mut seen = HashSet::new();
+    let mut errors = Vec::new();
+    for (i, line) in content.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or_default().trim();
+        if line.is_empty() {
+            continue;
+        }
+        match line.parse::<u64>() {
+            Ok(block_no) => {
+                if seen.insert(block_no) {
+                    blocks.push(block_no);
+                }
+            }
+            Err(e) => errors.push(format!("line {}: {:?}: {}", i + 1, line, e)),
+        }
+    }
+    if !errors.is_empty() {
+        anyhow::bail!("invalid blocks file {}:\n{}", path, errors.join("\n"));
+    }
+    Ok(blocks)
+}
+
+/// Write `blocks` in the format accepted by BLOCKS_FILE.
+pub fn write_blocks_file(path: &Path, blocks: &[u64]) -> std::io::Result<()> {
+    let mut content = String::new();
+    for block_no in blocks {
+        content.push_str(&block_no.to_string());
+        content.push('\n');
+    }
+    std::fs::write(path, content)
+}
+
+/// Append `blocks` to a file in the BLOCKS_FILE format, after a `# note`
+/// comment line.
+pub fn append_blocks_file(
+    path: &Path,
+    note: &str,
+    blocks: impl IntoIterator<Item = u64>,
+) -> std::io::Result<()> {
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?;
+    let mut content = format!("# {}\n", note);
+    for block_no in blocks {
+        content.push_str(&block_no.to_string());
+        content.push('\n');
+    }
+    file.write_all(content.as_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn blocks_file(name: &str, content: &str) -> String {
+        let path = std::env::temp_dir().join(format!("blocks-{}-{}", std::process::id(), name));
+        std::fs::write(&path, content).unwrap();
+        path.to_string_lossy().into_owned()
+    }
+
+    #[test]
+    fn block_spec() {
+        assert_eq!(BlockSpec::parse("1200").unwrap(), BlockSpec::Number(1200));
+        assert_eq!(
+            BlockSpec::parse(" latest ").unwrap(),
+            BlockSpec::Latest { offset: 0 }
+        );
+        assert_eq!(
+            BlockSpec::parse("latest-10").unwrap(),
+            BlockSpec::Latest { offset: 10 }
+        );
+        assert_eq!(
+            BlockSpec::parse("latest- 3").unwrap(),
+            BlockSpec::Latest { offset: 3 }
+        );
+        for invalid in [
+            "",
+            "latest10",
+            "latest+1",
+            "latest-",
+            "latest--1",
+            "-1",
+            "0x10",
+        ] {
+            assert!(BlockSpec::parse(invalid).is_err(), "{:?}", invalid);
+        }
+    }
+
+    #[test]
+    fn blocks_file_skips_comments_and_duplicates() {
+        let path = blocks_file(
+            "list",
+            "# reorged\n1202\n\n1200 # twice\n  1202\n1201\n1200\n",
+        );
+        assert_eq!(parse_blocks_file(&path).unwrap(), vec![1202, 1200, 1201]);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn blocks_file_reports_every_invalid_line() {
+        let path = blocks_file("invalid", "1200\nabc\n1201\n-5\n");
+        let e = parse_blocks_file(&path).unwrap_err().to_string();
+        assert!(e.contains("line 2: \"abc\""), "{}", e);
+        assert!(e.contains("line 4: \"-5\""), "{}", e);
+        std::fs::remove_file(path).unwrap();
+
+        assert!(parse_blocks_file("/nonexistent/blocks.txt").is_err());
+    }
+
+    #[test]
+    fn written_blocks_files_parse_back() {
+        let path = std::env::temp_dir().join(format!("blocks-{}-written", std::process::id()));
+        write_blocks_file(&path, &[3, 1]).unwrap();
+        append_blocks_file(&path, "failed", [2, 3]).unwrap();
+        let parsed = parse_blocks_file(path.to_str().unwrap()).unwrap();
+        assert_eq!(parsed, vec![3, 1, 2]);
+        std::fs::remove_file(path).unwrap();
+    }
+}
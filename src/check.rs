@@ -1,47 +1,1194 @@
+use crate::atomic_file;
+use crate::check_exceptions;
+use crate::check_trace::{CheckTrace, TraceMode};
+use crate::goat_precompiles;
+use crate::merkle_trie::{log_rlp_hash, state_merkle_trie_root};
 use k256::ecdsa::SigningKey;
-use revm::primitives::Address;
+use rayon::prelude::*;
+use revm::primitives::alloy_primitives::Bloom;
+use revm::primitives::{Address, ExecutionResult, Log, B256, U256};
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet, HashMap, VecDeque};
+use std::convert::Infallible;
+use std::env;
+use std::fmt::Write;
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
 
 use revm::{
-    db::CacheState,
-    primitives::{calc_excess_blob_gas, keccak256, Bytecode, Env, SpecId, TransactTo},
-    Evm,
+    db::{
+        states::bundle_state::BundleRetention, CacheDB, CacheState, EmptyDB, State, WrapDatabaseRef,
+    },
+    inspector_handle_register,
+    inspectors::TracerEip3155,
+    interpreter::{gas::validate_initial_tx_gas, InstructionResult, Interpreter},
+    primitives::{
+        address, calc_excess_blob_gas, keccak256, AuthorizationList, Bytecode, Bytes, EVMError,
+        Env, InvalidTransaction, ResultAndState, SpecId, TransactTo, TxEnv, GAS_PER_BLOB,
+        MAX_BLOB_NUMBER_PER_BLOCK, MAX_INITCODE_SIZE, VERSIONED_HASH_VERSION_KZG,
+    },
+    Database, DatabaseCommit, Evm, EvmContext, Inspector,
 };
 
 use models::*;
 
-/// Recover the address from a private key (SigningKey).
-pub fn recover_address(private_key: &[u8]) -> Option<Address> {
-    let key = SigningKey::from_slice(private_key).ok()?;
+/// Why a suite failed to decode or to reproduce its expected results.
+#[derive(Debug, thiserror::Error)]
+pub enum CheckError {
+    #[error("failed to deserialize suite: {0}")]
+    Deserialize(String),
+    #[error("failed to recover the sender of unit {unit}: {reason}")]
+    SenderRecovery { unit: String, reason: KeyError },
+    #[error(
+        "sender {sender} of unit {unit} does not match {recovered} recovered from its secret key"
+    )]
+    SenderMismatch {
+        unit: String,
+        sender: Address,
+        recovered: Address,
+    },
+    #[error("{kind} index {index} of unit {unit} is out of range, it has {len} entries")]
+    IndexOutOfRange {
+        unit: String,
+        kind: &'static str,
+        index: usize,
+        len: usize,
+    },
+    #[error("unexpected exception in {spec} test {index}: {err}")]
+    UnexpectedException {
+        spec: String,
+        index: usize,
+        err: String,
+    },
+    #[error("expected exception {expected} in {spec} test {index} but the transaction succeeded")]
+    MissingExpectedException {
+        spec: String,
+        index: usize,
+        expected: String,
+    },
+    #[error("expected exception {expected} in {spec} test {index} but got: {actual}")]
+    ExceptionMismatch {
+        spec: String,
+        index: usize,
+        expected: String,
+        actual: String,
+    },
+    #[error("logs mismatch in {spec} test {index}: expected hash {expected}, got {actual}")]
+    LogsMismatch {
+        spec: String,
+        index: usize,
+        expected: B256,
+        actual: B256,
+    },
+    /// The spec filter left no post entry of the suite to execute.
+    #[error("no specs executed")]
+    NoSpecsExecuted,
+    /// Rejected by `preflight` without an expected exception.
+    #[error("transaction of {spec} test {index} is invalid: {err}")]
+    Rejected {
+        spec: String,
+        index: usize,
+        err: InvalidTransaction,
+    },
+    #[error("block gas limit {gas_limit} exceeded at unit {unit}, {gas_used} gas used")]
+    BlockGasLimitExceeded {
+        unit: String,
+        gas_used: u64,
+        gas_limit: U256,
+    },
+    #[error("coinbase of {spec} test {index} gained {actual}, expected {expected}")]
+    CoinbaseRewardMismatch {
+        spec: String,
+        index: usize,
+        expected: U256,
+        actual: U256,
+    },
+    #[error("{spec} test {index} burned {actual}, expected {expected}")]
+    BurnMismatch {
+        spec: String,
+        index: usize,
+        expected: U256,
+        actual: U256,
+    },
+    /// Halted once past CHECK_TEST_TIMEOUT_SECS.
+    #[error("{spec} test {index} timed out after {} s", .timeout.as_secs())]
+    Timeout {
+        spec: String,
+        index: usize,
+        timeout: Duration,
+    },
+    #[error("gas limit {gas_limit} of {spec} test {index} is above the budget of {budget}")]
+    GasBudgetExceeded {
+        spec: String,
+        index: usize,
+        gas_limit: u64,
+        budget: U256,
+    },
+    #[error("invalid blob transaction in {spec} test {index}: {reason}")]
+    InvalidBlobTx {
+        spec: String,
+        index: usize,
+        reason: String,
+    },
+    /// `diff` is empty unless CHECK_STATE_DIFF is set.
+    #[error(
+        "post state mismatch in {spec} test {index}: expected hash {expected}, got {actual}{diff}"
+    )]
+    PostStateMismatch {
+        spec: String,
+        index: usize,
+        expected: B256,
+        actual: B256,
+        diff: String,
+    },
+    /// Every failed test of a unit checked with `continue_on_error`, in spec
+    /// then index order. Past MAX_FAILURES only the count is kept.
+    #[error(
+        "{} tests of unit {unit} failed:{}",
+        .errors.len() + .omitted,
+        failure_lines(.errors, *.omitted)
+    )]
+    Failures {
+        unit: String,
+        errors: Vec<CheckError>,
+        omitted: usize,
+    },
+}
+
+fn failure_lines(errors: &[CheckError], omitted: usize) -> String {
+    let mut lines = String::new();
+    for e in errors {
+        let _ = write!(lines, "\n  {}", e);
+    }
+    if omitted > 0 {
+        let _ = write!(lines, "\n  ... and {} more", omitted);
+    }
+    lines
+}
+
+impl From<CheckError> for String {
+    fn from(e: CheckError) -> Self {
+        e.to_string()
+    }
+}
+
+/// Why a secret key does not yield an address.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum KeyError {
+    #[error("secret key has {0} bytes, expected 32")]
+    Length(usize),
+    #[error("secret key is all zeros")]
+    Zero,
+    #[error("secret key is not a valid secp256k1 scalar")]
+    NotOnCurve,
+}
+
+/// Recover the address from a private key (SigningKey), given as 32 bytes or
+/// as their hex text with or without `0x`.
+///
+/// ```
+/// use goat_prover::check::{recover_address, KeyError};
+///
+/// let key = "0x0000000000000000000000000000000000000000000000000000000000000001";
+/// let address = recover_address(key.as_bytes()).unwrap();
+/// assert_eq!(
+///     format!("{:x}", address),
+///     "7e5f4552091a69125d5dfcb7b8c2659029395bdf"
+/// );
+/// assert_eq!(recover_address(&[0; 32]), Err(KeyError::Zero));
+/// ```
+pub fn recover_address(private_key: &[u8]) -> Result<Address, KeyError> {
+    let decoded;
+    let private_key = match private_key.len() {
+        32 => private_key,
+        _ => {
+            let text = private_key.strip_prefix(b"0x").unwrap_or(private_key);
+            decoded = hex::decode(text).map_err(|_| KeyError::Length(private_key.len()))?;
+            decoded.as_slice()
+        }
+    };
+    if private_key.len() != 32 {
+        return Err(KeyError::Length(private_key.len()));
+    }
+    if private_key.iter().all(|&b| b == 0) {
+        return Err(KeyError::Zero);
+    }
+    let key = SigningKey::from_slice(private_key).map_err(|_| KeyError::NotOnCurve)?;
     let public_key = key.verifying_key().to_encoded_point(false);
-    Some(Address::from_raw_public_key(&public_key.as_bytes()[1..]))
+    Ok(Address::from_raw_public_key(&public_key.as_bytes()[1..]))
 }
 
-pub fn execute_test_suite(test_data: &[u8]) -> Result<(), String> {
-    let json_string: String = bincode::deserialize(test_data).map_err(|e| e.to_string())?;
-    let test_suite = serde_json::from_str::<TestSuite>(&json_string).map_err(|e| e.to_string())?;
-    for test_unit in test_suite.0.iter() {
-        execute_test_unit(test_unit.1)?;
+/// The `sender` of `tx`, or the address of its secret key if it has none.
+/// A secret key given alongside the sender must belong to it.
+fn tx_sender(unit: &str, tx: &TransactionParts) -> Result<Address, CheckError> {
+    if let (Some(sender), true) = (tx.sender, tx.secret_key.is_zero()) {
+        return Ok(sender);
+    }
+    let recovered =
+        recover_address(tx.secret_key.as_slice()).map_err(|reason| CheckError::SenderRecovery {
+            unit: unit.to_string(),
+            reason,
+        })?;
+    match tx.sender {
+        Some(sender) if sender != recovered => Err(CheckError::SenderMismatch {
+            unit: unit.to_string(),
+            sender,
+            recovered,
+        }),
+        _ => Ok(recovered),
+    }
+}
+
+/// Receipt of a transaction as reproduced by the host execution.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Receipt {
+    pub success: bool,
+    pub gas_used: u64,
+    /// Gas used by this and all preceding transactions of the suite.
+    pub cumulative_gas_used: u64,
+    pub logs: Vec<Log>,
+    pub logs_bloom: Bloom,
+    /// Spec the transaction was executed with.
+    pub spec_id: SpecId,
+    /// Sender and nonce of the transaction, which place it in its block as
+    /// units are not keyed by tx index.
+    pub sender: Address,
+    pub nonce: u64,
+}
+
+impl Receipt {
+    /// Receipt of `result`, the caller filling in the sender and nonce.
+    fn from_result(result: &ExecutionResult, spec_id: SpecId) -> Self {
+        let mut logs_bloom = Bloom::default();
+        for log in result.logs() {
+            logs_bloom.accrue_log(log);
+        }
+        Self {
+            success: result.is_success(),
+            gas_used: result.gas_used(),
+            cumulative_gas_used: result.gas_used(),
+            logs: result.logs().to_vec(),
+            logs_bloom,
+            spec_id,
+            sender: Address::ZERO,
+            nonce: 0,
+        }
+    }
+}
+
+/// EVM settings the suites are checked with.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CheckConfig {
+    pub chain_id: u64,
+    /// Accept transactions priced below the base fee.
+    pub disable_base_fee: bool,
+    pub disable_block_gas_limit: bool,
+    pub specs: SpecFilter,
+    /// Accept any error for an expected exception instead of the kind it
+    /// names, CHECK_LOOSE_EXCEPTIONS.
+    pub loose_exceptions: bool,
+    /// Times every transaction is executed, `check --bench`, its timings
+    /// being averaged over the runs.
+    pub bench_runs: u32,
+    /// Check the coinbase reward and the burned fees of every transaction,
+    /// CHECK_FEES. Payments to the coinbase made by contracts fail it.
+    pub check_fees: bool,
+    /// Check every test of a unit and report all failures together instead
+    /// of stopping at the first, CHECK_CONTINUE_ON_ERROR. The `run` pipeline
+    /// also checks every unit of a suite then, logging all failures before
+    /// refusing to prove it.
+    pub continue_on_error: bool,
+    /// Wall-clock budget of executing a test, CHECK_TEST_TIMEOUT_SECS, none
+    /// when unset or 0. Bounding a test runs it under an inspector, which
+    /// slows down every instruction, so it is opt-in. Traced runs are not
+    /// bounded.
+    pub test_timeout: Option<Duration>,
+    /// Largest gas limit of a transaction as a multiple of the block gas
+    /// limit, CHECK_MAX_TX_GAS_RATIO, no cap when 0. It still applies with
+    /// `disable_block_gas_limit`.
+    pub max_tx_gas_ratio: u64,
+    /// Accept a Cancun post state that only matches once the accounts that
+    /// ran SELFDESTRUCT are removed, as suites written before the EIP-6780
+    /// models update expect, CHECK_SELFDESTRUCT_LEGACY_SUITES.
+    pub selfdestruct_legacy_suites: bool,
+}
+
+/// The forks whose post entries are executed, by case-insensitive spec name.
+/// Forks the check never supports stay skipped either way.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SpecFilter {
+    /// Run only these, all when `None`.
+    pub only: Option<Vec<String>>,
+    pub skip: Vec<String>,
+}
+
+impl SpecFilter {
+    /// Spec names of a comma-separated list such as `cancun,shanghai`.
+    pub fn parse_list(list: &str) -> Vec<String> {
+        list.split(',')
+            .map(|name| name.trim().to_ascii_lowercase())
+            .filter(|name| !name.is_empty())
+            .collect()
+    }
+
+    fn is_active(&self) -> bool {
+        self.only.is_some() || !self.skip.is_empty()
+    }
+
+    fn includes(&self, spec_name: &SpecName) -> bool {
+        let name = format!("{:?}", spec_name).to_ascii_lowercase();
+        self.only.as_ref().map_or(true, |only| only.contains(&name)) && !self.skip.contains(&name)
+    }
+}
+
+/// Mainnet without the base fee check, how suites were always checked.
+impl Default for CheckConfig {
+    fn default() -> Self {
+        Self {
+            chain_id: 1,
+            disable_base_fee: true,
+            disable_block_gas_limit: false,
+            specs: SpecFilter::default(),
+            loose_exceptions: false,
+            bench_runs: 1,
+            check_fees: false,
+            continue_on_error: false,
+            test_timeout: None,
+            max_tx_gas_ratio: 4,
+            selfdestruct_legacy_suites: false,
+        }
+    }
+}
+
+impl CheckConfig {
+    /// Settings for CHAIN_ID `chain_id`, with CHECK_DISABLE_BASE_FEE (true),
+    /// CHECK_DISABLE_BLOCK_GAS_LIMIT (false), CHECK_LOOSE_EXCEPTIONS (false),
+    /// CHECK_FEES (false), CHECK_CONTINUE_ON_ERROR (false),
+    /// CHECK_TEST_TIMEOUT_SECS (unset), CHECK_MAX_TX_GAS_RATIO (4) and
+    /// CHECK_SELFDESTRUCT_LEGACY_SUITES (false).
+    pub fn from_env(chain_id: u64) -> Self {
+        let defaults = Self::default();
+        let disable_base_fee = env::var("CHECK_DISABLE_BASE_FEE").unwrap_or("true".to_string());
+        let disable_block_gas_limit =
+            env::var("CHECK_DISABLE_BLOCK_GAS_LIMIT").unwrap_or("false".to_string());
+        let loose_exceptions = env::var("CHECK_LOOSE_EXCEPTIONS").unwrap_or("false".to_string());
+        let check_fees = env::var("CHECK_FEES").unwrap_or("false".to_string());
+        let continue_on_error = env::var("CHECK_CONTINUE_ON_ERROR").unwrap_or("false".to_string());
+        let test_timeout_secs = env::var("CHECK_TEST_TIMEOUT_SECS").unwrap_or("0".to_string());
+        let max_tx_gas_ratio = env::var("CHECK_MAX_TX_GAS_RATIO").unwrap_or("4".to_string());
+        let selfdestruct_legacy_suites =
+            env::var("CHECK_SELFDESTRUCT_LEGACY_SUITES").unwrap_or("false".to_string());
+        Self {
+            chain_id,
+            disable_base_fee: disable_base_fee
+                .parse()
+                .unwrap_or(defaults.disable_base_fee),
+            disable_block_gas_limit: disable_block_gas_limit
+                .parse()
+                .unwrap_or(defaults.disable_block_gas_limit),
+            specs: defaults.specs,
+            loose_exceptions: loose_exceptions
+                .parse()
+                .unwrap_or(defaults.loose_exceptions),
+            bench_runs: defaults.bench_runs,
+            check_fees: check_fees.parse().unwrap_or(defaults.check_fees),
+            continue_on_error: continue_on_error
+                .parse()
+                .unwrap_or(defaults.continue_on_error),
+            test_timeout: match test_timeout_secs.parse() {
+                Ok(0) => None,
+                Ok(secs) => Some(Duration::from_secs(secs)),
+                Err(_) => defaults.test_timeout,
+            },
+            max_tx_gas_ratio: max_tx_gas_ratio
+                .parse()
+                .unwrap_or(defaults.max_tx_gas_ratio),
+            selfdestruct_legacy_suites: selfdestruct_legacy_suites
+                .parse()
+                .unwrap_or(defaults.selfdestruct_legacy_suites),
+        }
+    }
+
+    /// Whether the post entries of `spec_name` are executed, forks the check
+    /// does not support never are.
+    fn checks_spec(&self, spec_name: &SpecName) -> bool {
+        !matches!(
+            spec_name,
+            SpecName::ByzantiumToConstantinopleAt5 | SpecName::Constantinople | SpecName::Unknown
+        ) && self.specs.includes(spec_name)
+    }
+}
+
+/// Outcome of one post test of a unit.
+#[derive(Debug, Clone)]
+pub struct TestOutcome {
+    pub spec: String,
+    /// Indexes into the data, gas and value lists of the transaction.
+    pub indexes: [usize; 3],
+    pub gas_used: u64,
+    pub success: bool,
+    pub output_len: usize,
+    pub logs_count: usize,
+    /// `None` when the transaction failed with the expected exception.
+    pub receipt: Option<Receipt>,
+    /// Position of the test among the tests of its spec.
+    pub index: usize,
+    pub calldata_len: usize,
+    /// Mean time of setting up the state for a run of the transaction.
+    pub setup_micros: u64,
+    /// Mean time of executing the transaction.
+    pub evm_micros: u64,
+}
+
+impl TestOutcome {
+    /// Outcome of a transaction that was not executed.
+    fn new(spec_name: &SpecName, index: usize, test: &Test) -> Self {
+        Self {
+            spec: format!("{:?}", spec_name),
+            indexes: [test.indexes.data, test.indexes.gas, test.indexes.value],
+            gas_used: 0,
+            success: false,
+            output_len: 0,
+            logs_count: 0,
+            receipt: None,
+            index,
+            calldata_len: 0,
+            setup_micros: 0,
+            evm_micros: 0,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct UnitReport {
+    pub per_test: Vec<TestOutcome>,
+    /// Wall-clock time of executing the unit.
+    pub micros: u64,
+    /// Why the unit failed, `per_test` is empty then.
+    pub error: Option<CheckError>,
+}
+
+/// Reports of the units of a suite, keyed and ordered by unit name.
+#[derive(Debug, Default)]
+pub struct SuiteReport {
+    pub units: BTreeMap<String, UnitReport>,
+}
+
+/// How the units of a suite are checked, SUITE_MODE.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SuiteMode {
+    /// Every unit on its own pre state, `unit` (default).
+    #[default]
+    Unit,
+    /// The units in order on one state, `block`.
+    Block,
+}
+
+impl SuiteMode {
+    pub fn from_env() -> anyhow::Result<Self> {
+        match env::var("SUITE_MODE")
+            .unwrap_or("unit".to_string())
+            .as_str()
+        {
+            "unit" => Ok(Self::Unit),
+            "block" => Ok(Self::Block),
+            other => anyhow::bail!("invalid SUITE_MODE: {}, expected unit|block", other),
+        }
+    }
+}
+
+/// Outcome of executing the units of a suite as one block.
+#[derive(Debug, Default)]
+pub struct BlockReport {
+    /// Receipts of the transactions included in the block, in order.
+    pub receipts: Vec<Receipt>,
+    pub gas_used: u64,
+    pub state_root: B256,
+}
+
+/// Pass/fail table of a suite report, as printed by `check`.
+#[derive(Debug, Serialize)]
+pub struct CheckSummary<'a> {
+    pub units: Vec<UnitRow<'a>>,
+    pub passed: usize,
+    pub failed: usize,
+    pub tx_count: usize,
+    pub gas_used: u64,
+    pub micros: u64,
+    /// Specs executed by any unit.
+    pub specs: BTreeSet<&'a str>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct UnitRow<'a> {
+    pub unit: &'a str,
+    pub status: &'static str,
+    pub error: Option<String>,
+    pub micros: u64,
+    pub tx_count: usize,
+    pub gas_used: u64,
+    pub specs: BTreeSet<&'a str>,
+}
+
+impl SuiteReport {
+    /// The first error by unit key, if any unit failed or the spec filter of
+    /// `config` left nothing to execute.
+    pub fn into_result(mut self, config: &CheckConfig) -> Result<Self, CheckError> {
+        if let Some(e) = self.units.values_mut().find_map(|unit| unit.error.take()) {
+            return Err(e);
+        }
+        if config.specs.is_active() && self.units.values().all(|unit| unit.per_test.is_empty()) {
+            return Err(CheckError::NoSpecsExecuted);
+        }
+        Ok(self)
+    }
+
+    pub fn failed(&self) -> usize {
+        self.units
+            .values()
+            .filter(|unit| unit.error.is_some())
+            .count()
+    }
+
+    pub fn summary(&self) -> CheckSummary<'_> {
+        let units: Vec<_> = self
+            .units
+            .iter()
+            .map(|(name, unit)| UnitRow {
+                unit: name,
+                status: if unit.error.is_some() { "fail" } else { "pass" },
+                error: unit.error.as_ref().map(CheckError::to_string),
+                micros: unit.micros,
+                tx_count: unit.per_test.len(),
+                gas_used: unit.per_test.iter().map(|outcome| outcome.gas_used).sum(),
+                specs: unit
+                    .per_test
+                    .iter()
+                    .map(|outcome| outcome.spec.as_str())
+                    .collect(),
+            })
+            .collect();
+        let failed = self.failed();
+        CheckSummary {
+            passed: units.len() - failed,
+            failed,
+            tx_count: units.iter().map(|row| row.tx_count).sum(),
+            gas_used: units.iter().map(|row| row.gas_used).sum(),
+            micros: units.iter().map(|row| row.micros).sum(),
+            specs: units
+                .iter()
+                .flat_map(|row| row.specs.iter().copied())
+                .collect(),
+            units,
+        }
+    }
+
+    /// Receipts of the executed transactions in block order, one per unit
+    /// from its first executed test. Units are not keyed by tx index, so the
+    /// order comes from `senders`, the sender of every transaction of the
+    /// block by tx index, the transactions of a sender being included by
+    /// ascending nonce. Receipts of senders it misses go last, by sender and
+    /// nonce.
+    pub fn receipts(&self, senders: &[Address]) -> Vec<Receipt> {
+        let mut by_sender: BTreeMap<Address, VecDeque<&Receipt>> = BTreeMap::new();
+        for unit in self.units.values() {
+            if let Some(receipt) = unit.per_test.iter().find_map(|o| o.receipt.as_ref()) {
+                by_sender
+                    .entry(receipt.sender)
+                    .or_default()
+                    .push_back(receipt);
+            }
+        }
+        for receipts in by_sender.values_mut() {
+            receipts
+                .make_contiguous()
+                .sort_by_key(|receipt| receipt.nonce);
+        }
+        let mut ordered: Vec<&Receipt> = senders
+            .iter()
+            .filter_map(|sender| by_sender.get_mut(sender)?.pop_front())
+            .collect();
+        ordered.extend(by_sender.into_values().flatten());
+
+        let mut cumulative_gas_used = 0;
+        ordered
+            .into_iter()
+            .map(|receipt| {
+                cumulative_gas_used += receipt.gas_used;
+                Receipt {
+                    cumulative_gas_used,
+                    ..receipt.clone()
+                }
+            })
+            .collect()
+    }
+
+    pub fn gas_used(&self) -> u64 {
+        self.units
+            .values()
+            .flat_map(|unit| &unit.per_test)
+            .map(|outcome| outcome.gas_used)
+            .sum()
     }
-    Ok(())
 }
 
-pub fn execute_test_unit(unit: &TestUnit) -> Result<(), String> {
-    // Create database and insert cache
+/// Execute the units of the suite as one block, see [`execute_block`].
+pub fn execute_block_suite(
+    test_data: &[u8],
+    config: &CheckConfig,
+) -> Result<BlockReport, CheckError> {
+    let test_suite = decode_test_suite(test_data)?;
+    let units: Vec<_> = test_suite
+        .0
+        .iter()
+        .map(|(name, unit)| (name.as_str(), unit))
+        .collect();
+    execute_block(&units, config)
+}
+
+/// Execute every unit of the suite.
+pub fn execute_test_suite(
+    test_data: &[u8],
+    config: &CheckConfig,
+    trace: Option<&CheckTrace>,
+) -> Result<SuiteReport, CheckError> {
+    match plain_json(test_data) {
+        Some(json) => execute_test_suite_json(json?, config, trace),
+        None => execute_suite(&decode_test_suite(test_data)?, config, trace),
+    }
+}
+
+/// Execute every unit of the suite, carrying on past failing ones to report
+/// them all. Only a suite that does not decode is an error.
+pub fn run_suite_report(
+    test_data: &[u8],
+    config: &CheckConfig,
+    trace: Option<&CheckTrace>,
+) -> Result<SuiteReport, CheckError> {
+    Ok(report_suite(&decode_test_suite(test_data)?, config, trace))
+}
+
+/// Execute a suite given as plain JSON.
+pub fn execute_test_suite_json(
+    json: &str,
+    config: &CheckConfig,
+    trace: Option<&CheckTrace>,
+) -> Result<SuiteReport, CheckError> {
+    execute_suite(&decode_test_suite_json(json)?, config, trace)
+}
+
+/// Decode a suite given either as plain JSON, e.g. a hand-edited fixture, or
+/// as the bincode-wrapped JSON string read by the guest.
+pub fn decode_test_suite(test_data: &[u8]) -> Result<TestSuite, CheckError> {
+    if let Some(json) = plain_json(test_data) {
+        return decode_test_suite_json(json?);
+    }
+    let json_string: String = bincode::deserialize(test_data).map_err(|e| {
+        CheckError::Deserialize(format!(
+            "neither plain JSON nor a bincode-wrapped JSON string: {}",
+            e
+        ))
+    })?;
+    decode_test_suite_json(&json_string)
+}
+
+/// `test_data` as a string if it looks like plain JSON rather than bincode.
+fn plain_json(test_data: &[u8]) -> Option<Result<&str, CheckError>> {
+    if !test_data.trim_ascii_start().starts_with(b"{") {
+        return None;
+    }
+    Some(
+        std::str::from_utf8(test_data)
+            .map_err(|e| CheckError::Deserialize(format!("not plain JSON: {}", e))),
+    )
+}
+
+fn decode_test_suite_json(json: &str) -> Result<TestSuite, CheckError> {
+    serde_json::from_str::<TestSuite>(json).map_err(|e| CheckError::Deserialize(e.to_string()))
+}
+
+/// Pool the units of a suite are executed on, CHECK_THREADS wide, one thread
+/// per CPU by default. `cargo bench --bench check_suite` times it against
+/// CHECK_THREADS=1.
+fn check_pool() -> &'static rayon::ThreadPool {
+    static POOL: OnceLock<rayon::ThreadPool> = OnceLock::new();
+    POOL.get_or_init(|| {
+        let threads = env::var("CHECK_THREADS").unwrap_or("0".to_string());
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(threads.parse().unwrap_or(0))
+            .thread_name(|i| format!("check-{}", i))
+            .build()
+            .expect("failed to build the check thread pool")
+    })
+}
+
+/// Execute the units of the suite in parallel. Results are collected in unit
+/// key order, so the report and the error returned, the first by key, do not
+/// depend on scheduling.
+pub fn execute_suite(
+    test_suite: &TestSuite,
+    config: &CheckConfig,
+    trace: Option<&CheckTrace>,
+) -> Result<SuiteReport, CheckError> {
+    report_suite(test_suite, config, trace).into_result(config)
+}
+
+fn report_suite(
+    test_suite: &TestSuite,
+    config: &CheckConfig,
+    trace: Option<&CheckTrace>,
+) -> SuiteReport {
+    let units: Vec<_> = test_suite.0.iter().collect();
+    let reports: Vec<_> = check_pool().install(|| {
+        units
+            .par_iter()
+            .map(|(name, unit)| {
+                let start = Instant::now();
+                let result = execute_test_unit(name, unit, config, trace);
+                let mut report = result.unwrap_or_else(|e| UnitReport {
+                    error: Some(e),
+                    ..Default::default()
+                });
+                report.micros = start.elapsed().as_micros() as u64;
+                report
+            })
+            .collect()
+    });
+    SuiteReport {
+        units: units
+            .into_iter()
+            .map(|(name, _)| name.clone())
+            .zip(reports)
+            .collect(),
+    }
+}
+
+pub fn execute_test_unit(
+    name: &str,
+    unit: &TestUnit,
+    config: &CheckConfig,
+    trace: Option<&CheckTrace>,
+) -> Result<UnitReport, CheckError> {
+    // Create database and insert cache. Every test reads the pre state
+    // through `pre_db` and keeps its writes in its own `State` rather than
+    // cloning `cache_state`, a copy that grows with the pre storage;
+    // `cargo bench --bench check_suite storage` times a test against that
+    // clone. `cache_state` is the pre state of the state root.
     let mut cache_state = CacheState::new(false);
+    let mut pre_db = CacheDB::new(EmptyDB::default());
     for (address, info) in &unit.pre {
-        let acc_info = revm::primitives::AccountInfo {
-            balance: info.balance,
-            code_hash: keccak256(&info.code),
-            code: Some(Bytecode::new_raw(info.code.clone())),
-            nonce: info.nonce,
-        };
+        let acc_info = pre_account_info(info);
+        pre_db.insert_account_info(*address, acc_info.clone());
+        if let Some(account) = pre_db.accounts.get_mut(address) {
+            account
+                .storage
+                .extend(info.storage.iter().map(|(k, v)| (*k, *v)));
+        }
         cache_state.insert_account_with_storage(*address, acc_info, info.storage.clone());
     }
 
+    let mut env = unit_env(name, unit, config)?;
+
+    // post and execution
+    let mut run_test = |spec_name: &SpecName,
+                        spec_id: SpecId,
+                        index: usize,
+                        test: &Test|
+     -> Result<TestOutcome, CheckError> {
+        let tx = &unit.transaction;
+        set_test_tx(name, unit, test, &mut env)?;
+
+        let beacon_root = unit
+            .env
+            .parent_beacon_block_root
+            .filter(|_| SpecId::enabled(spec_id, SpecId::CANCUN));
+        if SpecId::enabled(spec_id, SpecId::CANCUN) {
+            if let Err(reason) = validate_blob_tx(&env) {
+                if test.expect_exception.is_none() {
+                    return Err(CheckError::InvalidBlobTx {
+                        spec: format!("{:?}", spec_name),
+                        index,
+                        reason,
+                    });
+                }
+                return Ok(TestOutcome::new(spec_name, index, test));
+            }
+        }
+        check_gas_budget(spec_name, index, &env, config)?;
+        let (state_nonce, balance) = unit
+            .pre
+            .get(&env.tx.caller)
+            .map_or((0, U256::ZERO), |acc| (acc.nonce, acc.balance));
+        if let Err(err) = preflight(
+            &env,
+            spec_id,
+            tx.nonce.saturating_to(),
+            state_nonce,
+            balance,
+        ) {
+            let spec = format!("{:?}", spec_name);
+            return match &test.expect_exception {
+                None => Err(CheckError::Rejected { spec, index, err }),
+                Some(expected)
+                    if config.loose_exceptions
+                        || check_exceptions::matches(
+                            expected,
+                            &EVMError::<Infallible>::Transaction(err.clone()),
+                        ) =>
+                {
+                    Ok(TestOutcome::new(spec_name, index, test))
+                }
+                Some(expected) => Err(CheckError::ExceptionMismatch {
+                    spec,
+                    index,
+                    expected: expected.clone(),
+                    actual: err.to_string(),
+                }),
+            };
+        }
+
+        let traced = trace.and_then(|trace| trace.writer(TraceMode::All, name, index));
+        let legacy_selfdestruct =
+            config.selfdestruct_legacy_suites && SpecId::enabled(spec_id, SpecId::CANCUN);
+        let watch = || {
+            (config.test_timeout.is_some() || legacy_selfdestruct)
+                .then(|| Watch::new(config.test_timeout))
+        };
+        let (exec_result, mut state, mut timing, watched) =
+            transact(&pre_db, &env, spec_id, beacon_root, traced, watch());
+        let destructed = match watched {
+            Some(watched) if watched.timed_out => {
+                return Err(CheckError::Timeout {
+                    spec: format!("{:?}", spec_name),
+                    index,
+                    timeout: config.test_timeout.unwrap_or_default(),
+                })
+            }
+            Some(watched) => watched.destructed,
+            None => Vec::new(),
+        };
+        let runs = config.bench_runs.max(1);
+        for _ in 1..runs {
+            let (_, _, run, _) = transact(&pre_db, &env, spec_id, beacon_root, None, watch());
+            timing.setup_micros += run.setup_micros;
+            timing.evm_micros += run.evm_micros;
+        }
+        let withdrawn = apply_withdrawals(&mut state, unit, spec_id);
+        let post = PostState {
+            pre: &cache_state,
+            cache: &state.cache,
+            removed: &[],
+        };
+        let legacy_exec_result =
+            (legacy_selfdestruct && !destructed.is_empty()).then(|| exec_result.clone());
+        let result = verify_test(
+            spec_name,
+            index,
+            test,
+            &unit.pre,
+            exec_result,
+            post,
+            config.loose_exceptions,
+        );
+        let result = match (result, legacy_exec_result) {
+            (Err(e @ CheckError::PostStateMismatch { .. }), Some(exec_result)) => {
+                let legacy_post = PostState {
+                    removed: &destructed,
+                    ..post
+                };
+                match verify_test(
+                    spec_name,
+                    index,
+                    test,
+                    &unit.pre,
+                    exec_result,
+                    legacy_post,
+                    config.loose_exceptions,
+                ) {
+                    Ok(outcome) => {
+                        log::warn!(
+                            "Unit {} {:?} test {} passes only with the pre-Cancun SELFDESTRUCT of {:?}",
+                            name,
+                            spec_name,
+                            index,
+                            destructed
+                        );
+                        Ok(outcome)
+                    }
+                    Err(_) => Err(e),
+                }
+            }
+            (result, _) => result,
+        };
+        let result = result.and_then(|outcome| {
+            if config.check_fees {
+                verify_fees(&outcome, &env, withdrawn, &unit.pre, post)?;
+            }
+            Ok(outcome)
+        });
+        if let Some(trace) = trace.filter(|trace| trace.dumps_state(result.is_err())) {
+            write_state_diff(trace, name, spec_name, index, &mut state);
+        }
+        if result.is_err() {
+            // Traced only now to keep passing tests fast.
+            if let Some(out) = trace.and_then(|trace| trace.writer(TraceMode::Failed, name, index))
+            {
+                transact(&pre_db, &env, spec_id, beacon_root, Some(out), None);
+            }
+        }
+        result.map(|outcome| TestOutcome {
+            receipt: outcome.receipt.map(|receipt| Receipt {
+                sender: env.tx.caller,
+                nonce: tx.nonce.saturating_to(),
+                ..receipt
+            }),
+            calldata_len: env.tx.data.len(),
+            setup_micros: timing.setup_micros / u64::from(runs),
+            evm_micros: timing.evm_micros / u64::from(runs),
+            ..outcome
+        })
+    };
+
+    let mut report = UnitReport::default();
+    let mut failures = Failures::default();
+    for (spec_name, tests) in &unit.post {
+        if !config.checks_spec(spec_name) {
+            continue;
+        }
+
+        let spec_id = spec_name.to_spec_id();
+        // Authorization lists only exist from Prague on.
+        if unit.transaction.authorization_list.is_some()
+            && !SpecId::enabled(spec_id, SpecId::PRAGUE)
+        {
+            continue;
+        }
+        for (index, test) in tests.iter().enumerate() {
+            match run_test(spec_name, spec_id, index, test) {
+                Ok(outcome) => report.per_test.push(outcome),
+                Err(e) if config.continue_on_error => failures.push(e),
+                Err(e) => return Err(e),
+            }
+        }
+    }
+    failures.into_result(name)?;
+    Ok(report)
+}
+
+/// Execute the units of a block in order on one state, every transaction
+/// seeing the changes of those before it. The state starts from the union of
+/// the pre states, an account coming from the first unit that has it. Each
+/// unit runs the first test of the first spec the config checks, under the
+/// same preflight, blob, exception and timeout checks as
+/// [`execute_test_unit`]. The final state root must match the post hash of
+/// the last unit, which ends on the state of the whole block, unless it is
+/// zero.
+pub fn execute_block(
+    units: &[(&str, &TestUnit)],
+    config: &CheckConfig,
+) -> Result<BlockReport, CheckError> {
+    let mut cache_state = CacheState::new(false);
+    for (_, unit) in units {
+        for (address, info) in &unit.pre {
+            if !cache_state.accounts.contains_key(address) {
+                cache_state.insert_account_with_storage(
+                    *address,
+                    pre_account_info(info),
+                    info.storage.clone(),
+                );
+            }
+        }
+    }
+    let mut state = State::builder()
+        .with_cached_prestate(cache_state)
+        .with_bundle_update()
+        .build();
+
+    let mut report = BlockReport::default();
+    let mut last = None;
+    for (name, unit) in units {
+        let Some((spec_name, test)) = unit
+            .post
+            .iter()
+            .filter(|(spec_name, _)| config.checks_spec(spec_name))
+            .find_map(|(spec_name, tests)| Some((spec_name, tests.first()?)))
+        else {
+            continue;
+        };
+        let spec_id = spec_name.to_spec_id();
+        let spec = format!("{:?}", spec_name);
+        let mut env = unit_env(name, unit, config)?;
+        set_test_tx(name, unit, test, &mut env)?;
+        state.set_state_clear_flag(SpecId::enabled(spec_id, SpecId::SPURIOUS_DRAGON));
+        if last.is_none() {
+            if let Some(beacon_root) = unit.env.parent_beacon_block_root {
+                if SpecId::enabled(spec_id, SpecId::CANCUN) {
+                    apply_beacon_root(&mut state, &env, spec_id, beacon_root);
+                }
+            }
+        }
+        last = Some((*unit, spec_name, spec_id, test));
+
+        if SpecId::enabled(spec_id, SpecId::CANCUN) {
+            if let Err(reason) = validate_blob_tx(&env) {
+                if test.expect_exception.is_none() {
+                    return Err(CheckError::InvalidBlobTx {
+                        spec,
+                        index: 0,
+                        reason,
+                    });
+                }
+                continue;
+            }
+        }
+        check_gas_budget(spec_name, 0, &env, config)?;
+        let (state_nonce, balance) = state
+            .basic(env.tx.caller)
+            .unwrap_or_else(|e| match e {})
+            .map_or((0, U256::ZERO), |acc| (acc.nonce, acc.balance));
+        let nonce = unit.transaction.nonce.saturating_to();
+        if let Err(err) = preflight(&env, spec_id, nonce, state_nonce, balance) {
+            match &test.expect_exception {
+                None => {
+                    return Err(CheckError::Rejected {
+                        spec,
+                        index: 0,
+                        err,
+                    })
+                }
+                Some(expected)
+                    if config.loose_exceptions
+                        || check_exceptions::matches(
+                            expected,
+                            &EVMError::<Infallible>::Transaction(err.clone()),
+                        ) =>
+                {
+                    continue
+                }
+                Some(expected) => {
+                    return Err(CheckError::ExceptionMismatch {
+                        spec,
+                        index: 0,
+                        expected: expected.clone(),
+                        actual: err.to_string(),
+                    })
+                }
+            }
+        }
+        let block_gas_limit = env.block.gas_limit;
+        let sender = env.tx.caller;
+
+        let builder = Evm::builder().with_db(&mut state).modify_env(|e| **e = env);
+        let (result, timed_out) = match config.test_timeout {
+            None => {
+                let result = builder
+                    .with_spec_id(spec_id)
+                    .append_handler_register(goat_precompiles::register(config.chain_id))
+                    .build()
+                    .transact_commit();
+                (result, false)
+            }
+            Some(timeout) => {
+                let mut evm = builder
+                    .with_external_context(Watch::new(Some(timeout)))
+                    .with_spec_id(spec_id)
+                    .append_handler_register(inspector_handle_register)
+                    .append_handler_register(goat_precompiles::register(config.chain_id))
+                    .build();
+                let result = evm.transact_commit();
+                (result, evm.into_context().external.timed_out)
+            }
+        };
+        if timed_out {
+            return Err(CheckError::Timeout {
+                spec,
+                index: 0,
+                timeout: config.test_timeout.unwrap_or_default(),
+            });
+        }
+        match (result, &test.expect_exception) {
+            (Ok(result), None) => {
+                let mut receipt = Receipt::from_result(&result, spec_id);
+                report.gas_used += receipt.gas_used;
+                receipt.cumulative_gas_used = report.gas_used;
+                receipt.sender = sender;
+                receipt.nonce = nonce;
+                report.receipts.push(receipt);
+            }
+            // Rejected as expected, the transaction is not part of the block.
+            (Err(e), Some(expected))
+                if config.loose_exceptions || check_exceptions::matches(expected, &e) => {}
+            (Err(e), Some(expected)) => {
+                return Err(CheckError::ExceptionMismatch {
+                    spec,
+                    index: 0,
+                    expected: expected.clone(),
+                    actual: e.to_string(),
+                })
+            }
+            (Err(e), None) => {
+                return Err(CheckError::UnexpectedException {
+                    spec,
+                    index: 0,
+                    err: e.to_string(),
+                })
+            }
+            (Ok(_), Some(expected)) => {
+                return Err(CheckError::MissingExpectedException {
+                    spec,
+                    index: 0,
+                    expected: expected.clone(),
+                })
+            }
+        }
+        if !config.disable_block_gas_limit && U256::from(report.gas_used) > block_gas_limit {
+            return Err(CheckError::BlockGasLimitExceeded {
+                unit: name.to_string(),
+                gas_used: report.gas_used,
+                gas_limit: block_gas_limit,
+            });
+        }
+    }
+    let Some((unit, spec_name, spec_id, test)) = last else {
+        return Ok(report);
+    };
+    apply_withdrawals(&mut state, unit, spec_id);
+
+    report.state_root = state_merkle_trie_root(
+        state
+            .cache
+            .trie_account()
+            .into_iter()
+            .map(|(address, account)| (address, &account.info, &account.storage)),
+    );
+    if test.hash != B256::ZERO && report.state_root != test.hash {
+        return Err(CheckError::PostStateMismatch {
+            spec: format!("{:?}", spec_name),
+            index: 0,
+            expected: test.hash,
+            actual: report.state_root,
+            diff: String::new(),
+        });
+    }
+    Ok(report)
+}
+
+/// Account of the pre state of a unit as the EVM sees it.
+fn pre_account_info(info: &AccountInfo) -> revm::primitives::AccountInfo {
+    revm::primitives::AccountInfo {
+        balance: info.balance,
+        code_hash: keccak256(&info.code),
+        code: Some(Bytecode::new_raw(info.code.clone())),
+        nonce: info.nonce,
+    }
+}
+
+/// Block and transaction settings shared by the tests of `unit`.
+fn unit_env(name: &str, unit: &TestUnit, config: &CheckConfig) -> Result<Env, CheckError> {
     let mut env = Env::default();
-    // for mainnet
-    env.cfg.chain_id = 1;
-    env.cfg.disable_base_fee = true;
+    env.cfg.chain_id = config.chain_id;
+    env.cfg.disable_base_fee = config.disable_base_fee;
+    env.cfg.disable_block_gas_limit = config.disable_block_gas_limit;
     // env.cfg.spec_id is set down the road
 
     // block env
@@ -66,10 +1213,7 @@ pub fn execute_test_unit(unit: &TestUnit) -> Result<(), String> {
     }
 
     // tx env
-    env.tx.caller = match unit.transaction.sender {
-        Some(address) => address,
-        _ => recover_address(unit.transaction.secret_key.as_slice()).ok_or_else(String::new)?,
-    };
+    env.tx.caller = tx_sender(name, &unit.transaction)?;
     env.tx.gas_price = unit
         .transaction
         .gas_price
@@ -79,86 +1223,1007 @@ pub fn execute_test_unit(unit: &TestUnit) -> Result<(), String> {
     // EIP-4844
     env.tx.blob_hashes = unit.transaction.blob_versioned_hashes.clone();
     env.tx.max_fee_per_blob_gas = unit.transaction.max_fee_per_blob_gas;
+    // EIP-7702, the authorities are recovered from the signatures up front.
+    env.tx.authorization_list = unit.transaction.authorization_list.as_ref().map(|list| {
+        AuthorizationList::Signed(
+            list.iter()
+                .map(|auth| auth.signed_authorization())
+                .collect(),
+        )
+        .into_recovered()
+    });
+    Ok(env)
+}
 
-    // post and execution
-    for (spec_name, tests) in &unit.post {
-        if matches!(
-            spec_name,
-            SpecName::ByzantiumToConstantinopleAt5 | SpecName::Constantinople | SpecName::Unknown
-        ) {
-            continue;
+/// Set the gas limit, data, value and access list of the transaction of
+/// `test`.
+fn set_test_tx(name: &str, unit: &TestUnit, test: &Test, env: &mut Env) -> Result<(), CheckError> {
+    let tx = &unit.transaction;
+    env.tx.gas_limit = tx_part(name, "gas", &tx.gas_limit, test.indexes.gas)?.saturating_to();
+    env.tx.data = tx_part(name, "data", &tx.data, test.indexes.data)?.clone();
+    env.tx.value = *tx_part(name, "value", &tx.value, test.indexes.value)?;
+
+    // Access lists are indexed alongside the data, a suite without
+    // any has none for every data entry.
+    let access_list = if tx.access_lists.is_empty() {
+        None
+    } else {
+        tx_part(name, "access list", &tx.access_lists, test.indexes.data)?.as_deref()
+    };
+    env.tx.access_list = access_list
+        .unwrap_or_default()
+        .iter()
+        .map(|item| revm::primitives::AccessListItem {
+            address: item.address,
+            storage_keys: item.storage_keys.clone(),
+        })
+        .collect();
+
+    let to = match unit.transaction.to {
+        Some(add) => TransactTo::Call(add),
+        None => revm::primitives::TxKind::Create,
+    };
+    env.tx.transact_to = to;
+    Ok(())
+}
+
+/// Failures collected by `continue_on_error`, bounded in number and in the
+/// size of each one's state diff.
+#[derive(Default)]
+struct Failures {
+    errors: Vec<CheckError>,
+    omitted: usize,
+}
+
+impl Failures {
+    const MAX_FAILURES: usize = 64;
+    const MAX_DIFF_BYTES: usize = 16 * 1024;
+
+    fn push(&mut self, mut e: CheckError) {
+        if self.errors.len() == Self::MAX_FAILURES {
+            self.omitted += 1;
+            return;
+        }
+        if let CheckError::PostStateMismatch { diff, .. } = &mut e {
+            if diff.len() > Self::MAX_DIFF_BYTES {
+                let mut end = Self::MAX_DIFF_BYTES;
+                while !diff.is_char_boundary(end) {
+                    end -= 1;
+                }
+                diff.truncate(end);
+                diff.push_str("\n  ... (diff truncated)");
+            }
         }
+        self.errors.push(e);
+    }
 
-        let spec_id = spec_name.to_spec_id();
-        for test in tests.iter() {
-            env.tx.gas_limit = unit.transaction.gas_limit[test.indexes.gas].saturating_to();
-
-            env.tx.data = unit
-                .transaction
-                .data
-                .get(test.indexes.data)
-                .unwrap()
-                .clone();
-            env.tx.value = unit.transaction.value[test.indexes.value];
-
-            env.tx.access_list = unit
-                .transaction
-                .access_lists
-                .get(test.indexes.data)
-                .and_then(Option::as_deref)
-                .unwrap_or_default()
-                .iter()
-                .map(|item| revm::primitives::AccessListItem {
-                    address: item.address,
-                    storage_keys: item.storage_keys.clone(),
-                })
-                .collect();
+    /// A lone failure as is, several as one `CheckError::Failures`.
+    fn into_result(mut self, unit: &str) -> Result<(), CheckError> {
+        match self.errors.len() {
+            0 => Ok(()),
+            1 if self.omitted == 0 => Err(self.errors.remove(0)),
+            _ => Err(CheckError::Failures {
+                unit: unit.to_string(),
+                errors: self.errors,
+                omitted: self.omitted,
+            }),
+        }
+    }
+}
 
-            let to = match unit.transaction.to {
-                Some(add) => TransactTo::Call(add),
-                None => revm::primitives::TxKind::Create,
-            };
-            env.tx.transact_to = to;
+/// Entry `index` of the `kind` list of the transaction of `unit`.
+fn tx_part<'a, T>(
+    unit: &str,
+    kind: &'static str,
+    list: &'a [T],
+    index: usize,
+) -> Result<&'a T, CheckError> {
+    list.get(index).ok_or_else(|| CheckError::IndexOutOfRange {
+        unit: unit.to_string(),
+        kind,
+        index,
+        len: list.len(),
+    })
+}
 
-            let mut cache = cache_state.clone();
-            cache.set_state_clear_flag(SpecId::enabled(
-                spec_id,
-                revm::primitives::SpecId::SPURIOUS_DRAGON,
+/// Consensus rules of EIP-4844 blob transactions the EVM does not enforce on
+/// its own, the reason the blob transaction of `env` would be rejected.
+fn validate_blob_tx(env: &Env) -> Result<(), String> {
+    let Some(max_fee_per_blob_gas) = env.tx.max_fee_per_blob_gas else {
+        return Ok(());
+    };
+    let blobs = env.tx.blob_hashes.len();
+    if blobs == 0 {
+        return Err("blob transaction without blobs".to_string());
+    }
+    if blobs > MAX_BLOB_NUMBER_PER_BLOCK as usize {
+        return Err(format!(
+            "{} blobs, at most {} fit in a block",
+            blobs, MAX_BLOB_NUMBER_PER_BLOCK
+        ));
+    }
+    if let Some(hash) = env
+        .tx
+        .blob_hashes
+        .iter()
+        .find(|hash| hash[0] != VERSIONED_HASH_VERSION_KZG)
+    {
+        return Err(format!(
+            "versioned hash {} does not have version {:#04x}",
+            hash, VERSIONED_HASH_VERSION_KZG
+        ));
+    }
+    if !matches!(env.tx.transact_to, TransactTo::Call(_)) {
+        return Err("blob transaction without a to address".to_string());
+    }
+    if let Some(blob) = &env.block.blob_excess_gas_and_price {
+        let blob_gas_price = U256::from(blob.blob_gasprice);
+        if max_fee_per_blob_gas < blob_gas_price {
+            return Err(format!(
+                "max_fee_per_blob_gas {} is below the blob gas price {}",
+                max_fee_per_blob_gas, blob_gas_price
             ));
-            let mut state = revm::db::State::builder()
-                .with_cached_prestate(cache)
-                .with_bundle_update()
-                .build();
+        }
+    }
+    Ok(())
+}
+
+/// Consensus checks of the transaction of `env` against the `state_nonce`
+/// and `balance` of its sender that reject it before execution: intrinsic
+/// gas, EIP-3860 init code size, `nonce` and the balance for the maximum
+/// cost.
+fn preflight(
+    env: &Env,
+    spec_id: SpecId,
+    nonce: u64,
+    state_nonce: u64,
+    balance: U256,
+) -> Result<(), InvalidTransaction> {
+    let tx = &env.tx;
+    let is_create = tx.transact_to.is_create();
+    let authorizations = tx.authorization_list.as_ref().map_or(0, |list| list.len());
+    let intrinsic_gas = validate_initial_tx_gas(
+        spec_id,
+        &tx.data,
+        is_create,
+        &tx.access_list,
+        authorizations as u64,
+    );
+    if intrinsic_gas > tx.gas_limit {
+        return Err(InvalidTransaction::CallGasCostMoreThanGasLimit);
+    }
+    if is_create && SpecId::enabled(spec_id, SpecId::SHANGHAI) && tx.data.len() > MAX_INITCODE_SIZE
+    {
+        return Err(InvalidTransaction::CreateInitCodeSizeLimit);
+    }
+
+    if nonce > state_nonce {
+        return Err(InvalidTransaction::NonceTooHigh {
+            tx: nonce,
+            state: state_nonce,
+        });
+    }
+    if nonce < state_nonce {
+        return Err(InvalidTransaction::NonceTooLow {
+            tx: nonce,
+            state: state_nonce,
+        });
+    }
+
+    let blob_gas = U256::from(GAS_PER_BLOB * tx.blob_hashes.len() as u64);
+    let max_fee = U256::from(tx.gas_limit)
+        .checked_mul(tx.gas_price)
+        .and_then(|fee| fee.checked_add(tx.value))
+        .and_then(|fee| fee.checked_add(tx.max_fee_per_blob_gas.unwrap_or_default() * blob_gas))
+        .ok_or(InvalidTransaction::OverflowPaymentInTransaction)?;
+    if max_fee > balance {
+        return Err(InvalidTransaction::LackOfFundForMaxFee {
+            fee: Box::new(max_fee),
+            balance: Box::new(balance),
+        });
+    }
+    Ok(())
+}
+
+/// Database of a transaction, reading the shared pre state of its unit.
+type PreDb<'a> = WrapDatabaseRef<&'a CacheDB<EmptyDB>>;
+
+/// Run the transaction of `env` over `pre`, with an EIP-3155 tracer writing to
+/// `trace` if given. The EIP-4788 system call storing `beacon_root` goes
+/// first.
+fn transact<'a>(
+    pre: &'a CacheDB<EmptyDB>,
+    env: &Env,
+    spec_id: SpecId,
+    beacon_root: Option<B256>,
+    trace: Option<Box<dyn std::io::Write>>,
+    watch: Option<Watch>,
+) -> (
+    Result<ExecutionResult, EVMError<Infallible>>,
+    State<PreDb<'a>>,
+    Timing,
+    Option<Watch>,
+) {
+    let start = Instant::now();
+    let mut state = State::builder()
+        .with_database_ref(pre)
+        .with_bundle_update()
+        .build();
+    state.set_state_clear_flag(SpecId::enabled(
+        spec_id,
+        revm::primitives::SpecId::SPURIOUS_DRAGON,
+    ));
+    if let Some(beacon_root) = beacon_root {
+        apply_beacon_root(&mut state, env, spec_id, beacon_root);
+    }
+    let setup_end = Instant::now();
+    let mut watched = None;
+    let exec_result = match (trace, watch) {
+        (None, None) => Evm::builder()
+            .with_db(&mut state)
+            .modify_env(|e| **e = env.clone())
+            .with_spec_id(spec_id)
+            .append_handler_register(goat_precompiles::register(env.cfg.chain_id))
+            .build()
+            .transact_commit(),
+        (None, Some(watch)) => {
             let mut evm = Evm::builder()
                 .with_db(&mut state)
                 .modify_env(|e| **e = env.clone())
+                .with_external_context(watch)
                 .with_spec_id(spec_id)
+                .append_handler_register(inspector_handle_register)
+                .append_handler_register(goat_precompiles::register(env.cfg.chain_id))
                 .build();
+            let result = evm.transact_commit();
+            watched = Some(evm.into_context().external);
+            result
+        }
+        (Some(out), _) => Evm::builder()
+            .with_db(&mut state)
+            .modify_env(|e| **e = env.clone())
+            .with_external_context(TracerEip3155::new(out).without_summary())
+            .with_spec_id(spec_id)
+            .append_handler_register(inspector_handle_register)
+            .append_handler_register(goat_precompiles::register(env.cfg.chain_id))
+            .build()
+            .transact_commit(),
+    };
+    let timing = Timing {
+        setup_micros: setup_end.duration_since(start).as_micros() as u64,
+        evm_micros: setup_end.elapsed().as_micros() as u64,
+    };
+    (exec_result, state, timing, watched)
+}
 
-            // do the deed
-            //let timer = Instant::now();
-            let mut check = || {
-                let exec_result = evm.transact_commit();
-
-                match (&test.expect_exception, &exec_result) {
-                    // do nothing
-                    (None, Ok(_)) => (),
-                    // return okay, exception is expected.
-                    (Some(_), Err(_e)) => {
-                        return Ok(());
-                    }
-                    _ => {
-                        let s = exec_result.clone().err().map(|e| e.to_string()).unwrap();
-                        return Err(s);
-                    }
-                }
-                Ok(())
-            };
+/// Split of the time `transact` took.
+#[derive(Debug, Clone, Copy)]
+struct Timing {
+    setup_micros: u64,
+    evm_micros: u64,
+}
 
-            let Err(e) = check() else { continue };
+/// Inspector of an untraced execution. Past `deadline` it halts every frame
+/// out of gas, the clock being read every DEADLINE_STEPS instructions, and it
+/// records the contracts that ran SELFDESTRUCT.
+struct Watch {
+    deadline: Option<Instant>,
+    steps: u32,
+    /// Halted by the deadline, the result is meaningless then.
+    timed_out: bool,
+    destructed: Vec<Address>,
+}
 
-            return Err(e);
+const DEADLINE_STEPS: u32 = 4096;
+
+impl Watch {
+    fn new(timeout: Option<Duration>) -> Self {
+        Self {
+            deadline: timeout.map(|timeout| Instant::now() + timeout),
+            steps: 0,
+            timed_out: false,
+            destructed: Vec::new(),
+        }
+    }
+}
+
+impl<DB: Database> Inspector<DB> for Watch {
+    fn step(&mut self, interp: &mut Interpreter, _context: &mut EvmContext<DB>) {
+        let Some(deadline) = self.deadline else {
+            return;
+        };
+        self.steps = self.steps.wrapping_add(1);
+        if !self.timed_out && self.steps % DEADLINE_STEPS == 0 {
+            self.timed_out = Instant::now() >= deadline;
+        }
+        if self.timed_out {
+            interp.instruction_result = InstructionResult::OutOfGas;
+        }
+    }
+
+    fn selfdestruct(&mut self, contract: Address, _target: Address, _value: U256) {
+        if !self.destructed.contains(&contract) {
+            self.destructed.push(contract);
         }
     }
+}
+
+/// Reject a transaction whose gas limit is above `max_tx_gas_ratio` times the
+/// block gas limit, before it gets to expand memory for minutes.
+fn check_gas_budget(
+    spec_name: &SpecName,
+    index: usize,
+    env: &Env,
+    config: &CheckConfig,
+) -> Result<(), CheckError> {
+    if config.max_tx_gas_ratio == 0 {
+        return Ok(());
+    }
+    let budget = env
+        .block
+        .gas_limit
+        .saturating_mul(U256::from(config.max_tx_gas_ratio));
+    if U256::from(env.tx.gas_limit) > budget {
+        return Err(CheckError::GasBudgetExceeded {
+            spec: format!("{:?}", spec_name),
+            index,
+            gas_limit: env.tx.gas_limit,
+            budget,
+        });
+    }
     Ok(())
 }
+
+/// Credit the withdrawals of the block of `unit`, which come after its
+/// transactions, returning the wei credited. Their amounts are in gwei.
+fn apply_withdrawals<DB: Database<Error = Infallible>>(
+    state: &mut State<DB>,
+    unit: &TestUnit,
+    spec_id: SpecId,
+) -> U256 {
+    let Some(withdrawals) = &unit.env.withdrawals else {
+        return U256::ZERO;
+    };
+    if !SpecId::enabled(spec_id, SpecId::SHANGHAI) {
+        return U256::ZERO;
+    }
+    let credits: Vec<_> = withdrawals
+        .iter()
+        .map(|withdrawal| {
+            (
+                withdrawal.address,
+                u128::from(withdrawal.amount) * 1_000_000_000,
+            )
+        })
+        .collect();
+    let withdrawn = credits
+        .iter()
+        .fold(U256::ZERO, |sum, (_, amount)| sum + U256::from(*amount));
+    state
+        .increment_balances(credits)
+        .unwrap_or_else(|e| match e {});
+    withdrawn
+}
+
+/// Caller of system calls, EIP-4788.
+const SYSTEM_ADDRESS: Address = address!("fffffffffffffffffffffffffffffffffffffffe");
+const BEACON_ROOTS_ADDRESS: Address = address!("000f3df6d732807ef1319fb7b8bb8522d0beac02");
+
+/// Store `beacon_root` in the beacon roots contract the way a Cancun block
+/// begins, from the system address and without charging gas to the block.
+fn apply_beacon_root<DB: Database<Error = Infallible>>(
+    state: &mut State<DB>,
+    env: &Env,
+    spec_id: SpecId,
+    beacon_root: B256,
+) {
+    let mut env = env.clone();
+    env.tx = TxEnv {
+        caller: SYSTEM_ADDRESS,
+        transact_to: TransactTo::Call(BEACON_ROOTS_ADDRESS),
+        data: Bytes::copy_from_slice(beacon_root.as_slice()),
+        gas_limit: 30_000_000,
+        ..Default::default()
+    };
+    env.cfg.disable_base_fee = true;
+    env.cfg.disable_block_gas_limit = true;
+    let coinbase = env.block.coinbase;
+    let result = Evm::builder()
+        .with_db(&mut *state)
+        .modify_env(|e| **e = env)
+        .with_spec_id(spec_id)
+        .build()
+        .transact();
+    match result {
+        Ok(ResultAndState {
+            state: mut changes, ..
+        }) => {
+            // The system call leaves no trace on the caller and the coinbase.
+            changes.remove(&SYSTEM_ADDRESS);
+            changes.remove(&coinbase);
+            state.commit(changes);
+        }
+        Err(e) => log::warn!("Beacon root system call failed: {:?}", e),
+    }
+}
+
+/// An account changed by a transaction, code by hash only.
+#[derive(Serialize)]
+struct AccountDiff {
+    address: Address,
+    created: bool,
+    destroyed: bool,
+    old_balance: Option<U256>,
+    new_balance: Option<U256>,
+    old_nonce: Option<u64>,
+    new_nonce: Option<u64>,
+    old_code_hash: Option<B256>,
+    new_code_hash: Option<B256>,
+    /// Changed slots in ascending order.
+    storage: Vec<SlotDiff>,
+}
+
+#[derive(Serialize)]
+struct SlotDiff {
+    slot: U256,
+    old: U256,
+    new: U256,
+}
+
+/// Write the accounts changed by test `index` of `spec_name`, sorted by
+/// address so that dumps of two runs can be diffed.
+fn write_state_diff(
+    trace: &CheckTrace,
+    unit: &str,
+    spec_name: &SpecName,
+    index: usize,
+    state: &mut State<PreDb<'_>>,
+) {
+    state.merge_transitions(BundleRetention::PlainState);
+    let mut accounts: Vec<AccountDiff> = state
+        .bundle_state
+        .state
+        .iter()
+        .filter(|(_, account)| {
+            account.is_info_changed()
+                || account.is_contract_changed()
+                || !account.storage.is_empty()
+        })
+        .map(|(address, account)| {
+            let mut storage: Vec<SlotDiff> = account
+                .storage
+                .iter()
+                .filter(|(_, slot)| slot.is_changed())
+                .map(|(slot, value)| SlotDiff {
+                    slot: *slot,
+                    old: value.previous_or_original_value,
+                    new: value.present_value,
+                })
+                .collect();
+            storage.sort_by_key(|slot| slot.slot);
+            let old = account.original_info.as_ref();
+            let new = account.info.as_ref();
+            AccountDiff {
+                address: *address,
+                created: old.is_none() && new.is_some(),
+                destroyed: account.status.was_destroyed() || new.is_none(),
+                old_balance: old.map(|info| info.balance),
+                new_balance: new.map(|info| info.balance),
+                old_nonce: old.map(|info| info.nonce),
+                new_nonce: new.map(|info| info.nonce),
+                old_code_hash: old.map(|info| info.code_hash),
+                new_code_hash: new.map(|info| info.code_hash),
+                storage,
+            }
+        })
+        .collect();
+    accounts.sort_by_key(|account| account.address);
+
+    let path = trace.state_diff_path(unit, &format!("{:?}", spec_name), index);
+    let written = match serde_json::to_vec_pretty(&accounts) {
+        Ok(buf) => atomic_file::write(&path, buf).map_err(|e| e.to_string()),
+        Err(e) => Err(e.to_string()),
+    };
+    match written {
+        Ok(()) => log::info!("Wrote state diff to {}", path.display()),
+        Err(e) => log::warn!("Failed to write state diff {}: {}", path.display(), e),
+    }
+}
+
+/// Match the outcome of a test against its expected exception, logs and post
+/// state.
+fn verify_test(
+    spec_name: &SpecName,
+    index: usize,
+    test: &Test,
+    pre: &HashMap<Address, AccountInfo>,
+    exec_result: Result<ExecutionResult, EVMError<Infallible>>,
+    post: PostState,
+    loose_exceptions: bool,
+) -> Result<TestOutcome, CheckError> {
+    let mut outcome = TestOutcome::new(spec_name, index, test);
+    let logs = match (&test.expect_exception, exec_result) {
+        (None, Ok(result)) => {
+            let receipt = Receipt::from_result(&result, spec_name.to_spec_id());
+            outcome.gas_used = receipt.gas_used;
+            outcome.success = receipt.success;
+            outcome.output_len = result.output().map_or(0, |output| output.len());
+            outcome.logs_count = receipt.logs.len();
+            outcome.receipt = Some(receipt);
+            result.into_logs()
+        }
+        // okay, exception is expected.
+        (Some(exception), Err(e)) => {
+            if !loose_exceptions && !check_exceptions::matches(exception, &e) {
+                return Err(CheckError::ExceptionMismatch {
+                    spec: format!("{:?}", spec_name),
+                    index,
+                    expected: exception.clone(),
+                    actual: e.to_string(),
+                });
+            }
+            Vec::new()
+        }
+        (None, Err(e)) => {
+            return Err(CheckError::UnexpectedException {
+                spec: format!("{:?}", spec_name),
+                index,
+                err: e.to_string(),
+            });
+        }
+        (Some(exception), Ok(_)) => {
+            return Err(CheckError::MissingExpectedException {
+                spec: format!("{:?}", spec_name),
+                index,
+                expected: exception.clone(),
+            });
+        }
+    };
+
+    // A zero hash means the suite carries no expected logs.
+    if test.logs != B256::ZERO {
+        let actual = log_rlp_hash(&logs);
+        if actual != test.logs {
+            return Err(CheckError::LogsMismatch {
+                spec: format!("{:?}", spec_name),
+                index,
+                expected: test.logs,
+                actual,
+            });
+        }
+    }
+
+    // Suites written before the executor filled in the post state carry a
+    // zero hash.
+    if test.hash != B256::ZERO {
+        let actual = state_merkle_trie_root(post.accounts());
+        if actual != test.hash {
+            let mut diff = String::new();
+            if dump_state_diff() {
+                let expected = if test.post_state.is_empty() {
+                    pre
+                } else {
+                    &test.post_state
+                };
+                diff = state_diff(expected, post);
+            }
+            return Err(CheckError::PostStateMismatch {
+                spec: format!("{:?}", spec_name),
+                index,
+                expected: test.hash,
+                actual,
+                diff,
+            });
+        }
+    }
+    Ok(outcome)
+}
+
+/// Fee accounting of an executed transaction: the coinbase gains the
+/// priority fee from London on and the whole gas price before, and the base
+/// and blob fees are burned, the balances of all accounts dropping by them
+/// net of the `withdrawn` wei. The coinbase is not checked when the
+/// transaction sends from or to it.
+fn verify_fees(
+    outcome: &TestOutcome,
+    env: &Env,
+    withdrawn: U256,
+    pre: &HashMap<Address, AccountInfo>,
+    post: PostState,
+) -> Result<(), CheckError> {
+    let Some(receipt) = &outcome.receipt else {
+        return Ok(());
+    };
+    let london = SpecId::enabled(receipt.spec_id, SpecId::LONDON);
+    let gas_used = U256::from(outcome.gas_used);
+    let coinbase = env.block.coinbase;
+    let pre_balance = |address| pre.get(&address).map_or(U256::ZERO, |acc| acc.balance);
+    let price = env.effective_gas_price();
+    let reward_per_gas = if london {
+        price.saturating_sub(env.block.basefee)
+    } else {
+        price
+    };
+
+    let moves_coinbase = env.tx.caller == coinbase
+        || env.tx.transact_to.to() == Some(&coinbase)
+        || withdrawn != U256::ZERO;
+    if !moves_coinbase {
+        let expected = reward_per_gas * gas_used;
+        let actual = post.balance(coinbase).wrapping_sub(pre_balance(coinbase));
+        if actual != expected {
+            return Err(CheckError::CoinbaseRewardMismatch {
+                spec: outcome.spec.clone(),
+                index: outcome.index,
+                expected,
+                actual,
+            });
+        }
+    }
+
+    let expected = env.calc_data_fee().unwrap_or_default() + (price - reward_per_gas) * gas_used;
+    let pre_total = pre
+        .values()
+        .fold(U256::ZERO, |sum, acc| sum.wrapping_add(acc.balance));
+    let post_total = post.accounts().fold(U256::ZERO, |sum, (_, info, _)| {
+        sum.wrapping_add(info.balance)
+    });
+    let actual = pre_total.wrapping_add(withdrawn).wrapping_sub(post_total);
+    if actual != expected {
+        return Err(CheckError::BurnMismatch {
+            spec: outcome.spec.clone(),
+            index: outcome.index,
+            expected,
+            actual,
+        });
+    }
+    Ok(())
+}
+
+/// CHECK_STATE_DIFF=true appends the per-account diff to post state
+/// mismatch errors.
+fn dump_state_diff() -> bool {
+    env::var("CHECK_STATE_DIFF")
+        .unwrap_or("false".to_string())
+        .parse()
+        .unwrap_or(false)
+}
+
+/// The accounts after a transaction executed over `pre`: those it loaded into
+/// `cache` on top of the untouched ones of `pre`. Slots it did not load keep
+/// their pre value unless the account was destroyed.
+#[derive(Clone, Copy)]
+struct PostState<'a> {
+    pre: &'a CacheState,
+    cache: &'a CacheState,
+    /// Accounts left out, see `selfdestruct_legacy_suites`.
+    removed: &'a [Address],
+}
+
+type Slots<'a> = Box<dyn Iterator<Item = (&'a U256, &'a U256)> + 'a>;
+
+impl<'a> PostState<'a> {
+    fn accounts(
+        self,
+    ) -> impl Iterator<Item = (Address, &'a revm::primitives::AccountInfo, Slots<'a>)> {
+        let Self {
+            pre,
+            cache,
+            removed,
+        } = self;
+        let touched = cache.accounts.iter().filter_map(move |(address, account)| {
+            if removed.contains(address) {
+                return None;
+            }
+            let post = account.account.as_ref()?;
+            let kept = pre
+                .accounts
+                .get(address)
+                .filter(|_| !account.status.was_destroyed())
+                .and_then(|pre| pre.account.as_ref())
+                .into_iter()
+                .flat_map(|pre| &pre.storage)
+                .filter(|(slot, _)| !post.storage.contains_key(*slot));
+            let slots: Slots<'a> = Box::new(kept.chain(&post.storage));
+            Some((*address, &post.info, slots))
+        });
+        let untouched = pre
+            .accounts
+            .iter()
+            .filter(move |(address, _)| !cache.accounts.contains_key(*address))
+            .filter_map(|(address, account)| {
+                let account = account.account.as_ref()?;
+                let slots: Slots<'a> = Box::new(account.storage.iter());
+                Some((*address, &account.info, slots))
+            });
+        touched.chain(untouched)
+    }
+
+    fn balance(self, address: Address) -> U256 {
+        let account = self
+            .cache
+            .accounts
+            .get(&address)
+            .or_else(|| self.pre.accounts.get(&address));
+        account
+            .and_then(|account| account.account.as_ref())
+            .map_or(U256::ZERO, |account| account.info.balance)
+    }
+}
+
+/// One line per differing balance, nonce or storage slot between `expected`
+/// and the accounts of `post`. Missing slots count as zero.
+fn state_diff(expected: &HashMap<Address, AccountInfo>, post: PostState) -> String {
+    let actual: BTreeMap<_, _> = post
+        .accounts()
+        .map(|(address, info, slots)| {
+            let storage: HashMap<U256, U256> = slots.map(|(k, v)| (*k, *v)).collect();
+            (address, (info, storage))
+        })
+        .collect();
+    let addresses: BTreeSet<_> = expected.keys().chain(actual.keys()).collect();
+    let mut out = String::new();
+    for address in addresses {
+        let (expected, (info, storage)) = match (expected.get(address), actual.get(address)) {
+            (Some(expected), Some(actual)) => (expected, actual),
+            (Some(_), None) => {
+                let _ = write!(out, "\n  {}: missing", address);
+                continue;
+            }
+            (None, Some((info, _))) => {
+                let _ = write!(
+                    out,
+                    "\n  {}: unexpected, balance {} nonce {}",
+                    address, info.balance, info.nonce
+                );
+                continue;
+            }
+            (None, None) => continue,
+        };
+        if expected.balance != info.balance {
+            let _ = write!(
+                out,
+                "\n  {}: balance {} != {}",
+                address, expected.balance, info.balance
+            );
+        }
+        if expected.nonce != info.nonce {
+            let _ = write!(
+                out,
+                "\n  {}: nonce {} != {}",
+                address, expected.nonce, info.nonce
+            );
+        }
+        let slots: BTreeSet<_> = expected.storage.keys().chain(storage.keys()).collect();
+        for slot in slots {
+            let expected = expected.storage.get(slot).copied().unwrap_or(U256::ZERO);
+            let actual = storage.get(slot).copied().unwrap_or(U256::ZERO);
+            if expected != actual {
+                let _ = write!(
+                    out,
+                    "\n  {}: storage {} {} != {}",
+                    address, slot, expected, actual
+                );
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use revm::primitives::{b256, hex, Output, SuccessReason};
+
+    /// An ERC-20 Transfer of 1000 from 0xa1 to 0xb2 on the USDT contract,
+    /// and an anonymous log without data.
+    fn transfer_logs() -> Vec<Log> {
+        vec![
+            Log::new_unchecked(
+                address!("dac17f958d2ee523a2206206994597c13d831ec7"),
+                vec![
+                    b256!("ddf252ad1be2c89b69c2b068fc378daa952ba7f163c4a11628f55a4df523b3ef"),
+                    b256!("00000000000000000000000000000000000000000000000000000000000000a1"),
+                    b256!("00000000000000000000000000000000000000000000000000000000000000b2"),
+                ],
+                Bytes::from(U256::from(1000).to_be_bytes_vec()),
+            ),
+            Log::new_unchecked(
+                address!("00000000000000000000000000000000000000a1"),
+                vec![],
+                Bytes::new(),
+            ),
+        ]
+    }
+
+    #[test]
+    fn receipt_logs_bloom() {
+        let result = ExecutionResult::Success {
+            reason: SuccessReason::Return,
+            gas_used: 51_883,
+            gas_refunded: 0,
+            logs: transfer_logs(),
+            output: Output::Call(Bytes::new()),
+        };
+        let receipt = Receipt::from_result(&result, SpecId::CANCUN);
+        assert!(receipt.success);
+        assert_eq!(receipt.gas_used, 51_883);
+        assert_eq!(receipt.cumulative_gas_used, 51_883);
+        assert_eq!(
+            receipt.logs_bloom,
+            Bloom::new(hex!(
+                "0000000000000000010000000000000020000000000000000000000000000000"
+                "0000000000000000000000000000010000000000000000000000000000000000"
+                "0000000000000000400000080000000000000000000000000000000000000000"
+                "0000000000000000000000000000000000000000000000000020001000000000"
+                "0004000000000000000000000000000000000000000000000000000000100000"
+                "0000000010000000000000800000000000000000004000000000000000000000"
+                "0000000200000000020000000000000000000000000000000000000000000000"
+                "0000000010000000000000000000000000000000000000000000000000000000"
+            ))
+        );
+        assert_eq!(
+            log_rlp_hash(&receipt.logs),
+            b256!("da7746cd8946a6fa47c0379a4c5e566d551201d31c4d38f092cd4891e084b91d")
+        );
+    }
+
+    #[test]
+    fn receipt_without_logs_has_an_empty_bloom() {
+        let result = ExecutionResult::Revert {
+            gas_used: 21_000,
+            output: Bytes::new(),
+        };
+        let receipt = Receipt::from_result(&result, SpecId::SHANGHAI);
+        assert!(!receipt.success);
+        assert_eq!(receipt.logs_bloom, Bloom::ZERO);
+        assert!(receipt.logs.is_empty());
+    }
+
+    fn outcome(sender: Address, nonce: u64, gas_used: u64) -> TestOutcome {
+        TestOutcome {
+            spec: "Cancun".to_string(),
+            indexes: [0; 3],
+            gas_used,
+            success: true,
+            output_len: 0,
+            logs_count: 0,
+            receipt: Some(Receipt {
+                success: true,
+                gas_used,
+                cumulative_gas_used: gas_used,
+                logs: Vec::new(),
+                logs_bloom: Bloom::ZERO,
+                spec_id: SpecId::CANCUN,
+                sender,
+                nonce,
+            }),
+            index: 0,
+            calldata_len: 0,
+            setup_micros: 0,
+            evm_micros: 0,
+        }
+    }
+
+    #[test]
+    fn receipts_in_block_order() {
+        let (a, b) = (
+            address!("00000000000000000000000000000000000000aa"),
+            address!("00000000000000000000000000000000000000bb"),
+        );
+        // Keys out of tx order, and a unit with two post tests.
+        let units = [
+            ("0xff", vec![outcome(a, 0, 100)]),
+            ("0x01", vec![outcome(a, 1, 200)]),
+            ("0x80", vec![outcome(b, 7, 300), outcome(b, 7, 301)]),
+        ];
+        let report = SuiteReport {
+            units: units
+                .into_iter()
+                .map(|(name, per_test)| {
+                    let unit = UnitReport {
+                        per_test,
+                        ..Default::default()
+                    };
+                    (name.to_string(), unit)
+                })
+                .collect(),
+        };
+        let order = |receipts: Vec<Receipt>| -> Vec<_> {
+            receipts
+                .iter()
+                .map(|r| (r.sender, r.nonce, r.gas_used, r.cumulative_gas_used))
+                .collect()
+        };
+        assert_eq!(
+            order(report.receipts(&[b, a, a])),
+            [(b, 7, 300, 300), (a, 0, 100, 400), (a, 1, 200, 600)]
+        );
+        assert_eq!(
+            order(report.receipts(&[a])),
+            [(a, 0, 100, 100), (a, 1, 200, 300), (b, 7, 300, 600)]
+        );
+    }
+
+    #[test]
+    fn recover_address_from_bytes_or_hex() {
+        let key = hex!("45a915e4d060149eb4365960e6a7a45f334393093061116b197e3240065ff2d8");
+        let sender = address!("a94f5374fce5edbc8e2a8697c15331677e6ebf0b");
+        assert_eq!(recover_address(&key), Ok(sender));
+        let text = hex::encode(key);
+        assert_eq!(recover_address(text.as_bytes()), Ok(sender));
+        assert_eq!(
+            recover_address(format!("0x{}", text).as_bytes()),
+            Ok(sender)
+        );
+    }
+
+    #[test]
+    fn recover_address_key_errors() {
+        assert_eq!(recover_address(&[1; 31]), Err(KeyError::Length(31)));
+        assert_eq!(recover_address(&[1; 33]), Err(KeyError::Length(33)));
+        assert_eq!(recover_address(b"0xzz"), Err(KeyError::Length(4)));
+        assert_eq!(recover_address(b"0x0101"), Err(KeyError::Length(2)));
+        assert_eq!(recover_address(&[0; 32]), Err(KeyError::Zero));
+        // Not below the order of the curve.
+        assert_eq!(recover_address(&[0xff; 32]), Err(KeyError::NotOnCurve));
+    }
+
+    #[test]
+    fn state_diff_lists_every_difference() {
+        let (a, b, c) = (
+            address!("0000000000000000000000000000000000000011"),
+            address!("0000000000000000000000000000000000000022"),
+            address!("0000000000000000000000000000000000000033"),
+        );
+        let mut pre = CacheState::new(false);
+        pre.insert_account_with_storage(
+            a,
+            revm::primitives::AccountInfo {
+                balance: U256::from(10),
+                nonce: 1,
+                ..Default::default()
+            },
+            HashMap::from([(U256::from(1), U256::from(7))]),
+        );
+        pre.insert_account(
+            b,
+            revm::primitives::AccountInfo {
+                balance: U256::from(5),
+                ..Default::default()
+            },
+        );
+        let cache = CacheState::new(false);
+        let post = PostState {
+            pre: &pre,
+            cache: &cache,
+            removed: &[],
+        };
+        let account = |balance: u64, nonce: u64, storage: &[(u64, u64)]| AccountInfo {
+            balance: U256::from(balance),
+            code: Bytes::new(),
+            nonce,
+            storage: storage
+                .iter()
+                .map(|(k, v)| (U256::from(*k), U256::from(*v)))
+                .collect(),
+        };
+        let expected = HashMap::from([(a, account(11, 2, &[(1, 8)])), (c, account(0, 0, &[]))]);
+        assert_eq!(
+            state_diff(&expected, post),
+            "\n  0x0000000000000000000000000000000000000011: balance 11 != 10\
+             \n  0x0000000000000000000000000000000000000011: nonce 2 != 1\
+             \n  0x0000000000000000000000000000000000000011: storage 1 8 != 7\
+             \n  0x0000000000000000000000000000000000000022: unexpected, balance 5 nonce 0\
+             \n  0x0000000000000000000000000000000000000033: missing"
+        );
+
+        let removed = [a];
+        let post = PostState {
+            removed: &removed,
+            ..post
+        };
+        assert!(state_diff(&expected, post)
+            .contains("0000000000000000000000000000000000000011: missing"));
+    }
+}
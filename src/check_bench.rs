@@ -0,0 +1,101 @@
+use crate::arg_value;
+use crate::atomic_file;
+use crate::check::{SuiteReport, TestOutcome};
+use crate::error::ProverError;
+use std::fmt::Write;
+
+/// `check <suite> --bench [RUNS] [--bench-top K] [--bench-csv FILE]`: time
+/// every transaction over RUNS executions (1) and print the slowest K (10).
+#[derive(Debug, Clone)]
+pub struct BenchOptions {
+    pub runs: u32,
+    pub top: usize,
+    pub csv: Option<String>,
+}
+
+impl BenchOptions {
+    /// `None` without `--bench`.
+    pub fn from_args(args: &[String]) -> Option<Self> {
+        let i = args.iter().position(|arg| arg == "--bench")?;
+        Some(Self {
+            runs: args
+                .get(i + 1)
+                .and_then(|runs| runs.parse().ok())
+                .unwrap_or(1),
+            top: arg_value(args, "--bench-top")
+                .and_then(|top| top.parse().ok())
+                .unwrap_or(10),
+            csv: arg_value(args, "--bench-csv").map(str::to_string),
+        })
+    }
+}
+
+/// Print the histogram of the EVM time of the transactions of `report` and
+/// the slowest ones, and write them all to the CSV file if asked to.
+pub fn report(report: &SuiteReport, options: &BenchOptions) -> anyhow::Result<()> {
+    let mut outcomes: Vec<(&str, &TestOutcome)> = report
+        .units
+        .iter()
+        .flat_map(|(name, unit)| {
+            unit.per_test
+                .iter()
+                .map(move |outcome| (name.as_str(), outcome))
+        })
+        .collect();
+
+    // Power of two buckets, bucket `b` holds times below 2^b micros.
+    let mut buckets = Vec::<usize>::new();
+    for (_, outcome) in &outcomes {
+        let bucket = (u64::BITS - outcome.evm_micros.leading_zeros()) as usize;
+        if buckets.len() <= bucket {
+            buckets.resize(bucket + 1, 0);
+        }
+        buckets[bucket] += 1;
+    }
+    let max = buckets.iter().copied().max().unwrap_or(0).max(1);
+    println!("evm micros histogram over {} runs", options.runs);
+    for (bucket, count) in buckets.iter().enumerate() {
+        println!(
+            "< {:>10}  {:>6}  {}",
+            1u64 << bucket,
+            count,
+            "#".repeat(count * 50 / max)
+        );
+    }
+
+    outcomes.sort_by_key(|(_, outcome)| std::cmp::Reverse(outcome.evm_micros));
+    println!("slowest {} transactions", options.top);
+    println!("unit\tspec\tindex\tgas_used\tcalldata_len\tsetup_micros\tevm_micros");
+    for (name, outcome) in outcomes.iter().take(options.top) {
+        println!(
+            "{}\t{}\t{}\t{}\t{}\t{}\t{}",
+            name,
+            outcome.spec,
+            outcome.index,
+            outcome.gas_used,
+            outcome.calldata_len,
+            outcome.setup_micros,
+            outcome.evm_micros
+        );
+    }
+
+    if let Some(path) = &options.csv {
+        let mut csv = "unit,spec,index,gas_used,calldata_len,setup_micros,evm_micros\n".to_string();
+        for (name, outcome) in &outcomes {
+            let _ = writeln!(
+                csv,
+                "\"{}\",{},{},{},{},{},{}",
+                name.replace('"', "\"\""),
+                outcome.spec,
+                outcome.index,
+                outcome.gas_used,
+                outcome.calldata_len,
+                outcome.setup_micros,
+                outcome.evm_micros
+            );
+        }
+        atomic_file::write(path, csv).map_err(|e| ProverError::io(path, e))?;
+        println!("Timings written to {}", path);
+    }
+    Ok(())
+}
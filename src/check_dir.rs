@@ -0,0 +1,126 @@
+use crate::check::CheckConfig;
+use crate::check_trace::CheckTrace;
+use crate::error::ProverError;
+use crate::{check, suite};
+use futures::stream::{self, StreamExt};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+enum FileResult {
+    Pass,
+    /// The file could not be read or decoded into a suite.
+    Corrupt(String),
+    /// The suite decoded but its execution failed.
+    Fail(String),
+}
+
+fn check_file(path: &Path, config: &CheckConfig, trace: Option<CheckTrace>) -> FileResult {
+    let buf = match suite::read(path) {
+        Ok(buf) => buf,
+        Err(e) => return FileResult::Corrupt(e.to_string()),
+    };
+    let test_suite = match check::decode_test_suite(&buf) {
+        Ok(test_suite) => test_suite,
+        Err(e) => return FileResult::Corrupt(e.to_string()),
+    };
+    let trace = trace.map(|trace| CheckTrace {
+        block_no: suite::block_no(path).unwrap_or_default(),
+        ..trace
+    });
+    match check::execute_suite(&test_suite, config, trace.as_ref()) {
+        Ok(_) => FileResult::Pass,
+        Err(e) => FileResult::Fail(e.to_string()),
+    }
+}
+
+/// Check every suite file in `dir` matching `pattern`, one per CPU at a time,
+/// and print a line per file. Fails after the whole directory was checked if
+/// any file failed.
+pub async fn check_dir(dir: &str, pattern: &str, config: CheckConfig) -> anyhow::Result<()> {
+    let glob_pattern = Path::new(dir).join(pattern);
+    let mut files: Vec<PathBuf> = glob::glob(&glob_pattern.to_string_lossy())
+        .map_err(|e| ProverError::Config(format!("invalid --pattern {:?}: {}", pattern, e)))?
+        .filter_map(Result::ok)
+        .filter(|path| suite::is_suite_file(path))
+        .collect();
+    files.sort();
+    let parallelism = std::thread::available_parallelism().map_or(1, |n| n.get());
+    let trace = CheckTrace::from_env(dir, 0)?;
+
+    let start = Instant::now();
+    let results = stream::iter(files)
+        .map(|path| {
+            let trace = trace.clone();
+            let config = config.clone();
+            tokio::task::spawn_blocking(move || {
+                let start = Instant::now();
+                let result = check_file(&path, &config, trace);
+                (path, result, start.elapsed())
+            })
+        })
+        .buffered(parallelism)
+        .collect::<Vec<_>>()
+        .await
+        .into_iter()
+        .collect::<Result<Vec<(PathBuf, FileResult, Duration)>, _>>()?;
+
+    let mut passed = 0;
+    let mut corrupt = Vec::new();
+    let mut failed = Vec::new();
+    for (path, result, elapsed) in &results {
+        let (status, error) = match result {
+            FileResult::Pass => {
+                passed += 1;
+                ("pass", "")
+            }
+            FileResult::Corrupt(e) => {
+                corrupt.push(path);
+                ("corrupt", e.as_str())
+            }
+            FileResult::Fail(e) => {
+                failed.push(path);
+                ("fail", e.as_str())
+            }
+        };
+        println!(
+            "{}\t{}\t{}ms\t{}",
+            path.display(),
+            status,
+            elapsed.as_millis(),
+            error
+        );
+    }
+    println!(
+        "{} files checked in {:?}: pass: {} fail: {} corrupt: {}",
+        results.len(),
+        start.elapsed(),
+        passed,
+        failed.len(),
+        corrupt.len()
+    );
+    if failed.is_empty() && corrupt.is_empty() {
+        return Ok(());
+    }
+    let block_nos = |paths: &[&PathBuf]| {
+        paths
+            .iter()
+            .map(|path| match suite::block_no(path) {
+                Some(block_no) => block_no.to_string(),
+                None => path.display().to_string(),
+            })
+            .collect::<Vec<_>>()
+            .join(",")
+    };
+    if !failed.is_empty() {
+        println!("Failed blocks: {}", block_nos(&failed));
+    }
+    if !corrupt.is_empty() {
+        println!("Corrupt blocks: {}", block_nos(&corrupt));
+    }
+    Err(ProverError::Check(format!(
+        "{} suites failed, {} corrupt",
+        failed.len(),
+        corrupt.len()
+    ))
+    .into())
+}
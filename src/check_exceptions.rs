@@ -0,0 +1,134 @@
+use revm::primitives::{EVMError, InvalidTransaction};
+
+/// Expected exceptions that do not single out one revm error, any error
+/// satisfies them.
+const AMBIGUOUS: &[&str] = &["typenotsupported", "trtypenotsupported", "invalidtx"];
+
+/// Whether `err` is of the kind an expect_exception string names. The string
+/// may list alternatives separated by `|`, each in the ethereum/tests form
+/// `TransactionException.INTRINSIC_GAS_TOO_LOW`, the legacy `TR_IntrinsicGas`
+/// form or as written by our suite generator. Unknown names match nothing.
+pub fn matches<E>(expected: &str, err: &EVMError<E>) -> bool {
+    expected.split('|').any(|alternative| {
+        let name = normalize(alternative);
+        if AMBIGUOUS.contains(&name.as_str()) {
+            return true;
+        }
+        match err {
+            EVMError::Transaction(err) => matches_kind(&name, err).unwrap_or(false),
+            _ => false,
+        }
+    })
+}
+
+/// Lower-case alphanumerics of the name without its `...Exception.` prefix.
+fn normalize(name: &str) -> String {
+    let name = name.trim();
+    let name = name.rsplit('.').next().unwrap_or(name);
+    name.chars()
+        .filter(char::is_ascii_alphanumeric)
+        .map(|c| c.to_ascii_lowercase())
+        .collect()
+}
+
+/// `None` for names without a known kind.
+fn matches_kind(name: &str, err: &InvalidTransaction) -> Option<bool> {
+    use InvalidTransaction::*;
+    let matched = match name {
+        "intrinsicgastoolow" | "trintrinsicgas" => matches!(err, CallGasCostMoreThanGasLimit),
+        "insufficientaccountfunds" | "trnofunds" | "trnofundsovergaslimit" => {
+            matches!(
+                err,
+                LackOfFundForMaxFee { .. } | OverflowPaymentInTransaction
+            )
+        }
+        "gasallowanceexceeded" | "trgaslimitreached" => {
+            matches!(err, CallerGasLimitMoreThanBlock)
+        }
+        "priorityfeegreaterthanmaxfeepergas" | "trtipgtfeecap" => {
+            matches!(err, PriorityFeeGreaterThanMaxFee)
+        }
+        "insufficientmaxfeepergas" | "trfeecaplessthanblocks" => {
+            matches!(err, GasPriceLessThanBasefee)
+        }
+        "sendernoteoa" | "trnoteoa" => matches!(err, RejectCallerWithCode),
+        "nonceismax" | "noncemismatchoverflow" => matches!(err, NonceOverflowInTransaction),
+        "noncemismatchtoohigh" | "noncetoohigh" => matches!(err, NonceTooHigh { .. }),
+        "noncemismatchtoolow" | "noncetoolow" => matches!(err, NonceTooLow { .. }),
+        "initcodesizeexceeded" | "trinitcodelimitexceeded" => {
+            matches!(err, CreateInitCodeSizeLimit)
+        }
+        "invalidchainid" | "trinvalidchainid" => matches!(err, InvalidChainId),
+        "type1txprefork" => matches!(err, AccessListNotSupported),
+        "type3txprefork" => matches!(
+            err,
+            BlobVersionedHashesNotSupported | MaxFeePerBlobGasNotSupported
+        ),
+        "type3txcontractcreation" => matches!(err, BlobCreateTransaction),
+        "type3txzeroblobs" => matches!(err, EmptyBlobs),
+        "type3txinvalidblobversionedhash" => matches!(err, BlobVersionNotSupported),
+        "type3txblobcountexceeded" | "type3txmaxblobgasallowanceexceeded" => {
+            matches!(err, TooManyBlobs { .. })
+        }
+        "insufficientmaxfeeperblobgas" => matches!(err, BlobGasPriceGreaterThanMax),
+        _ => return None,
+    };
+    Some(matched)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tx_error(err: InvalidTransaction) -> EVMError<()> {
+        EVMError::Transaction(err)
+    }
+
+    #[test]
+    fn names_in_every_form() {
+        let err = tx_error(InvalidTransaction::CallGasCostMoreThanGasLimit);
+        assert!(matches("TransactionException.INTRINSIC_GAS_TOO_LOW", &err));
+        assert!(matches("TR_IntrinsicGas", &err));
+        assert!(matches(" intrinsic gas too low ", &err));
+        assert!(!matches("TransactionException.NONCE_IS_MAX", &err));
+    }
+
+    #[test]
+    fn alternatives() {
+        let err = tx_error(InvalidTransaction::NonceTooLow { tx: 0, state: 1 });
+        assert!(matches(
+            "TransactionException.NONCE_MISMATCH_TOO_HIGH|TransactionException.NONCE_MISMATCH_TOO_LOW",
+            &err
+        ));
+        assert!(!matches(
+            "TransactionException.NONCE_MISMATCH_TOO_HIGH|TransactionException.INITCODE_SIZE_EXCEEDED",
+            &err
+        ));
+    }
+
+    #[test]
+    fn ambiguous_names_match_any_error() {
+        let errors = [
+            tx_error(InvalidTransaction::RejectCallerWithCode),
+            EVMError::Database(()),
+            EVMError::Custom("boom".to_string()),
+        ];
+        for err in &errors {
+            assert!(matches("TransactionException.TYPE_NOT_SUPPORTED", err));
+            assert!(matches("TR_TypeNotSupported", err));
+            assert!(matches("invalid tx", err));
+        }
+    }
+
+    #[test]
+    fn unknown_names_and_non_transaction_errors_match_nothing() {
+        assert!(!matches(
+            "TransactionException.SOMETHING_NEW",
+            &tx_error(InvalidTransaction::RejectCallerWithCode)
+        ));
+        assert!(!matches(
+            "TransactionException.INTRINSIC_GAS_TOO_LOW",
+            &EVMError::<()>::Database(())
+        ));
+    }
+}
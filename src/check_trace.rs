@@ -0,0 +1,148 @@
+use crate::error::ProverError;
+use std::env;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+/// Which transactions CHECK_TRACE writes EIP-3155 traces of, and
+/// CHECK_DUMP_STATE the state diffs of.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TraceMode {
+    /// Re-run a transaction with the tracer once its test failed, passing
+    /// tests run untraced.
+    Failed,
+    /// Trace every transaction as it is checked.
+    All,
+}
+
+/// Debug output of the transactions of a suite: EIP-3155 traces written to
+/// `{dir}/{block_no}_{unit}_{index}.trace.jsonl` and cut off after
+/// CHECK_TRACE_MAX_BYTES (64 MiB), and the accounts they changed written to
+/// `{dir}/{block_no}_{unit}_{spec}_{index}_statediff.json`.
+#[derive(Debug, Clone)]
+pub struct CheckTrace {
+    pub mode: Option<TraceMode>,
+    pub dump_state: Option<TraceMode>,
+    pub dir: PathBuf,
+    pub block_no: u64,
+    pub max_bytes: u64,
+}
+
+impl CheckTrace {
+    /// `None` unless CHECK_TRACE is `failed` or `all` or CHECK_DUMP_STATE is
+    /// `failed` or `always`.
+    pub fn from_env(dir: impl AsRef<Path>, block_no: u64) -> anyhow::Result<Option<Self>> {
+        let mode = mode_from_env("CHECK_TRACE", "off", "all")?;
+        let dump_state = mode_from_env("CHECK_DUMP_STATE", "never", "always")?;
+        if mode.is_none() && dump_state.is_none() {
+            return Ok(None);
+        }
+        let max_bytes = env::var("CHECK_TRACE_MAX_BYTES").unwrap_or("67108864".to_string());
+        Ok(Some(Self {
+            mode,
+            dump_state,
+            dir: dir.as_ref().to_path_buf(),
+            block_no,
+            max_bytes: max_bytes.parse().unwrap_or(67108864),
+        }))
+    }
+
+    pub fn path(&self, unit: &str, index: usize) -> PathBuf {
+        self.dir.join(format!(
+            "{}_{}_{}.trace.jsonl",
+            self.block_no,
+            sanitize(unit),
+            index
+        ))
+    }
+
+    pub fn state_diff_path(&self, unit: &str, spec: &str, index: usize) -> PathBuf {
+        self.dir.join(format!(
+            "{}_{}_{}_{}_statediff.json",
+            self.block_no,
+            sanitize(unit),
+            spec,
+            index
+        ))
+    }
+
+    /// Whether the state diff of a test that `failed` or not is dumped.
+    pub fn dumps_state(&self, failed: bool) -> bool {
+        match self.dump_state {
+            Some(TraceMode::All) => true,
+            Some(TraceMode::Failed) => failed,
+            None => false,
+        }
+    }
+
+    /// Writer for the trace of test `index` of `unit` if tracing `mode`,
+    /// `None` if the file cannot be created.
+    pub fn writer(&self, mode: TraceMode, unit: &str, index: usize) -> Option<Box<dyn Write>> {
+        if Some(mode) != self.mode {
+            return None;
+        }
+        let path = self.path(unit, index);
+        match File::create(&path) {
+            Ok(file) => {
+                log::info!("Writing trace to {}", path.display());
+                Some(Box::new(CappedWriter {
+                    inner: BufWriter::new(file),
+                    remaining: self.max_bytes,
+                    truncated: false,
+                }))
+            }
+            Err(e) => {
+                log::warn!("Failed to create trace file {}: {}", path.display(), e);
+                None
+            }
+        }
+    }
+}
+
+/// Mode of `var`, `None` for `off`, the default.
+fn mode_from_env(var: &str, off: &str, all: &str) -> Result<Option<TraceMode>, ProverError> {
+    let mode = env::var(var).unwrap_or(off.to_string());
+    match mode.as_str() {
+        "failed" => Ok(Some(TraceMode::Failed)),
+        _ if mode == off => Ok(None),
+        _ if mode == all => Ok(Some(TraceMode::All)),
+        _ => Err(ProverError::Config(format!(
+            "invalid {} {:?}, expected {}, failed or {}",
+            var, mode, off, all
+        ))),
+    }
+}
+
+fn sanitize(unit: &str) -> String {
+    unit.chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect()
+}
+
+/// Drops everything after the first `remaining` bytes, leaving a truncation
+/// marker line instead.
+struct CappedWriter<W: Write> {
+    inner: W,
+    remaining: u64,
+    truncated: bool,
+}
+
+impl<W: Write> Write for CappedWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.truncated {
+            return Ok(buf.len());
+        }
+        if buf.len() as u64 > self.remaining {
+            self.truncated = true;
+            self.inner.write_all(b"\n{\"truncated\":true}\n")?;
+            return Ok(buf.len());
+        }
+        self.remaining -= buf.len() as u64;
+        self.inner.write_all(buf)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
@@ -0,0 +1,313 @@
+use crate::atomic_file;
+use crate::schema::{self, SCHEMA_VERSION};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const CHECKPOINT_FILE: &str = "checkpoint.json";
+
+/// The last successfully proved block, persisted in `{OUTPUT_DIR}/checkpoint.json`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Checkpoint {
+    #[serde(default = "schema::legacy_schema_version")]
+    pub schema_version: u32,
+    pub last_block: u64,
+    /// Unix timestamp (seconds) at which the checkpoint was written.
+    pub timestamp: u64,
+    /// Proof file of `last_block`, `None` for execute-only or suite-only runs.
+    pub proof_path: Option<String>,
+    /// Corrections of the SEG_SIZE=auto estimate from the recent blocks.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub seg_size_ratios: Vec<f64>,
+}
+
+impl Checkpoint {
+    pub fn new(last_block: u64, proof_path: Option<&Path>) -> Self {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+        Self {
+            schema_version: SCHEMA_VERSION,
+            last_block,
+            timestamp,
+            proof_path: proof_path.map(|p| p.to_string_lossy().into_owned()),
+            seg_size_ratios: Vec::new(),
+        }
+    }
+
+    fn path(outdir: &str) -> PathBuf {
+        Path::new(outdir).join(CHECKPOINT_FILE)
+    }
+
+    /// Load the checkpoint from `outdir`, returns `None` if there is none yet.
+    pub fn load(outdir: &str) -> anyhow::Result<Option<Self>> {
+        let path = Self::path(outdir);
+        if !path.exists() {
+            return Ok(None);
+        }
+        let buf = std::fs::read(&path)?;
+        let checkpoint = serde_json::from_slice(&buf)
+            .map_err(|e| anyhow::anyhow!("invalid checkpoint {}: {}", path.display(), e))?;
+        Ok(Some(checkpoint))
+    }
+
+    /// The last block of the checkpoint in `outdir` a sequential run resumes
+    /// after, `None` without a checkpoint.
+    pub fn resume(outdir: &str) -> anyhow::Result<Option<u64>> {
+        Ok(Self::load(outdir)?.map(|checkpoint| {
+            log::info!(
+                "Resuming from checkpoint: last_block:{} proof:{:?}",
+                checkpoint.last_block,
+                checkpoint.proof_path
+            );
+            checkpoint.last_block
+        }))
+    }
+
+    /// Write the checkpoint atomically, a crash mid-write never leaves a
+    /// truncated checkpoint behind.
+    pub fn store(&self, outdir: &str) -> anyhow::Result<()> {
+        let path = Self::path(outdir);
+        let buf = serde_json::to_vec_pretty(self)?;
+        atomic_file::write(&path, buf)?;
+        Ok(())
+    }
+}
+
+/// Keeps the checkpoint on the contiguous prefix of completed blocks, a
+/// resumed run never skips a block that failed before it.
+#[derive(Debug, Default)]
+pub struct Frontier {
+    /// First block of each failed batch not recovered yet.
+    failed: BTreeSet<u64>,
+    /// Last block and proof of every batch completed after the first failure.
+    completed: BTreeMap<u64, Option<PathBuf>>,
+}
+
+impl Frontier {
+    /// Record the failure of the batch starting at `block_no`.
+    pub fn fail(&mut self, block_no: u64) {
+        self.failed.insert(block_no);
+    }
+
+    /// The batch ending at `last_block_no` is done with `proof_path`, returns
+    /// the block to checkpoint, `None` while an earlier block is failed.
+    pub fn complete(&mut self, last_block_no: u64, proof_path: Option<&Path>) -> Option<u64> {
+        if self.failed.is_empty() {
+            return Some(last_block_no);
+        }
+        self.completed
+            .insert(last_block_no, proof_path.map(Path::to_path_buf));
+        None
+    }
+
+    /// The failed batch `block_no..=last_block_no` was proved by a retry
+    /// pass, returns the block the checkpoint can move to and its proof.
+    pub fn recover(
+        &mut self,
+        block_no: u64,
+        last_block_no: u64,
+        proof_path: Option<&Path>,
+    ) -> Option<(u64, Option<PathBuf>)> {
+        let was_first = self.failed.first() == Some(&block_no);
+        if !self.failed.remove(&block_no) {
+            return None;
+        }
+        self.completed
+            .insert(last_block_no, proof_path.map(Path::to_path_buf));
+        match self.failed.first() {
+            None => {
+                let last = self.completed.pop_last();
+                self.completed.clear();
+                last
+            }
+            // Everything up to the next failure was done in order.
+            Some(&next_failed) if was_first => {
+                let held = self.completed.split_off(&next_failed);
+                let done = std::mem::replace(&mut self.completed, held);
+                let proof_path = done.get(&(next_failed - 1)).cloned().flatten();
+                Some((next_failed - 1, proof_path))
+            }
+            Some(_) => None,
+        }
+    }
+
+    /// A reorg from `fork` drops the failures on the replaced blocks, returns
+    /// the block to rewind the checkpoint to.
+    pub fn rewind(&mut self, fork: u64) -> Option<u64> {
+        self.failed.retain(|&block_no| block_no < fork);
+        self.completed.retain(|&block_no, _| block_no < fork);
+        if fork > 0 && self.failed.is_empty() {
+            Some(fork - 1)
+        } else {
+            None
+        }
+    }
+
+    /// First block the checkpoint is held before.
+    pub fn first_failed(&self) -> Option<u64> {
+        self.failed.first().copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checkpoint_round_trip() {
+        let mut checkpoint = Checkpoint::new(42, Some(Path::new("/out/42.proof")));
+        checkpoint.seg_size_ratios = vec![1.25];
+        let buf = serde_json::to_vec(&checkpoint).unwrap();
+        let decoded: Checkpoint = serde_json::from_slice(&buf).unwrap();
+        assert_eq!(decoded.schema_version, SCHEMA_VERSION);
+        assert_eq!(decoded.last_block, 42);
+        assert_eq!(decoded.timestamp, checkpoint.timestamp);
+        assert_eq!(decoded.proof_path.as_deref(), Some("/out/42.proof"));
+        assert_eq!(decoded.seg_size_ratios, vec![1.25]);
+    }
+
+    #[test]
+    fn checkpoint_without_schema_version_is_legacy() {
+        let decoded: Checkpoint =
+            serde_json::from_str(r#"{"last_block":7,"timestamp":1,"proof_path":null}"#).unwrap();
+        assert_eq!(decoded.schema_version, schema::legacy_schema_version());
+        assert!(decoded.seg_size_ratios.is_empty());
+    }
+
+    #[test]
+    fn store_and_load() {
+        let dir = std::env::temp_dir().join(format!("checkpoint-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let outdir = dir.to_str().unwrap();
+        assert!(Checkpoint::load(outdir).unwrap().is_none());
+        Checkpoint::new(9, None).store(outdir).unwrap();
+        assert_eq!(Checkpoint::load(outdir).unwrap().unwrap().last_block, 9);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn frontier_moves_with_completed_blocks() {
+        let mut frontier = Frontier::default();
+        assert_eq!(frontier.complete(1, None), Some(1));
+        assert_eq!(frontier.complete(2, None), Some(2));
+        assert_eq!(frontier.first_failed(), None);
+    }
+
+    #[test]
+    fn frontier_holds_before_a_failed_block() {
+        let mut frontier = Frontier::default();
+        assert_eq!(frontier.complete(1, None), Some(1));
+        frontier.fail(2);
+        assert_eq!(frontier.complete(3, None), None);
+        assert_eq!(frontier.complete(4, None), None);
+        assert_eq!(frontier.first_failed(), Some(2));
+        assert_eq!(frontier.recover(2, 2, None), Some((4, None)));
+        assert_eq!(frontier.complete(5, None), Some(5));
+    }
+
+    #[test]
+    fn frontier_recovers_failures_in_any_order() {
+        let mut frontier = Frontier::default();
+        frontier.fail(2);
+        assert_eq!(frontier.complete(3, None), None);
+        frontier.fail(4);
+        assert_eq!(frontier.complete(5, None), None);
+        // 2 is still failed, recovering 4 moves nothing.
+        assert_eq!(frontier.recover(4, 4, None), None);
+        assert_eq!(frontier.recover(2, 2, None), Some((5, None)));
+
+        frontier.fail(6);
+        assert_eq!(frontier.complete(7, None), None);
+        frontier.fail(8);
+        assert_eq!(frontier.recover(6, 6, None), Some((7, None)));
+        assert_eq!(frontier.recover(8, 8, None), Some((8, None)));
+        assert_eq!(frontier.recover(8, 8, None), None);
+    }
+
+    #[test]
+    fn frontier_keeps_the_proof_of_the_checkpointed_block() {
+        let proof = |block_no: u64| PathBuf::from(format!("/out/{}.proof", block_no));
+        let mut frontier = Frontier::default();
+        frontier.fail(2);
+        assert_eq!(frontier.complete(3, Some(&proof(3))), None);
+        frontier.fail(4);
+        assert_eq!(frontier.complete(5, Some(&proof(5))), None);
+        // Recovering 2 moves the checkpoint up to the failed 4, onto 3.
+        assert_eq!(
+            frontier.recover(2, 2, Some(&proof(2))),
+            Some((3, Some(proof(3))))
+        );
+        assert_eq!(
+            frontier.recover(4, 4, Some(&proof(4))),
+            Some((5, Some(proof(5))))
+        );
+
+        // A recovered block that is the last one keeps the retry's proof.
+        frontier.fail(6);
+        assert_eq!(
+            frontier.recover(6, 6, Some(&proof(6))),
+            Some((6, Some(proof(6))))
+        );
+    }
+
+    #[test]
+    fn interrupted_run_resumes_after_the_checkpoint() {
+        let dir = std::env::temp_dir().join(format!("checkpoint-resume-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let outdir = dir.to_str().unwrap();
+        let proof = |block_no: u64| dir.join(format!("{}.proof", block_no));
+        // What the prover loop stores for each outcome.
+        let store = |last: Option<(u64, Option<PathBuf>)>| {
+            if let Some((block_no, proof_path)) = last {
+                Checkpoint::new(block_no, proof_path.as_deref())
+                    .store(outdir)
+                    .unwrap();
+            }
+        };
+
+        // First run: 10 proved, 11 empty, 12 failed, 13 proved, interrupted.
+        let mut frontier = Frontier::default();
+        store(
+            frontier
+                .complete(10, Some(&proof(10)))
+                .map(|b| (b, Some(proof(10)))),
+        );
+        store(frontier.complete(11, None).map(|b| (b, None)));
+        assert_eq!(Checkpoint::resume(outdir).unwrap(), Some(11));
+        frontier.fail(12);
+        store(
+            frontier
+                .complete(13, Some(&proof(13)))
+                .map(|b| (b, Some(proof(13)))),
+        );
+        assert_eq!(Checkpoint::resume(outdir).unwrap(), Some(11));
+
+        // Second run from 12: its retry pass recovers 12 after 13 is proved.
+        let mut frontier = Frontier::default();
+        frontier.fail(12);
+        store(
+            frontier
+                .complete(13, Some(&proof(13)))
+                .map(|b| (b, Some(proof(13)))),
+        );
+        store(frontier.recover(12, 12, Some(&proof(12))));
+        let checkpoint = Checkpoint::load(outdir).unwrap().unwrap();
+        assert_eq!(checkpoint.last_block, 13);
+        assert_eq!(checkpoint.proof_path.as_deref(), proof(13).to_str());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn frontier_rewinds_on_reorg() {
+        let mut frontier = Frontier::default();
+        frontier.fail(5);
+        assert_eq!(frontier.complete(6, None), None);
+        assert_eq!(frontier.rewind(6), None);
+        assert_eq!(frontier.rewind(4), Some(3));
+        assert_eq!(frontier.first_failed(), None);
+        assert_eq!(frontier.complete(4, None), Some(4));
+    }
+}
@@ -0,0 +1,148 @@
+use crate::rpc::FailoverClient;
+use ethers_providers::Provider;
+use std::env;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const DATE_REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Thresholds for the difference between the local clock and the `Date`
+/// header of the RPC endpoint's responses, configured by CLOCK_SKEW_WARN_SECS
+/// and CLOCK_SKEW_MAX_SECS. The server clock is independent of the chain, a
+/// lagging node or a slow block time does not count as skew.
+#[derive(Debug, Clone)]
+pub struct ClockSkewPolicy {
+    warn_secs: u64,
+    max_secs: u64,
+    http: reqwest::Client,
+}
+
+impl ClockSkewPolicy {
+    pub fn from_env() -> anyhow::Result<Self> {
+        let warn_secs = env::var("CLOCK_SKEW_WARN_SECS").unwrap_or("60".to_string());
+        let max_secs = env::var("CLOCK_SKEW_MAX_SECS").unwrap_or("600".to_string());
+        Self::new(
+            warn_secs.parse().unwrap_or(60),
+            max_secs.parse().unwrap_or(600),
+        )
+    }
+
+    fn new(warn_secs: u64, max_secs: u64) -> anyhow::Result<Self> {
+        Ok(Self {
+            warn_secs,
+            max_secs,
+            http: reqwest::Client::builder()
+                .timeout(DATE_REQUEST_TIMEOUT)
+                .build()?,
+        })
+    }
+
+    /// Compare the local clock with the `Date` header of the endpoint `client`
+    /// last used. Returns an error when the skew exceeds the hard limit, an
+    /// endpoint without the header is not checked.
+    pub async fn check(&self, client: &Provider<FailoverClient>) -> anyhow::Result<()> {
+        let url = http_url(client.as_ref().last_endpoint());
+        let Some(skew) = self.measure(&url).await? else {
+            log::warn!(
+                "RPC endpoint {} sends no Date header, the clock skew is not checked",
+                url
+            );
+            return Ok(());
+        };
+        if skew.unsigned_abs() > self.max_secs {
+            anyhow::bail!(
+                "local clock is {} secs off the clock of {} (limit {} secs), fix the clock or pass --ignore-clock-skew",
+                skew,
+                url,
+                self.max_secs
+            );
+        }
+        if skew.unsigned_abs() > self.warn_secs {
+            log::warn!("Local clock is {} secs off the clock of {}", skew, url);
+        } else {
+            log::debug!("Clock skew: {} secs against {}", skew, url);
+        }
+        Ok(())
+    }
+
+    /// Seconds the local clock is ahead of the `Date` of a response of `url`,
+    /// taken halfway through the request. `None` without the header.
+    async fn measure(&self, url: &str) -> anyhow::Result<Option<i64>> {
+        let sent = SystemTime::now();
+        let response = self
+            .http
+            .post(url)
+            .json(&serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": 1,
+                "method": "eth_blockNumber",
+                "params": [],
+            }))
+            .send()
+            .await
+            .map_err(|e| anyhow::anyhow!("failed to read the clock of {}: {}", url, e))?;
+        let received = SystemTime::now();
+        let Some(date) = response.headers().get(reqwest::header::DATE) else {
+            return Ok(None);
+        };
+        let date = date.to_str().unwrap_or_default();
+        let server = chrono::DateTime::parse_from_rfc2822(date)
+            .map_err(|e| anyhow::anyhow!("invalid Date header {:?} of {}: {}", date, url, e))?
+            .timestamp();
+        let midway = sent + received.duration_since(sent).unwrap_or_default() / 2;
+        let now = midway.duration_since(UNIX_EPOCH)?.as_secs() as i64;
+        Ok(Some(now - server))
+    }
+}
+
+/// The HTTP url of an RPC endpoint, also for a websocket endpoint.
+fn http_url(url: &str) -> String {
+    if let Some(rest) = url.strip_prefix("ws://") {
+        format!("http://{}", rest)
+    } else if let Some(rest) = url.strip_prefix("wss://") {
+        format!("https://{}", rest)
+    } else {
+        url.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::MockServer;
+    use axum::http::StatusCode;
+
+    #[test]
+    fn websocket_urls_map_to_http() {
+        assert_eq!(http_url("ws://localhost:8546"), "http://localhost:8546");
+        assert_eq!(
+            http_url("wss://rpc.goat.network/ws"),
+            "https://rpc.goat.network/ws"
+        );
+        assert_eq!(http_url("http://localhost:8545"), "http://localhost:8545");
+    }
+
+    #[tokio::test]
+    async fn skew_against_the_date_header() {
+        // The server shares the local clock, a response carries `Date`.
+        let server = MockServer::start(StatusCode::OK);
+        let policy = ClockSkewPolicy::new(60, 600).unwrap();
+        let skew = policy.measure(&server.url).await.unwrap().unwrap();
+        assert!(skew.abs() <= 1, "{}", skew);
+        assert_eq!(server.bodies()[0]["method"], "eth_blockNumber");
+    }
+
+    #[tokio::test]
+    async fn endpoint_on_the_local_clock_passes_the_check() {
+        let server = MockServer::rpc(vec![Some(serde_json::json!("0x1"))]);
+        let provider = crate::rpc::connect(&server.url).await.unwrap();
+        let policy = ClockSkewPolicy::new(60, 600).unwrap();
+        policy.check(&provider).await.unwrap();
+        assert_eq!(server.bodies().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn unreachable_endpoint_is_an_error() {
+        let policy = ClockSkewPolicy::new(60, 600).unwrap();
+        assert!(policy.measure("http://127.0.0.1:1").await.is_err());
+    }
+}
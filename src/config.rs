@@ -0,0 +1,120 @@
+use serde::Deserialize;
+use std::env;
+
+/// Prover configuration file passed with `--config`. Every value is the
+/// default of the environment variable it maps to, so the environment
+/// overrides the file, e.g. to keep PRIVATE_KEY out of it.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProverConfig {
+    #[serde(default)]
+    pub rpc: RpcConfig,
+    #[serde(default)]
+    pub prover: ProverSection,
+    #[serde(default)]
+    pub run: RunConfig,
+    #[serde(default)]
+    pub metrics: Option<ServerConfig>,
+    #[serde(default)]
+    pub status: Option<ServerConfig>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RpcConfig {
+    /// RPC_URL, comma-separated for failover.
+    pub url: Option<String>,
+    pub chain_id: Option<u64>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProverSection {
+    pub zkm_prover: Option<String>,
+    pub endpoint: Option<String>,
+    pub ca_cert_path: Option<String>,
+    pub cert_path: Option<String>,
+    pub key_path: Option<String>,
+    pub domain_name: Option<String>,
+    pub private_key: Option<String>,
+    pub vk_path: Option<String>,
+    pub elf_path: Option<String>,
+    pub seg_size: Option<u32>,
+    pub execute_only: Option<bool>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RunConfig {
+    /// First block of a sequential run, BLOCK_NO.
+    pub start_block: Option<u64>,
+    pub blocks_file: Option<String>,
+    #[serde(rename = "loop")]
+    pub prove_loop: Option<bool>,
+    pub resume: Option<bool>,
+    pub output_dir: Option<String>,
+    pub max_lag_blocks: Option<u64>,
+    /// LAG_POLICY: block, skip or alert.
+    pub lag_policy: Option<String>,
+    pub lag_skip_file: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ServerConfig {
+    pub addr: String,
+}
+
+impl ProverConfig {
+    pub fn load(path: &str) -> anyhow::Result<Self> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| anyhow::anyhow!("failed to read config {}: {}", path, e))?;
+        toml::from_str(&content).map_err(|e| anyhow::anyhow!("invalid config {}: {}", path, e))
+    }
+
+    /// Set the environment variable of every configured value that is not set
+    /// in the environment already.
+    pub fn apply_to_env(&self) {
+        let values = [
+            ("RPC_URL", self.rpc.url.clone()),
+            ("CHAIN_ID", self.rpc.chain_id.map(|v| v.to_string())),
+            ("ZKM_PROVER", self.prover.zkm_prover.clone()),
+            ("ENDPOINT", self.prover.endpoint.clone()),
+            ("CA_CERT_PATH", self.prover.ca_cert_path.clone()),
+            ("CERT_PATH", self.prover.cert_path.clone()),
+            ("KEY_PATH", self.prover.key_path.clone()),
+            ("DOMAIN_NAME", self.prover.domain_name.clone()),
+            ("PRIVATE_KEY", self.prover.private_key.clone()),
+            ("VK_PATH", self.prover.vk_path.clone()),
+            ("ELF_PATH", self.prover.elf_path.clone()),
+            ("SEG_SIZE", self.prover.seg_size.map(|v| v.to_string())),
+            (
+                "EXECUTE_ONLY",
+                self.prover.execute_only.map(|v| v.to_string()),
+            ),
+            ("BLOCK_NO", self.run.start_block.map(|v| v.to_string())),
+            ("BLOCKS_FILE", self.run.blocks_file.clone()),
+            ("PROVE_LOOP", self.run.prove_loop.map(|v| v.to_string())),
+            ("RESUME", self.run.resume.map(|v| v.to_string())),
+            ("OUTPUT_DIR", self.run.output_dir.clone()),
+            (
+                "MAX_LAG_BLOCKS",
+                self.run.max_lag_blocks.map(|v| v.to_string()),
+            ),
+            ("LAG_POLICY", self.run.lag_policy.clone()),
+            ("LAG_SKIP_FILE", self.run.lag_skip_file.clone()),
+            (
+                "METRICS_ADDR",
+                self.metrics.as_ref().map(|m| m.addr.clone()),
+            ),
+            ("STATUS_ADDR", self.status.as_ref().map(|s| s.addr.clone())),
+        ];
+        for (name, value) in values {
+            if let Some(value) = value {
+                if env::var_os(name).is_none() {
+                    env::set_var(name, value);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,157 @@
+use crate::summary::BlockSummary;
+use rusqlite::{params, Connection};
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const SCHEMA: &str = "CREATE TABLE IF NOT EXISTS blocks (
+    block_no INTEGER PRIMARY KEY,
+    status TEXT NOT NULL,
+    tx_count INTEGER NOT NULL DEFAULT 0,
+    seg_size INTEGER NOT NULL DEFAULT 0,
+    prove_secs INTEGER NOT NULL DEFAULT 0,
+    proof_path TEXT,
+    error TEXT,
+    created_at INTEGER NOT NULL,
+    updated_at INTEGER NOT NULL
+)";
+
+/// Statuses after which a block does not need to be processed again.
+const DONE_STATUSES: &str = "('proved', 'executed', 'generated', 'skipped', 'sampled_out')";
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+/// Per-block record of proving runs, kept in the SQLite database at DB_PATH.
+pub struct ProverDb {
+    conn: Mutex<Connection>,
+}
+
+impl ProverDb {
+    pub fn open(path: &str) -> anyhow::Result<Self> {
+        let conn = Connection::open(path)
+            .map_err(|e| anyhow::anyhow!("failed to open database {}: {}", path, e))?;
+        conn.execute(SCHEMA, [])?;
+        Ok(Self {
+            conn: Mutex::new(conn),
+        })
+    }
+
+    fn conn(&self) -> std::sync::MutexGuard<'_, Connection> {
+        self.conn.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Mark `block_no` as being processed.
+    pub fn record_start(&self, block_no: u64, tx_count: usize) -> anyhow::Result<()> {
+        let now = now();
+        self.conn().execute(
+            "INSERT INTO blocks (block_no, status, tx_count, created_at, updated_at)
+             VALUES (?1, 'proving', ?2, ?3, ?3)
+             ON CONFLICT(block_no) DO UPDATE SET
+                status = 'proving', tx_count = ?2, error = NULL, updated_at = ?3",
+            params![block_no, tx_count as u64, now],
+        )?;
+        Ok(())
+    }
+
+    /// Store the final result of `summary.block_no`.
+    pub fn record_finish(
+        &self,
+        summary: &BlockSummary,
+        proof_path: Option<&Path>,
+    ) -> anyhow::Result<()> {
+        let now = now();
+        self.conn().execute(
+            "INSERT INTO blocks (block_no, status, tx_count, seg_size, prove_secs, proof_path,
+                error, created_at, updated_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?8)
+             ON CONFLICT(block_no) DO UPDATE SET
+                status = ?2, tx_count = ?3, seg_size = ?4, prove_secs = ?5, proof_path = ?6,
+                error = ?7, updated_at = ?8",
+            params![
+                summary.block_no,
+                summary.status.as_str(),
+                summary.tx_count as u64,
+                summary.seg_size,
+                summary.prove_secs,
+                proof_path.map(|p| p.to_string_lossy().into_owned()),
+                summary.error,
+                now
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Mark `block_no` as replaced by a reorg, so it is proved again.
+    pub fn invalidate(&self, block_no: u64) -> anyhow::Result<()> {
+        self.conn().execute(
+            "UPDATE blocks SET status = 'reorged', updated_at = ?2 WHERE block_no = ?1",
+            params![block_no, now()],
+        )?;
+        Ok(())
+    }
+
+    /// Whether `block_no` was already proved, executed, generated or skipped.
+    pub fn is_done(&self, block_no: u64) -> anyhow::Result<bool> {
+        let done: u64 = self.conn().query_row(
+            &format!(
+                "SELECT COUNT(*) FROM blocks WHERE block_no = ?1 AND status IN {}",
+                DONE_STATUSES
+            ),
+            params![block_no],
+            |row| row.get(0),
+        )?;
+        Ok(done > 0)
+    }
+
+    /// The highest block that is done, the database counterpart of the checkpoint.
+    pub fn last_block(&self) -> anyhow::Result<Option<u64>> {
+        let last_block = self.conn().query_row(
+            &format!(
+                "SELECT MAX(block_no) FROM blocks WHERE status IN {}",
+                DONE_STATUSES
+            ),
+            [],
+            |row| row.get(0),
+        )?;
+        Ok(last_block)
+    }
+
+    /// Print the number of blocks per status and the p50/p95 prove times.
+    pub fn print_stats(&self) -> anyhow::Result<()> {
+        let conn = self.conn();
+        let mut stmt =
+            conn.prepare("SELECT status, COUNT(*) FROM blocks GROUP BY status ORDER BY status")?;
+        let counts = stmt.query_map([], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, u64>(1)?))
+        })?;
+        for count in counts {
+            let (status, count) = count?;
+            println!("{}: {}", status, count);
+        }
+
+        let mut stmt = conn
+            .prepare("SELECT prove_secs FROM blocks WHERE status = 'proved' ORDER BY prove_secs")?;
+        let prove_secs = stmt
+            .query_map([], |row| row.get::<_, u64>(0))?
+            .collect::<Result<Vec<_>, _>>()?;
+        match (percentile(&prove_secs, 50), percentile(&prove_secs, 95)) {
+            (Some(p50), Some(p95)) => println!("prove_secs p50: {} p95: {}", p50, p95),
+            _ => println!("prove_secs: no proved blocks"),
+        }
+        Ok(())
+    }
+}
+
+/// Nearest-rank percentile of the ascending `sorted`.
+pub fn percentile(sorted: &[u64], p: usize) -> Option<u64> {
+    if sorted.is_empty() {
+        return None;
+    }
+    let rank = (p * sorted.len()).div_ceil(100).max(1);
+    sorted.get(rank - 1).copied()
+}
@@ -0,0 +1,55 @@
+use crate::atomic_file;
+use crate::error::ProverError;
+use sha2::{Digest, Sha256};
+use std::env;
+use std::path::Path;
+
+/// Resolve ELF_PATH to a local file. An http(s) URL is downloaded into
+/// ELF_CACHE_DIR as `{sha256}.elf`; with an ELF_SHA256 pin a cached copy is
+/// used without downloading, and any other content is rejected.
+pub async fn resolve(elf_path: &str) -> anyhow::Result<String> {
+    if !(elf_path.starts_with("http://") || elf_path.starts_with("https://")) {
+        return Ok(elf_path.to_string());
+    }
+    let cache_dir = env::var("ELF_CACHE_DIR").unwrap_or("./elf_cache".to_string());
+    let pin = env::var("ELF_SHA256")
+        .ok()
+        .map(|sha| sha.trim().trim_start_matches("0x").to_lowercase())
+        .filter(|sha| !sha.is_empty());
+    if let Some(pin) = &pin {
+        let cached = Path::new(&cache_dir).join(format!("{}.elf", pin));
+        if cached.exists() {
+            log::info!("Using cached ELF {} for {}", cached.display(), elf_path);
+            return Ok(cached.to_string_lossy().into_owned());
+        }
+    }
+
+    log::info!("Downloading ELF from {}", elf_path);
+    let download_error = |e: reqwest::Error| {
+        ProverError::Config(format!("failed to download ELF {}: {}", elf_path, e))
+    };
+    let elf = reqwest::get(elf_path)
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(download_error)?
+        .bytes()
+        .await
+        .map_err(download_error)?;
+    let sha = hex::encode(Sha256::digest(&elf));
+    if let Some(pin) = &pin {
+        if *pin != sha {
+            return Err(ProverError::Config(format!(
+                "ELF {} has sha256 {}, ELF_SHA256 pins {}",
+                elf_path, sha, pin
+            ))
+            .into());
+        }
+    }
+    std::fs::create_dir_all(&cache_dir).map_err(|e| ProverError::io(&cache_dir, e))?;
+    let cached = Path::new(&cache_dir).join(format!("{}.elf", sha));
+    if !cached.exists() {
+        atomic_file::write(&cached, &elf).map_err(|e| ProverError::io(&cached, e))?;
+    }
+    log::info!("ELF {} cached as {}", elf_path, cached.display());
+    Ok(cached.to_string_lossy().into_owned())
+}
@@ -0,0 +1,94 @@
+use std::path::{Path, PathBuf};
+
+/// Error categories that decide the exit code of the process.
+#[derive(Debug, thiserror::Error)]
+pub enum ProverError {
+    #[error("config error: {0}")]
+    Config(String),
+    #[error("rpc error: {0}")]
+    Rpc(String),
+    #[error("check error: {0}")]
+    Check(String),
+    #[error("prove error: {0}")]
+    Prove(String),
+    /// The requested block is too far ahead of the chain head, or did not
+    /// appear within MAX_WAIT_FOR_BLOCK_SECS.
+    #[error("block unavailable: {0}")]
+    BlockUnavailable(String),
+    /// The proof does not commit to the suite it was generated from.
+    #[error("public input mismatch: {0}")]
+    PublicInputMismatch(String),
+    #[error("io error: {}: {source}", path.display())]
+    Io {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+}
+
+impl ProverError {
+    pub fn io(path: impl AsRef<Path>, source: std::io::Error) -> Self {
+        Self::Io {
+            path: path.as_ref().to_path_buf(),
+            source,
+        }
+    }
+
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Self::Config(_) => 2,
+            Self::Rpc(_) => 3,
+            Self::Check(_) => 4,
+            Self::Prove(_) | Self::PublicInputMismatch(_) => 5,
+            Self::Io { .. } => 6,
+            Self::BlockUnavailable(_) => 7,
+        }
+    }
+}
+
+/// Exit code of `e`, 1 for errors without a `ProverError` category.
+pub fn exit_code(e: &anyhow::Error) -> i32 {
+    e.chain()
+        .find_map(|cause| cause.downcast_ref::<ProverError>())
+        .map_or(1, ProverError::exit_code)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Context;
+
+    #[test]
+    fn exit_codes() {
+        let io = std::io::Error::from(std::io::ErrorKind::NotFound);
+        let cases = [
+            (ProverError::Config("invalid CHAIN_ID".to_string()), 2),
+            (ProverError::Rpc("block_no 7 not found".to_string()), 3),
+            (ProverError::Check("7.json: unit mismatch".to_string()), 4),
+            (ProverError::Prove("block_no 7 timed out".to_string()), 5),
+            (ProverError::PublicInputMismatch("7".to_string()), 5),
+            (ProverError::io("/out/7.json", io), 6),
+            (ProverError::BlockUnavailable("7".to_string()), 7),
+        ];
+        for (e, code) in cases {
+            assert_eq!(exit_code(&e.into()), code);
+        }
+    }
+
+    #[test]
+    fn exit_code_through_context() {
+        let e = Err::<(), _>(ProverError::Rpc("timeout".to_string()))
+            .context("fetching block_no 7")
+            .unwrap_err();
+        assert_eq!(exit_code(&e), 3);
+        assert_eq!(exit_code(&anyhow::anyhow!("uncategorized")), 1);
+    }
+
+    #[test]
+    fn io_error_names_the_path() {
+        let e = ProverError::io(
+            "/out/7.json",
+            std::io::Error::from(std::io::ErrorKind::PermissionDenied),
+        );
+        assert!(e.to_string().starts_with("io error: /out/7.json: "));
+    }
+}
@@ -0,0 +1,112 @@
+use crate::atomic_file;
+use crate::error::ProverError;
+use crate::schema;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// Execution statistics of a prover result. The fields of the result differ
+/// between SDK versions, so they are looked up by name and left out when
+/// absent.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ExecutionStats {
+    /// Cycles executed by the guest, `total_steps` of the result.
+    pub cycles: Option<u64>,
+    /// Segments the execution was split into, computed from the cycles and
+    /// SEG_SIZE unless the result reports them.
+    pub segments: Option<u64>,
+    /// Duration of every stage reported as `{stage}_cost`, in milliseconds.
+    #[serde(default)]
+    pub stage_millis: BTreeMap<String, u64>,
+}
+
+impl ExecutionStats {
+    pub fn from_result(result: &impl Serialize, seg_size: u32) -> Self {
+        let Ok(serde_json::Value::Object(fields)) = serde_json::to_value(result) else {
+            return Self::default();
+        };
+        let number = |name: &str| fields.get(name).and_then(|v| v.as_u64());
+        let cycles = number("total_steps").or_else(|| number("cycles"));
+        let segments = number("segments")
+            .or_else(|| number("segment_count"))
+            .or_else(|| cycles.map(|cycles| cycles.div_ceil(seg_size.max(1) as u64)));
+        let stage_millis = fields
+            .iter()
+            .filter_map(|(name, v)| Some((name.strip_suffix("_cost")?.to_string(), v.as_u64()?)))
+            .collect();
+        Self {
+            cycles,
+            segments,
+            stage_millis,
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        let count = |v: Option<u64>| v.map_or("unknown".to_string(), |v| v.to_string());
+        format!(
+            "{} cycles in {} segments",
+            count(self.cycles),
+            count(self.segments)
+        )
+    }
+}
+
+/// Result of an EXECUTE_ONLY run, written to `{block_no}_execute_report.json`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExecuteReport {
+    #[serde(default = "schema::legacy_schema_version")]
+    pub schema_version: u32,
+    pub block_no: u64,
+    /// Last block of a BLOCKS_PER_PROOF batch starting at `block_no`.
+    pub last_block_no: Option<u64>,
+    pub seg_size: u32,
+    pub execute_secs: u64,
+    #[serde(flatten)]
+    pub stats: ExecutionStats,
+}
+
+impl ExecuteReport {
+    pub fn path(outdir: &str, block_no: u64) -> PathBuf {
+        Path::new(outdir).join(format!("{}_execute_report.json", block_no))
+    }
+
+    pub fn write(&self, outdir: &str) -> anyhow::Result<PathBuf> {
+        let path = Self::path(outdir, self.block_no);
+        atomic_file::write(&path, serde_json::to_vec_pretty(self)?)
+            .map_err(|e| ProverError::io(&path, e))?;
+        Ok(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn stats_are_looked_up_by_name() {
+        let result = json!({
+            "total_steps": 2_500_000,
+            "segments": 3,
+            "prove_cost": 1200,
+            "aggregate_cost": 300,
+            "proof": "0x",
+        });
+        let stats = ExecutionStats::from_result(&result, 1 << 20);
+        assert_eq!(stats.cycles, Some(2_500_000));
+        assert_eq!(stats.segments, Some(3));
+        assert_eq!(
+            stats.stage_millis,
+            BTreeMap::from([("aggregate".to_string(), 300), ("prove".to_string(), 1200)])
+        );
+        assert_eq!(stats.describe(), "2500000 cycles in 3 segments");
+    }
+
+    #[test]
+    fn segments_follow_from_the_cycles_and_seg_size() {
+        let stats = ExecutionStats::from_result(&json!({ "cycles": 2_500_000 }), 1 << 20);
+        assert_eq!(stats.segments, Some(3));
+        let stats = ExecutionStats::from_result(&json!("not an object"), 1 << 20);
+        assert_eq!(stats.describe(), "unknown cycles in unknown segments");
+    }
+}
@@ -0,0 +1,99 @@
+use crate::check::recover_address;
+use models::{TestSuite, TestUnit};
+use revm::primitives::Address;
+use std::collections::HashSet;
+use std::env;
+
+/// Addresses from TARGET_ADDRESSES, blocks without a transaction touching one
+/// of them are not proved.
+#[derive(Debug, Clone)]
+pub struct TargetFilter {
+    targets: HashSet<Address>,
+}
+
+impl TargetFilter {
+    /// `None` when TARGET_ADDRESSES is empty, every block is proved then.
+    pub fn from_env() -> anyhow::Result<Option<Self>> {
+        let addresses = env::var("TARGET_ADDRESSES").unwrap_or("".to_string());
+        let mut targets = HashSet::new();
+        for address in addresses
+            .split(',')
+            .map(str::trim)
+            .filter(|a| !a.is_empty())
+        {
+            let address = address
+                .parse::<Address>()
+                .map_err(|e| anyhow::anyhow!("invalid TARGET_ADDRESSES {:?}: {}", address, e))?;
+            targets.insert(address);
+        }
+        Ok((!targets.is_empty()).then_some(Self { targets }))
+    }
+
+    /// Whether any transaction of `suite` calls or creates a target address.
+    pub fn is_relevant(&self, suite: &TestSuite) -> bool {
+        suite.0.values().any(|unit| self.touches(unit))
+    }
+
+    fn touches(&self, unit: &TestUnit) -> bool {
+        let tx = &unit.transaction;
+        if let Some(to) = tx.to {
+            return self.targets.contains(&to);
+        }
+        // Contract creation, the created address follows from sender and nonce.
+        let sender = tx
+            .sender
+            .or_else(|| recover_address(tx.secret_key.as_slice()).ok());
+        sender.is_some_and(|sender| self.targets.contains(&sender.create(tx.nonce.to())))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use revm::primitives::address;
+
+    const SENDER: Address = address!("a94f5374fce5edbc8e2a8697c15331677e6ebf0b");
+
+    fn transfer_suite() -> TestSuite {
+        let path = concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/tests/fixtures/check/transfer.json"
+        );
+        crate::check::decode_test_suite(&std::fs::read(path).unwrap()).unwrap()
+    }
+
+    fn filter(targets: &[Address]) -> TargetFilter {
+        TargetFilter {
+            targets: targets.iter().copied().collect(),
+        }
+    }
+
+    #[test]
+    fn call_to_a_target_is_relevant() {
+        let suite = transfer_suite();
+        assert!(
+            filter(&[address!("0000000000000000000000000000000000001000")]).is_relevant(&suite)
+        );
+        assert!(!filter(&[SENDER]).is_relevant(&suite));
+    }
+
+    #[test]
+    fn creation_of_a_target_is_relevant() {
+        let mut suite = transfer_suite();
+        for unit in suite.0.values_mut() {
+            unit.transaction.to = None;
+        }
+        assert!(filter(&[SENDER.create(0)]).is_relevant(&suite));
+        assert!(!filter(&[SENDER.create(1)]).is_relevant(&suite));
+    }
+
+    #[test]
+    fn created_address_follows_from_the_secret_key_without_a_sender() {
+        let mut suite = transfer_suite();
+        for unit in suite.0.values_mut() {
+            unit.transaction.to = None;
+            unit.transaction.sender = None;
+        }
+        assert!(filter(&[SENDER.create(0)]).is_relevant(&suite));
+    }
+}
@@ -0,0 +1,98 @@
+use revm::{
+    handler::register::EvmHandler,
+    precompile::{secp256r1, PrecompileSpecId, PrecompileWithAddress},
+    primitives::SpecId,
+    ContextPrecompiles, Database,
+};
+use std::sync::Arc;
+
+/// Chain ids of GOAT mainnet and testnet3.
+const GOAT_CHAIN_IDS: &[u64] = &[2345, 48816];
+
+/// Precompiles of the GOAT chain on top of the Ethereum ones of the spec:
+/// the RIP-7212 P256VERIFY at 0x100 for 3450 gas, returning the 32-byte 1
+/// for a valid secp256r1 signature and nothing otherwise.
+static PRECOMPILES: &[PrecompileWithAddress] = &[secp256r1::P256VERIFY];
+
+pub fn is_goat_chain(chain_id: u64) -> bool {
+    GOAT_CHAIN_IDS.contains(&chain_id)
+}
+
+/// Handler register loading the GOAT precompiles on GOAT chains, leaving
+/// other chains with the stock set.
+pub fn register<EXT, DB: Database>(chain_id: u64) -> fn(&mut EvmHandler<'_, EXT, DB>) {
+    if is_goat_chain(chain_id) {
+        handle_register
+    } else {
+        |_| {}
+    }
+}
+
+fn handle_register<EXT, DB: Database>(handler: &mut EvmHandler<'_, EXT, DB>) {
+    let spec_id: SpecId = handler.cfg.spec_id;
+    handler.pre_execution.load_precompiles = Arc::new(move || {
+        let mut precompiles = ContextPrecompiles::new(PrecompileSpecId::from_spec_id(spec_id));
+        precompiles.extend(PRECOMPILES.iter().cloned());
+        precompiles
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use revm::precompile::{Precompile, PrecompileError, PrecompileErrors, PrecompileResult};
+    use revm::primitives::{address, hex, Bytes, B256};
+
+    /// Hash, r, s, x and y of a secp256r1 signature of keccak256("goat
+    /// p256verify").
+    const INPUT: [u8; 160] = hex!(
+        "682a24c7b5f584b4cf5eca26ca1d31b6946c8674f5ce5c162df19b22367f67cc"
+        "7932fae9b30508edd270592aa9b68b3d26f4c62a26966741f569826c25fa8f30"
+        "a17f32c5205da5e78f1bea3f1a26d17448872b05ba7c2c97339bb885e404c5bc"
+        "b5a104b6caadfa15a6fb9eb3939237284d404e9d9486b706411457a16f12e84a"
+        "18bbebd97e525867075442bd530ea4d29ee7ad873a82a62e804be3c08e34c9d6"
+    );
+
+    fn p256verify(input: &[u8], gas_limit: u64) -> PrecompileResult {
+        let precompile = PRECOMPILES
+            .iter()
+            .find(|precompile| precompile.0 == address!("0000000000000000000000000000000000000100"))
+            .unwrap();
+        match &precompile.1 {
+            Precompile::Standard(run) => run(&Bytes::copy_from_slice(input), gas_limit),
+            _ => unreachable!("P256VERIFY is a standard precompile"),
+        }
+    }
+
+    #[test]
+    fn valid_signature() {
+        let output = p256verify(&INPUT, 10_000).unwrap();
+        assert_eq!(output.gas_used, 3_450);
+        assert_eq!(output.bytes[..], B256::with_last_byte(1)[..]);
+    }
+
+    #[test]
+    fn invalid_signature_returns_nothing() {
+        let mut input = INPUT;
+        input[0] ^= 1;
+        let output = p256verify(&input, 10_000).unwrap();
+        assert_eq!(output.gas_used, 3_450);
+        assert!(output.bytes.is_empty());
+        assert!(p256verify(&INPUT[..159], 10_000).unwrap().bytes.is_empty());
+    }
+
+    #[test]
+    fn out_of_gas() {
+        assert!(matches!(
+            p256verify(&INPUT, 3_449),
+            Err(PrecompileErrors::Error(PrecompileError::OutOfGas))
+        ));
+    }
+
+    #[test]
+    fn goat_chains() {
+        assert!(is_goat_chain(2345));
+        assert!(is_goat_chain(48816));
+        assert!(!is_goat_chain(1));
+    }
+}
@@ -0,0 +1,136 @@
+use crate::metrics::METRICS;
+use ethers_providers::{JsonRpcClient, Middleware, Provider};
+
+/// Follows the chain head and keeps the proving frontier CONFIRMATIONS
+/// blocks behind it. The head is only queried again once the frontier is
+/// reached, so a backlog is worked through without an RPC call per block.
+#[derive(Debug)]
+pub struct HeadTracker {
+    confirmations: u64,
+    head: Option<u64>,
+}
+
+impl HeadTracker {
+    pub fn new(confirmations: u64) -> Self {
+        Self {
+            confirmations,
+            head: None,
+        }
+    }
+
+    pub fn head(&self) -> Option<u64> {
+        self.head
+    }
+
+    /// The last block that is confirmed at the latest head seen.
+    pub fn frontier(&self) -> Option<u64> {
+        self.head
+            .map(|head| head.saturating_sub(self.confirmations))
+    }
+
+    /// Query the head, which never moves backwards: a lower answer comes from
+    /// a node that is behind, a reorg is detected from the parent hashes.
+    async fn refresh<P: JsonRpcClient>(&mut self, client: &Provider<P>) -> anyhow::Result<u64> {
+        let answered = client.get_block_number().await?.as_u64();
+        let head = self.head.map_or(answered, |head| head.max(answered));
+        if head > answered {
+            log::warn!(
+                "Ignoring head {} behind the head {} seen before",
+                answered,
+                head
+            );
+        }
+        METRICS.head_block_number.set(head);
+        self.head = Some(head);
+        Ok(head)
+    }
+
+    /// Whether `block_no` is at or behind the frontier.
+    pub async fn is_ready<P: JsonRpcClient>(
+        &mut self,
+        client: &Provider<P>,
+        block_no: u64,
+    ) -> anyhow::Result<bool> {
+        if self.frontier().is_some_and(|frontier| block_no <= frontier) {
+            return Ok(true);
+        }
+        let head = self.refresh(client).await?;
+        Ok(block_no + self.confirmations <= head)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rpc;
+    use crate::test_util::MockServer;
+    use ethers_core::types::U64;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn stays_confirmations_behind_the_head() {
+        let (client, mock) = Provider::mocked();
+        let mut head = HeadTracker::new(2);
+        assert_eq!(head.frontier(), None);
+
+        mock.push(U64::from(10)).unwrap();
+        assert!(head.is_ready(&client, 8).await.unwrap());
+        assert_eq!(head.frontier(), Some(8));
+        // Behind the frontier, the head is not queried again.
+        assert!(head.is_ready(&client, 5).await.unwrap());
+
+        mock.push(U64::from(10)).unwrap();
+        assert!(!head.is_ready(&client, 9).await.unwrap());
+        mock.push(U64::from(11)).unwrap();
+        assert!(head.is_ready(&client, 9).await.unwrap());
+        assert_eq!(head.head(), Some(11));
+    }
+
+    #[tokio::test]
+    async fn head_never_moves_backwards() {
+        let (client, mock) = Provider::mocked();
+        let mut head = HeadTracker::new(0);
+        mock.push(U64::from(10)).unwrap();
+        assert!(head.is_ready(&client, 10).await.unwrap());
+        mock.push(U64::from(7)).unwrap();
+        assert!(!head.is_ready(&client, 11).await.unwrap());
+        assert_eq!(head.head(), Some(10));
+        assert_eq!(head.frontier(), Some(10));
+    }
+
+    #[tokio::test]
+    async fn flapping_endpoints_schedule_every_block_once() {
+        // The synced endpoint fails now and then, the lagging one answers.
+        let synced = MockServer::rpc(vec![
+            Some(json!("0xa")),
+            None,
+            Some(json!("0xc")),
+            None,
+            Some(json!("0xd")),
+        ]);
+        let lagging = MockServer::rpc(vec![Some(json!("0x5")), Some(json!("0x6"))]);
+        let client = rpc::connect(&format!("{},{}", synced.url, lagging.url))
+            .await
+            .unwrap();
+        let mut head = HeadTracker::new(2);
+        let mut scheduled = Vec::new();
+        let mut next = 1;
+        for _ in 0..4 {
+            while head.is_ready(&client, next).await.unwrap() {
+                scheduled.push(next);
+                next += 1;
+            }
+            // The lag to the last scheduled block never goes negative.
+            assert!(head.head().unwrap() >= next - 1);
+        }
+        assert_eq!(scheduled, (1..=11).collect::<Vec<_>>());
+        assert_eq!(head.head(), Some(13));
+    }
+
+    #[test]
+    fn frontier_of_a_young_chain() {
+        let mut head = HeadTracker::new(6);
+        head.head = Some(3);
+        assert_eq!(head.frontier(), Some(0));
+    }
+}
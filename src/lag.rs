@@ -0,0 +1,102 @@
+use crate::error::ProverError;
+use std::env;
+use std::path::{Path, PathBuf};
+
+/// What to do once proving falls more than MAX_LAG_BLOCKS behind the head.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LagPolicy {
+    /// Keep working through every block in order.
+    Block,
+    /// Jump to the head, the passed over blocks are appended to
+    /// LAG_SKIP_FILE for a later BLOCKS_FILE backfill.
+    Skip,
+    /// Count and notify, then carry on like `block`.
+    Alert,
+}
+
+/// Backpressure of a sequential loop run, configured by MAX_LAG_BLOCKS (0,
+/// the default, disables it), LAG_POLICY (block, skip or alert) and
+/// LAG_SKIP_FILE (`{OUTPUT_DIR}/skipped_blocks.txt`).
+#[derive(Debug, Clone)]
+pub struct LagGuard {
+    pub max_lag: u64,
+    pub policy: LagPolicy,
+    pub skip_file: PathBuf,
+    /// Whether the lag is over the limit, an alert is only raised when it
+    /// gets there.
+    exceeded: bool,
+}
+
+impl LagGuard {
+    /// `None` when MAX_LAG_BLOCKS is 0.
+    pub fn from_env(outdir: &str) -> anyhow::Result<Option<Self>> {
+        let max_lag = env::var("MAX_LAG_BLOCKS").unwrap_or("0".to_string());
+        let max_lag = max_lag.parse::<u64>().unwrap_or(0);
+        if max_lag == 0 {
+            return Ok(None);
+        }
+        let policy = env::var("LAG_POLICY").unwrap_or("block".to_string());
+        let policy = match policy.as_str() {
+            "block" => LagPolicy::Block,
+            "skip" => LagPolicy::Skip,
+            "alert" => LagPolicy::Alert,
+            _ => {
+                return Err(ProverError::Config(format!(
+                    "invalid LAG_POLICY {:?}, expected block, skip or alert",
+                    policy
+                ))
+                .into())
+            }
+        };
+        let skip_file = match env::var("LAG_SKIP_FILE") {
+            Ok(path) if !path.is_empty() => PathBuf::from(path),
+            _ => Path::new(outdir).join("skipped_blocks.txt"),
+        };
+        Ok(Some(Self {
+            max_lag,
+            policy,
+            skip_file,
+            exceeded: false,
+        }))
+    }
+
+    /// Record the lag `head - block_no`, returns `true` when it just went
+    /// over MAX_LAG_BLOCKS.
+    pub fn update(&mut self, lag: u64) -> bool {
+        let exceeded = lag > self.max_lag;
+        let crossed = exceeded && !self.exceeded;
+        self.exceeded = exceeded;
+        crossed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn guard(max_lag: u64) -> LagGuard {
+        LagGuard {
+            max_lag,
+            policy: LagPolicy::Alert,
+            skip_file: PathBuf::from("skipped_blocks.txt"),
+            exceeded: false,
+        }
+    }
+
+    #[test]
+    fn crossing_the_limit_is_reported_once() {
+        let mut guard = guard(10);
+        assert!(!guard.update(10));
+        assert!(guard.update(11));
+        assert!(!guard.update(50));
+        assert!(!guard.update(3));
+        assert!(guard.update(12));
+    }
+
+    #[test]
+    fn zero_lag_limit_reports_any_lag() {
+        let mut guard = guard(0);
+        assert!(!guard.update(0));
+        assert!(guard.update(1));
+    }
+}
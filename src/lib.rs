@@ -0,0 +1,15 @@
+//! Suite generation, checking and proving of goat_prover, for services that
+//! embed them instead of running the binary. [`suite::build`] generates the
+//! suite of a block, [`check::run_suite_report`] and
+//! [`check::execute_test_suite`] check it on the host under a
+//! [`check::CheckConfig`], and [`proof::prove`] proves it.
+
+pub mod atomic_file;
+pub mod check;
+mod check_exceptions;
+pub mod check_trace;
+pub mod error;
+mod goat_precompiles;
+mod merkle_trie;
+pub mod proof;
+pub mod suite;
@@ -0,0 +1,177 @@
+use crate::error::ProverError;
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::os::fd::AsRawFd;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const LOCK_FILE: &str = ".lock";
+
+#[derive(Debug, Serialize, Deserialize)]
+struct LockInfo {
+    pid: u32,
+    /// Unix timestamp (seconds) at which the holder started.
+    started_at: u64,
+}
+
+/// Advisory lock of an OUTPUT_DIR, `{OUTPUT_DIR}/.lock` holding the pid and
+/// start time of the prover writing to it. Released on drop.
+#[derive(Debug)]
+pub struct OutputLock {
+    path: PathBuf,
+}
+
+impl OutputLock {
+    /// Lock `outdir`. A lock of a process that is no longer running is
+    /// broken; a live one fails with `None` returned, the caller decides
+    /// whether to share the directory.
+    pub fn acquire(outdir: &str) -> anyhow::Result<Option<Self>> {
+        let path = Path::new(outdir).join(LOCK_FILE);
+        let info = LockInfo {
+            pid: std::process::id(),
+            started_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or_default(),
+        };
+        // Another instance may break the same stale lock, only one of the
+        // creates after it wins.
+        for _ in 0..2 {
+            match std::fs::OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(&path)
+            {
+                Ok(mut file) => {
+                    file.write_all(&serde_json::to_vec(&info)?)
+                        .map_err(|e| ProverError::io(&path, e))?;
+                    return Ok(Some(Self { path }));
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {}
+                Err(e) => return Err(ProverError::io(&path, e).into()),
+            }
+            let holder = std::fs::read(&path)
+                .ok()
+                .and_then(|buf| serde_json::from_slice::<LockInfo>(&buf).ok());
+            match holder {
+                Some(holder) if is_running(holder.pid) => {
+                    log::warn!(
+                        "{} is locked by pid {} started at {}",
+                        outdir,
+                        holder.pid,
+                        holder.started_at
+                    );
+                    return Ok(None);
+                }
+                holder => {
+                    log::warn!(
+                        "Breaking stale lock {} of pid {:?}, the process is not running",
+                        path.display(),
+                        holder.map(|holder| holder.pid)
+                    );
+                    let _ = std::fs::remove_file(&path);
+                }
+            }
+        }
+        Ok(None)
+    }
+}
+
+impl Drop for OutputLock {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// Append `buf` to `path` under an exclusive `flock` of the file, fsynced
+/// before the lock is released, so lines appended by concurrent writers,
+/// threads or instances sharing an OUTPUT_DIR, never interleave.
+pub fn append_locked(path: &Path, buf: &[u8]) -> std::io::Result<()> {
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?;
+    if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    let written = file.write_all(buf).and_then(|_| file.sync_data());
+    unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_UN) };
+    written
+}
+
+/// Whether a process with `pid` exists; one owned by another user counts.
+fn is_running(pid: u32) -> bool {
+    let Ok(pid) = libc::pid_t::try_from(pid) else {
+        return false;
+    };
+    // Signal 0 only checks that the process can be signalled.
+    if unsafe { libc::kill(pid, 0) } == 0 {
+        return true;
+    }
+    std::io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("lock-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn live_lock_is_not_shared() {
+        let dir = temp_dir("live");
+        let outdir = dir.to_str().unwrap();
+        let lock = OutputLock::acquire(outdir).unwrap().unwrap();
+        assert!(OutputLock::acquire(outdir).unwrap().is_none());
+        drop(lock);
+        assert!(!dir.join(LOCK_FILE).exists());
+        assert!(OutputLock::acquire(outdir).unwrap().is_some());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn stale_lock_is_broken() {
+        let dir = temp_dir("stale");
+        let outdir = dir.to_str().unwrap();
+        let stale = LockInfo {
+            pid: u32::MAX,
+            started_at: 1,
+        };
+        std::fs::write(dir.join(LOCK_FILE), serde_json::to_vec(&stale).unwrap()).unwrap();
+        let lock = OutputLock::acquire(outdir).unwrap().unwrap();
+        let holder: LockInfo =
+            serde_json::from_slice(&std::fs::read(dir.join(LOCK_FILE)).unwrap()).unwrap();
+        assert_eq!(holder.pid, std::process::id());
+        drop(lock);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn concurrent_appends_do_not_interleave() {
+        let dir = temp_dir("append");
+        let path = dir.join("results.jsonl");
+        std::thread::scope(|scope| {
+            for writer in 0..8 {
+                let path = &path;
+                scope.spawn(move || {
+                    let line = format!("{}\n", writer.to_string().repeat(4096));
+                    for _ in 0..20 {
+                        append_locked(path, line.as_bytes()).unwrap();
+                    }
+                });
+            }
+        });
+        let results = std::fs::read_to_string(&path).unwrap();
+        let lines: Vec<_> = results.lines().collect();
+        assert_eq!(lines.len(), 8 * 20);
+        assert!(lines
+            .iter()
+            .all(|line| line.len() == 4096 && line.chars().all(|c| line.starts_with(c))));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,221 +1,2013 @@
-use common::file;
-use ethers_providers::{Http, Provider};
+use alerts::{AlertsConfig, Event, EventKind, Notifier};
+use ethers_providers::Middleware;
+use goat_prover::{atomic_file, check, check_trace, error, proof, suite};
 use std::env;
 use std::fs::read;
-use std::path::Path;
+use std::ops::RangeInclusive;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
-use std::time::Instant;
-use zkm_sdk::{prover::ClientCfg, prover::ProverInput, ProverClient};
+use std::time::{Duration, Instant};
+use tokio::signal::unix::{signal, SignalKind};
+use tokio_util::sync::CancellationToken;
+use tracing::Instrument;
+use zkm_sdk::prover::ClientCfg;
 
-mod check;
+/// How often the clock skew against the chain head is re-checked in loop mode.
+const CLOCK_CHECK_INTERVAL: Duration = Duration::from_secs(600);
+
+mod aggregate;
+mod api;
+mod binding;
+mod blocks;
+mod check_bench;
+mod check_dir;
+mod checkpoint;
+mod clock;
+mod config;
+mod db;
+mod elf;
+mod execution;
+mod filter;
+mod head;
+mod lag;
+mod lock;
+mod meta;
+mod metrics;
+mod naming;
+mod output_stats;
+mod pipeline;
+mod preflight;
+mod prover;
+mod publish;
+mod ratelimit;
+mod receipts;
+mod reorg;
+mod revalidate;
+mod rpc;
+mod run_stats;
+mod schema;
+mod seg_size;
+mod status;
+mod submit;
+mod summary;
+mod telemetry;
+#[cfg(test)]
+mod test_util;
+mod tracing_otel;
+mod upload;
+mod verify;
+
+use api::{JobQueue, JobStatus};
+use binding::PublicInputsBinding;
+use blocks::{BlockQueue, BlockSpec};
+use check::{CheckConfig, SpecFilter, SuiteMode};
+use check_bench::BenchOptions;
+use check_trace::CheckTrace;
+use checkpoint::{Checkpoint, Frontier};
+use clock::ClockSkewPolicy;
+use config::ProverConfig;
+use db::ProverDb;
+use error::ProverError;
+use execution::{ExecuteReport, ExecutionStats};
+use filter::TargetFilter;
+use head::HeadTracker;
+use lag::LagGuard;
+use lock::OutputLock;
+use meta::BlockMeta;
+use metrics::METRICS;
+use pipeline::{Prefetched, PreparedBlock, Producer};
+use proof::{ProofOutcome, SuiteArtifacts};
+use prover::SharedProver;
+use publish::DaPublisher;
+use receipts::{ReceiptCheck, ReceiptTolerance};
+use reorg::ReorgDetector;
+use rpc::FetchRetry;
+use run_stats::RunStatsCsv;
+use seg_size::AutoSegSize;
+use status::{Phase, StatusHandle};
+use submit::ProofSubmitter;
+use summary::{BlockStatus, BlockSummary};
+use telemetry::Telemetry;
+use upload::S3Sink;
+use verify::ProofVerifier;
+
+/// Outcome of proving a single block.
+#[derive(Debug)]
+enum ProveOutcome {
+    /// The proof was generated with `seg_size` and written to `proof_path`;
+    /// `verified` tells whether it was checked against the vk.
+    Proved {
+        proof_path: PathBuf,
+        seg_size: u32,
+        verified: bool,
+        stats: ExecutionStats,
+    },
+    /// EXECUTE_ONLY run; the guest was executed but no proof was saved.
+    Executed { stats: ExecutionStats },
+    /// GENERATE_ONLY run, proving was not attempted.
+    Skipped,
+    /// No transaction touches TARGET_ADDRESSES, proving was not attempted.
+    Irrelevant,
+    /// PROVE_EVERY_N does not select the block, proving was not attempted.
+    SampledOut,
+    /// Every attempt failed; `error` is the last error returned by the prover.
+    Failed { attempts: u32, error: String },
+}
+
+/// Per-run block counters, logged when the loop exits.
+#[derive(Debug, Default)]
+struct RunStats {
+    proved: u64,
+    executed: u64,
+    generated: u64,
+    /// Blocks skipped because a proof already exists in OUTPUT_DIR.
+    skipped: u64,
+    /// Blocks checked but not selected by PROVE_EVERY_N, not a gap.
+    sampled_out: u64,
+    failed: u64,
+    /// Status of every block, only kept when proving an explicit BLOCKS_FILE.
+    blocks: Vec<(u64, &'static str)>,
+}
+
+impl RunStats {
+    fn record(&mut self, list_mode: bool, block_no: u64, status: &'static str) {
+        if list_mode {
+            self.blocks.push((block_no, status));
+        }
+    }
+
+    /// Replace the recorded status of `block_no`, after a retry pass.
+    fn update(&mut self, list_mode: bool, block_no: u64, status: &'static str) {
+        if let Some(entry) = self.blocks.iter_mut().find(|(n, _)| *n == block_no) {
+            entry.1 = status;
+        } else {
+            self.record(list_mode, block_no, status);
+        }
+    }
+}
+
+/// Checkpoints track sequential progress, a BLOCKS_FILE run leaves them alone.
+fn store_checkpoint(
+    list_mode: bool,
+    opts: &ProveOptions,
+    block_no: u64,
+    proof_path: Option<&Path>,
+) -> anyhow::Result<()> {
+    if list_mode {
+        return Ok(());
+    }
+    let mut checkpoint = Checkpoint::new(block_no, proof_path);
+    if let Some(auto_seg_size) = &opts.auto_seg_size {
+        checkpoint.seg_size_ratios = auto_seg_size.ratios();
+    }
+    checkpoint.store(&opts.outdir)
+}
+
+/// Move the artifacts of a block replaced by a reorg aside as `*.reorged`.
+fn invalidate_block(opts: &ProveOptions, block_no: u64) -> anyhow::Result<()> {
+    let outdir = &opts.outdir;
+    let paths = [
+        BlockSummary::path(outdir, block_no),
+        BlockMeta::path(outdir, block_no),
+    ];
+    let proof = naming::find_proof(outdir, block_no);
+    for path in paths
+        .into_iter()
+        .chain(proof)
+        .chain(suite::file_paths(outdir, block_no))
+    {
+        if path.exists() {
+            let mut reorged = path.clone().into_os_string();
+            reorged.push(".reorged");
+            std::fs::rename(&path, reorged)?;
+        }
+    }
+    if let Some(db) = &opts.db {
+        db.invalidate(block_no)?;
+    }
+    Ok(())
+}
+
+/// Whether `path` holds a non-empty proof file that parses as JSON.
+fn has_valid_proof(path: &Path) -> bool {
+    match read(path) {
+        Ok(buf) => !buf.is_empty() && serde_json::from_slice::<serde_json::Value>(&buf).is_ok(),
+        Err(_) => false,
+    }
+}
+
+/// Blocks selected for proving by PROVE_EVERY_N and PROVE_SAMPLE_OFFSET,
+/// every other block is only checked.
+#[derive(Debug, Clone, Copy)]
+struct Sampling {
+    every: u64,
+    offset: u64,
+}
+
+impl Sampling {
+    /// `None` when every block is proved.
+    fn from_env() -> Option<Self> {
+        let every = env::var("PROVE_EVERY_N").unwrap_or("1".to_string());
+        let every = every.parse::<u64>().unwrap_or(1);
+        let offset = env::var("PROVE_SAMPLE_OFFSET").unwrap_or("0".to_string());
+        (every > 1).then(|| Self {
+            every,
+            offset: offset.parse::<u64>().unwrap_or(0) % every,
+        })
+    }
+
+    fn includes(&self, block_no: u64) -> bool {
+        block_no % self.every == self.offset
+    }
+}
+
+/// Retry policy for the prover call, configured by PROVE_MAX_RETRIES and
+/// PROVE_RETRY_BACKOFF_SECS.
+#[derive(Debug, Clone)]
+struct RetryPolicy {
+    max_retries: u32,
+    backoff_secs: u64,
+}
+
+impl RetryPolicy {
+    fn from_env() -> Self {
+        let max_retries = env::var("PROVE_MAX_RETRIES").unwrap_or("3".to_string());
+        let backoff_secs = env::var("PROVE_RETRY_BACKOFF_SECS").unwrap_or("30".to_string());
+        Self {
+            max_retries: max_retries.parse().unwrap_or(3),
+            backoff_secs: backoff_secs.parse().unwrap_or(30),
+        }
+    }
+
+    /// Exponential backoff before the retry following `attempt` (1-based).
+    fn backoff(&self, attempt: u32) -> Duration {
+        let factor = 1u64 << (attempt.saturating_sub(1)).min(16);
+        Duration::from_secs(self.backoff_secs.saturating_mul(factor))
+    }
+}
+
+/// Settings shared by every block proved in a run.
+struct ProveOptions {
+    /// Only generate and check the test suite, never construct a ProverClient.
+    generate_only: bool,
+    elf_path: String,
+    seg_size: u32,
+    /// SEG_SIZE=auto, `seg_size` only fills in for the summaries of
+    /// unproved blocks then.
+    auto_seg_size: Option<AutoSegSize>,
+    /// Floor for halving SEG_SIZE when the prover returns an empty proof.
+    min_seg_size: u32,
+    execute_only: bool,
+    outdir: String,
+    /// COMPRESS_SUITES, write the test suites as `{block_no}.json.gz`.
+    compress_suites: bool,
+    /// PRIVATE_INPUT_PATH, a file or a template containing `{block_no}`.
+    private_input: Option<String>,
+    retry: RetryPolicy,
+    sampling: Option<Sampling>,
+    /// Upper bound of a single prover call, PROVE_TIMEOUT_SECS.
+    timeout: Duration,
+    receipt_check: ReceiptCheck,
+    receipt_tolerance: ReceiptTolerance,
+    check_config: CheckConfig,
+    suite_mode: SuiteMode,
+    verifier: Option<ProofVerifier>,
+    binding: Option<PublicInputsBinding>,
+    submitter: Option<ProofSubmitter>,
+    publisher: Option<DaPublisher>,
+    db: Option<ProverDb>,
+    targets: Option<TargetFilter>,
+    /// `None` for the offline prove-file subcommand.
+    run_stats: Option<RunStatsCsv>,
+    s3: Option<Arc<S3Sink>>,
+    notifier: Option<Notifier>,
+    telemetry: Option<Telemetry>,
+}
+
+/// File of the private input stream of `block_no`, if PRIVATE_INPUT_PATH is set.
+fn private_input_path(opts: &ProveOptions, block_no: u64) -> Option<String> {
+    let template = opts.private_input.as_ref()?;
+    Some(template.replace("{block_no}", &block_no.to_string()))
+}
 
 async fn prove(
-    cfg: &ClientCfg,
+    prover: &SharedProver,
+    opts: &ProveOptions,
     json_path: &str,
-    elf_path: &str,
+    blocks: RangeInclusive<u64>,
+    block_hash: Option<&str>,
     seg_size: u32,
-    execute_only: bool,
-    outdir: &str,
-    block_no: u64,
-) {
+    shutdown: &CancellationToken,
+) -> anyhow::Result<ProveOutcome> {
+    let (block_no, last_block_no) = (*blocks.start(), *blocks.end());
     log::info!("Start prove block! block_no:{}", block_no);
-    let prover_client = ProverClient::new(cfg).await;
-    let input = ProverInput {
-        elf: read(elf_path).unwrap(),
-        public_inputstream: read(json_path).unwrap(),
-        private_inputstream: vec![],
+    // A missing private input fails the block, the guest cannot be proved without it.
+    let private_inputstream = match private_input_path(opts, block_no) {
+        Some(path) => match read(&path) {
+            Ok(buf) => buf,
+            Err(e) => {
+                let error = format!("failed to read private input {}: {}", path, e);
+                log::error!("Fail: {}. block_no:{}", error, block_no);
+                return Ok(ProveOutcome::Failed { attempts: 0, error });
+            }
+        },
+        None => vec![],
+    };
+    let artifacts = SuiteArtifacts {
+        private_inputstream,
+        ..SuiteArtifacts::read(&opts.elf_path, json_path)?
+    };
+    let mut call = proof::ProveOptions {
         seg_size,
-        execute_only,
+        execute_only: opts.execute_only,
+        timeout: opts.timeout,
     };
 
-    let start = Instant::now();
-    let proving_result = prover_client.prover.prove(&input, None).await;
-    match proving_result {
-        Ok(Some(prover_result)) => {
-            if !execute_only {
+    // `attempts` counts every prover call, `failures` only the ones charged
+    // against the retry budget.
+    let mut attempts = 0;
+    let mut failures = 0;
+    let mut reconnected = false;
+    loop {
+        attempts += 1;
+        let start = Instant::now();
+        let prover_client = prover.client().await;
+        let proving_result = proof::prove_with(&prover_client, &artifacts, &call).await;
+        let elapsed = Instant::now().duration_since(start);
+        let ended_by = match &proving_result {
+            Ok(ProofOutcome::Completed(_)) => "completion",
+            Ok(ProofOutcome::TimedOut) => "timeout",
+            _ => "error",
+        };
+        log::info!(
+            "Elapsed time: {:?} secs block_no:{} attempt:{} seg_size:{} ended_by:{}",
+            elapsed.as_secs(),
+            block_no,
+            attempts,
+            call.seg_size,
+            ended_by
+        );
+
+        let error = match proving_result {
+            Ok(ProofOutcome::Completed(prover_result)) => {
+                let stats = ExecutionStats::from_result(&prover_result, call.seg_size);
+                if let Some(cycles) = stats.cycles {
+                    METRICS.cycles_total.set(cycles);
+                }
+                if let Some(segments) = stats.segments {
+                    METRICS.segments_per_block.observe(segments as f64);
+                }
+                if opts.execute_only {
+                    log::info!("Executed {}. block_no:{}", stats.describe(), block_no);
+                    let report = ExecuteReport {
+                        schema_version: schema::SCHEMA_VERSION,
+                        block_no,
+                        last_block_no: (last_block_no != block_no).then_some(last_block_no),
+                        seg_size: call.seg_size,
+                        execute_secs: elapsed.as_secs(),
+                        stats,
+                    };
+                    let report_path = report.write(&opts.outdir)?;
+                    log::info!("Execute report written to {}", report_path.display());
+                    return Ok(ProveOutcome::Executed {
+                        stats: report.stats,
+                    });
+                }
                 if prover_result.proof_with_public_inputs.is_empty() {
-                    log::info!(
-                        "Fail: snark_proof_with_public_inputs.len() is : {}.Please try setting SEG_SIZE={}",
-                        prover_result.proof_with_public_inputs.len(), seg_size/2
+                    let next_seg_size = call.seg_size / 2;
+                    if next_seg_size < opts.min_seg_size {
+                        let error =
+                            format!("empty proof at the minimum SEG_SIZE={}", call.seg_size);
+                        log::error!("Fail: {}. block_no:{}", error, block_no);
+                        return Ok(ProveOutcome::Failed { attempts, error });
+                    }
+                    log::warn!(
+                        "Fail: snark_proof_with_public_inputs is empty with SEG_SIZE={}, retrying with SEG_SIZE={}. block_no:{}",
+                        call.seg_size,
+                        next_seg_size,
+                        block_no
                     );
+                    call.seg_size = next_seg_size;
+                    continue;
                 }
-                let output_path = Path::new(outdir);
                 let proof_result_path =
-                    output_path.join(format!("{}_snark_proof_with_public_inputs.json", block_no));
-                let mut f = file::new(&proof_result_path.to_string_lossy());
-                match f.write(prover_result.proof_with_public_inputs.as_slice()) {
-                    Ok(bytes_written) => {
-                        log::info!("Proof: successfully written {} bytes.", bytes_written);
+                    naming::proof_path(&opts.outdir, block_no, last_block_no, block_hash);
+                let proof = &prover_result.proof_with_public_inputs;
+                if let Err(e) = atomic_file::write(&proof_result_path, proof) {
+                    let error = format!(
+                        "failed to write proof {}: {}",
+                        proof_result_path.display(),
+                        e
+                    );
+                    log::error!("Fail: {}. block_no:{}", error, block_no);
+                    return Ok(ProveOutcome::Failed { attempts, error });
+                }
+                log::info!("Proof: successfully written {} bytes.", proof.len());
+                METRICS.proof_bytes_written.inc_by(proof.len() as u64);
+                log::info!(
+                    "Generating proof successfully, {}. block_no:{}",
+                    stats.describe(),
+                    block_no
+                );
+                if let Some(binding) = &opts.binding {
+                    let bound = binding.check(
+                        &prover_result.proof_with_public_inputs,
+                        &artifacts.public_inputstream,
+                    );
+                    if let Err(e) = bound {
+                        // Proving the same suite again commits to the same hash.
+                        let _ = std::fs::remove_file(&proof_result_path);
+                        let error = e.to_string();
+                        log::error!("Fail: {}. block_no:{}", error, block_no);
+                        return Ok(ProveOutcome::Failed { attempts, error });
                     }
-                    Err(e) => {
-                        log::info!("Proof: failed to write to file: {}", e);
+                }
+                match &opts.verifier {
+                    None => {
+                        return Ok(ProveOutcome::Proved {
+                            proof_path: proof_result_path,
+                            seg_size: call.seg_size,
+                            verified: false,
+                            stats,
+                        });
                     }
+                    Some(verifier) => match verifier.verify(&proof_result_path).await {
+                        Ok(()) => {
+                            log::info!("Proof verified. block_no:{}", block_no);
+                            return Ok(ProveOutcome::Proved {
+                                proof_path: proof_result_path,
+                                seg_size: call.seg_size,
+                                verified: true,
+                                stats,
+                            });
+                        }
+                        Err(e) => {
+                            // never leave an invalid proof where the skip-existing check finds it
+                            let _ = std::fs::remove_file(&proof_result_path);
+                            let error = format!("proof verification failed: {}", e);
+                            if let Some(notifier) = &opts.notifier {
+                                let mut event =
+                                    Event::new(EventKind::VerificationFailed, Some(block_no));
+                                event.error = Some(error.clone());
+                                notifier.notify(event);
+                            }
+                            error
+                        }
+                    },
                 }
-                log::info!("Generating proof successfully.");
-            } else {
-                log::info!("Generating proof successfully .The proof is not saved.");
             }
+            Ok(ProofOutcome::NoResult) => "The result is None".to_string(),
+            Err(e) => {
+                let error = e.to_string();
+                // The connection of the shared client may have gone stale, retry
+                // once on a new one before charging the retry budget.
+                if !reconnected && prover::is_connection_error(&error) {
+                    log::warn!(
+                        "Prover connection lost, reconnecting. block_no:{} error: {}",
+                        block_no,
+                        error
+                    );
+                    reconnected = true;
+                    prover.reset().await;
+                    continue;
+                }
+                error
+            }
+            Ok(ProofOutcome::TimedOut) => {
+                format!("prover timed out after {} secs", opts.timeout.as_secs())
+            }
+        };
+
+        failures += 1;
+        if failures > opts.retry.max_retries {
+            log::error!(
+                "Failed to generate proof after {} attempts. block_no:{} error: {}",
+                attempts,
+                block_no,
+                error
+            );
+            return Ok(ProveOutcome::Failed { attempts, error });
         }
-        Ok(None) => {
-            log::info!("Failed to generate proof.The result is None.");
-        }
-        Err(e) => {
-            log::info!("Failed to generate proof. error: {}", e);
+        let backoff = opts.retry.backoff(failures);
+        log::warn!(
+            "Failed to generate proof. block_no:{} attempt:{} error: {}. Retrying in {} secs",
+            block_no,
+            attempts,
+            error,
+            backoff.as_secs()
+        );
+        tokio::select! {
+            _ = tokio::time::sleep(backoff) => {}
+            _ = shutdown.cancelled() => {
+                log::warn!("Shutdown requested, giving up retries for block_no:{}", block_no);
+                return Ok(ProveOutcome::Failed { attempts, error });
+            }
         }
     }
-
-    let end = Instant::now();
-    let elapsed = end.duration_since(start);
-    log::info!(
-        "Elapsed time: {:?} secs block_no:{}",
-        elapsed.as_secs(),
-        block_no
-    );
 }
 
 async fn prove_tx(
-    cfg: &ClientCfg,
-    outdir: &str,
-    elf_path: &str,
-    seg_size: u32,
-    execute_only: bool,
-    test_suite: &models::TestSuite,
-    block_no: u64,
-) -> anyhow::Result<()> {
-    let mut buf = Vec::new();
-    let json_string = serde_json::to_string(&test_suite).expect("Failed to serialize");
-    log::debug!("test_suite: {}", json_string);
-    bincode::serialize_into(&mut buf, &json_string).expect("serialization failed");
-    let suite_json_path = format!("{}/{}.json", outdir, block_no);
-    std::fs::write(suite_json_path.clone(), &buf)?;
-    let check_start_time = Instant::now();
-    crate::check::execute_test_suite(&buf).unwrap();
-    let check_end_time = Instant::now();
-    log::info!(
-        "Elapsed time: {:?} micros check block_no:{}",
-        check_end_time.duration_since(check_start_time).as_micros(),
-        block_no
-    );
-    if elf_path.is_empty() {
-        log::info!("ELF_PATH is empty, skip proving");
-        return Ok(());
+    prover: &SharedProver,
+    opts: &ProveOptions,
+    status: &StatusHandle,
+    prepared: &PreparedBlock,
+    shutdown: &CancellationToken,
+    pass: u32,
+) -> anyhow::Result<ProveOutcome> {
+    let block_no = prepared.block_no;
+    let test_suite = &prepared.test_suite;
+    let buf = &prepared.suite;
+    if !prepared.relevant {
+        log::info!(
+            "No transaction touches TARGET_ADDRESSES, skip block_no: {}",
+            block_no
+        );
+        let mut summary = BlockSummary::new(block_no, BlockStatus::Skipped);
+        summary.tx_count = test_suite.0.len();
+        summary.suite_bytes = buf.len();
+        summary.skip_reason = Some("no relevant txs".to_string());
+        record_summary(opts, &summary, None)?;
+        return Ok(ProveOutcome::Irrelevant);
+    }
+    let mut summary = BlockSummary::new(block_no, BlockStatus::Generated);
+    summary.pass = pass;
+    summary.tx_count = test_suite.0.len();
+    summary.suite_bytes = buf.len();
+    summary.check_micros = prepared.check_micros;
+    summary.check_gas_used = prepared.check_gas_used;
+    summary.receipts = prepared.receipts.clone();
+    summary.seg_size = opts.seg_size;
+    summary.execute_only = opts.execute_only;
+    summary.last_block_no = (prepared.last_block_no != block_no).then_some(prepared.last_block_no);
+    summary.private_input_bytes = private_input_path(opts, block_no)
+        .and_then(|path| std::fs::metadata(path).ok())
+        .map_or(0, |metadata| metadata.len());
+    if opts.generate_only {
+        log::info!("GENERATE_ONLY is set, skip proving");
+        record_summary(opts, &summary, None)?;
+        return Ok(ProveOutcome::Skipped);
     }
+    if let Some(sampling) = &opts.sampling {
+        if !sampling.includes(block_no) {
+            log::info!(
+                "PROVE_EVERY_N does not select block_no: {}, skip proving",
+                block_no
+            );
+            summary.status = BlockStatus::SampledOut;
+            record_summary(opts, &summary, None)?;
+            return Ok(ProveOutcome::SampledOut);
+        }
+    }
+    let seg_size_estimate = match &opts.auto_seg_size {
+        Some(auto_seg_size) => {
+            let gas_used = prepared.meta.iter().map(|meta| meta.gas_used).sum();
+            auto_seg_size.estimate(block_no, gas_used, test_suite.0.len())
+        }
+        None => opts.seg_size,
+    };
+    summary.seg_size = seg_size_estimate;
+    status.set_phase(block_no, Phase::Proving);
     let start_time = Instant::now();
-    prove(
-        cfg,
-        &suite_json_path,
-        elf_path,
-        seg_size,
-        execute_only,
-        outdir,
+    let span = tracing::info_span!(
+        "prove",
         block_no,
+        tx_count = test_suite.0.len(),
+        seg_size = seg_size_estimate,
+        pass
+    );
+    let outcome = prove(
+        prover,
+        opts,
+        &prepared.suite_path,
+        block_no..=prepared.last_block_no,
+        prepared.meta.first().map(|meta| meta.block_hash.as_str()),
+        seg_size_estimate,
+        shutdown,
     )
-    .await;
+    .instrument(span)
+    .await?;
     let end_time = Instant::now();
+    METRICS
+        .proof_duration_seconds
+        .observe(end_time.duration_since(start_time).as_secs_f64());
+    summary.prove_secs = end_time.duration_since(start_time).as_secs();
+    let mut meta_paths = Vec::new();
+    match &outcome {
+        ProveOutcome::Proved {
+            proof_path,
+            seg_size,
+            verified,
+            stats,
+        } => {
+            METRICS.blocks_proved_total.inc();
+            summary.set_execution(stats);
+            METRICS.last_proved_block_number.set(block_no);
+            status.record_success(block_no);
+            summary.status = BlockStatus::Proved;
+            summary.seg_size = *seg_size;
+            if let Some(auto_seg_size) = &opts.auto_seg_size {
+                auto_seg_size.record(seg_size_estimate, *seg_size);
+            }
+            summary.verified = *verified;
+            summary.proof_bytes = std::fs::metadata(proof_path)?.len();
+            let suite_keccak256 = format!("{:?}", suite::suite_hash(test_suite)?);
+            let proof_keccak256 = meta::keccak256_hex(&read(proof_path)?);
+            for meta in &prepared.meta {
+                let mut meta = meta.clone();
+                meta.suite_keccak256 = suite_keccak256.clone();
+                meta.proof_keccak256 = proof_keccak256.clone();
+                meta_paths.push(meta.write(&opts.outdir)?);
+            }
+            if let Some(submitter) = &opts.submitter {
+                let proof = read(proof_path)?;
+                match submitter.submit(proof, buf, block_no).await {
+                    Ok(submission) => {
+                        log::info!(
+                            "Proof of block_no:{} committed on chain, tx:{:?} gas_used:{:?}",
+                            block_no,
+                            submission.tx_hash,
+                            submission.gas_used
+                        );
+                        summary.submit_tx_hash = Some(format!("{:?}", submission.tx_hash));
+                        summary.submit_gas_used = submission.gas_used;
+                    }
+                    Err(e) => {
+                        log::error!("Failed to submit proof of block_no:{}: {}", block_no, e);
+                        summary.submit_error = Some(e.to_string());
+                    }
+                }
+            }
+            if let Some(publisher) = &opts.publisher {
+                let proof = read(proof_path)?;
+                match publisher.publish(&proof, block_no).await {
+                    Ok(submission) => {
+                        log::info!(
+                            "Proof of block_no:{} published to DA, height:{} commitment:{}",
+                            block_no,
+                            submission.height,
+                            submission.commitment
+                        );
+                        summary.da_height = Some(submission.height);
+                        summary.da_commitment = Some(submission.commitment);
+                    }
+                    Err(e) => {
+                        log::error!("Failed to publish proof of block_no:{}: {}", block_no, e);
+                        summary.da_error = Some(e.to_string());
+                    }
+                }
+            }
+        }
+        ProveOutcome::Failed { error, .. } => {
+            METRICS.blocks_failed_total.inc();
+            status.record_error(format!("block_no {}: {}", block_no, error));
+            summary.status = BlockStatus::Failed;
+            summary.error = Some(error.clone());
+        }
+        ProveOutcome::Executed { stats } => {
+            summary.status = BlockStatus::Executed;
+            summary.set_execution(stats);
+        }
+        ProveOutcome::Skipped | ProveOutcome::Irrelevant | ProveOutcome::SampledOut => {}
+    }
+    let proof_path = match &outcome {
+        ProveOutcome::Proved { proof_path, .. } => Some(proof_path.as_path()),
+        _ => None,
+    };
+    record_summary(opts, &summary, proof_path)?;
+    if let Some(notifier) = &opts.notifier {
+        let kind = match &outcome {
+            ProveOutcome::Proved { .. } => Some(EventKind::BlockProved),
+            ProveOutcome::Failed { .. } => Some(EventKind::BlockFailed),
+            _ => None,
+        };
+        if let Some(kind) = kind {
+            let mut event = Event::new(kind, Some(block_no));
+            event.error = summary.error.clone();
+            event.duration_secs = Some(summary.prove_secs);
+            notifier.notify(event);
+        }
+    }
+    if let (Some(s3), Some(proof_path)) = (&opts.s3, proof_path) {
+        let mut files = vec![
+            proof_path.to_path_buf(),
+            PathBuf::from(&prepared.suite_path),
+        ];
+        files.extend(meta_paths);
+        s3.spawn_upload(block_no, files);
+    }
     log::info!(
         "Elapsed time: {};{};{};{}",
         block_no,
         test_suite.0.len(),
+        // An empty suite has no environment to report.
         test_suite
             .0
             .first_key_value()
-            .unwrap()
-            .1
-            .env
-            .parent_blob_gas_used
+            .and_then(|(_, unit)| unit.env.parent_blob_gas_used)
             .unwrap_or_default(),
         end_time.duration_since(start_time).as_secs(),
     );
 
+    Ok(outcome)
+}
+
+/// Write `summary` to OUTPUT_DIR, run_stats.csv and, when DB_PATH is set, the
+/// database.
+fn record_summary(
+    opts: &ProveOptions,
+    summary: &BlockSummary,
+    proof_path: Option<&Path>,
+) -> anyhow::Result<()> {
+    summary.write(&opts.outdir)?;
+    if let Some(run_stats) = &opts.run_stats {
+        run_stats.append(summary)?;
+    }
+    if let Some(telemetry) = &opts.telemetry {
+        telemetry.send(summary);
+    }
+    if let Some(db) = &opts.db {
+        db.record_finish(summary, proof_path)?;
+        // Every block of a batch shares the result of its proof.
+        if let Some(last_block_no) = summary.last_block_no {
+            let mut summary = summary.clone();
+            for block_no in summary.block_no + 1..=last_block_no {
+                summary.block_no = block_no;
+                db.record_finish(&summary, proof_path)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// How `check` prints its report: the tables (default), the report as JSON
+/// on stdout with `--json`, or nothing with `--quiet` for the exit code only.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CheckOutput {
+    Table,
+    Json,
+    Quiet,
+}
+
+impl CheckOutput {
+    fn from_args(args: &[String]) -> Self {
+        if args.iter().any(|arg| arg == "--quiet") {
+            Self::Quiet
+        } else if args.iter().any(|arg| arg == "--json") {
+            Self::Json
+        } else {
+            Self::Table
+        }
+    }
+}
+
+/// Report of `check` on one suite file.
+#[derive(Debug, serde::Serialize)]
+struct CheckReport<'a> {
+    file: &'a str,
+    block_no: u64,
+    elapsed_micros: u64,
+    #[serde(flatten)]
+    summary: check::CheckSummary<'a>,
+}
+
+/// `check SUITE [--json | --quiet] [--json-report] [--bench ...]`: check a
+/// suite file and print the result of every unit, with `--json-report` also
+/// written to `{name}_check_report.json` next to it. See [`BenchOptions`] for
+/// `--bench`. Fails if any unit fails.
+async fn check(
+    filepath: &str,
+    check_config: CheckConfig,
+    output: CheckOutput,
+    json_report: bool,
+    bench: Option<BenchOptions>,
+) -> anyhow::Result<()> {
+    let started = Instant::now();
+    let check_config = CheckConfig {
+        bench_runs: bench.as_ref().map_or(1, |bench| bench.runs),
+        ..check_config
+    };
+    let buf = suite::read(filepath)?;
+    let path = Path::new(filepath);
+    let block_no = suite::block_no(path).unwrap_or_default();
+    let trace = CheckTrace::from_env(path.parent().unwrap_or(Path::new(".")), block_no)?;
+    let report = check::run_suite_report(&buf, &check_config, trace.as_ref())
+        .map_err(|e| ProverError::Check(format!("{}: {}", filepath, e)))?;
+    let check_report = CheckReport {
+        file: filepath,
+        block_no,
+        elapsed_micros: started.elapsed().as_micros() as u64,
+        summary: report.summary(),
+    };
+    match output {
+        CheckOutput::Table => print_check_report(&check_report),
+        CheckOutput::Json => println!("{}", serde_json::to_string_pretty(&check_report)?),
+        CheckOutput::Quiet => {}
+    }
+    if json_report {
+        let name = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .and_then(|name| name.split('.').next())
+            .unwrap_or("suite");
+        let report_path = path.with_file_name(format!("{}_check_report.json", name));
+        atomic_file::write(&report_path, serde_json::to_vec_pretty(&check_report)?)
+            .map_err(|e| ProverError::io(&report_path, e))?;
+        if output != CheckOutput::Quiet {
+            eprintln!("Report written to {}", report_path.display());
+        }
+    }
+    let report = report
+        .into_result(&check_config)
+        .map_err(|e| ProverError::Check(format!("{}: {}", filepath, e)))?;
+    if output == CheckOutput::Table {
+        print_gas_table(&report, block_no);
+    }
+    if let Some(bench) = bench.filter(|_| output != CheckOutput::Quiet) {
+        check_bench::report(&report, &bench)?;
+    }
+    Ok(())
+}
+
+/// Print the pass/fail status of every unit, aligned, then the error of every
+/// failed unit under its highlighted key, and the totals.
+fn print_check_report(report: &CheckReport) {
+    let summary = &report.summary;
+    println!(
+        "{} block_no: {} units: {} specs: {}",
+        report.file,
+        report.block_no,
+        summary.units.len(),
+        summary.specs.iter().copied().collect::<Vec<_>>().join(",")
+    );
+    let width = summary
+        .units
+        .iter()
+        .map(|row| row.unit.len())
+        .max()
+        .unwrap_or(0)
+        .max(4);
+    println!(
+        "{:<width$}  {:<6}  {:>6}  {:>12}  {:>10}  error",
+        "unit", "status", "txs", "gas_used", "micros"
+    );
+    for row in &summary.units {
+        // The first line only, the whole error follows the table.
+        let error = row.error.as_deref().and_then(|e| e.lines().next());
+        println!(
+            "{:<width$}  {:<6}  {:>6}  {:>12}  {:>10}  {}",
+            row.unit,
+            row.status,
+            row.tx_count,
+            row.gas_used,
+            row.micros,
+            error.unwrap_or("")
+        );
+    }
+    let highlight = std::io::IsTerminal::is_terminal(&std::io::stdout());
+    for row in &summary.units {
+        let Some(error) = &row.error else {
+            continue;
+        };
+        if highlight {
+            println!("\n\x1b[1;31mFAILED {}\x1b[0m", row.unit);
+        } else {
+            println!("\nFAILED {}", row.unit);
+        }
+        for line in error.lines() {
+            println!("    {}", line);
+        }
+    }
+    println!(
+        "passed: {} failed: {} txs: {} gas_used: {} micros: {} elapsed micros: {}",
+        summary.passed,
+        summary.failed,
+        summary.tx_count,
+        summary.gas_used,
+        summary.micros,
+        report.elapsed_micros
+    );
+}
+
+/// Print the gas used by every transaction of the suite and the totals per
+/// block.
+fn print_gas_table(report: &check::SuiteReport, block_no: u64) {
+    let mut totals = std::collections::BTreeMap::<u64, (usize, u64)>::new();
+    println!("unit\tspec\tindexes\tgas_used\tsuccess\toutput_len\tlogs");
+    for (name, unit) in &report.units {
+        // Units of a batch suite are prefixed with their zero-padded block.
+        let unit_block_no = name
+            .split_once('_')
+            .filter(|(prefix, _)| prefix.len() == 20)
+            .and_then(|(prefix, _)| prefix.parse().ok())
+            .unwrap_or(block_no);
+        let (txs, gas_used) = totals.entry(unit_block_no).or_default();
+        for outcome in &unit.per_test {
+            println!(
+                "{}\t{}\t{:?}\t{}\t{}\t{}\t{}",
+                name,
+                outcome.spec,
+                outcome.indexes,
+                outcome.gas_used,
+                outcome.success,
+                outcome.output_len,
+                outcome.logs_count
+            );
+            *txs += 1;
+            *gas_used += outcome.gas_used;
+        }
+    }
+    for (block_no, (txs, gas_used)) in totals {
+        println!("block_no: {} txs: {} gas_used: {}", block_no, txs, gas_used);
+    }
+}
+
+/// `check-tx --tx HASH [--rpc-url URL] [--save FILE] [--out DIR] [--json |
+/// --quiet]`: fetch the suite of the block of transaction HASH from the
+/// node, RPC_URL by default, and check the unit of that transaction alone.
+/// `--save` writes the unit as a plain JSON suite that `check` replays
+/// offline, CHECK_TRACE traces go to `--out` (the current directory).
+async fn check_tx(args: &[String], rpc_url: &str, check_config: CheckConfig) -> anyhow::Result<()> {
+    let config_error = |msg: &str| ProverError::Config(format!("check-tx: {}", msg));
+    let hash = arg_value(args, "--tx").ok_or_else(|| config_error("--tx is required"))?;
+    let hash: ethers_core::types::H256 = hash
+        .parse()
+        .map_err(|_| config_error(&format!("invalid --tx {:?}", hash)))?;
+    let rpc_url = arg_value(args, "--rpc-url").unwrap_or(rpc_url);
+    let output = CheckOutput::from_args(args);
+
+    let client = rpc::connect(rpc_url)
+        .await
+        .map_err(|e| ProverError::Rpc(format!("failed to connect to {}: {}", rpc_url, e)))?;
+    let tx = client
+        .get_transaction(hash)
+        .await
+        .map_err(|e| ProverError::Rpc(format!("failed to fetch tx {:?}: {}", hash, e)))?
+        .ok_or_else(|| ProverError::Rpc(format!("tx {:?} not found", hash)))?;
+    let block_no = tx
+        .block_number
+        .ok_or_else(|| ProverError::Rpc(format!("tx {:?} is still pending", hash)))?
+        .as_u64();
+    let test_suite = suite::build(Arc::new(client), block_no, check_config.chain_id).await?;
+
+    // Units are keyed by the executor, find the one of the tx by its sender
+    // and nonce.
+    let sender = revm::primitives::Address::from(tx.from.0);
+    let nonce = revm::primitives::U256::from(tx.nonce.as_u64());
+    let (name, unit) = test_suite
+        .0
+        .into_iter()
+        .find(|(_, unit)| {
+            let tx = &unit.transaction;
+            let unit_sender = tx
+                .sender
+                .or_else(|| check::recover_address(tx.secret_key.as_slice()).ok());
+            unit_sender == Some(sender) && tx.nonce == nonce
+        })
+        .ok_or_else(|| {
+            ProverError::Check(format!(
+                "no unit of the suite of block_no {} is tx {:?}",
+                block_no, hash
+            ))
+        })?;
+    let test_suite = models::TestSuite(std::collections::BTreeMap::from([(name, unit)]));
+
+    if let Some(save_path) = arg_value(args, "--save") {
+        atomic_file::write(save_path, serde_json::to_vec_pretty(&test_suite)?)
+            .map_err(|e| ProverError::io(save_path, e))?;
+        if output != CheckOutput::Quiet {
+            eprintln!("Unit of tx {:?} written to {}", hash, save_path);
+        }
+    }
+
+    let started = Instant::now();
+    let outdir = arg_value(args, "--out").unwrap_or(".");
+    let trace = CheckTrace::from_env(outdir, block_no)?;
+    let mut report = check::SuiteReport::default();
+    for (name, unit) in &test_suite.0 {
+        let unit_started = Instant::now();
+        let mut unit_report = check::execute_test_unit(name, unit, &check_config, trace.as_ref())
+            .unwrap_or_else(|e| check::UnitReport {
+                error: Some(e),
+                ..Default::default()
+            });
+        unit_report.micros = unit_started.elapsed().as_micros() as u64;
+        report.units.insert(name.clone(), unit_report);
+    }
+    let file = format!("{:?}", hash);
+    let check_report = CheckReport {
+        file: &file,
+        block_no,
+        elapsed_micros: started.elapsed().as_micros() as u64,
+        summary: report.summary(),
+    };
+    match output {
+        CheckOutput::Table => print_check_report(&check_report),
+        CheckOutput::Json => println!("{}", serde_json::to_string_pretty(&check_report)?),
+        CheckOutput::Quiet => {}
+    }
+    let report = report
+        .into_result(&check_config)
+        .map_err(|e| ProverError::Check(format!("tx {}: {}", file, e)))?;
+    if output == CheckOutput::Table {
+        print_gas_table(&report, block_no);
+    }
+    Ok(())
+}
+
+fn client_cfg_from_env() -> ClientCfg {
+    ClientCfg {
+        zkm_prover: env::var("ZKM_PROVER").unwrap_or(String::from("network")),
+        vk_path: env::var("VK_PATH").unwrap_or(String::from("")),
+        endpoint: env::var("ENDPOINT").ok(),
+        ca_cert_path: env::var("CA_CERT_PATH").ok(),
+        cert_path: env::var("CERT_PATH").ok(),
+        key_path: env::var("KEY_PATH").ok(),
+        domain_name: env::var("DOMAIN_NAME").ok(),
+        private_key: env::var("PRIVATE_KEY").ok(),
+    }
+}
+
+/// Value following `flag` in `args`.
+fn arg_value<'a>(args: &'a [String], flag: &str) -> Option<&'a str> {
+    let i = args.iter().position(|arg| arg == flag)?;
+    args.get(i + 1).map(String::as_str)
+}
+
+/// `verify --proof <file or dir> [--vk-path DIR]`: check archived proofs
+/// against the vk, VK_PATH by default. Fails if any proof is rejected.
+async fn verify_proofs(args: &[String]) -> anyhow::Result<()> {
+    let proof_path = arg_value(args, "--proof")
+        .ok_or_else(|| ProverError::Config("verify: --proof is required".to_string()))?;
+    let vk_path = match arg_value(args, "--vk-path") {
+        Some(vk_path) => vk_path.to_string(),
+        None => client_cfg_from_env().vk_path,
+    };
+    if vk_path.is_empty() {
+        return Err(
+            ProverError::Config("verify: pass --vk-path or set VK_PATH".to_string()).into(),
+        );
+    }
+    let verifier_bin = env::var("VERIFIER_BIN").unwrap_or("zkm-verifier".to_string());
+    let verifier = ProofVerifier::new(&verifier_bin, &vk_path);
+    let failed = verify::verify_files(&verifier, proof_path).await?;
+    if failed > 0 {
+        anyhow::bail!("{} proofs failed verification", failed);
+    }
+    Ok(())
+}
+
+/// `revalidate --dir DIR [--vk-path DIR] [--older-than YYYY-MM-DD]
+/// [--quarantine] [--jobs N] [--report FILE]`: verify the proofs in DIR
+/// last modified before the date (UTC) against the vk, VK_PATH by default,
+/// one per CPU at a time. Writes the valid, invalid and unreadable proofs to
+/// FILE, `{DIR}/revalidate_report.json` by default, and with `--quarantine`
+/// moves the invalid ones to `{DIR}/quarantine`. Fails if any proof is
+/// invalid or unreadable.
+async fn revalidate_proofs(args: &[String]) -> anyhow::Result<()> {
+    let config_error = |msg: &str| ProverError::Config(format!("revalidate: {}", msg));
+    let dir = Path::new(arg_value(args, "--dir").ok_or_else(|| config_error("--dir is required"))?);
+    let vk_path = match arg_value(args, "--vk-path") {
+        Some(vk_path) => vk_path.to_string(),
+        None => client_cfg_from_env().vk_path,
+    };
+    if vk_path.is_empty() {
+        return Err(config_error("pass --vk-path or set VK_PATH").into());
+    }
+    let older_than = match arg_value(args, "--older-than") {
+        Some(date) => {
+            let midnight = date
+                .parse::<chrono::NaiveDate>()
+                .ok()
+                .and_then(|date| date.and_hms_opt(0, 0, 0))
+                .ok_or_else(|| config_error(&format!("invalid --older-than {:?}", date)))?;
+            let secs = u64::try_from(midnight.and_utc().timestamp()).unwrap_or(0);
+            Some(std::time::UNIX_EPOCH + Duration::from_secs(secs))
+        }
+        None => None,
+    };
+    let jobs = match arg_value(args, "--jobs") {
+        Some(jobs) => jobs
+            .parse()
+            .map_err(|_| config_error(&format!("invalid --jobs {:?}", jobs)))?,
+        None => std::thread::available_parallelism().map_or(1, |n| n.get()),
+    };
+    let options = revalidate::RevalidateOptions {
+        older_than,
+        quarantine: args.iter().any(|arg| arg == "--quarantine"),
+        jobs,
+        report_path: arg_value(args, "--report")
+            .map_or_else(|| dir.join("revalidate_report.json"), PathBuf::from),
+    };
+    let verifier_bin = env::var("VERIFIER_BIN").unwrap_or("zkm-verifier".to_string());
+    let verifier = ProofVerifier::new(&verifier_bin, &vk_path);
+    let report = revalidate::revalidate(&verifier, dir, &options).await?;
+    if !report.invalid.is_empty() || !report.unreadable.is_empty() {
+        anyhow::bail!(
+            "{} proofs invalid, {} unreadable",
+            report.invalid.len(),
+            report.unreadable.len()
+        );
+    }
+    Ok(())
+}
+
+/// `aggregate --from N --to M [--out DIR]`: prove the per-block proofs of
+/// the range in OUTPUT_DIR into one with the aggregation guest AGG_ELF_PATH.
+async fn aggregate_proofs(args: &[String]) -> anyhow::Result<()> {
+    let config_error = |msg: &str| ProverError::Config(format!("aggregate: {}", msg));
+    let block_arg = |flag: &str| -> anyhow::Result<u64> {
+        let value =
+            arg_value(args, flag).ok_or_else(|| config_error(&format!("{} is required", flag)))?;
+        let block_no = value
+            .parse()
+            .map_err(|_| config_error(&format!("invalid {} {:?}", flag, value)))?;
+        Ok(block_no)
+    };
+    let first = block_arg("--from")?;
+    let last = block_arg("--to")?;
+    let output_dir = env::var("OUTPUT_DIR").unwrap_or(String::from("./output"));
+    let outdir = arg_value(args, "--out").unwrap_or(&output_dir);
+    let agg_elf_path = env::var("AGG_ELF_PATH").unwrap_or("".to_string());
+    if agg_elf_path.is_empty() {
+        return Err(config_error("AGG_ELF_PATH is empty").into());
+    }
+    let seg_size = env::var("SEG_SIZE").unwrap_or("65536".to_string());
+    let prove_timeout_secs = env::var("PROVE_TIMEOUT_SECS").unwrap_or("7200".to_string());
+    let prover = SharedProver::new(client_cfg_from_env());
+    let verifier = ProofVerifier::from_env(&prover.cfg().vk_path)?;
+    aggregate::aggregate(
+        &prover,
+        verifier.as_ref(),
+        &agg_elf_path,
+        outdir,
+        first..=last,
+        seg_size.parse().unwrap_or(65536),
+        Duration::from_secs(prove_timeout_secs.parse().unwrap_or(7200)),
+    )
+    .await?;
+    Ok(())
+}
+
+/// `prove-file --suite <file> --elf <file> [--seg-size N] [--out DIR]
+/// [--block N] [--private-input FILE] [--skip-check]`: prove a suite written
+/// by an earlier run or given as plain JSON, without RPC access. The prover
+/// network settings come from the environment.
+async fn prove_file(args: &[String], check_config: CheckConfig) -> anyhow::Result<()> {
+    let config_error = |msg: &str| ProverError::Config(format!("prove-file: {}", msg));
+    let suite_path =
+        arg_value(args, "--suite").ok_or_else(|| config_error("--suite is required"))?;
+    let elf_path = arg_value(args, "--elf").ok_or_else(|| config_error("--elf is required"))?;
+    let seg_size = match arg_value(args, "--seg-size") {
+        Some(seg_size) => seg_size
+            .parse()
+            .map_err(|_| config_error(&format!("invalid --seg-size {:?}", seg_size)))?,
+        None => 65536,
+    };
+    let outdir = arg_value(args, "--out").unwrap_or("./output");
+    let block_no = match arg_value(args, "--block") {
+        Some(block_no) => block_no.parse().ok(),
+        // `{block_no}.json[.gz]` as written by prove_tx
+        None => Path::new(suite_path)
+            .file_name()
+            .and_then(|name| name.to_str())
+            .and_then(|name| name.split('.').next())
+            .and_then(|stem| stem.parse().ok()),
+    };
+    let block_no: u64 = block_no.ok_or_else(|| {
+        config_error(&format!(
+            "cannot derive the block number from {}, pass --block",
+            suite_path
+        ))
+    })?;
+
+    let buf = suite::read(suite_path)?;
+    let test_suite = check::decode_test_suite(&buf).map_err(|e| {
+        ProverError::Check(format!("{} is not a valid test suite: {}", suite_path, e))
+    })?;
+    log::info!(
+        "Suite {} of block_no: {} has {} txs",
+        suite_path,
+        block_no,
+        test_suite.0.len()
+    );
+    if !args.iter().any(|arg| arg == "--skip-check") {
+        let trace = CheckTrace::from_env(outdir, block_no)?;
+        check::execute_test_suite(&buf, &check_config, trace.as_ref())
+            .map_err(|e| ProverError::Check(format!("{}: {}", suite_path, e)))?;
+    }
+
+    // The guest reads the bincode-wrapped JSON, a plain JSON suite is proved
+    // from a wrapped copy next to the proof.
+    let prove_path = if bincode::deserialize::<String>(&buf).is_ok() {
+        suite_path.to_string()
+    } else {
+        std::fs::create_dir_all(outdir).map_err(|e| ProverError::io(outdir, e))?;
+        let path = suite::file_path(outdir, format!("{}.wrapped", block_no), false);
+        suite::write(&path, &suite::encode(&test_suite)?, false)?;
+        log::info!("Plain JSON suite wrapped into {}", path.display());
+        path.to_string_lossy().into_owned()
+    };
+
+    let prover = SharedProver::new(client_cfg_from_env());
+    let execute_only = env::var("EXECUTE_ONLY").unwrap_or("false".to_string());
+    let execute_only = execute_only.parse::<bool>().unwrap_or(false);
+    let min_seg_size = env::var("SEG_SIZE_MIN").unwrap_or("8192".to_string());
+    let prove_timeout_secs = env::var("PROVE_TIMEOUT_SECS").unwrap_or("7200".to_string());
+    let opts = ProveOptions {
+        generate_only: false,
+        elf_path: elf::resolve(elf_path).await?,
+        seg_size,
+        auto_seg_size: None,
+        min_seg_size: min_seg_size.parse().unwrap_or(8192),
+        execute_only,
+        outdir: outdir.to_string(),
+        compress_suites: false,
+        private_input: arg_value(args, "--private-input")
+            .map(str::to_string)
+            .or_else(|| env::var("PRIVATE_INPUT_PATH").ok()),
+        retry: RetryPolicy::from_env(),
+        sampling: None,
+        timeout: Duration::from_secs(prove_timeout_secs.parse().unwrap_or(7200)),
+        receipt_check: ReceiptCheck::Off,
+        receipt_tolerance: ReceiptTolerance::default(),
+        check_config,
+        suite_mode: SuiteMode::from_env()?,
+        verifier: if execute_only {
+            None
+        } else {
+            ProofVerifier::from_env(&prover.cfg().vk_path)?
+        },
+        binding: PublicInputsBinding::from_env()?,
+        submitter: None,
+        publisher: None,
+        db: None,
+        targets: None,
+        run_stats: None,
+        s3: None,
+        notifier: None,
+        telemetry: None,
+    };
+    let shutdown = CancellationToken::new();
+    spawn_signal_handler(shutdown.clone())?;
+    match prove(
+        &prover,
+        &opts,
+        &prove_path,
+        block_no..=block_no,
+        None,
+        seg_size,
+        &shutdown,
+    )
+    .await?
+    {
+        ProveOutcome::Proved { proof_path, .. } => {
+            log::info!(
+                "Block proved: block_no:{} proof: {}",
+                block_no,
+                proof_path.display()
+            );
+        }
+        ProveOutcome::Failed { error, .. } => {
+            return Err(ProverError::Prove(format!("block_no {}: {}", block_no, error)).into());
+        }
+        _ => log::info!("Block executed: block_no:{}", block_no),
+    }
+    Ok(())
+}
+
+/// Prove the blocks requested through the SERVE_ADDR API, one job at a time,
+/// until shutdown.
+async fn run_jobs(
+    producer: &Producer<'_>,
+    prover: &SharedProver,
+    opts: &ProveOptions,
+    status: &StatusHandle,
+    jobs: &JobQueue,
+    mut job_rx: tokio::sync::mpsc::Receiver<u64>,
+) -> anyhow::Result<()> {
+    loop {
+        let id = tokio::select! {
+            id = job_rx.recv() => id,
+            _ = producer.shutdown.cancelled() => None,
+        };
+        let Some(job) = id.and_then(|id| jobs.get(id)) else {
+            break;
+        };
+        let block_no = job.block_no;
+        let proof_path = naming::find_proof(&opts.outdir, block_no);
+        if let Some(proof_path) =
+            proof_path.filter(|path| !producer.force_reprove && has_valid_proof(path))
+        {
+            log::info!(
+                "Proof already exists, job {} block_no: {}",
+                job.id,
+                block_no
+            );
+            jobs.finish(job.id, Ok(Some(proof_path)));
+            continue;
+        }
+        log::info!("Starting job {} block_no: {}", job.id, block_no);
+        jobs.set_status(job.id, JobStatus::Checking);
+        let outcome = match producer.prepare_block(block_no).await {
+            Ok(Prefetched::Ready(prepared)) => {
+                jobs.set_status(job.id, JobStatus::Proving);
+                prove_tx(prover, opts, status, &prepared, producer.shutdown, 0).await
+            }
+            Ok(_) => Ok(ProveOutcome::Irrelevant),
+            Err(e) => Err(e),
+        };
+        let result = match outcome {
+            Ok(ProveOutcome::Proved { proof_path, .. }) => Ok(Some(proof_path)),
+            Ok(ProveOutcome::Failed { error, .. }) => Err(error),
+            Ok(_) => Ok(None),
+            Err(e) => {
+                log::error!("Job {} block_no: {} failed: {}", job.id, block_no, e);
+                status.record_error(format!("block_no {}: {}", block_no, e));
+                Err(e.to_string())
+            }
+        };
+        jobs.finish(job.id, result);
+    }
+    if let Some(notifier) = &opts.notifier {
+        notifier.flush().await;
+    }
+    if let Some(s3) = &opts.s3 {
+        s3.wait().await;
+    }
     Ok(())
 }
 
-async fn check(filepath: &str) -> anyhow::Result<()> {
-    let buf = std::fs::read(filepath).expect("Failed to read file");
-    crate::check::execute_test_suite(&buf).unwrap();
+/// Cancel `shutdown` on the first SIGINT/SIGTERM and exit immediately on a
+/// second SIGINT.
+fn spawn_signal_handler(shutdown: CancellationToken) -> anyhow::Result<()> {
+    let mut sigterm = signal(SignalKind::terminate())?;
+    tokio::spawn(async move {
+        tokio::select! {
+            _ = tokio::signal::ctrl_c() => {}
+            _ = sigterm.recv() => {}
+        }
+        log::info!(
+            "Shutdown requested, finishing the in-flight block. Press Ctrl-C again to force exit"
+        );
+        shutdown.cancel();
+        let _ = tokio::signal::ctrl_c().await;
+        log::warn!("Forced exit");
+        std::process::exit(130);
+    });
     Ok(())
 }
 
 #[tokio::main]
-async fn main() -> anyhow::Result<()> {
-    env_logger::try_init().unwrap_or_default();
-    let block_no = env::var("BLOCK_NO").unwrap_or(String::from("1"));
-    let mut block_no: u64 = block_no.parse().unwrap();
+async fn main() {
+    let tracing = tracing_otel::init();
+    if let Err(e) = &tracing {
+        eprintln!("Failed to set up tracing: {:#}", e);
+    }
+    let result = run().await;
+    // Flush the spans before a non-zero exit skips the destructors.
+    drop(tracing);
+    if let Err(e) = result {
+        log::error!("{:#}", e);
+        std::process::exit(error::exit_code(&e));
+    }
+}
+
+async fn run() -> anyhow::Result<()> {
+    let mut args: Vec<String> = env::args().collect();
+    if let Some(i) = args.iter().position(|arg| arg == "--config") {
+        let Some(config_path) = args.get(i + 1) else {
+            return Err(ProverError::Config("--config requires a path".to_string()).into());
+        };
+        ProverConfig::load(config_path)
+            .map_err(|e| ProverError::Config(e.to_string()))?
+            .apply_to_env();
+        log::info!("Loaded configuration from {}", config_path);
+        args.drain(i..=i + 1);
+    }
+    let block_no = env::var("BLOCK_NO").ok();
+    let resume = env::var("RESUME").unwrap_or("false".to_string());
+    let resume = resume.parse::<bool>().unwrap_or(false);
     let rpc_url = env::var("RPC_URL").unwrap_or(String::from("http://localhost:8545"));
     let chain_id = env::var("CHAIN_ID").unwrap_or(String::from("1"));
+    let chain_id: u64 = chain_id
+        .parse()
+        .map_err(|_| ProverError::Config(format!("invalid CHAIN_ID {:?}", chain_id)))?;
+    naming::init_from_env(chain_id)?;
+    let check_config = CheckConfig::from_env(chain_id);
     let output_dir = env::var("OUTPUT_DIR").unwrap_or(String::from("./output"));
-    let seg_size = env::var("SEG_SIZE").unwrap_or("65536".to_string());
-    let seg_size = seg_size.parse::<_>().unwrap_or(65536);
+    // RUN_ID keeps the artifacts of a run apart in `{OUTPUT_DIR}/{RUN_ID}`.
+    let output_dir = match env::var("RUN_ID") {
+        Ok(run_id) if !run_id.is_empty() => Path::new(&output_dir)
+            .join(run_id)
+            .to_string_lossy()
+            .into_owned(),
+        _ => output_dir,
+    };
+    let seg_size_var = env::var("SEG_SIZE").unwrap_or("65536".to_string());
+    let seg_size = seg_size_var.parse::<_>().unwrap_or(65536);
+    let min_seg_size_var = env::var("SEG_SIZE_MIN").unwrap_or("8192".to_string());
+    let min_seg_size = min_seg_size_var.parse::<_>().unwrap_or(8192);
     let execute_only = env::var("EXECUTE_ONLY").unwrap_or("false".to_string());
     let execute_only = execute_only.parse::<bool>().unwrap_or(false);
     let elf_path = env::var("ELF_PATH").unwrap_or("".to_string());
-    let endpoint = env::var("ENDPOINT").ok();
-    let ca_cert_path = env::var("CA_CERT_PATH").ok();
-    let cert_path = env::var("CERT_PATH").ok();
-    let key_path = env::var("KEY_PATH").ok();
-    let domain_name = env::var("DOMAIN_NAME").ok();
-    let private_key = env::var("PRIVATE_KEY").ok();
+    let compress_suites = env::var("COMPRESS_SUITES").unwrap_or("false".to_string());
+    let compress_suites = compress_suites.parse::<bool>().unwrap_or(false);
+    let generate_only = env::var("GENERATE_ONLY").unwrap_or("false".to_string());
+    let generate_only = generate_only.parse::<bool>().unwrap_or(false);
     let prove_loop = env::var("PROVE_LOOP").unwrap_or("false".to_string());
     let prove_loop = prove_loop.parse::<bool>().unwrap_or(false);
+    let force_reprove = env::var("FORCE_REPROVE").unwrap_or("false".to_string());
+    let force_reprove = force_reprove.parse::<bool>().unwrap_or(false);
+    let blocks_file = env::var("BLOCKS_FILE").ok();
+    let confirmations = env::var("CONFIRMATIONS").unwrap_or("0".to_string());
+    let confirmations = confirmations.parse::<u64>().unwrap_or(0);
+    let follow_head = env::var("FOLLOW_HEAD").unwrap_or("false".to_string());
+    let follow_head = follow_head.parse::<bool>().unwrap_or(false);
+    let prefetch_depth = env::var("PREFETCH_DEPTH").unwrap_or("1".to_string());
+    let prefetch_depth = prefetch_depth.parse::<usize>().unwrap_or(1);
+    let blocks_per_proof = env::var("BLOCKS_PER_PROOF").unwrap_or("1".to_string());
+    let blocks_per_proof = blocks_per_proof.parse::<usize>().unwrap_or(1);
+    let db_path = env::var("DB_PATH").unwrap_or("".to_string());
+    let metrics_addr = env::var("METRICS_ADDR").ok();
+    let status_addr = env::var("STATUS_ADDR").ok();
+    let serve_addr = env::var("SERVE_ADDR").ok();
+    let serve_queue_depth = env::var("SERVE_QUEUE_DEPTH").unwrap_or("16".to_string());
+    let serve_queue_depth = serve_queue_depth.parse::<usize>().unwrap_or(16);
+    let health_max_tick_secs = env::var("HEALTH_MAX_TICK_SECS").unwrap_or("300".to_string());
+    let health_max_tick = Duration::from_secs(health_max_tick_secs.parse().unwrap_or(300));
+    let prove_timeout_secs = env::var("PROVE_TIMEOUT_SECS").unwrap_or("7200".to_string());
+    let shutdown_grace_secs = env::var("SHUTDOWN_GRACE_SECS").unwrap_or("600".to_string());
+    let shutdown_grace = Duration::from_secs(shutdown_grace_secs.parse().unwrap_or(600));
+    let retry_passes = env::var("RETRY_PASSES").unwrap_or("0".to_string());
+    let retry_passes = retry_passes.parse::<u32>().unwrap_or(0);
+    let retry_pass_backoff_secs = env::var("RETRY_PASS_BACKOFF_SECS").unwrap_or("300".to_string());
+    let retry_pass_backoff = Duration::from_secs(retry_pass_backoff_secs.parse().unwrap_or(300));
 
-    let args: Vec<String> = env::args().collect();
+    let generate_only = generate_only || args.get(1).is_some_and(|arg| arg == "generate");
+    let ignore_clock_skew = args.iter().any(|arg| arg == "--ignore-clock-skew");
+    let preflight_only = args.iter().any(|arg| arg == "--preflight-only");
+    let allow_shared_output = args.iter().any(|arg| arg == "--allow-shared-output");
     if args.len() > 2 {
+        let check_config = CheckConfig {
+            specs: SpecFilter {
+                only: arg_value(&args, "--spec").map(SpecFilter::parse_list),
+                skip: arg_value(&args, "--skip-spec")
+                    .map(SpecFilter::parse_list)
+                    .unwrap_or_default(),
+            },
+            ..check_config
+        };
         match args[1].as_str() {
-            "check" => check(args[2].as_str()).await?,
-            &_ => todo!(),
+            "check" if Path::new(&args[2]).is_dir() => {
+                let pattern = arg_value(&args, "--pattern").unwrap_or("*.json*");
+                check_dir::check_dir(&args[2], pattern, check_config).await?
+            }
+            "check" => {
+                let output = CheckOutput::from_args(&args);
+                let json_report = args.iter().any(|arg| arg == "--json-report");
+                let bench = BenchOptions::from_args(&args);
+                check(args[2].as_str(), check_config, output, json_report, bench).await?
+            }
+            "check-tx" => check_tx(&args[2..], &rpc_url, check_config).await?,
+            "prove-file" => prove_file(&args[2..], check_config).await?,
+            "verify" => verify_proofs(&args[2..]).await?,
+            "revalidate" => revalidate_proofs(&args[2..]).await?,
+            "aggregate" => aggregate_proofs(&args[2..]).await?,
+            "stats" if Path::new(&args[2]).is_dir() => {
+                output_stats::print_dir_stats(&args[2], arg_value(&args, "--gaps-file"))?
+            }
+            "stats" => ProverDb::open(args[2].as_str())?.print_stats()?,
+            "migrate-artifacts" => {
+                let dry_run = args.iter().any(|arg| arg == "--dry-run");
+                schema::migrate_artifacts(args[2].as_str(), dry_run)?
+            }
+            subcommand => {
+                return Err(
+                    ProverError::Config(format!("unknown subcommand {}", subcommand)).into(),
+                )
+            }
         };
         return Ok(());
     }
 
-    let client = Provider::<Http>::try_from(rpc_url).unwrap();
+    let prover = SharedProver::new(client_cfg_from_env());
+    preflight::validate(&preflight::StartupConfig {
+        cfg: prover.cfg(),
+        elf_path: &elf_path,
+        generate_only,
+        execute_only,
+        seg_size: &seg_size_var,
+        min_seg_size: &min_seg_size_var,
+    })?;
+    let elf_path = if generate_only {
+        elf_path
+    } else {
+        elf::resolve(&elf_path).await?
+    };
+    preflight::run(&prover, &elf_path, &output_dir, !generate_only).await?;
+    if preflight_only {
+        return Ok(());
+    }
+    // Held until the run returns. A second instance on the same OUTPUT_DIR
+    // only starts with --allow-shared-output, and then keeps its checkpoint
+    // and artifacts in a subdirectory of its own.
+    let (_lock, output_dir) = match OutputLock::acquire(&output_dir)? {
+        Some(lock) => (lock, output_dir),
+        None if allow_shared_output => {
+            let instance_dir = Path::new(&output_dir)
+                .join(format!("instance-{}", std::process::id()))
+                .to_string_lossy()
+                .into_owned();
+            std::fs::create_dir_all(&instance_dir)
+                .map_err(|e| ProverError::io(&instance_dir, e))?;
+            let lock = OutputLock::acquire(&instance_dir)?.ok_or_else(|| {
+                ProverError::Config(format!("{} is locked by another prover", instance_dir))
+            })?;
+            log::warn!(
+                "OUTPUT_DIR {} is shared with another prover, writing to {}",
+                output_dir,
+                instance_dir
+            );
+            (lock, instance_dir)
+        }
+        None => {
+            return Err(ProverError::Config(format!(
+                "OUTPUT_DIR {} is locked by another prover, pass --allow-shared-output to run next to it",
+                output_dir
+            ))
+            .into())
+        }
+    };
+    let removed =
+        atomic_file::remove_stale(&output_dir).map_err(|e| ProverError::io(&output_dir, e))?;
+    if removed > 0 {
+        log::warn!(
+            "Removed {} partial writes left in {} by a previous run",
+            removed,
+            output_dir
+        );
+    }
+
+    let shutdown = CancellationToken::new();
+    spawn_signal_handler(shutdown.clone())?;
+
+    if let Some(metrics_addr) = metrics_addr {
+        let metrics_addr = metrics_addr.parse()?;
+        let shutdown = shutdown.clone();
+        tokio::spawn(async move {
+            if let Err(e) = metrics::serve(metrics_addr, shutdown).await {
+                log::error!("Metrics server failed: {}", e);
+            }
+        });
+    }
+    let status = StatusHandle::new();
+    if let Some(status_addr) = status_addr {
+        let status_addr = status_addr.parse()?;
+        let status = status.clone();
+        let shutdown = shutdown.clone();
+        tokio::spawn(async move {
+            if let Err(e) = status::serve(status_addr, status, health_max_tick, shutdown).await {
+                log::error!("Status server failed: {}", e);
+            }
+        });
+    }
+
+    let client = rpc::connect(&rpc_url)
+        .await
+        .map_err(|e| ProverError::Rpc(format!("failed to connect to {}: {}", rpc_url, e)))?;
     let client = Arc::new(client);
 
-    let prover_cfg = ClientCfg {
-        zkm_prover: env::var("ZKM_PROVER").unwrap_or(String::from("network")),
-        vk_path: env::var("VK_PATH").unwrap_or(String::from("")),
-        endpoint,
-        ca_cert_path,
-        cert_path,
-        key_path,
-        domain_name,
-        private_key,
+    let clock_skew = ClockSkewPolicy::from_env()?;
+    if ignore_clock_skew {
+        log::warn!("Clock skew check is disabled by --ignore-clock-skew");
+    } else {
+        clock_skew.check(&client).await?;
+    }
+
+    let verifier = if execute_only || generate_only {
+        None
+    } else {
+        ProofVerifier::from_env(&prover.cfg().vk_path)?
+    };
+    let opts = ProveOptions {
+        generate_only,
+        elf_path,
+        seg_size,
+        auto_seg_size: AutoSegSize::from_env(
+            &seg_size_var,
+            min_seg_size,
+            Checkpoint::load(&output_dir)?
+                .map(|checkpoint| checkpoint.seg_size_ratios)
+                .unwrap_or_default(),
+        ),
+        min_seg_size,
+        execute_only,
+        outdir: output_dir.clone(),
+        compress_suites,
+        private_input: env::var("PRIVATE_INPUT_PATH")
+            .ok()
+            .filter(|p| !p.is_empty()),
+        retry: RetryPolicy::from_env(),
+        sampling: Sampling::from_env(),
+        timeout: Duration::from_secs(prove_timeout_secs.parse().unwrap_or(7200)),
+        receipt_check: ReceiptCheck::from_env()?,
+        receipt_tolerance: ReceiptTolerance::from_env(),
+        check_config,
+        suite_mode: SuiteMode::from_env()?,
+        verifier,
+        binding: PublicInputsBinding::from_env()?,
+        submitter: ProofSubmitter::from_env().await?,
+        publisher: DaPublisher::from_env(chain_id).await?,
+        db: if db_path.is_empty() {
+            None
+        } else {
+            Some(ProverDb::open(&db_path)?)
+        },
+        targets: TargetFilter::from_env()?,
+        run_stats: Some(RunStatsCsv::create(&output_dir)?),
+        s3: S3Sink::from_env(&output_dir).await?,
+        notifier: Notifier::new(
+            &AlertsConfig::from_env()?,
+            "goat_prover",
+            &chain_id.to_string(),
+        )?,
+        telemetry: Telemetry::from_env()?,
     };
 
-    loop {
-        let test_suite =
-            executor::process(client.clone(), block_no, chain_id.parse().unwrap()).await;
-        match test_suite {
-            anyhow::Result::Ok(items) => {
-                log::info!(
-                    "Generating json file for block_no: {} is successful, txs: {}",
-                    block_no,
-                    items.0.len(),
-                );
+    let mut queue = if let Some(blocks_file) = &blocks_file {
+        let blocks = blocks::parse_blocks_file(blocks_file)
+            .map_err(|e| ProverError::Config(e.to_string()))?;
+        log::info!("Proving {} blocks from {}", blocks.len(), blocks_file);
+        BlockQueue::List { blocks, pos: 0 }
+    } else {
+        let last_block = if !(resume || block_no.is_none()) {
+            None
+        } else if let Some(db) = &opts.db {
+            let last_block = db.last_block()?;
+            if let Some(last_block) = last_block {
+                log::info!("Resuming from database: last_block:{}", last_block);
+            }
+            last_block
+        } else {
+            Checkpoint::resume(&output_dir)?
+        };
+        let next = match last_block {
+            Some(last_block) => last_block + 1,
+            None => {
+                let spec = BlockSpec::parse(block_no.as_deref().unwrap_or("1"))?;
+                spec.resolve(&client).await?
+            }
+        };
+        log::info!("Starting from block_no: {}", next);
+        BlockQueue::Sequential { next }
+    };
 
-                if !items.0.is_empty() {
-                    prove_tx(
-                        &prover_cfg,
-                        &output_dir,
-                        &elf_path,
-                        seg_size,
-                        execute_only,
-                        &items,
+    let list_mode = queue.is_list();
+    let keep_going = prove_loop || follow_head;
+    let producer = Producer {
+        client: client.clone(),
+        opts: &opts,
+        status: &status,
+        shutdown: &shutdown,
+        chain_id,
+        force_reprove,
+        keep_going,
+        clock_skew: (!ignore_clock_skew).then_some(clock_skew),
+        fetch_retry: FetchRetry::from_env(),
+        head: (follow_head || confirmations > 0).then(|| HeadTracker::new(confirmations)),
+        reorg: ReorgDetector::from_env(),
+        blocks_per_proof,
+        lag: LagGuard::from_env(&output_dir)?,
+    };
+    if let Some(serve_addr) = serve_addr {
+        let (jobs, job_rx) = JobQueue::new(serve_queue_depth);
+        let serve_addr = serve_addr.parse()?;
+        let api_jobs = jobs.clone();
+        let api_shutdown = shutdown.clone();
+        tokio::spawn(async move {
+            if let Err(e) = api::serve(serve_addr, api_jobs, api_shutdown).await {
+                log::error!("Job API failed: {}", e);
+            }
+        });
+        return run_jobs(&producer, &prover, &opts, &status, &jobs, job_rx).await;
+    }
+    let (tx, rx) = tokio::sync::mpsc::channel(prefetch_depth.max(1));
+    let mut stats = RunStats::default();
+    // Blocks that exhausted their retries, proved again once the queue is done.
+    let mut requeued: Vec<PreparedBlock> = Vec::new();
+    // Failed blocks of a PROVE_LOOP or FOLLOW_HEAD run, only listed.
+    let mut unretried: Vec<u64> = Vec::new();
+    let requeued_path = Path::new(&output_dir).join("requeued_blocks.txt");
+    let mut frontier = Frontier::default();
+    // The producer fetches and checks up to PREFETCH_DEPTH blocks ahead while
+    // this loop proves them in order.
+    let consumer = async {
+        let mut rx = rx;
+        loop {
+            status.set_waiting();
+            let Some(prefetched) = rx.recv().await else {
+                break;
+            };
+            let trailing = matches!(&prefetched, Prefetched::Ready(prepared) if prepared.trailing);
+            if shutdown.is_cancelled() && !trailing {
+                log::info!("Shutting down, dropping prefetched blocks");
+                break;
+            }
+            match prefetched {
+                Prefetched::Existing {
+                    block_no,
+                    proof_path,
+                } => {
+                    log::info!(
+                        "Proof already exists, skip block_no: {} proof: {}",
                         block_no,
-                    )
-                    .await?;
+                        proof_path.display()
+                    );
+                    stats.skipped += 1;
+                    stats.record(list_mode, block_no, "skipped");
+                    if frontier.complete(block_no, Some(&proof_path)).is_some() {
+                        store_checkpoint(list_mode, &opts, block_no, Some(&proof_path))?;
+                    }
+                }
+                Prefetched::Empty { block_no } => {
+                    stats.record(list_mode, block_no, "skipped");
+                    if frontier.complete(block_no, None).is_some() {
+                        store_checkpoint(list_mode, &opts, block_no, None)?;
+                    }
+                }
+                Prefetched::Reorg { fork, block_no } => {
+                    for n in fork..block_no {
+                        invalidate_block(&opts, n)?;
+                    }
+                    if let Some(last_block_no) = frontier.rewind(fork) {
+                        store_checkpoint(list_mode, &opts, last_block_no, None)?;
+                    }
+                }
+                Prefetched::Ready(prepared) => {
+                    let block_no = prepared.block_no;
+                    // A batch is done, and checkpointed, only once all its blocks are.
+                    let last_block_no = prepared.last_block_no;
+                    METRICS.current_block_number.set(block_no);
+                    let proving = prove_tx(&prover, &opts, &status, &prepared, &shutdown, 0);
+                    let grace_elapsed = async {
+                        shutdown.cancelled().await;
+                        tokio::time::sleep(shutdown_grace).await;
+                    };
+                    let outcome = tokio::select! {
+                        outcome = proving => outcome?,
+                        _ = grace_elapsed => {
+                            log::warn!(
+                                "Shutdown grace period of {} secs elapsed, abandoning block_no: {}",
+                                shutdown_grace.as_secs(),
+                                block_no
+                            );
+                            break;
+                        }
+                    };
+                    match outcome {
+                        ProveOutcome::Proved {
+                            proof_path,
+                            seg_size,
+                            verified,
+                            ..
+                        } => {
+                            log::info!(
+                                "Block proved: block_no:{} proof: {} seg_size: {} verified: {}",
+                                block_no,
+                                proof_path.display(),
+                                seg_size,
+                                verified
+                            );
+                            stats.proved += 1;
+                            stats.record(list_mode, block_no, "proved");
+                            if frontier
+                                .complete(last_block_no, Some(&proof_path))
+                                .is_some()
+                            {
+                                store_checkpoint(
+                                    list_mode,
+                                    &opts,
+                                    last_block_no,
+                                    Some(&proof_path),
+                                )?;
+                            }
+                        }
+                        ProveOutcome::Failed { attempts, error } => {
+                            log::error!(
+                                "Block failed: block_no:{} attempts:{} error: {}",
+                                block_no,
+                                attempts,
+                                error
+                            );
+                            stats.failed += 1;
+                            stats.record(list_mode, block_no, "failed");
+                            if frontier.first_failed().is_none() && !list_mode {
+                                log::warn!(
+                                    "Checkpoint held before block_no: {} until it is proved",
+                                    block_no
+                                );
+                            }
+                            frontier.fail(block_no);
+                            if retry_passes > 0 {
+                                // The queue of a PROVE_LOOP or FOLLOW_HEAD run never
+                                // drains, so no retry pass starts and holding the
+                                // prepared block would only grow the memory. It is
+                                // listed for a BLOCKS_FILE run instead.
+                                if keep_going {
+                                    unretried.push(block_no);
+                                } else {
+                                    requeued.push(prepared);
+                                }
+                                let blocks: Vec<u64> = unretried
+                                    .iter()
+                                    .copied()
+                                    .chain(requeued.iter().map(|prepared| prepared.block_no))
+                                    .collect();
+                                blocks::write_blocks_file(&requeued_path, &blocks)
+                                    .map_err(|e| ProverError::io(&requeued_path, e))?;
+                            }
+                        }
+                        ProveOutcome::Executed { .. } => {
+                            stats.executed += 1;
+                            stats.record(list_mode, block_no, "executed");
+                            if frontier.complete(last_block_no, None).is_some() {
+                                store_checkpoint(list_mode, &opts, last_block_no, None)?;
+                            }
+                        }
+                        ProveOutcome::Skipped => {
+                            stats.generated += 1;
+                            stats.record(list_mode, block_no, "generated");
+                            if frontier.complete(last_block_no, None).is_some() {
+                                store_checkpoint(list_mode, &opts, last_block_no, None)?;
+                            }
+                        }
+                        ProveOutcome::Irrelevant => {
+                            stats.skipped += 1;
+                            stats.record(list_mode, block_no, "skipped");
+                            if frontier.complete(last_block_no, None).is_some() {
+                                store_checkpoint(list_mode, &opts, last_block_no, None)?;
+                            }
+                        }
+                        ProveOutcome::SampledOut => {
+                            stats.sampled_out += 1;
+                            stats.record(list_mode, block_no, "sampled_out");
+                            if frontier.complete(last_block_no, None).is_some() {
+                                store_checkpoint(list_mode, &opts, last_block_no, None)?;
+                            }
+                        }
+                    }
                 }
-                block_no += 1;
-            }
-            Err(e) => {
-                log::error!("Generating json file for block_no: {} is failed", block_no);
-                log::error!("Error: {}", e);
-                tokio::time::sleep(tokio::time::Duration::from_secs(10)).await;
             }
         }
-
-        if !prove_loop {
+        anyhow::Ok(())
+    };
+    let (produced, consumed) = tokio::join!(producer.run(&mut queue, tx), consumer);
+    consumed?;
+    produced?;
+    for pass in 1..=retry_passes {
+        if requeued.is_empty() || shutdown.is_cancelled() {
             break;
         }
+        log::info!(
+            "Retry pass {} of {}: proving {} failed blocks again in {} secs",
+            pass,
+            retry_passes,
+            requeued.len(),
+            retry_pass_backoff.as_secs()
+        );
+        tokio::select! {
+            _ = tokio::time::sleep(retry_pass_backoff) => {}
+            _ = shutdown.cancelled() => break,
+        }
+        for prepared in std::mem::take(&mut requeued) {
+            if shutdown.is_cancelled() {
+                requeued.push(prepared);
+                continue;
+            }
+            let block_no = prepared.block_no;
+            match prove_tx(&prover, &opts, &status, &prepared, &shutdown, pass).await? {
+                ProveOutcome::Failed { error, .. } => {
+                    log::error!(
+                        "Block failed again: block_no:{} pass:{} error: {}",
+                        block_no,
+                        pass,
+                        error
+                    );
+                    requeued.push(prepared);
+                }
+                outcome => {
+                    log::info!("Block recovered: block_no:{} pass:{}", block_no, pass);
+                    stats.failed -= 1;
+                    let proof_path = match &outcome {
+                        ProveOutcome::Proved { proof_path, .. } => Some(proof_path.as_path()),
+                        _ => None,
+                    };
+                    if let Some((last_block_no, proof_path)) =
+                        frontier.recover(block_no, prepared.last_block_no, proof_path)
+                    {
+                        store_checkpoint(list_mode, &opts, last_block_no, proof_path.as_deref())?;
+                    }
+                    let result = match outcome {
+                        ProveOutcome::Proved { .. } => {
+                            stats.proved += 1;
+                            "proved"
+                        }
+                        ProveOutcome::Executed { .. } => {
+                            stats.executed += 1;
+                            "executed"
+                        }
+                        _ => "skipped",
+                    };
+                    stats.update(list_mode, block_no, result);
+                }
+            }
+        }
+        let blocks: Vec<u64> = requeued.iter().map(|prepared| prepared.block_no).collect();
+        blocks::write_blocks_file(&requeued_path, &blocks)
+            .map_err(|e| ProverError::io(&requeued_path, e))?;
+    }
+    if let Some(notifier) = &opts.notifier {
+        if shutdown.is_cancelled() {
+            notifier.notify(Event::new(EventKind::ShuttingDown, None));
+        }
+        notifier.flush().await;
+    }
+    if let Some(s3) = &opts.s3 {
+        s3.wait().await;
+    }
+    if let Some(run_stats) = &opts.run_stats {
+        run_stats.log_footer();
+    }
+    log::info!(
+        "Run summary: proved:{} executed:{} generated:{} skipped:{} sampled_out:{} failed:{}",
+        stats.proved,
+        stats.executed,
+        stats.generated,
+        stats.skipped,
+        stats.sampled_out,
+        stats.failed
+    );
+    if list_mode {
+        for (block_no, status) in &stats.blocks {
+            log::info!("Block result: block_no:{} status:{}", block_no, status);
+        }
+        let failed: Vec<u64> = stats
+            .blocks
+            .iter()
+            .filter(|(_, status)| *status == "failed")
+            .map(|(block_no, _)| *block_no)
+            .collect();
+        let failed_path = Path::new(&output_dir).join("failed_blocks.txt");
+        blocks::write_blocks_file(&failed_path, &failed)?;
+        log::info!(
+            "{} failed blocks written to {}",
+            failed.len(),
+            failed_path.display()
+        );
+    }
+    if stats.failed > 0 && !prove_loop && !follow_head {
+        return Err(ProverError::Prove(format!("{} blocks failed to prove", stats.failed)).into());
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn retry_backoff_doubles_per_attempt() {
+        let retry = RetryPolicy {
+            max_retries: 3,
+            backoff_secs: 30,
+        };
+        let backoffs: Vec<_> = (0..=4)
+            .map(|attempt| retry.backoff(attempt).as_secs())
+            .collect();
+        // Attempt 0 does not occur, it waits like the first one.
+        assert_eq!(backoffs, vec![30, 30, 60, 120, 240]);
+    }
+
+    #[test]
+    fn retry_backoff_saturates() {
+        let retry = RetryPolicy {
+            max_retries: 100,
+            backoff_secs: 30,
+        };
+        // The factor stops growing after 16 doublings.
+        assert_eq!(retry.backoff(17), retry.backoff(u32::MAX));
+        assert_eq!(retry.backoff(17).as_secs(), 30 << 16);
+
+        let retry = RetryPolicy {
+            max_retries: 3,
+            backoff_secs: u64::MAX,
+        };
+        assert_eq!(retry.backoff(5).as_secs(), u64::MAX);
+
+        let retry = RetryPolicy {
+            max_retries: 3,
+            backoff_secs: 0,
+        };
+        assert_eq!(retry.backoff(5), Duration::ZERO);
+    }
+}
@@ -0,0 +1,118 @@
+use alloy_rlp::{RlpEncodable, RlpMaxEncodedLen};
+use hash_db::Hasher;
+use plain_hasher::PlainHasher;
+use revm::primitives::{keccak256, AccountInfo, Address, Log, B256, U256};
+use triehash::sec_trie_root;
+
+/// Hash of the RLP list of `logs`, as in the `logs` field of state tests.
+pub fn log_rlp_hash(logs: &[Log]) -> B256 {
+    let mut out = Vec::with_capacity(alloy_rlp::list_length(logs));
+    alloy_rlp::encode_list(logs, &mut out);
+    keccak256(&out)
+}
+
+/// State root of `accounts`, given as their info and storage slots, computed
+/// the way the reference state tests do.
+pub fn state_merkle_trie_root<'a, S>(
+    accounts: impl IntoIterator<Item = (Address, &'a AccountInfo, S)>,
+) -> B256
+where
+    S: IntoIterator<Item = (&'a U256, &'a U256)>,
+{
+    trie_root(accounts.into_iter().map(|(address, info, storage)| {
+        (
+            address,
+            alloy_rlp::encode_fixed_size(&TrieAccount::new(info, storage)),
+        )
+    }))
+}
+
+#[derive(RlpEncodable, RlpMaxEncodedLen)]
+struct TrieAccount {
+    nonce: u64,
+    balance: U256,
+    root_hash: B256,
+    code_hash: B256,
+}
+
+impl TrieAccount {
+    fn new<'a>(
+        info: &AccountInfo,
+        storage: impl IntoIterator<Item = (&'a U256, &'a U256)>,
+    ) -> Self {
+        Self {
+            nonce: info.nonce,
+            balance: info.balance,
+            root_hash: sec_trie_root::<KeccakHasher, _, _, _>(
+                storage
+                    .into_iter()
+                    .filter(|(_, &v)| v != U256::ZERO)
+                    .map(|(k, v)| (k.to_be_bytes::<32>(), alloy_rlp::encode_fixed_size(v))),
+            ),
+            code_hash: info.code_hash,
+        }
+    }
+}
+
+fn trie_root<I, A, B>(input: I) -> B256
+where
+    I: IntoIterator<Item = (A, B)>,
+    A: AsRef<[u8]>,
+    B: AsRef<[u8]>,
+{
+    sec_trie_root::<KeccakHasher, _, _, _>(input)
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
+struct KeccakHasher;
+
+impl Hasher for KeccakHasher {
+    type Out = B256;
+    type StdHasher = PlainHasher;
+    const LENGTH: usize = 32;
+
+    fn hash(x: &[u8]) -> Self::Out {
+        keccak256(x)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use revm::primitives::{address, b256, Bytes};
+
+    #[test]
+    fn empty_logs_hash() {
+        // keccak256(rlp([])), the empty ommers hash.
+        assert_eq!(
+            log_rlp_hash(&[]),
+            b256!("1dcc4de8dec75d7aab85b567b6ccd41ad312451b948a7413f0a142fd40d49347")
+        );
+    }
+
+    #[test]
+    fn transfer_logs_hash() {
+        let transfer = Log::new_unchecked(
+            address!("dac17f958d2ee523a2206206994597c13d831ec7"),
+            vec![
+                b256!("ddf252ad1be2c89b69c2b068fc378daa952ba7f163c4a11628f55a4df523b3ef"),
+                b256!("00000000000000000000000000000000000000000000000000000000000000a1"),
+                b256!("00000000000000000000000000000000000000000000000000000000000000b2"),
+            ],
+            Bytes::from(U256::from(1000).to_be_bytes_vec()),
+        );
+        assert_eq!(
+            log_rlp_hash(&[transfer.clone()]),
+            b256!("ca813a4376d07775eca3c8aa11470a85d98ef4849ab0634fdfb969c854d1feb7")
+        );
+        let anonymous = Log::new_unchecked(
+            address!("00000000000000000000000000000000000000a1"),
+            vec![],
+            Bytes::new(),
+        );
+        assert_eq!(
+            log_rlp_hash(&[transfer, anonymous]),
+            b256!("da7746cd8946a6fa47c0379a4c5e566d551201d31c4d38f092cd4891e084b91d")
+        );
+    }
+}
@@ -0,0 +1,105 @@
+use crate::atomic_file;
+use crate::error::ProverError;
+use crate::rpc::FailoverClient;
+use crate::schema::{self, SCHEMA_VERSION};
+use ethers_providers::{Middleware, Provider};
+use models::{Env, TestSuite};
+use revm::primitives::keccak256;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// Chain context of a proved block, written to `{block_no}_meta.json` next to
+/// the proof. Downstream verifiers use it to bind a proof file to the block.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BlockMeta {
+    #[serde(default = "schema::legacy_schema_version")]
+    pub schema_version: u32,
+    pub block_no: u64,
+    pub block_hash: String,
+    pub parent_hash: String,
+    pub timestamp: u64,
+    pub coinbase: String,
+    pub gas_used: u64,
+    pub gas_limit: u64,
+    pub tx_count: usize,
+    pub blob_gas_used: Option<u64>,
+    pub excess_blob_gas: Option<u64>,
+    /// keccak256 of the bincode-wrapped suite JSON the proof was generated
+    /// from, the merged suite for a BLOCKS_PER_PROOF batch.
+    pub suite_keccak256: String,
+    pub proof_keccak256: String,
+}
+
+impl BlockMeta {
+    /// Collect the context of `block_no` from its suite and its header. The
+    /// hashes are filled in once the block is proved.
+    pub async fn fetch(
+        client: &Provider<FailoverClient>,
+        block_no: u64,
+        suite: &TestSuite,
+    ) -> anyhow::Result<Self> {
+        let env = block_env(block_no, suite)?;
+        let block = client
+            .get_block(block_no)
+            .await
+            .map_err(|e| ProverError::Rpc(format!("failed to fetch block_no {}: {}", block_no, e)))?
+            .ok_or_else(|| ProverError::Rpc(format!("block_no {} not found", block_no)))?;
+        Ok(Self {
+            schema_version: SCHEMA_VERSION,
+            block_no,
+            block_hash: format!("{:?}", block.hash.unwrap_or_default()),
+            parent_hash: format!("{:?}", block.parent_hash),
+            timestamp: env.current_timestamp.saturating_to(),
+            coinbase: format!("0x{}", hex::encode(env.current_coinbase)),
+            gas_used: block.gas_used.as_u64(),
+            gas_limit: env.current_gas_limit.saturating_to(),
+            tx_count: suite.0.len(),
+            blob_gas_used: block.blob_gas_used.map(|v| v.as_u64()),
+            excess_blob_gas: block.excess_blob_gas.map(|v| v.as_u64()),
+            suite_keccak256: String::new(),
+            proof_keccak256: String::new(),
+        })
+    }
+
+    pub fn path(outdir: &str, block_no: u64) -> PathBuf {
+        Path::new(outdir).join(format!("{}_meta.json", block_no))
+    }
+
+    pub fn write(&self, outdir: &str) -> anyhow::Result<PathBuf> {
+        let path = Self::path(outdir, self.block_no);
+        let json = serde_json::to_string_pretty(self)?;
+        atomic_file::write(&path, json).map_err(|e| ProverError::io(&path, e))?;
+        Ok(path)
+    }
+}
+
+pub fn keccak256_hex(bytes: &[u8]) -> String {
+    format!("0x{}", hex::encode(keccak256(bytes)))
+}
+
+/// Block-level environment of `suite`. Every unit carries a copy of it, a
+/// unit that disagrees with the first one means the suite is corrupt.
+fn block_env(block_no: u64, suite: &TestSuite) -> anyhow::Result<&Env> {
+    let mut envs = suite.0.iter().map(|(name, unit)| (name, &unit.env));
+    let (_, first) = envs
+        .next()
+        .ok_or_else(|| ProverError::Check(format!("suite of block_no {} is empty", block_no)))?;
+    for (name, env) in envs {
+        let same = env.current_number == first.current_number
+            && env.current_coinbase == first.current_coinbase
+            && env.current_timestamp == first.current_timestamp
+            && env.current_gas_limit == first.current_gas_limit
+            && env.current_base_fee == first.current_base_fee
+            && env.current_difficulty == first.current_difficulty
+            && env.parent_blob_gas_used == first.parent_blob_gas_used
+            && env.parent_excess_blob_gas == first.parent_excess_blob_gas;
+        if !same {
+            return Err(ProverError::Check(format!(
+                "unit {} of block_no {} disagrees with the other units on the block environment",
+                name, block_no
+            ))
+            .into());
+        }
+    }
+    Ok(first)
+}
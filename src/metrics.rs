@@ -0,0 +1,253 @@
+use axum::{routing::get, Router};
+use std::fmt::Write;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use tokio_util::sync::CancellationToken;
+
+/// Monotonically increasing counter.
+pub struct Counter(AtomicU64);
+
+impl Counter {
+    const fn new() -> Self {
+        Self(AtomicU64::new(0))
+    }
+
+    pub fn inc(&self) {
+        self.inc_by(1);
+    }
+
+    pub fn inc_by(&self, v: u64) {
+        self.0.fetch_add(v, Ordering::Relaxed);
+    }
+
+    pub fn get(&self) -> u64 {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// Gauge holding the last value set.
+pub struct Gauge(AtomicU64);
+
+impl Gauge {
+    const fn new() -> Self {
+        Self(AtomicU64::new(0))
+    }
+
+    pub fn set(&self, v: u64) {
+        self.0.store(v, Ordering::Relaxed);
+    }
+
+    pub fn get(&self) -> u64 {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// Cumulative histogram with fixed upper bounds.
+pub struct Histogram<const N: usize> {
+    bounds: [f64; N],
+    buckets: [AtomicU64; N],
+    count: AtomicU64,
+    /// Sum of observations, stored as f64 bits.
+    sum: AtomicU64,
+}
+
+impl<const N: usize> Histogram<N> {
+    const fn new(bounds: [f64; N]) -> Self {
+        Self {
+            bounds,
+            buckets: [const { AtomicU64::new(0) }; N],
+            count: AtomicU64::new(0),
+            sum: AtomicU64::new(0),
+        }
+    }
+
+    pub fn observe(&self, v: f64) {
+        for (bound, bucket) in self.bounds.iter().zip(self.buckets.iter()) {
+            if v <= *bound {
+                bucket.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        self.count.fetch_add(1, Ordering::Relaxed);
+        let _ = self
+            .sum
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |bits| {
+                Some((f64::from_bits(bits) + v).to_bits())
+            });
+    }
+
+    fn render(&self, out: &mut String, name: &str, help: &str) {
+        let _ = writeln!(out, "# HELP {} {}", name, help);
+        let _ = writeln!(out, "# TYPE {} histogram", name);
+        for (bound, bucket) in self.bounds.iter().zip(self.buckets.iter()) {
+            let _ = writeln!(
+                out,
+                "{}_bucket{{le=\"{}\"}} {}",
+                name,
+                bound,
+                bucket.load(Ordering::Relaxed)
+            );
+        }
+        let count = self.count.load(Ordering::Relaxed);
+        let _ = writeln!(out, "{}_bucket{{le=\"+Inf\"}} {}", name, count);
+        let sum = f64::from_bits(self.sum.load(Ordering::Relaxed));
+        let _ = writeln!(out, "{}_sum {}", name, sum);
+        let _ = writeln!(out, "{}_count {}", name, count);
+    }
+}
+
+pub struct Metrics {
+    pub blocks_proved_total: Counter,
+    pub blocks_failed_total: Counter,
+    pub proof_bytes_written: Counter,
+    pub rpc_throttled_total: Counter,
+    pub lag_exceeded_total: Counter,
+    pub lag_skipped_blocks_total: Counter,
+    pub current_block_number: Gauge,
+    pub last_proved_block_number: Gauge,
+    pub head_block_number: Gauge,
+    pub cycles_total: Gauge,
+    pub proof_duration_seconds: Histogram<10>,
+    pub test_suite_fetch_duration_seconds: Histogram<8>,
+    pub txs_per_block: Histogram<8>,
+    pub segments_per_block: Histogram<8>,
+}
+
+pub static METRICS: Metrics = Metrics {
+    blocks_proved_total: Counter::new(),
+    blocks_failed_total: Counter::new(),
+    proof_bytes_written: Counter::new(),
+    rpc_throttled_total: Counter::new(),
+    lag_exceeded_total: Counter::new(),
+    lag_skipped_blocks_total: Counter::new(),
+    current_block_number: Gauge::new(),
+    last_proved_block_number: Gauge::new(),
+    head_block_number: Gauge::new(),
+    cycles_total: Gauge::new(),
+    proof_duration_seconds: Histogram::new([
+        30.0, 60.0, 120.0, 300.0, 600.0, 900.0, 1800.0, 3600.0, 7200.0, 14400.0,
+    ]),
+    test_suite_fetch_duration_seconds: Histogram::new([0.1, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0]),
+    txs_per_block: Histogram::new([1.0, 5.0, 10.0, 25.0, 50.0, 100.0, 250.0, 500.0]),
+    segments_per_block: Histogram::new([1.0, 2.0, 4.0, 8.0, 16.0, 32.0, 64.0, 128.0]),
+};
+
+fn render_counter(out: &mut String, name: &str, help: &str, kind: &str, v: u64) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+    let _ = writeln!(out, "{} {}", name, v);
+}
+
+impl Metrics {
+    /// Render all metrics in the Prometheus text exposition format.
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        render_counter(
+            &mut out,
+            "blocks_proved_total",
+            "Blocks proved successfully.",
+            "counter",
+            self.blocks_proved_total.get(),
+        );
+        render_counter(
+            &mut out,
+            "blocks_failed_total",
+            "Blocks that failed to prove after all retries.",
+            "counter",
+            self.blocks_failed_total.get(),
+        );
+        render_counter(
+            &mut out,
+            "proof_bytes_written",
+            "Total bytes of proof files written.",
+            "counter",
+            self.proof_bytes_written.get(),
+        );
+        render_counter(
+            &mut out,
+            "rpc_throttled_total",
+            "RPC calls rate limited by the provider.",
+            "counter",
+            self.rpc_throttled_total.get(),
+        );
+        render_counter(
+            &mut out,
+            "lag_exceeded_total",
+            "Times proving fell more than MAX_LAG_BLOCKS behind the head.",
+            "counter",
+            self.lag_exceeded_total.get(),
+        );
+        render_counter(
+            &mut out,
+            "lag_skipped_blocks_total",
+            "Blocks passed over by LAG_POLICY=skip.",
+            "counter",
+            self.lag_skipped_blocks_total.get(),
+        );
+        render_counter(
+            &mut out,
+            "current_block_number",
+            "Block number currently being processed.",
+            "gauge",
+            self.current_block_number.get(),
+        );
+        let cycles = self.cycles_total.get();
+        if cycles > 0 {
+            render_counter(
+                &mut out,
+                "cycles_total",
+                "Cycles executed by the guest for the last proved or executed block.",
+                "gauge",
+                cycles,
+            );
+        }
+        let head = self.head_block_number.get();
+        if head > 0 {
+            render_counter(
+                &mut out,
+                "head_block_number",
+                "Latest chain head seen in head-following mode.",
+                "gauge",
+                head,
+            );
+            render_counter(
+                &mut out,
+                "head_lag_blocks",
+                "Chain head minus the last proved block.",
+                "gauge",
+                head.saturating_sub(self.last_proved_block_number.get()),
+            );
+        }
+        self.proof_duration_seconds.render(
+            &mut out,
+            "proof_duration_seconds",
+            "Wall-clock duration of proving a block.",
+        );
+        self.test_suite_fetch_duration_seconds.render(
+            &mut out,
+            "test_suite_fetch_duration_seconds",
+            "Duration of generating the test suite of a block from the RPC.",
+        );
+        self.txs_per_block.render(
+            &mut out,
+            "txs_per_block",
+            "Transactions per processed block.",
+        );
+        self.segments_per_block.render(
+            &mut out,
+            "segments_per_block",
+            "Segments the guest execution of a block was split into.",
+        );
+        out
+    }
+}
+
+/// Serve `/metrics` on `addr` until `shutdown` is cancelled.
+pub async fn serve(addr: SocketAddr, shutdown: CancellationToken) -> anyhow::Result<()> {
+    let app = Router::new().route("/metrics", get(|| async { METRICS.render() }));
+    log::info!("Metrics server listening on {}", addr);
+    axum::Server::bind(&addr)
+        .serve(app.into_make_service())
+        .with_graceful_shutdown(shutdown.cancelled_owned())
+        .await?;
+    Ok(())
+}
@@ -0,0 +1,272 @@
+use crate::error::ProverError;
+use std::env;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+pub const DEFAULT_TEMPLATE: &str = "{block_no}_snark_proof_with_public_inputs.json";
+/// Stands in for `{block_hash}` when the hash is not known, e.g. prove-file.
+const UNKNOWN_HASH: &str = "unknown";
+
+static OUTPUT_NAME: OnceLock<NameTemplate> = OnceLock::new();
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Part {
+    Literal(String),
+    BlockNo,
+    BlockHash,
+    ChainId,
+    Date,
+}
+
+/// File name of the proofs in OUTPUT_DIR, OUTPUT_NAME_TEMPLATE with the
+/// placeholders `{block_no}`, `{block_hash}`, `{chain_id}` and `{date}`
+/// (UTC, YYYY-MM-DD, of the proof). A BLOCKS_PER_PROOF batch renders
+/// `{block_no}` as `{first}_{last}`.
+#[derive(Debug, Clone)]
+pub struct NameTemplate {
+    parts: Vec<Part>,
+    chain_id: u64,
+}
+
+impl NameTemplate {
+    /// Unknown placeholders, a template without `{block_no}` and two
+    /// placeholders without text in between, which could not be told apart
+    /// when reading the names back, are rejected.
+    pub fn parse(template: &str, chain_id: u64) -> anyhow::Result<Self> {
+        let invalid = |reason: String| {
+            ProverError::Config(format!(
+                "invalid OUTPUT_NAME_TEMPLATE {:?}: {}",
+                template, reason
+            ))
+        };
+        let mut parts = Vec::new();
+        let mut rest = template;
+        while !rest.is_empty() {
+            let Some(start) = rest.find('{') else {
+                parts.push(Part::Literal(rest.to_string()));
+                break;
+            };
+            if start > 0 {
+                parts.push(Part::Literal(rest[..start].to_string()));
+            }
+            let end = rest[start..]
+                .find('}')
+                .ok_or_else(|| invalid("unclosed '{'".to_string()))?;
+            let part = match &rest[start + 1..start + end] {
+                "block_no" => Part::BlockNo,
+                "block_hash" => Part::BlockHash,
+                "chain_id" => Part::ChainId,
+                "date" => Part::Date,
+                other => return Err(invalid(format!("unknown placeholder {{{}}}", other)).into()),
+            };
+            if parts
+                .last()
+                .is_some_and(|last| !matches!(last, Part::Literal(_)))
+            {
+                return Err(invalid("placeholders must be separated by text".to_string()).into());
+            }
+            parts.push(part);
+            rest = &rest[start + end + 1..];
+        }
+        if !parts.contains(&Part::BlockNo) {
+            return Err(invalid("{block_no} is missing".to_string()).into());
+        }
+        if template.contains('/') {
+            return Err(invalid("must be a file name, not a path".to_string()).into());
+        }
+        Ok(Self { parts, chain_id })
+    }
+
+    fn render(&self, blocks: &str, block_hash: &str, date: &str) -> String {
+        let mut name = String::new();
+        for part in &self.parts {
+            match part {
+                Part::Literal(literal) => name.push_str(literal),
+                Part::BlockNo => name.push_str(blocks),
+                Part::BlockHash => name.push_str(block_hash),
+                Part::ChainId => name.push_str(&self.chain_id.to_string()),
+                Part::Date => name.push_str(date),
+            }
+        }
+        name
+    }
+
+    fn blocks(first: u64, last: u64) -> String {
+        if first == last {
+            first.to_string()
+        } else {
+            format!("{}_{}", first, last)
+        }
+    }
+
+    /// Whether the name of a proof depends on more than its blocks.
+    fn has_wildcards(&self) -> bool {
+        self.parts
+            .iter()
+            .any(|part| matches!(part, Part::BlockHash | Part::Date))
+    }
+
+    /// Blocks `{first}[_{last}]` of a proof file name, `None` if `name` does
+    /// not match the template.
+    pub fn block_range(&self, name: &str) -> Option<(u64, u64)> {
+        let mut rest = name;
+        let mut blocks = None;
+        for (i, part) in self.parts.iter().enumerate() {
+            if let Part::Literal(literal) = part {
+                rest = rest.strip_prefix(literal.as_str())?;
+                continue;
+            }
+            // A placeholder runs up to the text following it.
+            let end = match self.parts.get(i + 1) {
+                Some(Part::Literal(next)) => rest.find(next.as_str())?,
+                _ => rest.len(),
+            };
+            let value = &rest[..end];
+            match part {
+                Part::BlockNo => blocks = Some(value),
+                Part::ChainId if value != self.chain_id.to_string() => return None,
+                Part::BlockHash if !(value.starts_with("0x") || value == UNKNOWN_HASH) => {
+                    return None
+                }
+                Part::Date
+                    if value.len() != 10
+                        || !value.chars().all(|c| c.is_ascii_digit() || c == '-') =>
+                {
+                    return None
+                }
+                _ => {}
+            }
+            rest = &rest[end..];
+        }
+        if !rest.is_empty() {
+            return None;
+        }
+        match blocks?.split_once('_') {
+            Some((first, last)) => Some((first.parse().ok()?, last.parse().ok()?)),
+            None => {
+                let block_no = blocks?.parse().ok()?;
+                Some((block_no, block_no))
+            }
+        }
+    }
+}
+
+/// Set the template of the run, from OUTPUT_NAME_TEMPLATE.
+pub fn init_from_env(chain_id: u64) -> anyhow::Result<()> {
+    let template = env::var("OUTPUT_NAME_TEMPLATE").unwrap_or(DEFAULT_TEMPLATE.to_string());
+    let template = NameTemplate::parse(&template, chain_id)?;
+    let _ = OUTPUT_NAME.set(template);
+    Ok(())
+}
+
+pub fn template() -> &'static NameTemplate {
+    OUTPUT_NAME.get_or_init(|| {
+        NameTemplate::parse(DEFAULT_TEMPLATE, 1).expect("the default template is valid")
+    })
+}
+
+/// Path to write the proof of `first..=last` to.
+pub fn proof_path(outdir: &str, first: u64, last: u64, block_hash: Option<&str>) -> PathBuf {
+    let date = chrono::Utc::now().format("%Y-%m-%d").to_string();
+    let name = template().render(
+        &NameTemplate::blocks(first, last),
+        block_hash.unwrap_or(UNKNOWN_HASH),
+        &date,
+    );
+    Path::new(outdir).join(name)
+}
+
+/// The proof of `block_no` in `outdir` if there is one. With `{block_hash}`
+/// or `{date}` in the template the directory is searched for it.
+pub fn find_proof(outdir: &str, block_no: u64) -> Option<PathBuf> {
+    let template = template();
+    if !template.has_wildcards() {
+        let path = proof_path(outdir, block_no, block_no, None);
+        return path.exists().then_some(path);
+    }
+    let pattern = template.render(&block_no.to_string(), "*", "*");
+    let pattern = glob::Pattern::escape(outdir) + "/" + &escape_except_stars(&pattern);
+    glob::glob(&pattern)
+        .ok()?
+        .filter_map(Result::ok)
+        .filter(|path| {
+            path.file_name()
+                .and_then(|name| name.to_str())
+                .and_then(|name| template.block_range(name))
+                == Some((block_no, block_no))
+        })
+        .max()
+}
+
+/// Escape the glob metacharacters of a rendered name, keeping the `*`
+/// standing in for unknown placeholders.
+fn escape_except_stars(name: &str) -> String {
+    name.split('*')
+        .map(glob::Pattern::escape)
+        .collect::<Vec<_>>()
+        .join("*")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_template_round_trips() {
+        let template = NameTemplate::parse(DEFAULT_TEMPLATE, 1).unwrap();
+        let name = template.render(&NameTemplate::blocks(7, 7), UNKNOWN_HASH, "2024-01-02");
+        assert_eq!(name, "7_snark_proof_with_public_inputs.json");
+        assert_eq!(template.block_range(&name), Some((7, 7)));
+        let batch = template.render(&NameTemplate::blocks(7, 9), UNKNOWN_HASH, "2024-01-02");
+        assert_eq!(template.block_range(&batch), Some((7, 9)));
+        assert!(!template.has_wildcards());
+    }
+
+    #[test]
+    fn placeholders_are_checked_when_reading_names_back() {
+        let template = NameTemplate::parse(
+            "proof-{chain_id}-{block_no}-{block_hash}-{date}.json",
+            48816,
+        )
+        .unwrap();
+        assert!(template.has_wildcards());
+        let name = template.render("12", "0xabc", "2024-01-02");
+        assert_eq!(name, "proof-48816-12-0xabc-2024-01-02.json");
+        assert_eq!(template.block_range(&name), Some((12, 12)));
+        assert_eq!(
+            template.block_range("proof-1-12-0xabc-2024-01-02.json"),
+            None
+        );
+        assert_eq!(
+            template.block_range("proof-48816-12-abc-2024-01-02.json"),
+            None
+        );
+        assert_eq!(
+            template.block_range("proof-48816-12-0xabc-yesterday.json"),
+            None
+        );
+        assert_eq!(
+            template.block_range("proof-48816-x-0xabc-2024-01-02.json"),
+            None
+        );
+        assert_eq!(template.block_range("other.json"), None);
+    }
+
+    #[test]
+    fn invalid_templates_are_rejected() {
+        for template in [
+            "proof.json",
+            "{block_no}{chain_id}.json",
+            "{block_no}_{height}.json",
+            "{block_no.json",
+            "out/{block_no}.json",
+        ] {
+            assert!(NameTemplate::parse(template, 1).is_err(), "{}", template);
+        }
+    }
+
+    #[test]
+    fn glob_metacharacters_are_escaped() {
+        assert_eq!(escape_except_stars("[1]_*_?.json"), "[[]1[]]_*_[?].json");
+    }
+}
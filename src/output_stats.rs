@@ -0,0 +1,101 @@
+use crate::blocks::write_blocks_file;
+use crate::db::percentile;
+use crate::error::ProverError;
+use crate::summary::{BlockStatus, BlockSummary};
+use crate::{has_valid_proof, naming, suite};
+use std::collections::BTreeSet;
+use std::path::Path;
+
+const SUMMARY_SUFFIX: &str = "_summary.json";
+
+/// Blocks `{first}[_{last}]` named by the prefix of a proof or summary file.
+fn block_range(prefix: &str) -> Option<(u64, u64)> {
+    match prefix.split_once('_') {
+        Some((first, last)) => Some((first.parse().ok()?, last.parse().ok()?)),
+        None => {
+            let block_no = prefix.parse().ok()?;
+            Some((block_no, block_no))
+        }
+    }
+}
+
+/// Print coverage and performance of the artifacts in `dir` left by earlier
+/// runs. Files that do not parse, e.g. from a crashed run, are counted as
+/// corrupt. With `gaps_file` the blocks that have a suite but no proof are
+/// written there in the BLOCKS_FILE format.
+pub fn print_dir_stats(dir: &str, gaps_file: Option<&str>) -> anyhow::Result<()> {
+    let mut proved = BTreeSet::new();
+    let mut suites = BTreeSet::new();
+    // Not proved on purpose, not gaps.
+    let mut sampled_out = BTreeSet::new();
+    let mut proof_bytes = 0;
+    let mut prove_secs = Vec::new();
+    let mut corrupt = Vec::new();
+    let entries = std::fs::read_dir(dir).map_err(|e| ProverError::io(dir, e))?;
+    for entry in entries {
+        let path = entry?.path();
+        let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+            continue;
+        };
+        if name.ends_with(".tmp") {
+            corrupt.push(name.to_string());
+        } else if let Some((first, last)) = naming::template().block_range(name) {
+            if !has_valid_proof(&path) {
+                corrupt.push(name.to_string());
+                continue;
+            }
+            proof_bytes += std::fs::metadata(&path).map_or(0, |m| m.len());
+            proved.extend(first..=last);
+        } else if let Some(prefix) = name.strip_suffix(SUMMARY_SUFFIX) {
+            let Some((block_no, _)) = block_range(prefix) else {
+                continue;
+            };
+            match BlockSummary::load(dir, block_no) {
+                Ok(Some(summary)) if summary.status == BlockStatus::Proved => {
+                    prove_secs.push(summary.prove_secs)
+                }
+                Ok(Some(summary)) if summary.status == BlockStatus::SampledOut => {
+                    sampled_out.insert(block_no);
+                }
+                Ok(_) => {}
+                Err(_) => corrupt.push(name.to_string()),
+            }
+        } else if suite::is_suite_file(&path) {
+            if let Some(block_no) = suite::block_no(&path) {
+                suites.insert(block_no);
+            }
+        }
+    }
+    prove_secs.sort_unstable();
+    corrupt.sort();
+    let gaps: Vec<u64> = suites
+        .difference(&proved)
+        .filter(|block_no| !sampled_out.contains(block_no))
+        .copied()
+        .collect();
+
+    match (proved.first(), proved.last()) {
+        (Some(first), Some(last)) => {
+            println!("proved: {} blocks in {}..={}", proved.len(), first, last)
+        }
+        _ => println!("proved: no blocks"),
+    }
+    println!("suites: {}", suites.len());
+    println!("sampled_out: {}", sampled_out.len());
+    println!("gaps: {}", gaps.len());
+    println!("proof_bytes: {}", proof_bytes);
+    match (percentile(&prove_secs, 50), percentile(&prove_secs, 95)) {
+        (Some(p50), Some(p95)) => println!("prove_secs p50: {} p95: {}", p50, p95),
+        _ => println!("prove_secs: no proved blocks"),
+    }
+    println!("corrupt: {}", corrupt.len());
+    for name in &corrupt {
+        println!("  {}", name);
+    }
+    if let Some(gaps_file) = gaps_file {
+        write_blocks_file(Path::new(gaps_file), &gaps)
+            .map_err(|e| ProverError::io(gaps_file, e))?;
+        println!("{} gaps written to {}", gaps.len(), gaps_file);
+    }
+    Ok(())
+}
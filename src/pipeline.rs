@@ -0,0 +1,692 @@
+use crate::blocks;
+use crate::blocks::BlockQueue;
+use crate::check::{self, SuiteMode, SuiteReport};
+use crate::check_trace::CheckTrace;
+use crate::clock::ClockSkewPolicy;
+use crate::error::ProverError;
+use crate::head::HeadTracker;
+use crate::lag::{LagGuard, LagPolicy};
+use crate::meta::BlockMeta;
+use crate::metrics::METRICS;
+use crate::receipts::{self, ReceiptCheck, ReceiptMatch};
+use crate::reorg::ReorgDetector;
+use crate::rpc::{self, FailoverClient, FetchErrorKind, FetchRetry};
+use crate::status::{Phase, StatusHandle};
+use crate::suite;
+use crate::summary::BlockSummary;
+use crate::{has_valid_proof, naming, ProveOptions, CLOCK_CHECK_INTERVAL};
+use alerts::{Event, EventKind};
+use ethers_providers::{Middleware, Provider};
+use revm::primitives::Address;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Instant;
+use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
+use tracing::field::Empty;
+use tracing::Instrument;
+
+/// A block handed from the producer to the prover loop, in block order.
+pub enum Prefetched {
+    /// The block is done already, `proof_path` is its existing proof.
+    Existing { block_no: u64, proof_path: PathBuf },
+    /// The block has no transactions.
+    Empty { block_no: u64 },
+    /// The test suite was generated and checked.
+    Ready(PreparedBlock),
+    /// A reorg at `block_no` replaced the blocks from `fork` on.
+    Reorg { fork: u64, block_no: u64 },
+}
+
+/// Test suite of a block, written to OUTPUT_DIR and checked on the host.
+/// With BLOCKS_PER_PROOF the suites of `block_no..=last_block_no` merged.
+pub struct PreparedBlock {
+    pub block_no: u64,
+    pub last_block_no: u64,
+    pub test_suite: models::TestSuite,
+    /// The bincode-wrapped suite JSON, the prover's public input.
+    pub suite: Vec<u8>,
+    pub suite_path: String,
+    /// `false` when no transaction touches TARGET_ADDRESSES, the suite is
+    /// not checked then.
+    pub relevant: bool,
+    pub check_micros: u64,
+    /// Gas used by the transactions of the suite in the check.
+    pub check_gas_used: u64,
+    /// Receipt comparison of every transaction, empty with RECEIPT_CHECK=off.
+    pub receipts: Vec<ReceiptMatch>,
+    /// Chain context of every block of the suite, empty if not relevant.
+    pub meta: Vec<BlockMeta>,
+    /// A partial batch flushed on shutdown, still proved within the grace
+    /// period.
+    pub trailing: bool,
+}
+
+/// Checked blocks collected into the next BLOCKS_PER_PROOF proof.
+#[derive(Default)]
+struct Batch {
+    blocks: Vec<PreparedBlock>,
+    /// Blocks without a suite to prove extend the range of a pending batch.
+    last_block_no: u64,
+}
+
+/// Generates and checks the test suites of the blocks of the queue ahead of
+/// the prover loop.
+pub struct Producer<'a> {
+    pub client: Arc<Provider<FailoverClient>>,
+    pub opts: &'a ProveOptions,
+    pub status: &'a StatusHandle,
+    pub shutdown: &'a CancellationToken,
+    pub chain_id: u64,
+    pub force_reprove: bool,
+    /// PROVE_LOOP or FOLLOW_HEAD: keep going after the first block.
+    pub keep_going: bool,
+    /// `None` with --ignore-clock-skew.
+    pub clock_skew: Option<ClockSkewPolicy>,
+    pub fetch_retry: FetchRetry,
+    pub head: Option<HeadTracker>,
+    pub reorg: ReorgDetector,
+    /// BLOCKS_PER_PROOF, consecutive blocks merged into one proving input.
+    pub blocks_per_proof: usize,
+    /// MAX_LAG_BLOCKS backpressure, only applied in a sequential loop run.
+    pub lag: Option<LagGuard>,
+}
+
+impl Producer<'_> {
+    /// Work through `queue`, sending every block to `tx`. Returns when the
+    /// queue is done, on shutdown or once the prover loop is gone.
+    pub async fn run(
+        mut self,
+        queue: &mut BlockQueue,
+        tx: mpsc::Sender<Prefetched>,
+    ) -> anyhow::Result<()> {
+        let mut last_clock_check = Instant::now();
+        let mut fetch_attempts = 0;
+        let mut fetch_started = Instant::now();
+        // A single block run or BLOCKS_FILE list proves every block on its own.
+        let batching = self.blocks_per_proof > 1 && self.keep_going && !queue.is_list();
+        let mut batch = Batch::default();
+        loop {
+            let Some(block_no) = queue.current() else {
+                log::info!("All blocks of BLOCKS_FILE processed");
+                break;
+            };
+            if self.shutdown.is_cancelled() {
+                log::info!("Shutting down before block_no: {}", block_no);
+                break;
+            }
+            if let Some(clock_skew) = &self.clock_skew {
+                if last_clock_check.elapsed() > CLOCK_CHECK_INTERVAL {
+                    if let Err(e) = clock_skew.check(&self.client).await {
+                        log::error!("Clock check failed: {}", e);
+                    }
+                    last_clock_check = Instant::now();
+                }
+            }
+            if !queue.is_list() {
+                if let Some(head) = &mut self.head {
+                    let ready = head
+                        .is_ready(&self.client, block_no)
+                        .await
+                        .unwrap_or_else(|e| {
+                            log::error!("Failed to query the chain head: {}", e);
+                            false
+                        });
+                    if let Some(head) = head.head() {
+                        self.status.set_head(head);
+                    }
+                    if !ready {
+                        log::debug!(
+                            "Waiting for block_no: {} to be confirmed, head: {:?}",
+                            block_no,
+                            head.head()
+                        );
+                        self.status.set_prefetch(block_no, Phase::Sleeping);
+                        self.wait_for_new_head().await;
+                        continue;
+                    }
+                }
+                if let Some(next) = self.check_lag(block_no).await? {
+                    if !batch.blocks.is_empty() {
+                        let merged = self.merge_batch(&mut batch, false)?;
+                        if tx.send(merged).await.is_err() {
+                            break;
+                        }
+                    }
+                    queue.rewind(next);
+                    continue;
+                }
+                if let Some(fork) = self.reorg.check(&self.client, block_no).await? {
+                    log::warn!(
+                        "Reorg detected at block_no: {}, re-proving from block_no: {}",
+                        block_no,
+                        fork
+                    );
+                    batch.blocks.retain(|prepared| prepared.block_no < fork);
+                    batch.last_block_no = batch.last_block_no.min(fork.saturating_sub(1));
+                    if !batch.blocks.is_empty() {
+                        let merged = self.merge_batch(&mut batch, false)?;
+                        if tx.send(merged).await.is_err() {
+                            break;
+                        }
+                    }
+                    if tx.send(Prefetched::Reorg { fork, block_no }).await.is_err() {
+                        break;
+                    }
+                    queue.rewind(fork);
+                    continue;
+                }
+            }
+            let outdir = &self.opts.outdir;
+            // Only left in S3 or the database, named as it would be proved now.
+            let existing_proof = naming::find_proof(outdir, block_no)
+                .unwrap_or_else(|| naming::proof_path(outdir, block_no, block_no, None));
+            let already_done = match &self.opts.db {
+                Some(db) => db.is_done(block_no)?,
+                // With DELETE_AFTER_UPLOAD the proof may only be left in S3.
+                None => {
+                    has_valid_proof(&existing_proof)
+                        || BlockSummary::load(&self.opts.outdir, block_no)
+                            .ok()
+                            .flatten()
+                            .is_some_and(|summary| !summary.s3_keys.is_empty())
+                }
+            };
+            let prefetched = if !self.force_reprove && already_done {
+                Prefetched::Existing {
+                    block_no,
+                    proof_path: existing_proof,
+                }
+            } else {
+                self.status.set_prefetch(block_no, Phase::Fetching);
+                let fetch_start = Instant::now();
+                let span = tracing::info_span!("fetch", block_no, tx_count = Empty);
+                let test_suite = executor::process(self.client.clone(), block_no, self.chain_id)
+                    .instrument(span.clone())
+                    .await;
+                if let Ok(items) = &test_suite {
+                    span.record("tx_count", items.0.len());
+                }
+                METRICS
+                    .test_suite_fetch_duration_seconds
+                    .observe(fetch_start.elapsed().as_secs_f64());
+                match test_suite {
+                    Ok(items) => {
+                        fetch_attempts = 0;
+                        log::info!(
+                            "Generating json file for block_no: {} is successful, txs: {} rpc: {}",
+                            block_no,
+                            items.0.len(),
+                            self.client.provider().as_ref().last_endpoint(),
+                        );
+                        METRICS.txs_per_block.observe(items.0.len() as f64);
+                        if items.0.is_empty() {
+                            Prefetched::Empty { block_no }
+                        } else {
+                            Prefetched::Ready(self.prepare(items, block_no).await?)
+                        }
+                    }
+                    Err(e) => {
+                        if fetch_attempts == 0 {
+                            fetch_started = Instant::now();
+                        }
+                        fetch_attempts += 1;
+                        let kind = FetchErrorKind::classify(&e);
+                        log::error!(
+                            "Generating json file for block_no: {} is failed, attempt: {} {:?}",
+                            block_no,
+                            fetch_attempts,
+                            kind
+                        );
+                        log::error!("Error: {}", e);
+                        self.status
+                            .record_error(format!("block_no {}: {}", block_no, e));
+                        let waited = fetch_started.elapsed();
+                        let bounded = !self.keep_going || queue.is_list();
+                        if kind == FetchErrorKind::NotReady {
+                            if let Ok(head) = self.client.get_block_number().await {
+                                let head = head.as_u64();
+                                if block_no > head {
+                                    let ahead = block_no - head;
+                                    log::info!(
+                                        "Waiting for block {}, head is {} (behind by {})",
+                                        block_no,
+                                        head,
+                                        ahead
+                                    );
+                                    if bounded && self.fetch_retry.gives_up_waiting(ahead, waited) {
+                                        return Err(ProverError::BlockUnavailable(format!(
+                                            "block_no {} is {} blocks ahead of head {} after waiting {} secs, check BLOCK_NO",
+                                            block_no,
+                                            ahead,
+                                            head,
+                                            waited.as_secs()
+                                        ))
+                                        .into());
+                                    }
+                                }
+                            }
+                        }
+                        if bounded && self.fetch_retry.exhausted(kind, fetch_attempts, waited) {
+                            return Err(ProverError::Rpc(format!(
+                                "failed to fetch block_no {} after {} attempts over {} secs ({:?}): {}",
+                                block_no,
+                                fetch_attempts,
+                                waited.as_secs(),
+                                kind,
+                                e
+                            ))
+                            .into());
+                        }
+                        self.status.set_prefetch(block_no, Phase::Sleeping);
+                        self.wait_for_new_head().await;
+                        continue;
+                    }
+                }
+            };
+            let mut messages = Vec::new();
+            if !batching {
+                messages.push(prefetched);
+            } else {
+                match prefetched {
+                    Prefetched::Ready(prepared) if prepared.relevant => {
+                        batch.last_block_no = prepared.block_no;
+                        batch.blocks.push(prepared);
+                        if batch.blocks.len() >= self.blocks_per_proof {
+                            messages.push(self.merge_batch(&mut batch, false)?);
+                        }
+                    }
+                    Prefetched::Empty { block_no }
+                    | Prefetched::Ready(PreparedBlock { block_no, .. })
+                        if !batch.blocks.is_empty() =>
+                    {
+                        batch.last_block_no = block_no;
+                    }
+                    prefetched => {
+                        if !batch.blocks.is_empty() {
+                            messages.push(self.merge_batch(&mut batch, false)?);
+                        }
+                        messages.push(prefetched);
+                    }
+                }
+            }
+            for prefetched in messages {
+                if tx.send(prefetched).await.is_err() {
+                    return Ok(());
+                }
+            }
+            queue.advance();
+
+            if !self.keep_going && !queue.is_list() {
+                break;
+            }
+        }
+        if !batch.blocks.is_empty() {
+            let trailing = self.shutdown.is_cancelled();
+            let merged = self.merge_batch(&mut batch, trailing)?;
+            let _ = tx.send(merged).await;
+        }
+        Ok(())
+    }
+
+    /// Merge the pending batch into one suite. Every block of it was checked
+    /// on its own already, the units are independent of each other.
+    fn merge_batch(&self, batch: &mut Batch, trailing: bool) -> anyhow::Result<Prefetched> {
+        let blocks = std::mem::take(&mut batch.blocks);
+        let first = blocks[0].block_no;
+        let last = batch.last_block_no;
+        let mut suites = Vec::new();
+        let mut check_micros = 0;
+        let mut check_gas_used = 0;
+        let mut receipts = Vec::new();
+        let mut meta = Vec::new();
+        for block in blocks {
+            check_micros += block.check_micros;
+            check_gas_used += block.check_gas_used;
+            receipts.extend(block.receipts);
+            meta.extend(block.meta);
+            suites.push((block.block_no, block.test_suite));
+        }
+        let test_suite = merge_suites(suites);
+        let (suite, suite_path) = self.write_suite(&test_suite, format!("{}_{}", first, last))?;
+        log::info!(
+            "Batched block_no: {}..={} into {}, txs: {}",
+            first,
+            last,
+            suite_path,
+            test_suite.0.len()
+        );
+        Ok(Prefetched::Ready(PreparedBlock {
+            block_no: first,
+            last_block_no: last,
+            test_suite,
+            suite,
+            suite_path,
+            relevant: true,
+            check_micros,
+            check_gas_used,
+            receipts,
+            meta,
+            trailing,
+        }))
+    }
+
+    /// Write the encoded suite to OUTPUT_DIR as `name`, see [`suite::encode`].
+    fn write_suite(
+        &self,
+        test_suite: &models::TestSuite,
+        name: String,
+    ) -> anyhow::Result<(Vec<u8>, String)> {
+        let opts = self.opts;
+        let buf = suite::encode(test_suite)?;
+        log::debug!("test_suite {}: {} bytes", name, buf.len());
+        let suite_path = suite::file_path(&opts.outdir, name, opts.compress_suites);
+        suite::write(&suite_path, &buf, opts.compress_suites)?;
+        Ok((buf, suite_path.to_string_lossy().into_owned()))
+    }
+
+    /// Fetch and prepare `block_no` once, without retries, for a job of the
+    /// SERVE_ADDR API.
+    pub async fn prepare_block(&self, block_no: u64) -> anyhow::Result<Prefetched> {
+        self.status.set_prefetch(block_no, Phase::Fetching);
+        let test_suite = suite::build(self.client.clone(), block_no, self.chain_id).await?;
+        if test_suite.0.is_empty() {
+            return Ok(Prefetched::Empty { block_no });
+        }
+        Ok(Prefetched::Ready(self.prepare(test_suite, block_no).await?))
+    }
+
+    /// Compare `block_no` with the chain head and apply LAG_POLICY once it
+    /// is more than MAX_LAG_BLOCKS behind. Returns the block to continue
+    /// from when the blocks up to it were skipped.
+    async fn check_lag(&mut self, block_no: u64) -> anyhow::Result<Option<u64>> {
+        if !self.keep_going {
+            return Ok(None);
+        }
+        let Some(lag_guard) = &mut self.lag else {
+            return Ok(None);
+        };
+        let (head, frontier) = match &self.head {
+            Some(head) => match (head.head(), head.frontier()) {
+                (Some(head), Some(frontier)) => (head, frontier),
+                _ => return Ok(None),
+            },
+            None => match self.client.get_block_number().await {
+                Ok(head) => (head.as_u64(), head.as_u64()),
+                Err(e) => {
+                    log::error!("Failed to query the chain head: {}", e);
+                    return Ok(None);
+                }
+            },
+        };
+        let lag = head.saturating_sub(block_no);
+        if !lag_guard.update(lag) {
+            // Skip only once per crossing, the jump brings the lag back down.
+            return Ok(None);
+        }
+        METRICS.lag_exceeded_total.inc();
+        let message = format!(
+            "block_no {} is {} blocks behind head {}, MAX_LAG_BLOCKS is {}",
+            block_no, lag, head, lag_guard.max_lag
+        );
+        match lag_guard.policy {
+            LagPolicy::Block => {
+                log::warn!("{}, proving on in order", message);
+                Ok(None)
+            }
+            LagPolicy::Alert => {
+                log::warn!("{}", message);
+                if let Some(notifier) = &self.opts.notifier {
+                    let mut event = Event::new(EventKind::LagExceeded, Some(block_no));
+                    event.error = Some(message);
+                    notifier.notify(event);
+                }
+                Ok(None)
+            }
+            LagPolicy::Skip => {
+                let next = frontier;
+                if next <= block_no {
+                    return Ok(None);
+                }
+                let skip_file = &lag_guard.skip_file;
+                blocks::append_blocks_file(
+                    skip_file,
+                    &format!("skipped at head {}: {}", head, message),
+                    block_no..next,
+                )
+                .map_err(|e| ProverError::io(skip_file, e))?;
+                METRICS.lag_skipped_blocks_total.inc_by(next - block_no);
+                log::warn!(
+                    "{}, skipping block_no: {}..{} to {}",
+                    message,
+                    block_no,
+                    next,
+                    skip_file.display()
+                );
+                // Below the limit again from the new position.
+                lag_guard.update(head.saturating_sub(next));
+                Ok(Some(next))
+            }
+        }
+    }
+
+    async fn wait_for_new_head(&self) {
+        tokio::select! {
+            _ = rpc::wait_for_new_head(&self.client, self.fetch_retry.retry_interval) => {}
+            _ = self.shutdown.cancelled() => {}
+        }
+    }
+
+    /// Write the suite of `block_no` to OUTPUT_DIR and check it on the host,
+    /// comparing the receipts with the chain if RECEIPT_CHECK is set.
+    async fn prepare(
+        &self,
+        test_suite: models::TestSuite,
+        block_no: u64,
+    ) -> anyhow::Result<PreparedBlock> {
+        let opts = self.opts;
+        let (buf, suite_path) = self.write_suite(&test_suite, block_no.to_string())?;
+        let mut prepared = PreparedBlock {
+            block_no,
+            last_block_no: block_no,
+            test_suite,
+            suite: buf,
+            suite_path,
+            relevant: true,
+            check_micros: 0,
+            check_gas_used: 0,
+            receipts: Vec::new(),
+            meta: Vec::new(),
+            trailing: false,
+        };
+        if let Some(targets) = &opts.targets {
+            if !targets.is_relevant(&prepared.test_suite) {
+                prepared.relevant = false;
+                return Ok(prepared);
+            }
+        }
+        if let Some(db) = &opts.db {
+            db.record_start(block_no, prepared.test_suite.0.len())?;
+        }
+
+        // Fetched up front as they also give the block order of the units.
+        let chain_receipts = if opts.receipt_check == ReceiptCheck::Off {
+            Vec::new()
+        } else {
+            self.client
+                .get_block_receipts(block_no)
+                .await
+                .map_err(|e| {
+                    ProverError::Rpc(format!(
+                        "failed to fetch receipts of block_no {}: {}",
+                        block_no, e
+                    ))
+                })?
+        };
+        let senders: Vec<_> = chain_receipts
+            .iter()
+            .map(|receipt| Address::from(receipt.from.0))
+            .collect();
+
+        self.status.set_prefetch(block_no, Phase::Checking);
+        let check_start_time = Instant::now();
+        let suite = std::mem::take(&mut prepared.suite);
+        let span = tracing::info_span!("check", block_no, tx_count = prepared.test_suite.0.len());
+        let trace = CheckTrace::from_env(&opts.outdir, block_no)?;
+        let check_config = opts.check_config.clone();
+        let continue_on_error = check_config.continue_on_error;
+        let suite_mode = opts.suite_mode;
+        let (suite, checked) = tokio::task::spawn_blocking(move || {
+            let _span = span.entered();
+            let checked = match suite_mode {
+                // A state root other than the suite's fails the check.
+                SuiteMode::Block => {
+                    check::execute_block_suite(&suite, &check_config).map(|report| {
+                        log::info!(
+                            "Block state root of block_no: {}: {}",
+                            block_no,
+                            report.state_root
+                        );
+                        (report.gas_used, report.receipts)
+                    })
+                }
+                SuiteMode::Unit => {
+                    let report = if continue_on_error {
+                        check::run_suite_report(&suite, &check_config, trace.as_ref()).and_then(
+                            |report| {
+                                log_check_report(block_no, &report);
+                                report.into_result(&check_config)
+                            },
+                        )
+                    } else {
+                        check::execute_test_suite(&suite, &check_config, trace.as_ref())
+                    };
+                    report.map(|report| (report.gas_used(), report.receipts(&senders)))
+                }
+            };
+            (suite, checked)
+        })
+        .await?;
+        prepared.suite = suite;
+        let (gas_used, receipts) = checked.map_err(|e| {
+            ProverError::Check(format!(
+                "check of block_no {} ({}) failed: {}",
+                block_no, prepared.suite_path, e
+            ))
+        })?;
+        prepared.check_gas_used = gas_used;
+        let check_end_time = Instant::now();
+        prepared.check_micros = check_end_time.duration_since(check_start_time).as_micros() as u64;
+        log::info!(
+            "Elapsed time: {:?} micros check block_no:{}",
+            prepared.check_micros,
+            block_no
+        );
+        if opts.receipt_check != ReceiptCheck::Off {
+            prepared.receipts = receipts::compare_receipts(
+                block_no,
+                &receipts,
+                &chain_receipts,
+                opts.receipt_tolerance,
+            );
+            let failed = prepared
+                .receipts
+                .iter()
+                .filter(|outcome| !outcome.matches())
+                .count();
+            if failed > 0 && opts.receipt_check == ReceiptCheck::Strict {
+                return Err(ProverError::Check(format!(
+                    "{} receipts of block_no: {} differ from the chain",
+                    failed, block_no
+                ))
+                .into());
+            }
+        }
+        let meta = BlockMeta::fetch(&self.client, block_no, &prepared.test_suite).await?;
+        prepared.meta.push(meta);
+        Ok(prepared)
+    }
+}
+
+/// The units of the suites of `blocks` in one suite. The suite is ordered by
+/// name, the block number prefixed to the names keeps the units in block
+/// order.
+fn merge_suites(blocks: Vec<(u64, models::TestSuite)>) -> models::TestSuite {
+    let mut units = BTreeMap::new();
+    for (block_no, test_suite) in blocks {
+        for (name, unit) in test_suite.0 {
+            units.insert(format!("{:020}_{}", block_no, name), unit);
+        }
+    }
+    models::TestSuite(units)
+}
+
+/// Log every failed unit of a CHECK_CONTINUE_ON_ERROR check and the totals.
+fn log_check_report(block_no: u64, report: &SuiteReport) {
+    for (name, unit) in &report.units {
+        if let Some(e) = &unit.error {
+            log::error!(
+                "Check of unit {} of block_no: {} failed: {}",
+                name,
+                block_no,
+                e
+            );
+        }
+    }
+    let summary = report.summary();
+    log::info!(
+        "Check of block_no: {}: {} units passed, {} failed, {} txs in {} micros",
+        block_no,
+        summary.passed,
+        summary.failed,
+        summary.tx_count,
+        summary.micros
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The transfer fixture with its unit named `name`.
+    fn transfer_suite(name: &str) -> models::TestSuite {
+        let path = concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/tests/fixtures/check/transfer.json"
+        );
+        let json = std::fs::read_to_string(path).unwrap().replacen(
+            "\"transfer\"",
+            &format!("{:?}", name),
+            1,
+        );
+        check::decode_test_suite(json.as_bytes()).unwrap()
+    }
+
+    #[test]
+    fn merged_suites_keep_block_order() {
+        let mut two_units = transfer_suite("transfer");
+        two_units.0.extend(transfer_suite("a").0);
+        let merged = merge_suites(vec![(10, transfer_suite("transfer")), (9, two_units)]);
+        let names: Vec<_> = merged.0.keys().cloned().collect();
+        assert_eq!(
+            names,
+            [
+                "00000000000000000009_a",
+                "00000000000000000009_transfer",
+                "00000000000000000010_transfer",
+            ]
+        );
+    }
+
+    #[test]
+    fn merged_suite_checks_like_its_blocks() {
+        let merged = merge_suites(vec![
+            (1, transfer_suite("transfer")),
+            (2, transfer_suite("transfer")),
+        ]);
+        let report = check::execute_suite(&merged, &check::CheckConfig::default(), None).unwrap();
+        assert_eq!(report.units.len(), 2);
+        assert!(report.units.values().all(|unit| unit.error.is_none()));
+    }
+}
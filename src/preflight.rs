@@ -0,0 +1,201 @@
+use crate::error::ProverError;
+use crate::prover::SharedProver;
+use std::io::Read;
+use std::path::Path;
+use std::time::Duration;
+use zkm_sdk::prover::ClientCfg;
+
+const ELF_MAGIC: &[u8; 4] = b"\x7fELF";
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+/// SEG_SIZE bounds accepted by the prover.
+const MIN_SEG_SIZE: u32 = 1 << 10;
+const MAX_SEG_SIZE: u32 = 1 << 22;
+
+/// Settings checked against each other before anything else is done.
+pub struct StartupConfig<'a> {
+    pub cfg: &'a ClientCfg,
+    pub elf_path: &'a str,
+    pub generate_only: bool,
+    pub execute_only: bool,
+    /// SEG_SIZE and SEG_SIZE_MIN as given.
+    pub seg_size: &'a str,
+    pub min_seg_size: &'a str,
+}
+
+/// Check the cross-field constraints of the configuration. Every violation
+/// is reported in one error, not just the first one.
+pub fn validate(config: &StartupConfig) -> anyhow::Result<()> {
+    let mut problems = Vec::new();
+    if !config.generate_only {
+        if config.elf_path.is_empty() {
+            problems.push(if config.execute_only {
+                "EXECUTE_ONLY needs the guest, set ELF_PATH".to_string()
+            } else {
+                "ELF_PATH is empty, set ELF_PATH to prove or GENERATE_ONLY=true to only generate test suites".to_string()
+            });
+        }
+        let cfg = config.cfg;
+        match cfg.zkm_prover.as_str() {
+            "network" => {
+                for (name, value) in [
+                    ("ENDPOINT", &cfg.endpoint),
+                    ("PRIVATE_KEY", &cfg.private_key),
+                ] {
+                    if value.as_deref().unwrap_or("").is_empty() {
+                        problems.push(format!("ZKM_PROVER=network needs {}", name));
+                    }
+                }
+            }
+            "local" => {}
+            other => problems.push(format!(
+                "invalid ZKM_PROVER {:?}, expected network or local",
+                other
+            )),
+        }
+        // SEG_SIZE=auto picks a power of two within the bounds itself.
+        let seg_size = match config.seg_size.trim() {
+            "auto" => None,
+            seg_size => check_seg_size("SEG_SIZE", seg_size, &mut problems),
+        };
+        let min_seg_size = check_seg_size("SEG_SIZE_MIN", config.min_seg_size, &mut problems);
+        if let (Some(seg_size), Some(min_seg_size)) = (seg_size, min_seg_size) {
+            if min_seg_size > seg_size {
+                problems.push(format!(
+                    "SEG_SIZE_MIN {} is larger than SEG_SIZE {}",
+                    min_seg_size, seg_size
+                ));
+            }
+        }
+    }
+    if problems.is_empty() {
+        return Ok(());
+    }
+    Err(ProverError::Config(format!(
+        "invalid configuration:\n  {}",
+        problems.join("\n  ")
+    ))
+    .into())
+}
+
+fn check_seg_size(name: &str, value: &str, problems: &mut Vec<String>) -> Option<u32> {
+    let Ok(seg_size) = value.trim().parse::<u32>() else {
+        problems.push(format!("invalid {} {:?}, expected a number", name, value));
+        return None;
+    };
+    if !seg_size.is_power_of_two() || !(MIN_SEG_SIZE..=MAX_SEG_SIZE).contains(&seg_size) {
+        problems.push(format!(
+            "{} {} must be a power of two between {} and {}",
+            name, seg_size, MIN_SEG_SIZE, MAX_SEG_SIZE
+        ));
+        return None;
+    }
+    Some(seg_size)
+}
+
+/// Checks run once at startup, so a bad ELF_PATH, an unreachable prover or a
+/// read-only OUTPUT_DIR fail the run before the first block is fetched
+/// instead of at the first proof attempt.
+pub async fn run(
+    prover: &SharedProver,
+    elf_path: &str,
+    outdir: &str,
+    prove: bool,
+) -> anyhow::Result<()> {
+    check_outdir(outdir)?;
+    if prove {
+        check_elf(elf_path)?;
+        check_prover(prover).await?;
+    }
+    log::info!("Preflight checks passed");
+    Ok(())
+}
+
+fn check_elf(elf_path: &str) -> anyhow::Result<()> {
+    let mut magic = [0u8; 4];
+    std::fs::File::open(elf_path)
+        .and_then(|mut f| f.read_exact(&mut magic))
+        .map_err(|e| {
+            ProverError::Config(format!("ELF_PATH {} is not readable: {}", elf_path, e))
+        })?;
+    if &magic != ELF_MAGIC {
+        return Err(ProverError::Config(format!(
+            "ELF_PATH {} is not an ELF file, magic: {:02x?}",
+            elf_path, magic
+        ))
+        .into());
+    }
+    Ok(())
+}
+
+/// Create OUTPUT_DIR if needed and check that it is writable.
+fn check_outdir(outdir: &str) -> anyhow::Result<()> {
+    if !Path::new(outdir).is_dir() {
+        std::fs::create_dir_all(outdir).map_err(|e| {
+            ProverError::Config(format!("failed to create OUTPUT_DIR {}: {}", outdir, e))
+        })?;
+        log::info!("Created OUTPUT_DIR {}", outdir);
+    }
+    let probe = Path::new(outdir).join(".preflight.tmp");
+    std::fs::write(&probe, b"").map_err(|e| {
+        ProverError::Config(format!("OUTPUT_DIR {} is not writable: {}", outdir, e))
+    })?;
+    let _ = std::fs::remove_file(&probe);
+    Ok(())
+}
+
+/// In network mode check the TLS files and that ENDPOINT accepts
+/// connections, in local mode that the proving keys in VK_PATH exist. Then
+/// construct the client the run goes on with.
+async fn check_prover(prover: &SharedProver) -> anyhow::Result<()> {
+    let cfg = prover.cfg();
+    if cfg.zkm_prover == "network" {
+        let endpoint = cfg.endpoint.as_deref().unwrap_or("");
+        if endpoint.is_empty() {
+            return Err(
+                ProverError::Config("ZKM_PROVER=network needs ENDPOINT".to_string()).into(),
+            );
+        }
+        for (name, path) in [
+            ("CA_CERT_PATH", &cfg.ca_cert_path),
+            ("CERT_PATH", &cfg.cert_path),
+            ("KEY_PATH", &cfg.key_path),
+        ] {
+            if let Some(path) = path.as_deref().filter(|p| !p.is_empty()) {
+                std::fs::metadata(path).map_err(|e| {
+                    ProverError::Config(format!("{} {} is not readable: {}", name, path, e))
+                })?;
+            }
+        }
+        let url = reqwest::Url::parse(endpoint)
+            .map_err(|e| ProverError::Config(format!("invalid ENDPOINT {:?}: {}", endpoint, e)))?;
+        let host = url.host_str().unwrap_or_default().to_string();
+        let port = url.port_or_known_default().unwrap_or(443);
+        match tokio::time::timeout(
+            CONNECT_TIMEOUT,
+            tokio::net::TcpStream::connect((host, port)),
+        )
+        .await
+        {
+            Ok(Ok(_)) => {}
+            Ok(Err(e)) => {
+                return Err(ProverError::Prove(format!(
+                    "prover {} is unreachable: {}",
+                    endpoint, e
+                ))
+                .into())
+            }
+            Err(_) => {
+                return Err(ProverError::Prove(format!(
+                    "prover {} did not accept a connection within {} secs",
+                    endpoint,
+                    CONNECT_TIMEOUT.as_secs()
+                ))
+                .into())
+            }
+        }
+    } else if !cfg.vk_path.is_empty() && !Path::new(&cfg.vk_path).exists() {
+        return Err(ProverError::Config(format!("VK_PATH {} does not exist", cfg.vk_path)).into());
+    }
+    prover.client().await;
+    Ok(())
+}
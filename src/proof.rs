@@ -0,0 +1,141 @@
+//! Proving a test suite with the zkMIPS prover network or a local prover.
+
+use crate::error::ProverError;
+use crate::suite;
+use std::path::Path;
+use std::time::Duration;
+use zkm_sdk::prover::{ClientCfg, ProverInput, ProverResult};
+use zkm_sdk::ProverClient;
+
+/// The guest and its input streams, what the prover executes.
+#[derive(Debug, Clone, Default)]
+pub struct SuiteArtifacts {
+    pub elf: Vec<u8>,
+    /// The test suite, in the JSON or bincode format of [`suite::read`].
+    pub public_inputstream: Vec<u8>,
+    pub private_inputstream: Vec<u8>,
+}
+
+impl SuiteArtifacts {
+    /// Read the guest ELF and the suite file, which may be gzip compressed,
+    /// without a private input.
+    pub fn read(elf_path: impl AsRef<Path>, suite_path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let elf_path = elf_path.as_ref();
+        Ok(Self {
+            elf: std::fs::read(elf_path).map_err(|e| ProverError::io(elf_path, e))?,
+            public_inputstream: suite::read(suite_path)?,
+            private_inputstream: vec![],
+        })
+    }
+}
+
+/// Settings of a single prover call.
+#[derive(Debug, Clone)]
+pub struct ProveOptions {
+    pub seg_size: u32,
+    /// Execute the guest and report its statistics without proving it.
+    pub execute_only: bool,
+    /// Upper bound of the call.
+    pub timeout: Duration,
+}
+
+impl Default for ProveOptions {
+    fn default() -> Self {
+        Self {
+            seg_size: 65536,
+            execute_only: false,
+            timeout: Duration::from_secs(7200),
+        }
+    }
+}
+
+/// How a prover call ended. Errors returned by the prover are
+/// [`ProverError::Prove`] instead.
+#[derive(Debug)]
+pub enum ProofOutcome {
+    /// `proof_with_public_inputs` of the result is empty for an
+    /// `execute_only` call, and when the prover could not prove the suite
+    /// with the segment size asked for.
+    Completed(ProverResult),
+    /// The prover answered without a result.
+    NoResult,
+    /// The prover did not answer within `timeout`.
+    TimedOut,
+}
+
+/// Connect to the prover configured by `cfg` and prove `artifacts` once.
+///
+/// ```no_run
+/// use goat_prover::proof::{self, ProofOutcome, ProveOptions, SuiteArtifacts};
+/// use zkm_sdk::prover::ClientCfg;
+///
+/// # async fn run() -> anyhow::Result<()> {
+/// let cfg = ClientCfg {
+///     zkm_prover: "local".to_string(),
+///     vk_path: "/tmp/vk".to_string(),
+///     endpoint: None,
+///     ca_cert_path: None,
+///     cert_path: None,
+///     key_path: None,
+///     domain_name: None,
+///     private_key: None,
+/// };
+/// let artifacts = SuiteArtifacts::read("guest/evm", "/tmp/output/1.json")?;
+/// match proof::prove(&cfg, &artifacts, &ProveOptions::default()).await? {
+///     ProofOutcome::Completed(result) => {
+///         std::fs::write("1_snark_proof.json", &result.proof_with_public_inputs)?
+///     }
+///     outcome => anyhow::bail!("no proof: {:?}", outcome),
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub async fn prove(
+    cfg: &ClientCfg,
+    artifacts: &SuiteArtifacts,
+    options: &ProveOptions,
+) -> anyhow::Result<ProofOutcome> {
+    let client = ProverClient::new(cfg).await;
+    prove_with(&client, artifacts, options).await
+}
+
+/// [`prove`] on a connected client, which can be shared between calls.
+pub async fn prove_with(
+    client: &ProverClient,
+    artifacts: &SuiteArtifacts,
+    options: &ProveOptions,
+) -> anyhow::Result<ProofOutcome> {
+    let input = ProverInput {
+        elf: artifacts.elf.clone(),
+        public_inputstream: artifacts.public_inputstream.clone(),
+        private_inputstream: artifacts.private_inputstream.clone(),
+        seg_size: options.seg_size,
+        execute_only: options.execute_only,
+    };
+    match tokio::time::timeout(options.timeout, client.prover.prove(&input, None)).await {
+        Ok(Ok(Some(result))) => Ok(ProofOutcome::Completed(result)),
+        Ok(Ok(None)) => Ok(ProofOutcome::NoResult),
+        Ok(Err(e)) => Err(ProverError::Prove(e.to_string()).into()),
+        Err(_) => Ok(ProofOutcome::TimedOut),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_send_sync<T: Send + Sync>() {}
+
+    #[test]
+    fn public_types_are_send_and_sync() {
+        assert_send_sync::<SuiteArtifacts>();
+        assert_send_sync::<ProveOptions>();
+        assert_send_sync::<ProofOutcome>();
+    }
+
+    #[test]
+    fn read_reports_the_missing_elf() {
+        let e = SuiteArtifacts::read("/nonexistent/evm", "/nonexistent/1.json").unwrap_err();
+        assert_eq!(crate::error::exit_code(&e), 6);
+    }
+}
@@ -0,0 +1,56 @@
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use zkm_sdk::{prover::ClientCfg, ProverClient};
+
+/// The ProverClient of a run, constructed on first use and shared by every
+/// block instead of reconnecting for each proof.
+pub struct SharedProver {
+    cfg: ClientCfg,
+    client: Mutex<Option<Arc<ProverClient>>>,
+}
+
+impl SharedProver {
+    pub fn new(cfg: ClientCfg) -> Self {
+        Self {
+            cfg,
+            client: Mutex::new(None),
+        }
+    }
+
+    pub fn cfg(&self) -> &ClientCfg {
+        &self.cfg
+    }
+
+    pub async fn client(&self) -> Arc<ProverClient> {
+        let mut client = self.client.lock().await;
+        match &*client {
+            Some(client) => client.clone(),
+            None => {
+                let new_client = Arc::new(ProverClient::new(&self.cfg).await);
+                *client = Some(new_client.clone());
+                new_client
+            }
+        }
+    }
+
+    /// Drop the current client, the next `client()` connects again.
+    pub async fn reset(&self) {
+        *self.client.lock().await = None;
+    }
+}
+
+/// Whether a prover error means the connection to the prover network is
+/// gone rather than the proof failing.
+pub fn is_connection_error(error: &str) -> bool {
+    let error = error.to_lowercase();
+    [
+        "transport error",
+        "connection refused",
+        "connection reset",
+        "broken pipe",
+        "tcp connect",
+        "unavailable",
+    ]
+    .iter()
+    .any(|pattern| error.contains(pattern))
+}
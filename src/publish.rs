@@ -0,0 +1,81 @@
+use da_service::{BlobSubmission, CelestiaService, DaServiceConfig};
+use serde::Deserialize;
+use std::env;
+use std::time::Duration;
+
+/// Blob header: magic, then block number and chain id as big-endian u64.
+const BLOB_MAGIC: &[u8; 4] = b"GPRF";
+
+/// The `[daconfig]` section of the tx_transfer config file.
+#[derive(Deserialize)]
+struct DaConfigFile {
+    daconfig: DaServiceConfig,
+}
+
+/// Publishes proofs to Celestia, configured by DA_CONFIG_PATH.
+pub struct DaPublisher {
+    service: CelestiaService,
+    chain_id: u64,
+    max_retries: u32,
+    backoff_secs: u64,
+}
+
+impl DaPublisher {
+    /// `None` when DA_CONFIG_PATH is unset.
+    pub async fn from_env(chain_id: u64) -> anyhow::Result<Option<Self>> {
+        let Ok(config_path) = env::var("DA_CONFIG_PATH") else {
+            return Ok(None);
+        };
+        let content = std::fs::read_to_string(&config_path)
+            .map_err(|e| anyhow::anyhow!("failed to read DA config {}: {}", config_path, e))?;
+        let config: DaConfigFile = toml::from_str(&content)
+            .map_err(|e| anyhow::anyhow!("invalid DA config {}: {}", config_path, e))?;
+        let max_retries = env::var("DA_MAX_RETRIES").unwrap_or("3".to_string());
+        let backoff_secs = env::var("DA_RETRY_BACKOFF_SECS").unwrap_or("10".to_string());
+        Ok(Some(Self {
+            service: CelestiaService::new(config.daconfig).await,
+            chain_id,
+            max_retries: max_retries.parse().unwrap_or(3),
+            backoff_secs: backoff_secs.parse().unwrap_or(10),
+        }))
+    }
+
+    fn blob(&self, proof: &[u8], block_no: u64) -> Vec<u8> {
+        let mut blob = Vec::with_capacity(BLOB_MAGIC.len() + 16 + proof.len());
+        blob.extend_from_slice(BLOB_MAGIC);
+        blob.extend_from_slice(&block_no.to_be_bytes());
+        blob.extend_from_slice(&self.chain_id.to_be_bytes());
+        blob.extend_from_slice(proof);
+        blob
+    }
+
+    /// Submit `proof` of `block_no` as a blob, retrying with a linear backoff.
+    pub async fn publish(&self, proof: &[u8], block_no: u64) -> anyhow::Result<BlobSubmission> {
+        let blob = self.blob(proof, block_no);
+        let mut attempts = 0;
+        loop {
+            attempts += 1;
+            match self.service.send_transaction(&blob).await {
+                Ok(submission) => return Ok(submission),
+                Err(e) if attempts > self.max_retries => {
+                    anyhow::bail!(
+                        "failed to publish proof of block_no {} after {} attempts: {}",
+                        block_no,
+                        attempts,
+                        e
+                    );
+                }
+                Err(e) => {
+                    let backoff = Duration::from_secs(self.backoff_secs * attempts as u64);
+                    log::warn!(
+                        "Failed to publish proof of block_no:{} to DA: {}. Retrying in {:?}",
+                        block_no,
+                        e,
+                        backoff
+                    );
+                    tokio::time::sleep(backoff).await;
+                }
+            }
+        }
+    }
+}
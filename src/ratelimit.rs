@@ -0,0 +1,126 @@
+use std::env;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    refilled_at: Instant,
+    /// Set after the provider throttled us, no call is made before.
+    paused_until: Option<Instant>,
+}
+
+/// Token bucket shared by every RPC call of the process, RPC_MAX_RPS calls
+/// per second with bursts of up to RPC_BURST. RPC_MAX_RPS=0 (the default)
+/// disables the limit but keeps the cool-down after throttling.
+#[derive(Debug, Clone)]
+pub struct RateLimiter {
+    rate: f64,
+    burst: f64,
+    cooldown: Duration,
+    bucket: Arc<Mutex<Bucket>>,
+}
+
+impl RateLimiter {
+    pub fn from_env() -> Self {
+        let max_rps = env::var("RPC_MAX_RPS").unwrap_or("0".to_string());
+        let burst = env::var("RPC_BURST").unwrap_or("10".to_string());
+        let cooldown_secs = env::var("RPC_THROTTLE_COOLDOWN_SECS").unwrap_or("5".to_string());
+        let burst = burst.parse::<f64>().unwrap_or(10.0).max(1.0);
+        Self {
+            rate: max_rps.parse::<f64>().unwrap_or(0.0).max(0.0),
+            burst,
+            cooldown: Duration::from_secs(cooldown_secs.parse().unwrap_or(5)),
+            bucket: Arc::new(Mutex::new(Bucket {
+                tokens: burst,
+                refilled_at: Instant::now(),
+                paused_until: None,
+            })),
+        }
+    }
+
+    /// Wait for the cool-down and for a token.
+    pub async fn acquire(&self) {
+        loop {
+            let wait = {
+                let mut bucket = self.bucket.lock().unwrap_or_else(|e| e.into_inner());
+                let now = Instant::now();
+                match bucket.paused_until {
+                    Some(until) if until > now => until - now,
+                    _ if self.rate == 0.0 => return,
+                    _ => {
+                        let elapsed = now.duration_since(bucket.refilled_at).as_secs_f64();
+                        bucket.tokens = (bucket.tokens + elapsed * self.rate).min(self.burst);
+                        bucket.refilled_at = now;
+                        if bucket.tokens >= 1.0 {
+                            bucket.tokens -= 1.0;
+                            return;
+                        }
+                        Duration::from_secs_f64((1.0 - bucket.tokens) / self.rate)
+                    }
+                }
+            };
+            tokio::time::sleep(wait).await;
+        }
+    }
+
+    /// Hold off every call for RPC_THROTTLE_COOLDOWN_SECS.
+    pub fn throttled(&self) {
+        let mut bucket = self.bucket.lock().unwrap_or_else(|e| e.into_inner());
+        bucket.paused_until = Some(Instant::now() + self.cooldown);
+        bucket.tokens = 0.0;
+    }
+}
+
+/// Whether `error` says the provider rate limited the call.
+pub fn is_rate_limited(error: &str) -> bool {
+    let error = error.to_lowercase();
+    error.contains("429") || error.contains("rate limit") || error.contains("too many requests")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limiter(rate: f64, burst: f64, cooldown: Duration) -> RateLimiter {
+        RateLimiter {
+            rate,
+            burst,
+            cooldown,
+            bucket: Arc::new(Mutex::new(Bucket {
+                tokens: burst,
+                refilled_at: Instant::now(),
+                paused_until: None,
+            })),
+        }
+    }
+
+    #[tokio::test]
+    async fn calls_past_the_burst_wait_for_a_token() {
+        let limiter = limiter(20.0, 2.0, Duration::ZERO);
+        let start = Instant::now();
+        for _ in 0..3 {
+            limiter.acquire().await;
+        }
+        assert!(start.elapsed() >= Duration::from_millis(40));
+    }
+
+    #[tokio::test]
+    async fn throttling_pauses_even_an_unlimited_limiter() {
+        let limiter = limiter(0.0, 10.0, Duration::from_millis(50));
+        let start = Instant::now();
+        limiter.acquire().await;
+        assert!(start.elapsed() < Duration::from_millis(50));
+        limiter.throttled();
+        limiter.acquire().await;
+        assert!(start.elapsed() >= Duration::from_millis(50));
+    }
+
+    #[test]
+    fn rate_limit_errors() {
+        assert!(is_rate_limited("HTTP status 429"));
+        assert!(is_rate_limited("Too Many Requests"));
+        assert!(is_rate_limited("daily rate limit exceeded"));
+        assert!(!is_rate_limited("connection refused"));
+    }
+}
@@ -0,0 +1,303 @@
+use crate::check::Receipt;
+use ethers_core::types::TransactionReceipt;
+use revm::primitives::SpecId;
+use serde::{Deserialize, Serialize};
+use std::env;
+
+/// How divergences between the host receipts and the chain's receipts are
+/// handled, configured by RECEIPT_CHECK=off|warn|strict.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReceiptCheck {
+    Off,
+    Warn,
+    Strict,
+}
+
+impl ReceiptCheck {
+    pub fn from_env() -> anyhow::Result<Self> {
+        match env::var("RECEIPT_CHECK")
+            .unwrap_or("off".to_string())
+            .as_str()
+        {
+            "off" => Ok(Self::Off),
+            "warn" => Ok(Self::Warn),
+            "strict" => Ok(Self::Strict),
+            other => anyhow::bail!("invalid RECEIPT_CHECK: {}, expected off|warn|strict", other),
+        }
+    }
+}
+
+/// Receipt fields left out of the comparison.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReceiptTolerance {
+    /// Skip the gas of transactions executed before Byzantium, unless
+    /// RECEIPT_CHECK_PRE_BYZANTIUM_GAS is true.
+    pub skip_gas_pre_byzantium: bool,
+    /// Skip the logs bloom, RECEIPT_CHECK_SKIP_BLOOM (false).
+    pub skip_logs_bloom: bool,
+}
+
+impl Default for ReceiptTolerance {
+    fn default() -> Self {
+        Self {
+            skip_gas_pre_byzantium: true,
+            skip_logs_bloom: false,
+        }
+    }
+}
+
+impl ReceiptTolerance {
+    pub fn from_env() -> Self {
+        let pre_byzantium_gas =
+            env::var("RECEIPT_CHECK_PRE_BYZANTIUM_GAS").unwrap_or("false".to_string());
+        let skip_logs_bloom = env::var("RECEIPT_CHECK_SKIP_BLOOM").unwrap_or("false".to_string());
+        Self {
+            skip_gas_pre_byzantium: !pre_byzantium_gas.parse().unwrap_or(false),
+            skip_logs_bloom: skip_logs_bloom.parse().unwrap_or(false),
+        }
+    }
+}
+
+/// Outcome of the receipt comparison of one transaction, listed in the block
+/// summary.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReceiptMatch {
+    pub block_no: u64,
+    /// Index of the transaction in the block.
+    pub tx_index: usize,
+    pub tx_hash: Option<String>,
+    /// Every mismatching field, empty when the receipts match.
+    pub mismatches: Vec<String>,
+}
+
+impl ReceiptMatch {
+    pub fn matches(&self) -> bool {
+        self.mismatches.is_empty()
+    }
+}
+
+/// Compare the receipt of one transaction field by field, returning a
+/// description of every mismatching field.
+fn compare_receipt(
+    local: &Receipt,
+    remote: &TransactionReceipt,
+    tolerance: ReceiptTolerance,
+) -> Vec<String> {
+    let mut mismatches = Vec::new();
+    let remote_success = remote.status.map(|s| s.as_u64() == 1);
+    if remote_success.is_some_and(|s| s != local.success) {
+        mismatches.push(format!(
+            "status: local {} remote {}",
+            local.success,
+            remote_success.unwrap_or_default()
+        ));
+    }
+    let skip_gas =
+        tolerance.skip_gas_pre_byzantium && !SpecId::enabled(local.spec_id, SpecId::BYZANTIUM);
+    if !skip_gas && remote.cumulative_gas_used.as_u64() != local.cumulative_gas_used {
+        mismatches.push(format!(
+            "cumulative_gas_used: local {} remote {}",
+            local.cumulative_gas_used, remote.cumulative_gas_used
+        ));
+    }
+    if !tolerance.skip_logs_bloom && local.logs_bloom.as_slice() != remote.logs_bloom.as_bytes() {
+        mismatches.push("logs_bloom differs".to_string());
+    }
+    if local.logs.len() != remote.logs.len() {
+        mismatches.push(format!(
+            "logs: local {} entries remote {}",
+            local.logs.len(),
+            remote.logs.len()
+        ));
+    } else {
+        for (i, (l, r)) in local.logs.iter().zip(remote.logs.iter()).enumerate() {
+            let same_topics = l.data.topics().len() == r.topics.len()
+                && l.data
+                    .topics()
+                    .iter()
+                    .zip(r.topics.iter())
+                    .all(|(a, b)| a.as_slice() == b.as_bytes());
+            if l.address.as_slice() != r.address.as_bytes()
+                || !same_topics
+                || l.data.data.as_ref() != r.data.as_ref()
+            {
+                mismatches.push(format!("log {} differs", i));
+            }
+        }
+    }
+    mismatches
+}
+
+/// Compare the host receipts against the receipts recorded on chain, in
+/// transaction order. Returns the outcome of every transaction, a receipt
+/// on only one side is a mismatch.
+pub fn compare_receipts(
+    block_no: u64,
+    local: &[Receipt],
+    remote: &[TransactionReceipt],
+    tolerance: ReceiptTolerance,
+) -> Vec<ReceiptMatch> {
+    if local.len() != remote.len() {
+        log::warn!(
+            "Receipts: block_no:{} local has {} receipts, chain has {}",
+            block_no,
+            local.len(),
+            remote.len()
+        );
+    }
+    (0..local.len().max(remote.len()))
+        .map(|i| {
+            let mismatches = match (local.get(i), remote.get(i)) {
+                (Some(l), Some(r)) => compare_receipt(l, r, tolerance),
+                (Some(_), None) => vec!["missing on chain".to_string()],
+                _ => vec!["missing locally".to_string()],
+            };
+            let tx_hash = remote.get(i).map(|r| format!("{:?}", r.transaction_hash));
+            if mismatches.is_empty() {
+                log::debug!("Receipts: block_no:{} tx:{} matches", block_no, i);
+            } else {
+                log::warn!(
+                    "Receipts: block_no:{} tx:{} hash:{} mismatch: {}",
+                    block_no,
+                    i,
+                    tx_hash.as_deref().unwrap_or("-"),
+                    mismatches.join(", ")
+                );
+            }
+            ReceiptMatch {
+                block_no,
+                tx_index: i,
+                tx_hash,
+                mismatches,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use revm::primitives::alloy_primitives::Bloom;
+    use revm::primitives::{Address, Log};
+
+    fn local(spec_id: SpecId) -> Receipt {
+        Receipt {
+            success: true,
+            gas_used: 21_000,
+            cumulative_gas_used: 42_000,
+            logs: Vec::new(),
+            logs_bloom: Bloom::ZERO,
+            spec_id,
+            sender: Address::ZERO,
+            nonce: 0,
+        }
+    }
+
+    fn remote() -> TransactionReceipt {
+        TransactionReceipt {
+            status: Some(1u64.into()),
+            cumulative_gas_used: 42_000u64.into(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn matching_receipt() {
+        let tolerance = ReceiptTolerance::default();
+        assert!(compare_receipt(&local(SpecId::CANCUN), &remote(), tolerance).is_empty());
+    }
+
+    #[test]
+    fn status_and_logs() {
+        let mut receipt = local(SpecId::CANCUN);
+        receipt.success = false;
+        receipt.logs.push(Log::default());
+        assert_eq!(
+            compare_receipt(&receipt, &remote(), ReceiptTolerance::default()),
+            [
+                "status: local false remote true",
+                "logs: local 1 entries remote 0"
+            ]
+        );
+    }
+
+    #[test]
+    fn gas_before_byzantium() {
+        let mut chain = remote();
+        chain.cumulative_gas_used = 50_000u64.into();
+        let tolerance = ReceiptTolerance::default();
+        assert!(compare_receipt(&local(SpecId::HOMESTEAD), &chain, tolerance).is_empty());
+        assert_eq!(
+            compare_receipt(&local(SpecId::BYZANTIUM), &chain, tolerance),
+            ["cumulative_gas_used: local 42000 remote 50000"]
+        );
+        let tolerance = ReceiptTolerance {
+            skip_gas_pre_byzantium: false,
+            ..tolerance
+        };
+        assert_eq!(
+            compare_receipt(&local(SpecId::HOMESTEAD), &chain, tolerance).len(),
+            1
+        );
+    }
+
+    #[test]
+    fn logs_bloom() {
+        let mut receipt = local(SpecId::CANCUN);
+        receipt.logs_bloom = Bloom::repeat_byte(0xff);
+        let tolerance = ReceiptTolerance::default();
+        assert_eq!(
+            compare_receipt(&receipt, &remote(), tolerance),
+            ["logs_bloom differs"]
+        );
+        let tolerance = ReceiptTolerance {
+            skip_logs_bloom: true,
+            ..tolerance
+        };
+        assert!(compare_receipt(&receipt, &remote(), tolerance).is_empty());
+    }
+
+    #[test]
+    fn missing_receipts_count_as_failed() {
+        let tolerance = ReceiptTolerance::default();
+        let local = [local(SpecId::CANCUN)];
+        let matches = compare_receipts(1, &local, &[remote(), remote()], tolerance);
+        assert_eq!(matches.len(), 2);
+        assert!(matches[0].matches());
+        assert_eq!(matches[1].mismatches, ["missing locally"]);
+        assert!(compare_receipts(1, &local, &[remote()], tolerance)[0].matches());
+        assert_eq!(
+            compare_receipts(1, &local, &[], tolerance)[0].mismatches,
+            ["missing on chain"]
+        );
+    }
+
+    #[test]
+    fn outcome_per_transaction() {
+        let mut mismatching = local(SpecId::CANCUN);
+        mismatching.success = false;
+        let matches = compare_receipts(
+            7,
+            &[local(SpecId::CANCUN), mismatching],
+            &[remote(), remote()],
+            ReceiptTolerance::default(),
+        );
+        assert_eq!(
+            serde_json::to_value(&matches).unwrap(),
+            serde_json::json!([
+                {
+                    "block_no": 7,
+                    "tx_index": 0,
+                    "tx_hash": format!("{:?}", remote().transaction_hash),
+                    "mismatches": [],
+                },
+                {
+                    "block_no": 7,
+                    "tx_index": 1,
+                    "tx_hash": format!("{:?}", remote().transaction_hash),
+                    "mismatches": ["status: local false remote true"],
+                },
+            ])
+        );
+    }
+}
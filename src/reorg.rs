@@ -0,0 +1,185 @@
+use ethers_core::types::H256;
+use ethers_providers::{JsonRpcClient, Middleware, Provider};
+use std::collections::BTreeMap;
+use std::env;
+
+/// Detects reorgs by checking that every block's parent hash matches the hash
+/// recorded when its parent was processed. Hashes of the last
+/// REORG_MAX_DEPTH blocks are kept.
+#[derive(Debug)]
+pub struct ReorgDetector {
+    hashes: BTreeMap<u64, H256>,
+    max_depth: u64,
+}
+
+impl ReorgDetector {
+    pub fn from_env() -> Self {
+        let max_depth = env::var("REORG_MAX_DEPTH").unwrap_or("128".to_string());
+        Self {
+            hashes: BTreeMap::new(),
+            max_depth: max_depth.parse().unwrap_or(128),
+        }
+    }
+
+    async fn canonical_hash<P: JsonRpcClient>(
+        client: &Provider<P>,
+        block_no: u64,
+    ) -> anyhow::Result<Option<(H256, H256)>> {
+        let block = client.get_block(block_no).await?;
+        Ok(block.and_then(|block| Some((block.hash?, block.parent_hash))))
+    }
+
+    /// Check `block_no` against the recorded ancestry and record its hash.
+    /// Returns the first block that has to be re-proved when a reorg replaced
+    /// already processed blocks.
+    pub async fn check<P: JsonRpcClient>(
+        &mut self,
+        client: &Provider<P>,
+        block_no: u64,
+    ) -> anyhow::Result<Option<u64>> {
+        let Some((hash, parent_hash)) = Self::canonical_hash(client, block_no).await? else {
+            // Not mined yet, fetching the block reports it.
+            return Ok(None);
+        };
+        let parent_no = block_no.saturating_sub(1);
+        let fork = match self.hashes.get(&parent_no) {
+            Some(recorded) if *recorded != parent_hash => {
+                Some(self.find_fork_point(client, parent_no).await?)
+            }
+            _ => None,
+        };
+        match fork {
+            Some(fork) => {
+                self.hashes.split_off(&fork);
+            }
+            None => {
+                self.hashes.insert(block_no, hash);
+                while self.hashes.len() as u64 > self.max_depth {
+                    self.hashes.pop_first();
+                }
+            }
+        }
+        Ok(fork)
+    }
+
+    /// Walk back from `block_no` until the recorded hash is canonical again.
+    /// Returns the first non-canonical block.
+    async fn find_fork_point<P: JsonRpcClient>(
+        &self,
+        client: &Provider<P>,
+        block_no: u64,
+    ) -> anyhow::Result<u64> {
+        let mut fork = block_no;
+        for (&n, recorded) in self.hashes.range(..=block_no).rev() {
+            let canonical = Self::canonical_hash(client, n).await?.map(|(hash, _)| hash);
+            if canonical == Some(*recorded) {
+                break;
+            }
+            fork = n;
+        }
+        Ok(fork)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_trait::async_trait;
+    use ethers_core::types::Block;
+    use ethers_providers::MockError;
+    use serde::{de::DeserializeOwned, Serialize};
+    use std::fmt::Debug;
+    use std::sync::Mutex;
+
+    /// Serves `eth_getBlockByNumber` from blocks mined in memory, a fork
+    /// replaces the blocks from its first one on.
+    #[derive(Debug, Default)]
+    struct MockChain {
+        blocks: Mutex<BTreeMap<u64, Block<H256>>>,
+    }
+
+    impl MockChain {
+        fn mine(&self, from: u64, to: u64, fork: u64) {
+            let mut blocks = self.blocks.lock().unwrap();
+            for n in from..=to {
+                let parent_hash = n
+                    .checked_sub(1)
+                    .and_then(|parent| blocks.get(&parent)?.hash)
+                    .unwrap_or_default();
+                let block = Block {
+                    number: Some(n.into()),
+                    hash: Some(H256::from_low_u64_be(fork << 32 | n)),
+                    parent_hash,
+                    ..Default::default()
+                };
+                blocks.insert(n, block);
+            }
+        }
+    }
+
+    #[async_trait]
+    impl JsonRpcClient for MockChain {
+        type Error = MockError;
+
+        async fn request<T, R>(&self, method: &str, params: T) -> Result<R, Self::Error>
+        where
+            T: Debug + Serialize + Send + Sync,
+            R: DeserializeOwned + Send,
+        {
+            assert_eq!(method, "eth_getBlockByNumber");
+            let params = serde_json::to_value(params)?;
+            let number = params[0].as_str().unwrap().trim_start_matches("0x");
+            let block_no = u64::from_str_radix(number, 16).unwrap();
+            let block = self.blocks.lock().unwrap().get(&block_no).cloned();
+            Ok(serde_json::from_value(serde_json::to_value(block)?)?)
+        }
+    }
+
+    fn detector(max_depth: u64) -> ReorgDetector {
+        ReorgDetector {
+            hashes: BTreeMap::new(),
+            max_depth,
+        }
+    }
+
+    #[tokio::test]
+    async fn linear_chain() {
+        let client = Provider::new(MockChain::default());
+        client.as_ref().mine(0, 10, 0);
+        let mut reorg = detector(128);
+        for block_no in 1..=10 {
+            assert_eq!(reorg.check(&client, block_no).await.unwrap(), None);
+        }
+        // Not mined yet.
+        assert_eq!(reorg.check(&client, 11).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn reorg_restarts_from_the_fork_point() {
+        let client = Provider::new(MockChain::default());
+        client.as_ref().mine(0, 10, 0);
+        let mut reorg = detector(128);
+        for block_no in 1..=8 {
+            assert_eq!(reorg.check(&client, block_no).await.unwrap(), None);
+        }
+        client.as_ref().mine(6, 12, 1);
+        assert_eq!(reorg.check(&client, 9).await.unwrap(), Some(6));
+        // The replaced blocks are proved again on the new chain.
+        for block_no in 6..=12 {
+            assert_eq!(reorg.check(&client, block_no).await.unwrap(), None);
+        }
+    }
+
+    #[tokio::test]
+    async fn reorg_deeper_than_the_recorded_hashes() {
+        let client = Provider::new(MockChain::default());
+        client.as_ref().mine(0, 10, 0);
+        let mut reorg = detector(4);
+        for block_no in 1..=8 {
+            assert_eq!(reorg.check(&client, block_no).await.unwrap(), None);
+        }
+        client.as_ref().mine(2, 10, 1);
+        // Only 5..=8 are recorded, the oldest of them is the fork point.
+        assert_eq!(reorg.check(&client, 9).await.unwrap(), Some(5));
+    }
+}
@@ -0,0 +1,125 @@
+use crate::atomic_file;
+use crate::error::ProverError;
+use crate::naming;
+use crate::verify::{self, ProofStatus, ProofVerifier};
+use futures::stream::{self, StreamExt};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// Proofs of a directory sorted by their verification against the vk.
+#[derive(Debug, Default, Serialize)]
+pub struct RevalidateReport {
+    pub valid: Vec<ProofEntry>,
+    pub invalid: Vec<ProofEntry>,
+    pub unreadable: Vec<ProofEntry>,
+    /// Proofs modified after `older_than`, not verified.
+    pub skipped: usize,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ProofEntry {
+    pub file: String,
+    pub first_block: Option<u64>,
+    pub last_block: Option<u64>,
+    pub error: Option<String>,
+    /// Where an invalid proof was moved with `--quarantine`.
+    pub quarantined: Option<String>,
+}
+
+/// Options of `revalidate`, from the arguments of the subcommand.
+#[derive(Debug, Clone)]
+pub struct RevalidateOptions {
+    /// Verify only proofs last modified before this time.
+    pub older_than: Option<SystemTime>,
+    /// Move invalid proofs to `{dir}/quarantine`.
+    pub quarantine: bool,
+    pub jobs: usize,
+    pub report_path: PathBuf,
+}
+
+/// Verify the proofs in `dir` with `jobs` verifier processes at a time,
+/// print a line per proof and write the report to `report_path`.
+pub async fn revalidate(
+    verifier: &ProofVerifier,
+    dir: &Path,
+    options: &RevalidateOptions,
+) -> anyhow::Result<RevalidateReport> {
+    let mut report = RevalidateReport::default();
+    let mut proofs = Vec::new();
+    for path in verify::proof_files(dir)? {
+        match std::fs::metadata(&path).and_then(|metadata| metadata.modified()) {
+            Ok(modified)
+                if options
+                    .older_than
+                    .is_some_and(|older_than| modified >= older_than) =>
+            {
+                report.skipped += 1;
+            }
+            Ok(_) => proofs.push(path),
+            Err(e) => report.unreadable.push(entry(&path, Some(e.to_string()))),
+        }
+    }
+
+    let statuses = stream::iter(proofs)
+        .map(|path| async move {
+            let status = verifier.status(&path).await;
+            (path, status)
+        })
+        .buffered(options.jobs.max(1))
+        .collect::<Vec<_>>()
+        .await;
+
+    let quarantine_dir = dir.join("quarantine");
+    for (path, status) in statuses {
+        match status? {
+            ProofStatus::Valid => {
+                println!("{}\tvalid", path.display());
+                report.valid.push(entry(&path, None));
+            }
+            ProofStatus::Unreadable(e) => {
+                println!("{}\tunreadable\t{}", path.display(), e);
+                report.unreadable.push(entry(&path, Some(e)));
+            }
+            ProofStatus::Invalid(e) => {
+                println!("{}\tINVALID\t{}", path.display(), e);
+                let mut invalid = entry(&path, Some(e));
+                if options.quarantine {
+                    let file_name = path.file_name().unwrap_or_default();
+                    let target = quarantine_dir.join(file_name);
+                    std::fs::create_dir_all(&quarantine_dir)
+                        .and_then(|_| std::fs::rename(&path, &target))
+                        .map_err(|e| ProverError::io(&target, e))?;
+                    invalid.quarantined = Some(target.display().to_string());
+                }
+                report.invalid.push(invalid);
+            }
+        }
+    }
+
+    atomic_file::write(&options.report_path, serde_json::to_vec_pretty(&report)?)
+        .map_err(|e| ProverError::io(&options.report_path, e))?;
+    println!(
+        "valid: {} invalid: {} unreadable: {} skipped: {}, report written to {}",
+        report.valid.len(),
+        report.invalid.len(),
+        report.unreadable.len(),
+        report.skipped,
+        options.report_path.display()
+    );
+    Ok(report)
+}
+
+fn entry(path: &Path, error: Option<String>) -> ProofEntry {
+    let blocks = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .and_then(|name| naming::template().block_range(name));
+    ProofEntry {
+        file: path.display().to_string(),
+        first_block: blocks.map(|(first, _)| first),
+        last_block: blocks.map(|(_, last)| last),
+        error,
+        quarantined: None,
+    }
+}
@@ -0,0 +1,629 @@
+use crate::metrics::METRICS;
+use crate::ratelimit::{self, RateLimiter};
+use async_trait::async_trait;
+use ethers_core::types::U64;
+use ethers_providers::{
+    Http, HttpClientError, JsonRpcClient, JsonRpcError, Middleware, Provider, ProviderError,
+    RpcError, Ws, WsClientError,
+};
+use futures::StreamExt;
+use serde::{de::DeserializeOwned, Serialize};
+use std::env;
+use std::fmt::Debug;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, OnceLock, RwLock};
+use std::time::{Duration, Instant};
+use tokio::sync::watch;
+
+/// Attempts to re-establish a dropped websocket connection before giving up
+/// until a request finds it dead again.
+const WS_RECONNECTS: u32 = 10;
+
+/// Delay before the first reconnect attempt, doubled for every further one
+/// up to WS_RECONNECT_MAX_BACKOFF.
+const WS_RECONNECT_BACKOFF: Duration = Duration::from_secs(1);
+const WS_RECONNECT_MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+/// Delay before reconnect attempt `attempt` (1-based).
+fn reconnect_backoff(attempt: u32) -> Duration {
+    let factor = 1u32 << attempt.saturating_sub(1).min(16);
+    WS_RECONNECT_BACKOFF
+        .saturating_mul(factor)
+        .min(WS_RECONNECT_MAX_BACKOFF)
+}
+
+/// Throttled calls retried on the same endpoint after the cool-down.
+const THROTTLED_RETRIES: usize = 3;
+
+/// JSON-RPC transport selected from the scheme of RPC_URL.
+#[derive(Debug, Clone)]
+pub enum RpcClient {
+    Http(Http),
+    Ws(ReconnectingWs),
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum RpcClientError {
+    #[error(transparent)]
+    Http(#[from] HttpClientError),
+    #[error(transparent)]
+    Ws(#[from] WsClientError),
+    #[error("request to {0} timed out")]
+    Timeout(String),
+    #[error(transparent)]
+    Serde(#[from] serde_json::Error),
+}
+
+impl RpcError for RpcClientError {
+    fn as_error_response(&self) -> Option<&JsonRpcError> {
+        match self {
+            Self::Http(e) => e.as_error_response(),
+            Self::Ws(e) => e.as_error_response(),
+            Self::Timeout(_) | Self::Serde(_) => None,
+        }
+    }
+
+    fn as_serde_error(&self) -> Option<&serde_json::Error> {
+        match self {
+            Self::Http(e) => e.as_serde_error(),
+            Self::Ws(e) => e.as_serde_error(),
+            Self::Serde(e) => Some(e),
+            Self::Timeout(_) => None,
+        }
+    }
+}
+
+impl From<RpcClientError> for ProviderError {
+    fn from(e: RpcClientError) -> Self {
+        ProviderError::JsonRpcClientError(Box::new(e))
+    }
+}
+
+#[async_trait]
+impl JsonRpcClient for RpcClient {
+    type Error = RpcClientError;
+
+    async fn request<T, R>(&self, method: &str, params: T) -> Result<R, Self::Error>
+    where
+        T: Debug + Serialize + Send + Sync,
+        R: DeserializeOwned + Send,
+    {
+        match self {
+            Self::Http(http) => Ok(http.request(method, params).await?),
+            Self::Ws(ws) => Ok(ws.request(method, params).await?),
+        }
+    }
+}
+
+/// A websocket connection that is re-established in the background, with
+/// exponential backoff between the attempts, once a request finds it dead.
+/// New heads come from one subscription kept for the life of the process.
+#[derive(Debug, Clone)]
+pub struct ReconnectingWs {
+    url: String,
+    ws: Arc<RwLock<Ws>>,
+    reconnecting: Arc<AtomicBool>,
+    heads: Arc<OnceLock<watch::Receiver<Option<U64>>>>,
+}
+
+impl ReconnectingWs {
+    pub async fn connect(url: &str) -> Result<Self, WsClientError> {
+        Ok(Self {
+            url: url.to_string(),
+            ws: Arc::new(RwLock::new(Ws::connect(url).await?)),
+            reconnecting: Arc::new(AtomicBool::new(false)),
+            heads: Arc::new(OnceLock::new()),
+        })
+    }
+
+    fn current(&self) -> Ws {
+        self.ws.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    async fn request<T, R>(&self, method: &str, params: T) -> Result<R, WsClientError>
+    where
+        T: Debug + Serialize + Send + Sync,
+        R: DeserializeOwned + Send,
+    {
+        let result = self.current().request(method, params).await;
+        if let Err(WsClientError::UnexpectedClose | WsClientError::DeadChannel) = &result {
+            self.spawn_reconnect();
+        }
+        result
+    }
+
+    /// Start re-establishing the connection unless that is already under way.
+    fn spawn_reconnect(&self) {
+        if self.reconnecting.swap(true, Ordering::AcqRel) {
+            return;
+        }
+        let ws = self.clone();
+        tokio::spawn(async move {
+            ws.reconnect().await;
+            ws.reconnecting.store(false, Ordering::Release);
+        });
+    }
+
+    async fn reconnect(&self) {
+        for attempt in 1..=WS_RECONNECTS {
+            let backoff = reconnect_backoff(attempt);
+            log::warn!(
+                "Websocket {} dropped, reconnecting in {} secs (attempt {}/{})",
+                self.url,
+                backoff.as_secs(),
+                attempt,
+                WS_RECONNECTS
+            );
+            tokio::time::sleep(backoff).await;
+            match Ws::connect(self.url.as_str()).await {
+                Ok(ws) => {
+                    *self.ws.write().unwrap_or_else(|e| e.into_inner()) = ws;
+                    log::info!("Websocket {} reconnected", self.url);
+                    return;
+                }
+                Err(e) => log::warn!("Failed to reconnect to {}: {}", self.url, e),
+            }
+        }
+        log::error!(
+            "Giving up reconnecting to {} after {} attempts",
+            self.url,
+            WS_RECONNECTS
+        );
+    }
+
+    /// The number of the latest head, changed by every new head. The
+    /// subscription is made on first use.
+    fn heads(&self) -> watch::Receiver<Option<U64>> {
+        self.heads
+            .get_or_init(|| {
+                let (sender, receiver) = watch::channel(None);
+                tokio::spawn(self.clone().follow_heads(sender));
+                receiver
+            })
+            .clone()
+    }
+
+    /// Keep a new heads subscription open, subscribing again with backoff
+    /// when it ends or cannot be made.
+    async fn follow_heads(self, heads: watch::Sender<Option<U64>>) {
+        let mut failures = 0;
+        loop {
+            let provider = Provider::new(self.current());
+            match provider.subscribe_blocks().await {
+                Ok(mut stream) => {
+                    failures = 0;
+                    while let Some(head) = stream.next().await {
+                        log::debug!("New head: {:?}", head.number);
+                        heads.send_replace(head.number);
+                    }
+                    log::warn!("Head subscription to {} ended", self.url);
+                }
+                Err(e) => {
+                    log::warn!("Failed to subscribe to new heads of {}: {}", self.url, e);
+                    self.spawn_reconnect();
+                }
+            }
+            failures += 1;
+            tokio::time::sleep(reconnect_backoff(failures)).await;
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Endpoint {
+    url: String,
+    client: RpcClient,
+    /// When the endpoint last failed, it is skipped until the cooldown passes.
+    failed_at: Mutex<Option<Instant>>,
+    /// When the endpoint last answered a head below the highest one seen, it
+    /// is asked for the head last until the cooldown passes.
+    behind_at: Mutex<Option<Instant>>,
+}
+
+impl Endpoint {
+    fn is_healthy(&self, cooldown: Duration) -> bool {
+        let failed_at = self.failed_at.lock().unwrap_or_else(|e| e.into_inner());
+        failed_at.map_or(true, |at| at.elapsed() >= cooldown)
+    }
+
+    fn set_failed(&self, failed: bool) {
+        let mut failed_at = self.failed_at.lock().unwrap_or_else(|e| e.into_inner());
+        *failed_at = failed.then(Instant::now);
+    }
+
+    fn is_behind(&self, cooldown: Duration) -> bool {
+        let behind_at = self.behind_at.lock().unwrap_or_else(|e| e.into_inner());
+        behind_at.is_some_and(|at| at.elapsed() < cooldown)
+    }
+
+    fn set_behind(&self, behind: bool) {
+        let mut behind_at = self.behind_at.lock().unwrap_or_else(|e| e.into_inner());
+        *behind_at = behind.then(Instant::now);
+    }
+}
+
+/// Fails over between the endpoints of a comma-separated RPC_URL. A request
+/// goes to the first healthy endpoint; an endpoint that errors or times out
+/// is skipped for RPC_COOLDOWN_SECS. Every request waits for the shared
+/// rate limiter first.
+///
+/// Endpoints at different sync heights must not move the head backwards:
+/// `eth_blockNumber` never answers below the highest head seen, an endpoint
+/// answering lower is behind, not rolled back. Reorgs are detected from
+/// parent hashes, not from head numbers.
+#[derive(Debug, Clone)]
+pub struct FailoverClient {
+    endpoints: Arc<Vec<Endpoint>>,
+    timeout: Duration,
+    cooldown: Duration,
+    last_used: Arc<AtomicUsize>,
+    limiter: RateLimiter,
+    /// Highest head any endpoint answered.
+    max_head: Arc<AtomicU64>,
+}
+
+impl FailoverClient {
+    /// Url of the endpoint that served the last successful request.
+    pub fn last_endpoint(&self) -> &str {
+        &self.endpoints[self.last_used.load(Ordering::Relaxed)].url
+    }
+
+    /// The websocket transport of the first healthy endpoint, if it is one.
+    fn ws(&self) -> Option<&ReconnectingWs> {
+        let endpoint = self
+            .endpoints
+            .iter()
+            .find(|endpoint| endpoint.is_healthy(self.cooldown))?;
+        match &endpoint.client {
+            RpcClient::Ws(ws) => Some(ws),
+            RpcClient::Http(_) => None,
+        }
+    }
+
+    /// Healthy endpoints first, endpoints in cooldown only as a last resort.
+    /// For a head query, endpoints that were behind come after both.
+    fn ordered(&self, head_query: bool) -> Vec<(usize, &Endpoint)> {
+        let mut endpoints: Vec<_> = self.endpoints.iter().enumerate().collect();
+        endpoints.sort_by_key(|(_, endpoint)| {
+            (
+                head_query && endpoint.is_behind(self.cooldown),
+                !endpoint.is_healthy(self.cooldown),
+            )
+        });
+        endpoints
+    }
+
+    /// Send the request to `endpoints` in turn until one answers and
+    /// `accept` takes its answer. `None` if every answer was rejected.
+    async fn send_in_order<T, R>(
+        &self,
+        endpoints: Vec<(usize, &Endpoint)>,
+        method: &str,
+        params: &T,
+        accept: impl Fn(&Endpoint, &R) -> bool + Send + Sync,
+    ) -> Result<Option<R>, RpcClientError>
+    where
+        T: Debug + Serialize + Send + Sync,
+        R: DeserializeOwned + Send,
+    {
+        let mut last_error = None;
+        let mut rejected = false;
+        for (i, endpoint) in endpoints {
+            let mut throttled = 0;
+            let result = loop {
+                self.limiter.acquire().await;
+                let result =
+                    tokio::time::timeout(self.timeout, endpoint.client.request(method, params))
+                        .await
+                        .unwrap_or_else(|_| Err(RpcClientError::Timeout(endpoint.url.clone())));
+                match &result {
+                    Err(e) if ratelimit::is_rate_limited(&e.to_string()) => {
+                        METRICS.rpc_throttled_total.inc();
+                        self.limiter.throttled();
+                        throttled += 1;
+                        if throttled > THROTTLED_RETRIES {
+                            break result;
+                        }
+                        log::warn!("RPC {} to {} throttled, cooling down", method, endpoint.url);
+                    }
+                    _ => break result,
+                }
+            };
+            match result {
+                Ok(response) => {
+                    endpoint.set_failed(false);
+                    if !accept(endpoint, &response) {
+                        rejected = true;
+                        continue;
+                    }
+                    self.last_used.store(i, Ordering::Relaxed);
+                    return Ok(Some(response));
+                }
+                // The node answered, another one would give the same answer.
+                Err(e) if e.as_error_response().is_some() => return Err(e),
+                Err(e) => {
+                    log::warn!("RPC {} to {} failed: {}", method, endpoint.url, e);
+                    endpoint.set_failed(true);
+                    last_error = Some(e);
+                }
+            }
+        }
+        match last_error {
+            Some(e) if !rejected => Err(e),
+            _ => Ok(None),
+        }
+    }
+
+    /// `eth_blockNumber` from the first endpoint that is not behind the
+    /// highest head seen, or that head if every endpoint is.
+    async fn block_number<T>(&self, params: &T) -> Result<U64, RpcClientError>
+    where
+        T: Debug + Serialize + Send + Sync,
+    {
+        let max_head = self.max_head.load(Ordering::Relaxed);
+        let accept = |endpoint: &Endpoint, head: &U64| {
+            let behind = head.as_u64() < max_head;
+            if behind && !endpoint.is_behind(self.cooldown) {
+                log::warn!(
+                    "RPC endpoint {} is behind: head {}, {} seen before",
+                    endpoint.url,
+                    head,
+                    max_head
+                );
+            }
+            endpoint.set_behind(behind);
+            !behind
+        };
+        let head = self
+            .send_in_order(self.ordered(true), "eth_blockNumber", params, accept)
+            .await?;
+        Ok(match head {
+            Some(head) => {
+                let head = head.as_u64();
+                U64::from(self.max_head.fetch_max(head, Ordering::Relaxed).max(head))
+            }
+            None => U64::from(max_head),
+        })
+    }
+}
+
+#[async_trait]
+impl JsonRpcClient for FailoverClient {
+    type Error = RpcClientError;
+
+    async fn request<T, R>(&self, method: &str, params: T) -> Result<R, Self::Error>
+    where
+        T: Debug + Serialize + Send + Sync,
+        R: DeserializeOwned + Send,
+    {
+        if method == "eth_blockNumber" {
+            let head = self.block_number(&params).await?;
+            return Ok(serde_json::from_value(serde_json::to_value(head)?)?);
+        }
+        let response = self
+            .send_in_order(self.ordered(false), method, &params, |_, _: &R| true)
+            .await?;
+        Ok(response.expect("every answer is accepted"))
+    }
+}
+
+/// Connect to every url of the comma-separated `urls`, using a websocket
+/// transport for `ws://` and `wss://` urls and HTTP otherwise.
+pub async fn connect(urls: &str) -> anyhow::Result<Provider<FailoverClient>> {
+    let mut endpoints = Vec::new();
+    for url in urls.split(',').map(str::trim).filter(|url| !url.is_empty()) {
+        let client = if url.starts_with("ws://") || url.starts_with("wss://") {
+            RpcClient::Ws(ReconnectingWs::connect(url).await?)
+        } else {
+            RpcClient::Http(url.parse()?)
+        };
+        endpoints.push(Endpoint {
+            url: url.to_string(),
+            client,
+            failed_at: Mutex::new(None),
+            behind_at: Mutex::new(None),
+        });
+    }
+    if endpoints.is_empty() {
+        anyhow::bail!("RPC_URL is empty");
+    }
+    let timeout_secs = env::var("RPC_TIMEOUT_SECS").unwrap_or("30".to_string());
+    let cooldown_secs = env::var("RPC_COOLDOWN_SECS").unwrap_or("60".to_string());
+    Ok(Provider::new(FailoverClient {
+        endpoints: Arc::new(endpoints),
+        timeout: Duration::from_secs(timeout_secs.parse().unwrap_or(30)),
+        cooldown: Duration::from_secs(cooldown_secs.parse().unwrap_or(60)),
+        last_used: Arc::new(AtomicUsize::new(0)),
+        limiter: RateLimiter::from_env(),
+        max_head: Arc::new(AtomicU64::new(0)),
+    }))
+}
+
+/// Wait until a new head arrives or `timeout` elapses. Websocket providers
+/// wait on their new heads subscription, HTTP providers simply sleep.
+pub async fn wait_for_new_head(provider: &Provider<FailoverClient>, timeout: Duration) {
+    let Some(ws) = provider.as_ref().ws() else {
+        tokio::time::sleep(timeout).await;
+        return;
+    };
+    let mut heads = ws.heads();
+    heads.borrow_and_update();
+    let _ = tokio::time::timeout(timeout, heads.changed()).await;
+}
+
+/// Why fetching a block failed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FetchErrorKind {
+    /// The node could not be reached at all.
+    Connection,
+    /// Anything else, usually a block that does not exist yet.
+    NotReady,
+}
+
+impl FetchErrorKind {
+    pub fn classify(e: &anyhow::Error) -> Self {
+        let refused = e.chain().any(|cause| {
+            cause.downcast_ref::<std::io::Error>().is_some_and(|e| {
+                matches!(
+                    e.kind(),
+                    std::io::ErrorKind::ConnectionRefused
+                        | std::io::ErrorKind::ConnectionReset
+                        | std::io::ErrorKind::ConnectionAborted
+                )
+            })
+        });
+        let message = format!("{:#}", e).to_lowercase();
+        if refused || message.contains("connection refused") || message.contains("tcp connect") {
+            Self::Connection
+        } else {
+            Self::NotReady
+        }
+    }
+}
+
+/// Retry budget for fetching a block, configured by FETCH_RETRY_SECS,
+/// FETCH_MAX_RETRIES, FETCH_CONN_MAX_RETRIES, MAX_WAIT_FOR_BLOCK_SECS (or
+/// FETCH_MAX_WAIT_SECS) and MAX_AHEAD.
+#[derive(Debug, Clone)]
+pub struct FetchRetry {
+    pub retry_interval: Duration,
+    max_retries: u32,
+    /// Budget when the node is unreachable, shorter than waiting for a block.
+    conn_max_retries: u32,
+    max_wait: Duration,
+    /// How far beyond the chain head a block may be before waiting for it is
+    /// pointless.
+    pub max_ahead: u64,
+}
+
+impl FetchRetry {
+    pub fn from_env() -> Self {
+        let retry_secs = env::var("FETCH_RETRY_SECS").unwrap_or("10".to_string());
+        let max_retries = env::var("FETCH_MAX_RETRIES").unwrap_or("60".to_string());
+        let conn_max_retries = env::var("FETCH_CONN_MAX_RETRIES").unwrap_or("5".to_string());
+        let max_wait_secs = env::var("MAX_WAIT_FOR_BLOCK_SECS")
+            .or_else(|_| env::var("FETCH_MAX_WAIT_SECS"))
+            .unwrap_or("1800".to_string());
+        let max_ahead = env::var("MAX_AHEAD").unwrap_or("1000".to_string());
+        Self {
+            retry_interval: Duration::from_secs(retry_secs.parse().unwrap_or(10)),
+            max_retries: max_retries.parse().unwrap_or(60),
+            conn_max_retries: conn_max_retries.parse().unwrap_or(5),
+            max_wait: Duration::from_secs(max_wait_secs.parse().unwrap_or(1800)),
+            max_ahead: max_ahead.parse().unwrap_or(1000),
+        }
+    }
+
+    /// Whether `attempts` failed fetches over `waited` exhaust the budget for `kind`.
+    pub fn exhausted(&self, kind: FetchErrorKind, attempts: u32, waited: Duration) -> bool {
+        let max_retries = match kind {
+            FetchErrorKind::Connection => self.conn_max_retries,
+            FetchErrorKind::NotReady => self.max_retries,
+        };
+        attempts > max_retries || waited >= self.max_wait
+    }
+
+    /// Whether waiting for a block ahead of the head by `ahead` blocks for
+    /// `waited` should be given up.
+    pub fn gives_up_waiting(&self, ahead: u64, waited: Duration) -> bool {
+        ahead > self.max_ahead || waited >= self.max_wait
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::MockServer;
+    use serde_json::json;
+
+    #[test]
+    fn reconnect_backoff_doubles_up_to_the_maximum() {
+        let backoffs: Vec<_> = (1..=8).map(|a| reconnect_backoff(a).as_secs()).collect();
+        assert_eq!(backoffs, vec![1, 2, 4, 8, 16, 32, 60, 60]);
+        assert_eq!(reconnect_backoff(u32::MAX), WS_RECONNECT_MAX_BACKOFF);
+    }
+
+    #[tokio::test]
+    async fn lagging_endpoint_never_moves_the_head_back() {
+        let synced = MockServer::rpc(vec![
+            Some(json!("0xa")),
+            None,
+            Some(json!("0xc")),
+            None,
+            Some(json!("0xd")),
+        ]);
+        let lagging = MockServer::rpc(vec![Some(json!("0x5")), Some(json!("0x6"))]);
+        let provider = connect(&format!("{},{}", synced.url, lagging.url))
+            .await
+            .unwrap();
+        let mut heads = Vec::new();
+        for _ in 0..5 {
+            heads.push(provider.get_block_number().await.unwrap().as_u64());
+        }
+        assert_eq!(heads, vec![10, 10, 12, 12, 13]);
+        // The failed synced endpoint is still asked before the one behind.
+        assert_eq!(synced.bodies().len(), 5);
+        assert_eq!(lagging.bodies().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn failing_endpoint_fails_over_to_the_next() {
+        let failing = MockServer::rpc(vec![None]);
+        let healthy = MockServer::rpc(vec![Some(json!("0x1"))]);
+        let provider = connect(&format!("{},{}", failing.url, healthy.url))
+            .await
+            .unwrap();
+        assert_eq!(provider.get_chainid().await.unwrap().as_u64(), 1);
+        assert_eq!(provider.as_ref().last_endpoint(), healthy.url);
+        // The failed endpoint sits out its cooldown.
+        provider.get_chainid().await.unwrap();
+        assert_eq!(failing.bodies().len(), 1);
+        assert_eq!(healthy.bodies().len(), 2);
+    }
+
+    #[test]
+    fn refused_connections_are_told_from_missing_blocks() {
+        let refused =
+            anyhow::Error::new(std::io::Error::from(std::io::ErrorKind::ConnectionRefused))
+                .context("failed to fetch block");
+        assert_eq!(
+            FetchErrorKind::classify(&refused),
+            FetchErrorKind::Connection
+        );
+        let message = anyhow::anyhow!("error sending request: tcp connect error");
+        assert_eq!(
+            FetchErrorKind::classify(&message),
+            FetchErrorKind::Connection
+        );
+        let missing = anyhow::anyhow!("block 12 not found");
+        assert_eq!(FetchErrorKind::classify(&missing), FetchErrorKind::NotReady);
+    }
+
+    #[test]
+    fn fetch_retry_budget() {
+        let retry = FetchRetry {
+            retry_interval: Duration::from_secs(10),
+            max_retries: 60,
+            conn_max_retries: 5,
+            max_wait: Duration::from_secs(1800),
+            max_ahead: 1000,
+        };
+        let short = Duration::from_secs(60);
+        assert!(!retry.exhausted(FetchErrorKind::Connection, 5, short));
+        assert!(retry.exhausted(FetchErrorKind::Connection, 6, short));
+        assert!(!retry.exhausted(FetchErrorKind::NotReady, 60, short));
+        assert!(retry.exhausted(FetchErrorKind::NotReady, 61, short));
+        assert!(retry.exhausted(FetchErrorKind::NotReady, 1, retry.max_wait));
+        assert!(!retry.gives_up_waiting(1000, short));
+        assert!(retry.gives_up_waiting(1001, short));
+        assert!(retry.gives_up_waiting(1, retry.max_wait));
+    }
+
+    #[tokio::test]
+    async fn http_providers_wait_out_the_timeout() {
+        let provider = connect("http://127.0.0.1:1").await.unwrap();
+        let start = Instant::now();
+        wait_for_new_head(&provider, Duration::from_millis(50)).await;
+        assert!(start.elapsed() >= Duration::from_millis(50));
+    }
+}
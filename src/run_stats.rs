@@ -0,0 +1,149 @@
+use crate::summary::BlockSummary;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Instant;
+
+const RUN_STATS_FILE: &str = "run_stats.csv";
+const HEADER: &str =
+    "block_no,tx_count,suite_bytes,check_micros,prove_secs,seg_size,proof_bytes,status";
+
+#[derive(Debug, Default)]
+struct Totals {
+    blocks: u64,
+    proved: u64,
+    txs: u64,
+    prove_secs: u64,
+    proof_bytes: u64,
+}
+
+/// Per-block statistics of the current run in `{OUTPUT_DIR}/run_stats.csv`.
+/// Every row is appended as soon as its block is done, so a crash loses
+/// nothing.
+#[derive(Debug)]
+pub struct RunStatsCsv {
+    path: PathBuf,
+    started: Instant,
+    totals: Mutex<Totals>,
+}
+
+impl RunStatsCsv {
+    /// Start a new file for this run, replacing the one of a previous run.
+    pub fn create(outdir: &str) -> anyhow::Result<Self> {
+        let path = Path::new(outdir).join(RUN_STATS_FILE);
+        std::fs::write(&path, format!("{}\n", HEADER))?;
+        Ok(Self {
+            path,
+            started: Instant::now(),
+            totals: Mutex::new(Totals::default()),
+        })
+    }
+
+    pub fn append(&self, summary: &BlockSummary) -> anyhow::Result<()> {
+        let row = format!(
+            "{},{},{},{},{},{},{},{}\n",
+            summary.block_no,
+            summary.tx_count,
+            summary.suite_bytes,
+            summary.check_micros,
+            summary.prove_secs,
+            summary.seg_size,
+            summary.proof_bytes,
+            summary.status.as_str()
+        );
+        let mut file = std::fs::OpenOptions::new().append(true).open(&self.path)?;
+        file.write_all(row.as_bytes())?;
+        file.flush()?;
+
+        let mut totals = self.totals.lock().unwrap_or_else(|e| e.into_inner());
+        totals.blocks += 1;
+        totals.txs += summary.tx_count as u64;
+        totals.proof_bytes += summary.proof_bytes;
+        if summary.proof_bytes > 0 {
+            totals.proved += 1;
+            totals.prove_secs += summary.prove_secs;
+        }
+        Ok(())
+    }
+
+    /// Log totals, the average prove time and the throughput of the run.
+    pub fn log_footer(&self) {
+        let totals = self.totals.lock().unwrap_or_else(|e| e.into_inner());
+        let hours = self.started.elapsed().as_secs_f64() / 3600.0;
+        let avg_prove_secs = if totals.proved > 0 {
+            totals.prove_secs as f64 / totals.proved as f64
+        } else {
+            0.0
+        };
+        let blocks_per_hour = if hours > 0.0 {
+            totals.blocks as f64 / hours
+        } else {
+            0.0
+        };
+        log::info!(
+            "Run stats: blocks:{} proved:{} txs:{} proof_bytes:{} avg_prove_secs:{:.1} blocks/hour:{:.2} csv:{}",
+            totals.blocks,
+            totals.proved,
+            totals.txs,
+            totals.proof_bytes,
+            avg_prove_secs,
+            blocks_per_hour,
+            self.path.display()
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::summary::BlockStatus;
+
+    #[test]
+    fn rows_parse_back() {
+        let dir = std::env::temp_dir().join(format!("run-stats-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let csv = RunStatsCsv::create(dir.to_str().unwrap()).unwrap();
+
+        let mut proved = BlockSummary::new(1200, BlockStatus::Proved);
+        proved.tx_count = 3;
+        proved.suite_bytes = 48211;
+        proved.check_micros = 5120;
+        proved.prove_secs = 612;
+        proved.seg_size = 262144;
+        proved.proof_bytes = 1934;
+        csv.append(&proved).unwrap();
+        let mut failed = BlockSummary::new(1201, BlockStatus::Failed);
+        failed.tx_count = 5;
+        csv.append(&failed).unwrap();
+
+        let content = std::fs::read_to_string(dir.join(RUN_STATS_FILE)).unwrap();
+        let rows: Vec<Vec<&str>> = content
+            .lines()
+            .map(|line| line.split(',').collect())
+            .collect();
+        assert_eq!(
+            rows[0],
+            [
+                "block_no",
+                "tx_count",
+                "suite_bytes",
+                "check_micros",
+                "prove_secs",
+                "seg_size",
+                "proof_bytes",
+                "status"
+            ]
+        );
+        assert_eq!(
+            rows[1],
+            ["1200", "3", "48211", "5120", "612", "262144", "1934", "proved"]
+        );
+        assert_eq!(rows[2], ["1201", "5", "0", "0", "0", "0", "0", "failed"]);
+        assert_eq!(rows.len(), 3);
+
+        let totals = csv.totals.lock().unwrap();
+        assert_eq!((totals.blocks, totals.proved, totals.txs), (2, 1, 8));
+        assert_eq!(totals.prove_secs, 612);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,261 @@
+use crate::atomic_file;
+use serde_json::Value;
+use std::path::Path;
+
+/// Current schema version of the JSON artifacts persisted in OUTPUT_DIR. Every
+/// persisted structure carries it in a `schema_version` field.
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// Artifacts written before versioning was introduced have no
+/// `schema_version` field and are version 0.
+pub fn legacy_schema_version() -> u32 {
+    0
+}
+
+/// Suffixes of the per-block JSON artifacts, `_summary.json` also covers the
+/// `{first}_{last}_agg_summary.json` of aggregated proofs.
+const ARTIFACT_SUFFIXES: &[&str] = &["_summary.json", "_execute_report.json", "_meta.json"];
+
+/// Whether `file_name` is a JSON artifact owned by this schema.
+fn is_artifact(file_name: &str) -> bool {
+    file_name == "checkpoint.json"
+        || ARTIFACT_SUFFIXES
+            .iter()
+            .any(|suffix| file_name.ends_with(suffix))
+}
+
+/// Upgrade `value` in place to SCHEMA_VERSION. Returns the version it was
+/// upgraded from, or `None` if it is already current.
+pub fn migrate(value: &mut Value) -> anyhow::Result<Option<u32>> {
+    let Some(object) = value.as_object_mut() else {
+        anyhow::bail!("artifact is not a JSON object");
+    };
+    let version = match object.get("schema_version") {
+        None => legacy_schema_version(),
+        Some(v) => {
+            v.as_u64()
+                .ok_or_else(|| anyhow::anyhow!("invalid schema_version: {}", v))? as u32
+        }
+    };
+    if version > SCHEMA_VERSION {
+        anyhow::bail!(
+            "schema_version {} is newer than the supported {}",
+            version,
+            SCHEMA_VERSION
+        );
+    }
+    if version == SCHEMA_VERSION {
+        return Ok(None);
+    }
+    // v0 -> v1: only the version field was added.
+    object.insert("schema_version".to_string(), Value::from(SCHEMA_VERSION));
+    Ok(Some(version))
+}
+
+/// Migrate every artifact in `dir` to the current schema version, listing the
+/// changes without writing anything when `dry_run` is set.
+pub fn migrate_artifacts(dir: &str, dry_run: bool) -> anyhow::Result<()> {
+    let mut migrated = 0;
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        let file_name = path
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        if !is_artifact(&file_name) {
+            continue;
+        }
+        let buf = std::fs::read(&path)?;
+        let mut value: Value = serde_json::from_slice(&buf)
+            .map_err(|e| anyhow::anyhow!("invalid artifact {}: {}", path.display(), e))?;
+        let Some(from) =
+            migrate(&mut value).map_err(|e| anyhow::anyhow!("{}: {}", path.display(), e))?
+        else {
+            continue;
+        };
+        migrated += 1;
+        if dry_run {
+            println!(
+                "{}: v{} -> v{} (dry run)",
+                path.display(),
+                from,
+                SCHEMA_VERSION
+            );
+            continue;
+        }
+        write_json(&path, &value)?;
+        println!("{}: v{} -> v{}", path.display(), from, SCHEMA_VERSION);
+    }
+    println!("{} artifacts migrated in {}", migrated, dir);
+    Ok(())
+}
+
+fn write_json(path: &Path, value: &Value) -> anyhow::Result<()> {
+    atomic_file::write(path, serde_json::to_vec_pretty(value)?)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::aggregate::AggregateSummary;
+    use crate::checkpoint::Checkpoint;
+    use crate::execution::ExecuteReport;
+    use crate::meta::BlockMeta;
+    use crate::summary::{BlockStatus, BlockSummary};
+    use serde::de::DeserializeOwned;
+    use serde::Serialize;
+
+    macro_rules! fixture {
+        ($path:literal) => {
+            include_str!(concat!(
+                env!("CARGO_MANIFEST_DIR"),
+                "/tests/fixtures/schema/",
+                $path
+            ))
+        };
+    }
+
+    /// Load `json` saved at `version`, migrate it and check that it loads
+    /// and serializes back to the migrated JSON.
+    fn round_trip<T: Serialize + DeserializeOwned>(json: &str, version: u32) -> T {
+        let loaded: T = serde_json::from_str(json).unwrap();
+        let loaded = serde_json::to_value(&loaded).unwrap();
+        assert_eq!(loaded["schema_version"], version);
+
+        let mut value: Value = serde_json::from_str(json).unwrap();
+        let from = migrate(&mut value).unwrap();
+        assert_eq!(from, (version != SCHEMA_VERSION).then_some(version));
+        let migrated: T = serde_json::from_value(value).unwrap();
+        let migrated = serde_json::to_value(&migrated).unwrap();
+        assert_eq!(migrated["schema_version"], SCHEMA_VERSION);
+        // Writing the migrated artifact again loses nothing.
+        let reloaded: T = serde_json::from_value(migrated.clone()).unwrap();
+        assert_eq!(serde_json::to_value(&reloaded).unwrap(), migrated);
+        reloaded
+    }
+
+    #[test]
+    fn v0_checkpoint() {
+        let checkpoint: Checkpoint = round_trip(fixture!("v0/checkpoint.json"), 0);
+        assert_eq!(checkpoint.schema_version, SCHEMA_VERSION);
+        assert_eq!(checkpoint.last_block, 1200);
+        assert!(checkpoint.seg_size_ratios.is_empty());
+    }
+
+    #[test]
+    fn v1_checkpoint() {
+        let checkpoint: Checkpoint = round_trip(fixture!("v1/checkpoint.json"), 1);
+        assert_eq!(checkpoint.last_block, 1203);
+        assert_eq!(checkpoint.seg_size_ratios, vec![1.08, 0.97]);
+    }
+
+    #[test]
+    fn v1_summaries() {
+        // Written before the batch, submit, DA and S3 fields were added.
+        let summary: BlockSummary = round_trip(fixture!("v1/1200_summary.json"), 1);
+        assert_eq!(summary.status, BlockStatus::Proved);
+        assert_eq!(summary.last_block_no, None);
+        assert!(summary.s3_keys.is_empty());
+
+        let summary: BlockSummary = round_trip(fixture!("v1/1201_summary.json"), 1);
+        assert_eq!(summary.status, BlockStatus::Failed);
+        assert_eq!(summary.last_block_no, Some(1202));
+        assert_eq!(summary.pass, 1);
+    }
+
+    #[test]
+    fn v1_execute_report() {
+        let report: ExecuteReport = round_trip(fixture!("v1/1200_execute_report.json"), 1);
+        assert_eq!(report.stats.cycles, Some(9437184));
+        assert_eq!(report.stats.stage_millis["execute"], 40210);
+    }
+
+    #[test]
+    fn v1_aggregate_summary() {
+        let summary: AggregateSummary = round_trip(fixture!("v1/1200_1203_agg_summary.json"), 1);
+        assert_eq!((summary.first, summary.last), (1200, 1203));
+        assert_eq!(summary.proofs.len(), 3);
+    }
+
+    #[test]
+    fn meta() {
+        // Written before BlockMeta was versioned.
+        let meta: BlockMeta = round_trip(fixture!("v0/1200_meta.json"), 0);
+        assert_eq!(meta.block_no, 1200);
+        assert_eq!(meta.excess_blob_gas, Some(0));
+
+        let meta: BlockMeta = round_trip(fixture!("v1/1201_meta.json"), 1);
+        assert_eq!(meta.tx_count, 5);
+        assert_eq!(meta.blob_gas_used, None);
+    }
+
+    #[test]
+    fn migrate_rejects_unknown_versions() {
+        let mut newer = serde_json::json!({ "schema_version": SCHEMA_VERSION + 1 });
+        assert!(migrate(&mut newer).is_err());
+        let mut invalid = serde_json::json!({ "schema_version": "1" });
+        assert!(migrate(&mut invalid).is_err());
+        assert!(migrate(&mut Value::from(1)).is_err());
+    }
+
+    #[test]
+    fn migrate_leaves_current_artifacts_alone() {
+        let json = serde_json::json!({ "schema_version": SCHEMA_VERSION, "block_no": 1200 });
+        let mut value = json.clone();
+        assert_eq!(migrate(&mut value).unwrap(), None);
+        assert_eq!(value, json);
+    }
+
+    #[test]
+    fn migrate_artifacts_rewrites_only_artifacts() {
+        let dir = std::env::temp_dir().join(format!("schema-migrate-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let meta = dir.join("1200_meta.json");
+        let suite = dir.join("1200.json");
+        std::fs::write(&meta, fixture!("v0/1200_meta.json")).unwrap();
+        std::fs::write(&suite, "{}").unwrap();
+        let version = |path: &Path| {
+            let value: Value = serde_json::from_slice(&std::fs::read(path).unwrap()).unwrap();
+            value.get("schema_version").cloned()
+        };
+
+        migrate_artifacts(dir.to_str().unwrap(), true).unwrap();
+        assert_eq!(version(&meta), None);
+
+        migrate_artifacts(dir.to_str().unwrap(), false).unwrap();
+        assert_eq!(version(&meta), Some(Value::from(SCHEMA_VERSION)));
+        assert_eq!(std::fs::read(&suite).unwrap(), b"{}");
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn artifacts() {
+        assert!(is_artifact("checkpoint.json"));
+        assert!(is_artifact("1200_summary.json"));
+        assert!(is_artifact("1200_1203_agg_summary.json"));
+        assert!(is_artifact("1200_execute_report.json"));
+        assert!(is_artifact("1200_meta.json"));
+        assert!(!is_artifact("1200_snark_proof_with_public_inputs.json"));
+        assert!(!is_artifact("results.jsonl"));
+    }
+
+    #[test]
+    fn migrate_artifacts_dry_run_writes_nothing() {
+        let dir = std::env::temp_dir().join(format!("schema-dry-run-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("checkpoint.json");
+        std::fs::write(&path, fixture!("v0/checkpoint.json")).unwrap();
+        let outdir = dir.to_str().unwrap();
+
+        migrate_artifacts(outdir, true).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            fixture!("v0/checkpoint.json")
+        );
+        migrate_artifacts(outdir, false).unwrap();
+        let checkpoint = Checkpoint::load(outdir).unwrap().unwrap();
+        assert_eq!(checkpoint.schema_version, SCHEMA_VERSION);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
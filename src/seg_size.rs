@@ -0,0 +1,148 @@
+use std::collections::VecDeque;
+use std::env;
+use std::sync::Mutex;
+
+/// Largest SEG_SIZE=auto picks.
+const MAX_SEG_SIZE: u32 = 1 << 22;
+/// Recent blocks the estimate is corrected from.
+const RECENT_BLOCKS: usize = 20;
+
+/// SEG_SIZE=auto: the initial segment size of a block estimated from its gas
+/// used and tx count as
+/// `SEG_SIZE_AUTO_BASE + SEG_SIZE_PER_MGAS * Mgas + SEG_SIZE_PER_TX * txs`,
+/// scaled by how the estimates of recent blocks compared with the segment
+/// size they were proved with, and rounded up to a power of two.
+#[derive(Debug)]
+pub struct AutoSegSize {
+    base: f64,
+    per_mgas: f64,
+    per_tx: f64,
+    min_seg_size: u32,
+    /// Proved SEG_SIZE over the estimate of the last RECENT_BLOCKS blocks.
+    ratios: Mutex<VecDeque<f64>>,
+}
+
+impl AutoSegSize {
+    /// `None` unless SEG_SIZE is `auto`. `ratios` are the corrections
+    /// persisted by an earlier run.
+    pub fn from_env(seg_size: &str, min_seg_size: u32, ratios: Vec<f64>) -> Option<Self> {
+        if seg_size.trim() != "auto" {
+            return None;
+        }
+        let base = env::var("SEG_SIZE_AUTO_BASE").unwrap_or("32768".to_string());
+        let per_mgas = env::var("SEG_SIZE_PER_MGAS").unwrap_or("4096".to_string());
+        let per_tx = env::var("SEG_SIZE_PER_TX").unwrap_or("256".to_string());
+        let mut ratios: VecDeque<f64> = ratios.into_iter().filter(|r| *r > 0.0).collect();
+        while ratios.len() > RECENT_BLOCKS {
+            ratios.pop_front();
+        }
+        Some(Self {
+            base: base.parse().unwrap_or(32768.0),
+            per_mgas: per_mgas.parse().unwrap_or(4096.0),
+            per_tx: per_tx.parse().unwrap_or(256.0),
+            min_seg_size,
+            ratios: Mutex::new(ratios),
+        })
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, VecDeque<f64>> {
+        self.ratios.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Initial SEG_SIZE of a block, logged with how it was arrived at.
+    pub fn estimate(&self, block_no: u64, gas_used: u64, tx_count: usize) -> u32 {
+        let mgas = gas_used as f64 / 1_000_000.0;
+        let raw = self.base + self.per_mgas * mgas + self.per_tx * tx_count as f64;
+        let correction = median(&self.lock());
+        let seg_size = (raw * correction)
+            .clamp(self.min_seg_size as f64, MAX_SEG_SIZE as f64)
+            .ceil() as u32;
+        let seg_size = seg_size.next_power_of_two().min(MAX_SEG_SIZE);
+        log::info!(
+            "SEG_SIZE=auto picked {} for block_no:{}: {:.0} + {:.0}*{:.2} Mgas + {:.0}*{} txs = {:.0}, corrected by {:.2} from recent blocks",
+            seg_size,
+            block_no,
+            self.base,
+            self.per_mgas,
+            mgas,
+            self.per_tx,
+            tx_count,
+            raw,
+            correction
+        );
+        seg_size
+    }
+
+    /// Record that a block estimated at `estimate` was proved with `proved`.
+    pub fn record(&self, estimate: u32, proved: u32) {
+        let mut ratios = self.lock();
+        // `estimate` is corrected already, keep the correction it applied.
+        let correction = median(&ratios);
+        ratios.push_back(correction * proved as f64 / estimate.max(1) as f64);
+        while ratios.len() > RECENT_BLOCKS {
+            ratios.pop_front();
+        }
+    }
+
+    /// The corrections to persist in the checkpoint.
+    pub fn ratios(&self) -> Vec<f64> {
+        self.lock().iter().copied().collect()
+    }
+}
+
+/// Median correction of the recent blocks, 1 without any.
+fn median(ratios: &VecDeque<f64>) -> f64 {
+    let mut sorted: Vec<f64> = ratios.iter().copied().collect();
+    sorted.sort_by(f64::total_cmp);
+    sorted.get(sorted.len() / 2).copied().unwrap_or(1.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn auto(min_seg_size: u32, ratios: Vec<f64>) -> AutoSegSize {
+        AutoSegSize {
+            base: 32768.0,
+            per_mgas: 4096.0,
+            per_tx: 256.0,
+            min_seg_size,
+            ratios: Mutex::new(ratios.into()),
+        }
+    }
+
+    #[test]
+    fn only_auto_is_estimated() {
+        assert!(AutoSegSize::from_env("262144", 1024, Vec::new()).is_none());
+        assert!(AutoSegSize::from_env(" auto ", 1024, Vec::new()).is_some());
+    }
+
+    #[test]
+    fn estimate_rounds_up_to_a_power_of_two_within_bounds() {
+        let auto = auto(1024, Vec::new());
+        assert_eq!(auto.estimate(1, 0, 0), 32768);
+        // 32768 + 4096 + 256 rounds up.
+        assert_eq!(auto.estimate(1, 1_000_000, 1), 65536);
+        assert_eq!(auto.estimate(1, u64::MAX, 0), MAX_SEG_SIZE);
+        assert_eq!(self::auto(1 << 17, Vec::new()).estimate(1, 0, 0), 1 << 17);
+    }
+
+    #[test]
+    fn recorded_blocks_correct_the_estimate() {
+        let auto = auto(1024, Vec::new());
+        let estimate = auto.estimate(1, 1_000_000, 1);
+        auto.record(estimate, estimate * 2);
+        assert_eq!(auto.ratios(), vec![2.0]);
+        assert_eq!(auto.estimate(2, 1_000_000, 1), 131072);
+    }
+
+    #[test]
+    fn median_of_the_recent_ratios() {
+        assert_eq!(median(&VecDeque::new()), 1.0);
+        assert_eq!(median(&VecDeque::from(vec![4.0, 0.5, 2.0])), 2.0);
+        let auto = auto(1024, vec![1.0; RECENT_BLOCKS]);
+        auto.record(1024, 4096);
+        assert_eq!(auto.ratios().len(), RECENT_BLOCKS);
+        assert_eq!(auto.ratios().last(), Some(&4.0));
+    }
+}
@@ -0,0 +1,160 @@
+use axum::{extract::State, http::StatusCode, routing::get, Json, Router};
+use serde::Serialize;
+use std::net::SocketAddr;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+use tokio_util::sync::CancellationToken;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Phase {
+    Starting,
+    Fetching,
+    Checking,
+    Proving,
+    Sleeping,
+}
+
+/// Live state of the prover loop, served on `/status`.
+#[derive(Debug, Clone, Serialize)]
+pub struct ProverStatus {
+    pub current_block: Option<u64>,
+    pub phase: Phase,
+    pub last_success_block: Option<u64>,
+    /// Block the prefetcher is working on ahead of the prover.
+    pub prefetch_block: Option<u64>,
+    pub prefetch_phase: Phase,
+    pub last_error: Option<String>,
+    /// Chain head in head-following mode.
+    pub head_block: Option<u64>,
+    /// `head_block` minus `last_success_block`.
+    pub head_lag: Option<u64>,
+    /// Seconds since the loop last reported progress.
+    pub last_tick_secs: u64,
+    #[serde(skip)]
+    last_tick: Instant,
+}
+
+/// Shared handle to the `ProverStatus`, updated from the loop and `prove_tx`.
+#[derive(Debug, Clone)]
+pub struct StatusHandle(Arc<RwLock<ProverStatus>>);
+
+impl StatusHandle {
+    pub fn new() -> Self {
+        Self(Arc::new(RwLock::new(ProverStatus {
+            current_block: None,
+            phase: Phase::Starting,
+            last_success_block: None,
+            prefetch_block: None,
+            prefetch_phase: Phase::Starting,
+            last_error: None,
+            head_block: None,
+            head_lag: None,
+            last_tick_secs: 0,
+            last_tick: Instant::now(),
+        })))
+    }
+
+    fn update(&self, f: impl FnOnce(&mut ProverStatus)) {
+        let mut status = self.0.write().unwrap_or_else(|e| e.into_inner());
+        f(&mut status);
+        status.last_tick = Instant::now();
+    }
+
+    pub fn set_phase(&self, block_no: u64, phase: Phase) {
+        self.update(|status| {
+            status.current_block = Some(block_no);
+            status.phase = phase;
+        });
+    }
+
+    /// While the prover is not busy proving, its phase follows the prefetcher.
+    pub fn set_prefetch(&self, block_no: u64, phase: Phase) {
+        self.update(|status| {
+            status.prefetch_block = Some(block_no);
+            status.prefetch_phase = phase;
+            if status.phase != Phase::Proving {
+                status.current_block = Some(block_no);
+                status.phase = phase;
+            }
+        });
+    }
+
+    /// The prover waits for the prefetcher.
+    pub fn set_waiting(&self) {
+        self.update(|status| {
+            status.current_block = status.prefetch_block;
+            status.phase = status.prefetch_phase;
+        });
+    }
+
+    pub fn record_success(&self, block_no: u64) {
+        self.update(|status| status.last_success_block = Some(block_no));
+    }
+
+    pub fn record_error(&self, error: String) {
+        self.update(|status| status.last_error = Some(error));
+    }
+
+    pub fn set_head(&self, head: u64) {
+        self.update(|status| status.head_block = Some(head));
+    }
+
+    pub fn snapshot(&self) -> ProverStatus {
+        let mut status = self.0.read().unwrap_or_else(|e| e.into_inner()).clone();
+        status.last_tick_secs = status.last_tick.elapsed().as_secs();
+        status.head_lag = status
+            .head_block
+            .map(|head| head.saturating_sub(status.last_success_block.unwrap_or_default()));
+        status
+    }
+}
+
+impl Default for StatusHandle {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Clone)]
+struct ServerState {
+    status: StatusHandle,
+    max_tick_age: Duration,
+}
+
+/// Healthy if the loop ticked within `max_tick_age`. A proof can legitimately
+/// take longer than that, so the proving phase counts as alive.
+async fn healthz(State(state): State<ServerState>) -> StatusCode {
+    let status = state.status.snapshot();
+    if status.phase == Phase::Proving || status.last_tick_secs <= state.max_tick_age.as_secs() {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    }
+}
+
+async fn status(State(state): State<ServerState>) -> Json<ProverStatus> {
+    Json(state.status.snapshot())
+}
+
+/// Serve `/healthz` and `/status` on `addr` until `shutdown` is cancelled.
+pub async fn serve(
+    addr: SocketAddr,
+    status_handle: StatusHandle,
+    max_tick_age: Duration,
+    shutdown: CancellationToken,
+) -> anyhow::Result<()> {
+    let app = Router::new()
+        .route("/healthz", get(healthz))
+        .route("/status", get(status))
+        .with_state(ServerState {
+            status: status_handle,
+            max_tick_age,
+        });
+    log::info!("Status server listening on {}", addr);
+    axum::Server::bind(&addr)
+        .serve(app.into_make_service())
+        .with_graceful_shutdown(shutdown.cancelled_owned())
+        .await?;
+    Ok(())
+}
@@ -0,0 +1,229 @@
+use ethers::contract::{ContractCall, EthError};
+use ethers::prelude::*;
+use sha2::{Digest, Sha256};
+use std::env;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Mutex;
+
+abigen!(
+    VerifierContract,
+    r#"[
+        function verifyAndCommit(bytes proof, bytes publicInputs, uint64 blockNo)
+    ]"#
+);
+
+type SubmitClient = SignerMiddleware<Provider<Http>, LocalWallet>;
+
+/// Result of a successful proof submission.
+#[derive(Debug, Clone)]
+pub struct Submission {
+    pub tx_hash: H256,
+    pub gas_used: Option<u64>,
+}
+
+/// Submits proofs to the verifier contract, configured by VERIFIER_CONTRACT,
+/// SUBMIT_RPC_URL and SUBMITTER_PRIVATE_KEY.
+pub struct ProofSubmitter {
+    contract: VerifierContract<SubmitClient>,
+    max_retries: u32,
+    gas_bump_percent: u64,
+    receipt_timeout: Duration,
+    /// Serializes submissions so replacement transactions keep their nonce.
+    lock: Mutex<()>,
+}
+
+impl ProofSubmitter {
+    /// `None` when VERIFIER_CONTRACT is unset.
+    pub async fn from_env() -> anyhow::Result<Option<Self>> {
+        let Ok(contract) = env::var("VERIFIER_CONTRACT") else {
+            return Ok(None);
+        };
+        let contract: Address = contract
+            .parse()
+            .map_err(|e| anyhow::anyhow!("invalid VERIFIER_CONTRACT: {}", e))?;
+        let rpc_url = env::var("SUBMIT_RPC_URL")
+            .map_err(|_| anyhow::anyhow!("SUBMIT_RPC_URL is required with VERIFIER_CONTRACT"))?;
+        let private_key = env::var("SUBMITTER_PRIVATE_KEY").map_err(|_| {
+            anyhow::anyhow!("SUBMITTER_PRIVATE_KEY is required with VERIFIER_CONTRACT")
+        })?;
+        let max_retries = env::var("SUBMIT_MAX_RETRIES").unwrap_or("3".to_string());
+        let gas_bump_percent = env::var("SUBMIT_GAS_BUMP_PERCENT").unwrap_or("20".to_string());
+        let receipt_timeout_secs =
+            env::var("SUBMIT_RECEIPT_TIMEOUT_SECS").unwrap_or("300".to_string());
+
+        let provider = Provider::<Http>::try_from(rpc_url)?;
+        let chain_id = provider.get_chainid().await?.as_u64();
+        let wallet = private_key
+            .parse::<LocalWallet>()
+            .map_err(|e| anyhow::anyhow!("invalid SUBMITTER_PRIVATE_KEY: {}", e))?
+            .with_chain_id(chain_id);
+        let client = Arc::new(SignerMiddleware::new(provider, wallet));
+        Ok(Some(Self {
+            contract: VerifierContract::new(contract, client),
+            max_retries: max_retries.parse().unwrap_or(3),
+            gas_bump_percent: gas_bump_percent.parse().unwrap_or(20),
+            receipt_timeout: Duration::from_secs(receipt_timeout_secs.parse().unwrap_or(300)),
+            lock: Mutex::new(()),
+        }))
+    }
+
+    /// Submit `proof` for `block_no`. The public input is the sha256 of the
+    /// public input stream the proof was generated for. Transactions that are
+    /// dropped or not mined in time are replaced with the same nonce and a
+    /// bumped gas price. A revert fails the submission at once: it used up the
+    /// nonce, and the contract would reject the proof again.
+    pub async fn submit(
+        &self,
+        proof: Vec<u8>,
+        public_inputstream: &[u8],
+        block_no: u64,
+    ) -> anyhow::Result<Submission> {
+        let _guard = self.lock.lock().await;
+        let client = self.contract.client();
+        let public_inputs = Sha256::digest(public_inputstream).to_vec();
+        let nonce = client
+            .get_transaction_count(client.address(), Some(BlockNumber::Pending.into()))
+            .await?;
+        let mut gas_price = client.get_gas_price().await?;
+
+        let mut attempts = 0;
+        loop {
+            attempts += 1;
+            let call = self
+                .contract
+                .verify_and_commit(proof.clone().into(), public_inputs.clone().into(), block_no)
+                .nonce(nonce)
+                .gas_price(gas_price);
+            let error = match call.send().await {
+                Ok(pending) => {
+                    let tx_hash = pending.tx_hash();
+                    log::info!(
+                        "Submitted proof of block_no:{} tx:{:?} gas_price:{}",
+                        block_no,
+                        tx_hash,
+                        gas_price
+                    );
+                    match tokio::time::timeout(self.receipt_timeout, pending).await {
+                        Ok(Ok(Some(receipt))) if receipt.status == Some(1u64.into()) => {
+                            return Ok(Submission {
+                                tx_hash,
+                                gas_used: receipt.gas_used.map(|g| g.as_u64()),
+                            });
+                        }
+                        Ok(Ok(Some(receipt))) => {
+                            let reason = revert_reason(&call, receipt.block_number).await;
+                            anyhow::bail!(
+                                "proof of block_no {} reverted in transaction {:?}: {}",
+                                block_no,
+                                tx_hash,
+                                reason
+                            );
+                        }
+                        Ok(Ok(None)) => format!("transaction {:?} was dropped", tx_hash),
+                        // The transaction may have been mined, a replacement
+                        // could only fail with the nonce used up.
+                        Ok(Err(e)) => anyhow::bail!(
+                            "failed to wait for the proof of block_no {} in transaction {:?}: {}",
+                            block_no,
+                            tx_hash,
+                            e
+                        ),
+                        Err(_) => format!(
+                            "transaction {:?} not mined within {} secs",
+                            tx_hash,
+                            self.receipt_timeout.as_secs()
+                        ),
+                    }
+                }
+                Err(e) => e.to_string(),
+            };
+            if attempts > self.max_retries {
+                anyhow::bail!(
+                    "failed to submit proof of block_no {} after {} attempts: {}",
+                    block_no,
+                    attempts,
+                    error
+                );
+            }
+            gas_price = gas_price * (100 + self.gas_bump_percent) / 100;
+            log::warn!(
+                "Failed to submit proof of block_no:{}: {}. Retrying with gas_price:{}",
+                block_no,
+                error,
+                gas_price
+            );
+        }
+    }
+}
+
+/// Why `call` reverted, replayed on the state of the block it was mined in.
+async fn revert_reason(call: &ContractCall<SubmitClient, ()>, block: Option<U64>) -> String {
+    let mut call = call.clone();
+    if let Some(block) = block {
+        call = call.block(block);
+    }
+    match call.call().await {
+        Ok(()) => "no revert reason".to_string(),
+        Err(e) => match e.as_revert() {
+            Some(data) => decode_revert(data),
+            None => e.to_string(),
+        },
+    }
+}
+
+/// The message of an `Error(string)` revert, the data in hex otherwise.
+fn decode_revert(data: &[u8]) -> String {
+    match String::decode_with_selector(data) {
+        Some(reason) => reason,
+        None => format!("0x{}", hex::encode(data)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::MockServer;
+    use ethers::abi::{encode, Token};
+    use serde_json::json;
+
+    #[test]
+    fn revert_reasons() {
+        let mut data = vec![0x08, 0xc3, 0x79, 0xa0];
+        data.extend(encode(&[Token::String("invalid proof".to_string())]));
+        assert_eq!(decode_revert(&data), "invalid proof");
+        // A custom error is not decoded.
+        assert_eq!(decode_revert(&[0x12, 0x34, 0x56, 0x78]), "0x12345678");
+        assert_eq!(decode_revert(&[]), "0x");
+    }
+
+    #[tokio::test]
+    async fn failed_sends_are_retried_then_given_up() {
+        // The nonce and the gas price are answered, every later request fails.
+        let server = MockServer::rpc(vec![Some(json!("0x0")), Some(json!("0x64")), None]);
+        let provider = Provider::<Http>::try_from(server.url.as_str()).unwrap();
+        let wallet = "45a915e4d060149eb4365960e6a7a45f334393093061116b197e3240065ff2d8"
+            .parse::<LocalWallet>()
+            .unwrap()
+            .with_chain_id(1u64);
+        let submitter = ProofSubmitter {
+            contract: VerifierContract::new(
+                Address::zero(),
+                Arc::new(SignerMiddleware::new(provider, wallet)),
+            ),
+            max_retries: 2,
+            gas_bump_percent: 20,
+            receipt_timeout: Duration::from_secs(1),
+            lock: Mutex::new(()),
+        };
+        let e = submitter.submit(vec![1], b"input", 7).await.unwrap_err();
+        assert!(e.to_string().contains("after 3 attempts"), "{}", e);
+        let methods: Vec<_> = server
+            .bodies()
+            .iter()
+            .map(|body| body["method"].as_str().unwrap().to_string())
+            .collect();
+        assert_eq!(methods[..2], ["eth_getTransactionCount", "eth_gasPrice"]);
+        assert!(methods.len() >= 5, "{:?}", methods);
+    }
+}
@@ -0,0 +1,222 @@
+use crate::atomic_file;
+use crate::error::ProverError;
+use ethers_providers::{JsonRpcClient, Provider};
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use models::TestSuite;
+use revm::primitives::{keccak256, B256};
+use serde_json::Value;
+use std::fmt::Display;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// Path of the suite `name`, `{block_no}.json` or `{first}_{last}.json` for a
+/// batch, with COMPRESS_SUITES `.json.gz`.
+pub fn file_path(outdir: &str, name: impl Display, compress: bool) -> PathBuf {
+    let ext = if compress { "json.gz" } else { "json" };
+    Path::new(outdir).join(format!("{}.{}", name, ext))
+}
+
+/// Both possible suite paths of `block_no`.
+pub fn file_paths(outdir: &str, block_no: u64) -> [PathBuf; 2] {
+    [
+        file_path(outdir, block_no, false),
+        file_path(outdir, block_no, true),
+    ]
+}
+
+/// Suite of `block_no` as the executor generates it from the node behind
+/// `client`, one unit per transaction.
+pub async fn build<P: JsonRpcClient + 'static>(
+    client: Arc<Provider<P>>,
+    block_no: u64,
+    chain_id: u64,
+) -> Result<TestSuite, ProverError> {
+    executor::process(client, block_no, chain_id)
+        .await
+        .map_err(|e| ProverError::Rpc(format!("failed to fetch block_no {}: {}", block_no, e)))
+}
+
+/// Canonical JSON of `test_suite`: object keys sorted, no whitespace, and
+/// every value as written by the `models` serializers, integers being hex
+/// strings. The same suite always gives the same bytes.
+pub fn canonical_json(test_suite: &TestSuite) -> serde_json::Result<String> {
+    Ok(canonical_value(&serde_json::to_value(test_suite)?))
+}
+
+/// Canonical JSON of any `value`, as [`canonical_json`] writes a suite.
+///
+/// ```
+/// let value = serde_json::json!({"b": [1, {"d": "0x02", "c": null}], "a": true});
+/// assert_eq!(
+///     goat_prover::suite::canonical_value(&value),
+///     r#"{"a":true,"b":[1,{"c":null,"d":"0x02"}]}"#
+/// );
+/// ```
+pub fn canonical_value(value: &Value) -> String {
+    let mut json = String::new();
+    write_canonical(value, &mut json);
+    json
+}
+
+fn write_canonical(value: &Value, json: &mut String) {
+    match value {
+        Value::Object(map) => {
+            let mut entries: Vec<_> = map.iter().collect();
+            entries.sort_unstable_by(|(a, _), (b, _)| a.cmp(b));
+            json.push('{');
+            for (i, (key, value)) in entries.into_iter().enumerate() {
+                if i > 0 {
+                    json.push(',');
+                }
+                json.push_str(&Value::String(key.clone()).to_string());
+                json.push(':');
+                write_canonical(value, json);
+            }
+            json.push('}');
+        }
+        Value::Array(values) => {
+            json.push('[');
+            for (i, value) in values.iter().enumerate() {
+                if i > 0 {
+                    json.push(',');
+                }
+                write_canonical(value, json);
+            }
+            json.push(']');
+        }
+        _ => json.push_str(&value.to_string()),
+    }
+}
+
+/// The bincode-wrapped canonical JSON of `test_suite`, the bytes of the suite
+/// file and the prover's public input.
+pub fn encode(test_suite: &TestSuite) -> Result<Vec<u8>, ProverError> {
+    let serialize_error =
+        |e: &dyn Display| ProverError::Check(format!("failed to serialize suite: {}", e));
+    let json = canonical_json(test_suite).map_err(|e| serialize_error(&e))?;
+    bincode::serialize(&json).map_err(|e| serialize_error(&e))
+}
+
+/// keccak256 of the encoded `test_suite`, as recorded in the block metadata.
+pub fn suite_hash(test_suite: &TestSuite) -> Result<B256, ProverError> {
+    Ok(keccak256(encode(test_suite)?))
+}
+
+/// Write the bincode-wrapped suite `buf` to `path`, gzip-compressed if
+/// `compress` is set.
+pub fn write(path: &Path, buf: &[u8], compress: bool) -> anyhow::Result<()> {
+    if !compress {
+        atomic_file::write(path, buf).map_err(|e| ProverError::io(path, e))?;
+        return Ok(());
+    }
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder
+        .write_all(buf)
+        .and_then(|_| encoder.finish())
+        .and_then(|compressed| atomic_file::write(path, compressed))
+        .map_err(|e| ProverError::io(path, e))?;
+    Ok(())
+}
+
+/// Read a suite file, decompressing it if it starts with the gzip magic, so
+/// the caller always gets the bytes the uncompressed file would hold.
+pub fn read(path: impl AsRef<Path>) -> anyhow::Result<Vec<u8>> {
+    let path = path.as_ref();
+    let buf = std::fs::read(path).map_err(|e| ProverError::io(path, e))?;
+    if !buf.starts_with(&GZIP_MAGIC) {
+        return Ok(buf);
+    }
+    let mut decompressed = Vec::new();
+    GzDecoder::new(buf.as_slice())
+        .read_to_end(&mut decompressed)
+        .map_err(|e| ProverError::io(path, e))?;
+    Ok(decompressed)
+}
+
+/// Whether `path` is named like a suite, `{block_no}.json` or
+/// `{first}_{last}.json`, compressed or not.
+pub fn is_suite_file(path: &Path) -> bool {
+    let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+        return false;
+    };
+    let Some((stem, ext)) = name.split_once('.') else {
+        return false;
+    };
+    (ext == "json" || ext == "json.gz")
+        && stem
+            .split('_')
+            .all(|part| !part.is_empty() && part.bytes().all(|b| b.is_ascii_digit()))
+        && stem.split('_').count() <= 2
+}
+
+/// First block of the suite file `path`.
+pub fn block_no(path: &Path) -> Option<u64> {
+    let name = path.file_name()?.to_str()?;
+    name.split(['.', '_']).next()?.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A bincode-wrapped suite JSON, as `encode` writes it.
+    fn suite_bytes() -> Vec<u8> {
+        let json = r#"{"0x01":{"env":{"currentNumber":"0x4b0"}}}"#.repeat(64);
+        bincode::serialize(&json).unwrap()
+    }
+
+    #[test]
+    fn compressed_suite_reads_back_byte_identical() {
+        let dir = std::env::temp_dir().join(format!("suite-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let outdir = dir.to_str().unwrap();
+        let buf = suite_bytes();
+
+        let [plain, compressed] = file_paths(outdir, 1200);
+        assert!(compressed.to_string_lossy().ends_with("1200.json.gz"));
+        write(&plain, &buf, false).unwrap();
+        write(&compressed, &buf, true).unwrap();
+
+        let stored = std::fs::read(&compressed).unwrap();
+        assert!(stored.starts_with(&GZIP_MAGIC));
+        assert!(stored.len() < buf.len());
+        assert_eq!(std::fs::read(&plain).unwrap(), buf);
+        // The prover gets the same public input from either file.
+        assert_eq!(read(&plain).unwrap(), buf);
+        assert_eq!(read(&compressed).unwrap(), buf);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn truncated_gzip_is_an_io_error() {
+        let path = std::env::temp_dir().join(format!("suite-{}-7.json.gz", std::process::id()));
+        std::fs::write(&path, [0x1f, 0x8b, 0x08]).unwrap();
+        let e = read(&path).unwrap_err();
+        assert!(matches!(
+            e.downcast_ref::<ProverError>(),
+            Some(ProverError::Io { .. })
+        ));
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn suite_file_names() {
+        for name in ["1200.json", "1200.json.gz", "1200_1203.json"] {
+            assert!(is_suite_file(Path::new(name)), "{}", name);
+        }
+        for name in [
+            "1200_summary.json",
+            "1200.json.tmp",
+            "checkpoint.json",
+            "1_2_3.json",
+        ] {
+            assert!(!is_suite_file(Path::new(name)), "{}", name);
+        }
+        assert_eq!(block_no(Path::new("/out/1200_1203.json.gz")), Some(1200));
+    }
+}
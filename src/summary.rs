@@ -0,0 +1,265 @@
+use crate::atomic_file;
+use crate::error::ProverError;
+use crate::execution::ExecutionStats;
+use crate::lock;
+use crate::receipts::ReceiptMatch;
+use crate::schema::{self, SCHEMA_VERSION};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+const RESULTS_FILE: &str = "results.jsonl";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BlockStatus {
+    Proved,
+    Executed,
+    /// Only the test suite was generated and checked, ELF_PATH is empty.
+    Generated,
+    /// Not proved because the block is not relevant, see `skip_reason`.
+    Skipped,
+    /// Checked but not proved, PROVE_EVERY_N does not select the block.
+    SampledOut,
+    Failed,
+}
+
+impl BlockStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Proved => "proved",
+            Self::Executed => "executed",
+            Self::Generated => "generated",
+            Self::Skipped => "skipped",
+            Self::SampledOut => "sampled_out",
+            Self::Failed => "failed",
+        }
+    }
+}
+
+/// Machine-readable result of one block, written to `{block_no}_summary.json`
+/// and appended to `{OUTPUT_DIR}/results.jsonl`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BlockSummary {
+    #[serde(default = "schema::legacy_schema_version")]
+    pub schema_version: u32,
+    pub block_no: u64,
+    /// Last block of a BLOCKS_PER_PROOF batch starting at `block_no`.
+    #[serde(default)]
+    pub last_block_no: Option<u64>,
+    pub tx_count: usize,
+    pub suite_bytes: usize,
+    pub check_micros: u64,
+    /// Gas used by the transactions of the suite in the host check.
+    #[serde(default)]
+    pub check_gas_used: u64,
+    /// Receipt comparison with the chain of every transaction, RECEIPT_CHECK.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub receipts: Vec<ReceiptMatch>,
+    pub prove_secs: u64,
+    pub seg_size: u32,
+    pub execute_only: bool,
+    pub proof_bytes: u64,
+    pub verified: bool,
+    /// Size of the PRIVATE_INPUT_PATH file passed to the prover.
+    #[serde(default)]
+    pub private_input_bytes: u64,
+    /// Execution statistics reported by the prover, see ExecutionStats.
+    #[serde(default)]
+    pub cycles: Option<u64>,
+    #[serde(default)]
+    pub segments: Option<u64>,
+    #[serde(default)]
+    pub stage_millis: BTreeMap<String, u64>,
+    /// 0 for the run over the queue, N for the Nth RETRY_PASSES pass over
+    /// the blocks that failed.
+    #[serde(default)]
+    pub pass: u32,
+    pub status: BlockStatus,
+    pub error: Option<String>,
+    /// Transaction committing the proof to the verifier contract.
+    #[serde(default)]
+    pub submit_tx_hash: Option<String>,
+    #[serde(default)]
+    pub submit_gas_used: Option<u64>,
+    #[serde(default)]
+    pub submit_error: Option<String>,
+    /// Celestia height and blob commitment of the published proof.
+    #[serde(default)]
+    pub da_height: Option<u64>,
+    #[serde(default)]
+    pub da_commitment: Option<String>,
+    #[serde(default)]
+    pub da_error: Option<String>,
+    #[serde(default)]
+    pub skip_reason: Option<String>,
+    /// Object keys the proof artifacts were uploaded to.
+    #[serde(default)]
+    pub s3_keys: Vec<String>,
+    #[serde(default)]
+    pub s3_error: Option<String>,
+}
+
+impl BlockSummary {
+    pub fn new(block_no: u64, status: BlockStatus) -> Self {
+        Self {
+            schema_version: SCHEMA_VERSION,
+            block_no,
+            last_block_no: None,
+            tx_count: 0,
+            suite_bytes: 0,
+            check_micros: 0,
+            check_gas_used: 0,
+            receipts: Vec::new(),
+            prove_secs: 0,
+            seg_size: 0,
+            execute_only: false,
+            proof_bytes: 0,
+            verified: false,
+            private_input_bytes: 0,
+            cycles: None,
+            segments: None,
+            stage_millis: BTreeMap::new(),
+            pass: 0,
+            status,
+            error: None,
+            submit_tx_hash: None,
+            submit_gas_used: None,
+            submit_error: None,
+            da_height: None,
+            da_commitment: None,
+            da_error: None,
+            skip_reason: None,
+            s3_keys: Vec::new(),
+            s3_error: None,
+        }
+    }
+
+    pub fn set_execution(&mut self, stats: &ExecutionStats) {
+        self.cycles = stats.cycles;
+        self.segments = stats.segments;
+        self.stage_millis = stats.stage_millis.clone();
+    }
+
+    pub fn path(outdir: &str, block_no: u64) -> PathBuf {
+        Path::new(outdir).join(format!("{}_summary.json", block_no))
+    }
+
+    pub fn load(outdir: &str, block_no: u64) -> anyhow::Result<Option<Self>> {
+        let path = Self::path(outdir, block_no);
+        if !path.exists() {
+            return Ok(None);
+        }
+        let buf = std::fs::read(&path)?;
+        let summary = serde_json::from_slice(&buf)
+            .map_err(|e| anyhow::anyhow!("invalid summary {}: {}", path.display(), e))?;
+        Ok(Some(summary))
+    }
+
+    /// Rewrite the per-block summary file of `block_no` with `f` applied,
+    /// results.jsonl keeps the original entry.
+    pub fn update(outdir: &str, block_no: u64, f: impl FnOnce(&mut Self)) -> anyhow::Result<()> {
+        let mut summary = Self::load(outdir, block_no)?
+            .ok_or_else(|| anyhow::anyhow!("no summary for block_no {}", block_no))?;
+        f(&mut summary);
+        summary.write_file(outdir)
+    }
+
+    fn write_file(&self, outdir: &str) -> anyhow::Result<()> {
+        let path = Self::path(outdir, self.block_no);
+        atomic_file::write(&path, serde_json::to_vec_pretty(self)?)
+            .map_err(|e| ProverError::io(&path, e))?;
+        Ok(())
+    }
+
+    /// Write the per-block summary file and append it to the run's results.jsonl.
+    pub fn write(&self, outdir: &str) -> anyhow::Result<()> {
+        self.write_file(outdir)?;
+
+        let path = Path::new(outdir).join(RESULTS_FILE);
+        let mut line = serde_json::to_vec(self)?;
+        line.push(b'\n');
+        lock::append_locked(&path, &line).map_err(|e| ProverError::io(&path, e))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("summary-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn results(outdir: &str) -> Vec<BlockSummary> {
+        std::fs::read_to_string(Path::new(outdir).join(RESULTS_FILE))
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn write_and_load_round_trip() {
+        let dir = temp_dir("round-trip");
+        let outdir = dir.to_str().unwrap();
+        let mut summary = BlockSummary::new(7, BlockStatus::Proved);
+        summary.tx_count = 2;
+        summary.receipts = vec![ReceiptMatch {
+            block_no: 7,
+            tx_index: 1,
+            tx_hash: Some("0x01".to_string()),
+            mismatches: vec!["gas_used".to_string()],
+        }];
+        summary.stage_millis.insert("prove".to_string(), 1500);
+        summary.write(outdir).unwrap();
+
+        let loaded = BlockSummary::load(outdir, 7).unwrap().unwrap();
+        assert_eq!(loaded.schema_version, SCHEMA_VERSION);
+        assert_eq!(loaded.status, BlockStatus::Proved);
+        assert_eq!(loaded.tx_count, 2);
+        assert_eq!(loaded.receipts, summary.receipts);
+        assert_eq!(loaded.stage_millis["prove"], 1500);
+        assert_eq!(results(outdir).len(), 1);
+        assert!(BlockSummary::load(outdir, 8).unwrap().is_none());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn matching_receipts_are_not_serialized() {
+        let json = serde_json::to_value(BlockSummary::new(1, BlockStatus::Executed)).unwrap();
+        assert!(json.get("receipts").is_none());
+        assert_eq!(json["status"], "executed");
+    }
+
+    #[test]
+    fn update_rewrites_the_file_and_keeps_the_results_entry() {
+        let dir = temp_dir("update");
+        let outdir = dir.to_str().unwrap();
+        BlockSummary::new(3, BlockStatus::Proved)
+            .write(outdir)
+            .unwrap();
+        BlockSummary::new(4, BlockStatus::Failed)
+            .write(outdir)
+            .unwrap();
+        BlockSummary::update(outdir, 3, |summary| {
+            summary.submit_tx_hash = Some("0xab".to_string())
+        })
+        .unwrap();
+
+        let loaded = BlockSummary::load(outdir, 3).unwrap().unwrap();
+        assert_eq!(loaded.submit_tx_hash.as_deref(), Some("0xab"));
+        let results = results(outdir);
+        assert_eq!(
+            results.iter().map(|s| s.block_no).collect::<Vec<_>>(),
+            [3, 4]
+        );
+        assert!(results[0].submit_tx_hash.is_none());
+        assert!(BlockSummary::update(outdir, 5, |_| {}).is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
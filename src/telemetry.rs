@@ -0,0 +1,227 @@
+use crate::summary::BlockSummary;
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::env;
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+/// Upper bound of a telemetry POST, a slow collector must not hold back
+/// the queue.
+const POST_TIMEOUT: Duration = Duration::from_secs(5);
+/// How often buffered payloads are retried.
+const RETRY_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Payload POSTed to TELEMETRY_URL after every block: the block summary and
+/// the prover that wrote it.
+#[derive(Debug, Serialize)]
+struct Report<'a> {
+    instance_id: &'a str,
+    hostname: &'a str,
+    #[serde(flatten)]
+    summary: &'a BlockSummary,
+}
+
+/// Best-effort delivery of the per-block summaries to a central collector,
+/// configured by TELEMETRY_URL, TELEMETRY_BUFFER (failed posts kept for a
+/// retry, 100), TELEMETRY_MAX_BYTES (64 KiB) and INSTANCE_ID (the pid).
+/// TELEMETRY_DISABLE=true turns it off even if TELEMETRY_URL is set.
+#[derive(Debug, Clone)]
+pub struct Telemetry {
+    instance_id: String,
+    hostname: String,
+    max_bytes: usize,
+    tx: mpsc::Sender<Vec<u8>>,
+}
+
+impl Telemetry {
+    /// `None` when TELEMETRY_URL is unset or TELEMETRY_DISABLE is set.
+    /// Spawns the delivery task.
+    pub fn from_env() -> anyhow::Result<Option<Self>> {
+        let disable = env::var("TELEMETRY_DISABLE").unwrap_or("false".to_string());
+        if disable.parse::<bool>().unwrap_or(false) {
+            return Ok(None);
+        }
+        let url = match env::var("TELEMETRY_URL") {
+            Ok(url) if !url.is_empty() => url,
+            _ => return Ok(None),
+        };
+        let buffer = env::var("TELEMETRY_BUFFER").unwrap_or("100".to_string());
+        let max_bytes = env::var("TELEMETRY_MAX_BYTES").unwrap_or("65536".to_string());
+        let instance_id =
+            env::var("INSTANCE_ID").unwrap_or_else(|_| std::process::id().to_string());
+        Self::new(
+            url,
+            buffer.parse().unwrap_or(100),
+            max_bytes.parse().unwrap_or(65536),
+            instance_id,
+        )
+        .map(Some)
+    }
+
+    fn new(
+        url: String,
+        buffer: usize,
+        max_bytes: usize,
+        instance_id: String,
+    ) -> anyhow::Result<Self> {
+        let buffer = buffer.max(1);
+        let client = reqwest::Client::builder().timeout(POST_TIMEOUT).build()?;
+        let (tx, rx) = mpsc::channel(buffer);
+        tokio::spawn(deliver(client, url, rx, buffer));
+        Ok(Self {
+            instance_id,
+            hostname: hostname(),
+            max_bytes,
+            tx,
+        })
+    }
+
+    /// Queue `summary` for delivery. Never blocks, a payload over
+    /// TELEMETRY_MAX_BYTES or one arriving while the queue is full is
+    /// dropped.
+    pub fn send(&self, summary: &BlockSummary) {
+        let report = Report {
+            instance_id: &self.instance_id,
+            hostname: &self.hostname,
+            summary,
+        };
+        let payload = match serde_json::to_vec(&report) {
+            Ok(payload) => payload,
+            Err(e) => {
+                log::warn!(
+                    "Failed to encode telemetry of block_no {}: {}",
+                    summary.block_no,
+                    e
+                );
+                return;
+            }
+        };
+        if payload.len() > self.max_bytes {
+            log::warn!(
+                "Dropping telemetry of block_no {}, {} bytes exceed TELEMETRY_MAX_BYTES",
+                summary.block_no,
+                payload.len()
+            );
+            return;
+        }
+        if self.tx.try_send(payload).is_err() {
+            log::warn!(
+                "Telemetry queue is full, dropping block_no {}",
+                summary.block_no
+            );
+        }
+    }
+}
+
+/// POST every queued payload, keeping up to `buffer` failed ones to retry
+/// every RETRY_INTERVAL, the oldest are dropped first.
+async fn deliver(
+    client: reqwest::Client,
+    url: String,
+    mut rx: mpsc::Receiver<Vec<u8>>,
+    buffer: usize,
+) {
+    let mut failed: VecDeque<Vec<u8>> = VecDeque::new();
+    let mut retry = tokio::time::interval(RETRY_INTERVAL);
+    loop {
+        let mut batch = tokio::select! {
+            payload = rx.recv() => match payload {
+                Some(payload) => vec![payload],
+                None => break,
+            },
+            _ = retry.tick() => failed.drain(..).collect(),
+        };
+        for payload in batch.drain(..) {
+            let result = client
+                .post(&url)
+                .header(reqwest::header::CONTENT_TYPE, "application/json")
+                .body(payload.clone())
+                .send()
+                .await;
+            if let Err(e) = result.and_then(|response| response.error_for_status()) {
+                log::debug!("Failed to post telemetry to {}: {}", url, e);
+                if failed.len() >= buffer {
+                    failed.pop_front();
+                }
+                failed.push_back(payload);
+            }
+        }
+    }
+    if !failed.is_empty() {
+        log::warn!("{} telemetry reports were not delivered", failed.len());
+    }
+}
+
+fn hostname() -> String {
+    if let Ok(hostname) = env::var("HOSTNAME") {
+        return hostname;
+    }
+    std::fs::read_to_string("/proc/sys/kernel/hostname")
+        .map(|hostname| hostname.trim().to_string())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::summary::BlockStatus;
+    use crate::test_util::MockServer;
+    use axum::http::StatusCode;
+
+    fn summary() -> BlockSummary {
+        let mut summary = BlockSummary::new(1200, BlockStatus::Proved);
+        summary.tx_count = 3;
+        summary.prove_secs = 612;
+        summary
+    }
+
+    #[tokio::test]
+    async fn posts_the_summary_of_the_instance() {
+        let server = MockServer::start(StatusCode::OK);
+        let telemetry =
+            Telemetry::new(server.url.clone(), 4, 65536, "prover-1".to_string()).unwrap();
+        telemetry.send(&summary());
+
+        let mut bodies = server.bodies();
+        for _ in 0..100 {
+            if !bodies.is_empty() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            bodies = server.bodies();
+        }
+        assert_eq!(bodies.len(), 1);
+        let report = bodies[0].as_object().unwrap();
+        assert_eq!(report["instance_id"], "prover-1");
+        assert!(report["hostname"].is_string());
+        // The summary fields are flattened into the report.
+        let expected = serde_json::to_value(summary()).unwrap();
+        for (key, value) in expected.as_object().unwrap() {
+            assert_eq!(&report[key], value, "{}", key);
+        }
+        assert_eq!(report.len(), expected.as_object().unwrap().len() + 2);
+    }
+
+    #[tokio::test]
+    async fn oversized_payload_is_dropped() {
+        let server = MockServer::start(StatusCode::OK);
+        let telemetry = Telemetry::new(server.url.clone(), 4, 64, "prover-1".to_string()).unwrap();
+        telemetry.send(&summary());
+        assert_eq!(telemetry.tx.capacity(), 4);
+    }
+
+    #[tokio::test]
+    async fn send_never_blocks_on_a_full_queue() {
+        // Nothing listens on the discard port, deliveries fail and are kept.
+        let telemetry = Telemetry::new(
+            "http://127.0.0.1:9".to_string(),
+            1,
+            65536,
+            "prover-1".to_string(),
+        )
+        .unwrap();
+        for _ in 0..10 {
+            telemetry.send(&summary());
+        }
+    }
+}
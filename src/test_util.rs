@@ -0,0 +1,81 @@
+use axum::response::IntoResponse;
+use axum::{body::Bytes, http::StatusCode, http::Uri, Router};
+use std::sync::{Arc, Mutex};
+
+/// Local HTTP server answering every request with a fixed status and
+/// recording the path and body of each request.
+pub struct MockServer {
+    pub url: String,
+    requests: Arc<Mutex<Vec<(String, Bytes)>>>,
+}
+
+impl MockServer {
+    pub fn start(status: StatusCode) -> Self {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let recorded = requests.clone();
+        let app = Router::new().fallback(move |uri: Uri, body: Bytes| {
+            let recorded = recorded.clone();
+            async move {
+                recorded
+                    .lock()
+                    .unwrap()
+                    .push((uri.path().to_string(), body));
+                status
+            }
+        });
+        let server = axum::Server::from_tcp(listener)
+            .unwrap()
+            .serve(app.into_make_service());
+        tokio::spawn(server);
+        Self { url, requests }
+    }
+
+    /// JSON-RPC server answering the `n`th request with the `n`th result of
+    /// `results`, or with 503 for `None`, and the last one after the script
+    /// runs out.
+    pub fn rpc(results: Vec<Option<serde_json::Value>>) -> Self {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let recorded = requests.clone();
+        let results = Arc::new(results);
+        let app = Router::new().fallback(move |uri: Uri, body: Bytes| {
+            let recorded = recorded.clone();
+            let results = results.clone();
+            async move {
+                let request: serde_json::Value = serde_json::from_slice(&body).unwrap_or_default();
+                let mut recorded = recorded.lock().unwrap();
+                recorded.push((uri.path().to_string(), body));
+                let n = (recorded.len() - 1).min(results.len() - 1);
+                match &results[n] {
+                    Some(result) => (
+                        StatusCode::OK,
+                        axum::Json(serde_json::json!({
+                            "jsonrpc": "2.0",
+                            "id": request["id"],
+                            "result": result,
+                        })),
+                    )
+                        .into_response(),
+                    None => StatusCode::SERVICE_UNAVAILABLE.into_response(),
+                }
+            }
+        });
+        let server = axum::Server::from_tcp(listener)
+            .unwrap()
+            .serve(app.into_make_service());
+        tokio::spawn(server);
+        Self { url, requests }
+    }
+
+    /// Bodies of the requests received so far, parsed as JSON.
+    pub fn bodies(&self) -> Vec<serde_json::Value> {
+        let requests = self.requests.lock().unwrap();
+        requests
+            .iter()
+            .map(|(_, body)| serde_json::from_slice(body).unwrap())
+            .collect()
+    }
+}
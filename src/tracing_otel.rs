@@ -0,0 +1,64 @@
+use opentelemetry::KeyValue;
+use opentelemetry_otlp::WithExportConfig;
+use opentelemetry_sdk::{runtime, trace, Resource};
+use std::env;
+use tracing::Level;
+use tracing_subscriber::filter::{EnvFilter, Targets};
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::Layer;
+
+/// Flushes the spans still buffered when the run ends.
+pub struct TracingGuard {
+    otlp: bool,
+}
+
+impl Drop for TracingGuard {
+    fn drop(&mut self) {
+        if self.otlp {
+            opentelemetry::global::shutdown_tracer_provider();
+        }
+    }
+}
+
+/// Install the subscriber behind `log` and `tracing`. Log lines are filtered
+/// by RUST_LOG as env_logger did, errors only by default. With
+/// OTEL_EXPORTER_OTLP_ENDPOINT the pipeline stage spans of every block are
+/// exported there as well, as OTEL_SERVICE_NAME (goat_prover).
+pub fn init() -> anyhow::Result<TracingGuard> {
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("error"));
+    let fmt = tracing_subscriber::fmt::layer().with_filter(filter);
+    let endpoint = env::var("OTEL_EXPORTER_OTLP_ENDPOINT")
+        .ok()
+        .filter(|endpoint| !endpoint.is_empty());
+    let Some(endpoint) = endpoint else {
+        tracing_subscriber::registry().with(fmt).try_init()?;
+        return Ok(TracingGuard { otlp: false });
+    };
+    let service_name = env::var("OTEL_SERVICE_NAME").unwrap_or("goat_prover".to_string());
+    let tracer = opentelemetry_otlp::new_pipeline()
+        .tracing()
+        .with_exporter(
+            opentelemetry_otlp::new_exporter()
+                .tonic()
+                .with_endpoint(&endpoint),
+        )
+        .with_trace_config(
+            trace::config().with_resource(Resource::new(vec![KeyValue::new(
+                "service.name",
+                service_name,
+            )])),
+        )
+        .install_batch(runtime::Tokio)?;
+    // Only the spans of this crate, not those of every dependency.
+    let spans = Targets::new().with_target(env!("CARGO_PKG_NAME"), Level::INFO);
+    let otlp = tracing_opentelemetry::layer()
+        .with_tracer(tracer)
+        .with_filter(spans);
+    tracing_subscriber::registry()
+        .with(fmt)
+        .with(otlp)
+        .try_init()?;
+    log::info!("Exporting traces to {}", endpoint);
+    Ok(TracingGuard { otlp: true })
+}
@@ -0,0 +1,154 @@
+use crate::summary::BlockSummary;
+use aws_sdk_s3::primitives::ByteStream;
+use aws_sdk_s3::Client;
+use md5::{Digest, Md5};
+use std::env;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::task::JoinHandle;
+
+/// Uploads proof artifacts to S3-compatible storage, configured by S3_BUCKET,
+/// S3_ENDPOINT and S3_PREFIX. Credentials come from the standard AWS
+/// environment variables.
+pub struct S3Sink {
+    client: Client,
+    bucket: String,
+    prefix: String,
+    outdir: String,
+    /// DELETE_AFTER_UPLOAD: remove the local file once its ETag is verified.
+    delete_after_upload: bool,
+    max_retries: u32,
+    pending: Mutex<Vec<JoinHandle<()>>>,
+}
+
+impl S3Sink {
+    /// `None` when S3_BUCKET is unset.
+    pub async fn from_env(outdir: &str) -> anyhow::Result<Option<Arc<Self>>> {
+        let Ok(bucket) = env::var("S3_BUCKET") else {
+            return Ok(None);
+        };
+        let prefix = env::var("S3_PREFIX").unwrap_or("".to_string());
+        let delete_after_upload = env::var("DELETE_AFTER_UPLOAD").unwrap_or("false".to_string());
+        let max_retries = env::var("S3_MAX_RETRIES").unwrap_or("3".to_string());
+
+        let mut loader = aws_config::defaults(aws_config::BehaviorVersion::latest());
+        if let Ok(endpoint) = env::var("S3_ENDPOINT") {
+            loader = loader.endpoint_url(endpoint);
+        }
+        let config = loader.load().await;
+        // S3-compatible stores generally only support path-style addressing.
+        let s3_config = aws_sdk_s3::config::Builder::from(&config)
+            .force_path_style(true)
+            .build();
+        Ok(Some(Arc::new(Self {
+            client: Client::from_conf(s3_config),
+            bucket,
+            prefix: prefix.trim_end_matches('/').to_string(),
+            outdir: outdir.to_string(),
+            delete_after_upload: delete_after_upload.parse().unwrap_or(false),
+            max_retries: max_retries.parse().unwrap_or(3),
+            pending: Mutex::new(Vec::new()),
+        })))
+    }
+
+    fn key(&self, block_no: u64, file_name: &str) -> String {
+        if self.prefix.is_empty() {
+            format!("{}/{}", block_no, file_name)
+        } else {
+            format!("{}/{}/{}", self.prefix, block_no, file_name)
+        }
+    }
+
+    /// Upload `path` to `key`, checking the returned ETag against the MD5 of
+    /// the content.
+    async fn upload_file(&self, path: &Path, key: &str) -> anyhow::Result<()> {
+        let body = tokio::fs::read(path).await?;
+        let expected_etag = format!("\"{:x}\"", Md5::digest(&body));
+        let mut attempts = 0;
+        loop {
+            attempts += 1;
+            let result = self
+                .client
+                .put_object()
+                .bucket(&self.bucket)
+                .key(key)
+                .body(ByteStream::from(body.clone()))
+                .send()
+                .await;
+            let error = match result {
+                Ok(output) if output.e_tag() == Some(expected_etag.as_str()) => break,
+                Ok(output) => format!(
+                    "ETag mismatch: expected {} got {:?}",
+                    expected_etag,
+                    output.e_tag()
+                ),
+                Err(e) => e.to_string(),
+            };
+            if attempts > self.max_retries {
+                anyhow::bail!("{} after {} attempts", error, attempts);
+            }
+            log::warn!("Failed to upload {}: {}. Retrying", key, error);
+            tokio::time::sleep(Duration::from_secs(5 * attempts as u64)).await;
+        }
+        if self.delete_after_upload {
+            tokio::fs::remove_file(path).await?;
+        }
+        Ok(())
+    }
+
+    /// Upload `files` of `block_no` in the background and record the result in
+    /// the block's summary.
+    pub fn spawn_upload(self: &Arc<Self>, block_no: u64, files: Vec<PathBuf>) {
+        let sink = self.clone();
+        let handle = tokio::spawn(async move {
+            let mut keys = Vec::new();
+            let mut errors = Vec::new();
+            for path in files {
+                let file_name = path
+                    .file_name()
+                    .map(|n| n.to_string_lossy().into_owned())
+                    .unwrap_or_default();
+                let file_name = file_name
+                    .strip_prefix(&format!("{}_", block_no))
+                    .unwrap_or(&file_name)
+                    .to_string();
+                let key = sink.key(block_no, &file_name);
+                match sink.upload_file(&path, &key).await {
+                    Ok(()) => {
+                        log::info!(
+                            "Uploaded {} to s3://{}/{}",
+                            path.display(),
+                            sink.bucket,
+                            key
+                        );
+                        keys.push(key);
+                    }
+                    Err(e) => {
+                        log::error!("Failed to upload {}: {}", path.display(), e);
+                        errors.push(format!("{}: {}", key, e));
+                    }
+                }
+            }
+            let updated = BlockSummary::update(&sink.outdir, block_no, |summary| {
+                summary.s3_keys = keys;
+                summary.s3_error = (!errors.is_empty()).then(|| errors.join("; "));
+            });
+            if let Err(e) = updated {
+                log::error!("Failed to record upload of block_no:{}: {}", block_no, e);
+            }
+        });
+        self.pending
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(handle);
+    }
+
+    /// Wait for the uploads still in flight.
+    pub async fn wait(&self) {
+        let pending = std::mem::take(&mut *self.pending.lock().unwrap_or_else(|e| e.into_inner()));
+        for handle in pending {
+            let _ = handle.await;
+        }
+    }
+}
@@ -0,0 +1,178 @@
+use crate::naming;
+use std::env;
+use std::path::{Path, PathBuf};
+use tokio::process::Command;
+
+/// Verifies proof files against the verifying key in VK_PATH.
+///
+/// The zkm SDK has no in-process verifier, so the check is delegated to the
+/// verifier binary shipped with the vk (VERIFIER_BIN, default `zkm-verifier`),
+/// invoked as `VERIFIER_BIN <vk_path> <proof_path>` and exiting 0 on a valid proof.
+#[derive(Debug, Clone)]
+pub struct ProofVerifier {
+    verifier_bin: String,
+    vk_path: String,
+}
+
+impl ProofVerifier {
+    pub fn new(verifier_bin: &str, vk_path: &str) -> Self {
+        Self {
+            verifier_bin: verifier_bin.to_string(),
+            vk_path: vk_path.to_string(),
+        }
+    }
+
+    /// Build the verifier from VERIFY_PROOF and VERIFIER_BIN, `None` if
+    /// verification is disabled.
+    pub fn from_env(vk_path: &str) -> anyhow::Result<Option<Self>> {
+        if !verification_enabled(env::var("VERIFY_PROOF").ok().as_deref(), vk_path)? {
+            return Ok(None);
+        }
+        let verifier_bin = env::var("VERIFIER_BIN").unwrap_or("zkm-verifier".to_string());
+        Ok(Some(Self::new(&verifier_bin, vk_path)))
+    }
+
+    pub async fn verify(&self, proof_path: &Path) -> anyhow::Result<()> {
+        match self.status(proof_path).await? {
+            ProofStatus::Valid => Ok(()),
+            ProofStatus::Invalid(e) | ProofStatus::Unreadable(e) => Err(anyhow::anyhow!(e)),
+        }
+    }
+
+    /// Verify the proof at `proof_path`, failing only if the verifier binary
+    /// cannot be run.
+    pub async fn status(&self, proof_path: &Path) -> anyhow::Result<ProofStatus> {
+        let buf = match std::fs::read(proof_path) {
+            Ok(buf) => buf,
+            Err(e) => {
+                return Ok(ProofStatus::Unreadable(format!(
+                    "failed to read proof {}: {}",
+                    proof_path.display(),
+                    e
+                )))
+            }
+        };
+        if let Err(e) = serde_json::from_slice::<serde_json::Value>(&buf) {
+            return Ok(ProofStatus::Unreadable(format!(
+                "invalid proof {}: {}",
+                proof_path.display(),
+                e
+            )));
+        }
+        let output = Command::new(&self.verifier_bin)
+            .arg(&self.vk_path)
+            .arg(proof_path)
+            .output()
+            .await
+            .map_err(|e| anyhow::anyhow!("failed to run {}: {}", self.verifier_bin, e))?;
+        if !output.status.success() {
+            return Ok(ProofStatus::Invalid(format!(
+                "proof {} rejected by the verifier: {}",
+                proof_path.display(),
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+        Ok(ProofStatus::Valid)
+    }
+}
+
+/// Outcome of verifying one proof file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProofStatus {
+    Valid,
+    /// Rejected by the verifier.
+    Invalid(String),
+    /// Not readable or not a proof at all.
+    Unreadable(String),
+}
+
+/// Whether proofs are verified. Verification is on by default but needs
+/// VK_PATH, without it a proof is only verified when VERIFY_PROOF asks for it.
+fn verification_enabled(verify_proof: Option<&str>, vk_path: &str) -> anyhow::Result<bool> {
+    match verify_proof {
+        Some(verify_proof) if !verify_proof.parse::<bool>().unwrap_or(true) => Ok(false),
+        Some(_) if vk_path.is_empty() => {
+            anyhow::bail!(
+                "VERIFY_PROOF is enabled but VK_PATH is empty, set VK_PATH or VERIFY_PROOF=false"
+            )
+        }
+        None if vk_path.is_empty() => {
+            log::warn!("VK_PATH is not set, proofs are not verified");
+            Ok(false)
+        }
+        _ => Ok(true),
+    }
+}
+
+/// Proof files in `dir`, named after the OUTPUT_NAME template, sorted.
+pub fn proof_files(dir: &Path) -> anyhow::Result<Vec<PathBuf>> {
+    let mut proofs = Vec::new();
+    for entry in std::fs::read_dir(dir)
+        .map_err(|e| anyhow::anyhow!("failed to read {}: {}", dir.display(), e))?
+    {
+        let entry_path = entry?.path();
+        let is_proof = entry_path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .is_some_and(|name| naming::template().block_range(name).is_some());
+        if is_proof {
+            proofs.push(entry_path);
+        }
+    }
+    proofs.sort();
+    Ok(proofs)
+}
+
+/// Verify `path`, a proof file or a directory of them, and print one line per
+/// proof with its public inputs. Returns the number of proofs that failed.
+pub async fn verify_files(verifier: &ProofVerifier, path: &str) -> anyhow::Result<usize> {
+    let path = Path::new(path);
+    let proofs = if path.is_dir() {
+        proof_files(path)?
+    } else {
+        vec![path.to_path_buf()]
+    };
+
+    let mut failed = 0;
+    for proof_path in &proofs {
+        match verifier.verify(proof_path).await {
+            Ok(()) => {
+                let public_inputs = std::fs::read(proof_path)
+                    .ok()
+                    .and_then(|buf| serde_json::from_slice(&buf).ok())
+                    .and_then(|proof| crate::binding::describe_public_inputs(&proof))
+                    .unwrap_or("-".to_string());
+                println!("{}\tok\t{}", proof_path.display(), public_inputs);
+            }
+            Err(e) => {
+                failed += 1;
+                println!("{}\tFAILED\t{}", proof_path.display(), e);
+            }
+        }
+    }
+    println!(
+        "{} proofs verified, {} failed",
+        proofs.len() - failed,
+        failed
+    );
+    Ok(failed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn verification_without_vk_path() {
+        assert!(!verification_enabled(None, "").unwrap());
+        assert!(verification_enabled(Some("true"), "").is_err());
+        assert!(!verification_enabled(Some("false"), "").unwrap());
+    }
+
+    #[test]
+    fn verification_with_vk_path() {
+        assert!(verification_enabled(None, "/vk").unwrap());
+        assert!(verification_enabled(Some("true"), "/vk").unwrap());
+        assert!(!verification_enabled(Some("false"), "/vk").unwrap());
+    }
+}
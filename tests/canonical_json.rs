@@ -0,0 +1,32 @@
+//! Golden-file test of the canonical suite JSON, the bytes proofs and
+//! metadata hashes are computed over.
+
+use goat_prover::{check, suite};
+use revm::primitives::keccak256;
+
+fn fixture(path: &str) -> String {
+    let path = format!("{}/tests/fixtures/{}", env!("CARGO_MANIFEST_DIR"), path);
+    std::fs::read_to_string(&path).unwrap_or_else(|e| panic!("{}: {}", path, e))
+}
+
+#[test]
+fn canonical_bytes_match_the_golden_file() {
+    let value: serde_json::Value = serde_json::from_str(&fixture("suite/unordered.json")).unwrap();
+    assert_eq!(
+        suite::canonical_value(&value),
+        fixture("suite/unordered.canonical.json")
+    );
+}
+
+#[test]
+fn canonical_suite_is_stable() {
+    let test_suite = check::decode_test_suite(fixture("check/transfer.json").as_bytes()).unwrap();
+    let json = suite::canonical_json(&test_suite).unwrap();
+    assert!(!json.contains(['\n', ' ']));
+    let decoded = check::decode_test_suite(json.as_bytes()).unwrap();
+    assert_eq!(suite::canonical_json(&decoded).unwrap(), json);
+
+    let encoded = suite::encode(&test_suite).unwrap();
+    assert_eq!(encoded, bincode::serialize(&json).unwrap());
+    assert_eq!(suite::suite_hash(&test_suite).unwrap(), keccak256(&encoded));
+}
@@ -0,0 +1,367 @@
+//! Checks of the hand-written suites under `tests/fixtures/check`, whose
+//! expected roots were computed independently of the executor.
+
+use goat_prover::check::{self, CheckConfig, CheckError};
+use goat_prover::suite;
+use revm::primitives::{address, b256};
+use std::time::Duration;
+
+fn fixture(name: &str) -> String {
+    let path = format!(
+        "{}/tests/fixtures/check/{}",
+        env!("CARGO_MANIFEST_DIR"),
+        name
+    );
+    std::fs::read_to_string(&path).unwrap_or_else(|e| panic!("{}: {}", path, e))
+}
+
+const TRANSFER_ROOT: &str = "0xdb3a0973337986d3c47c5a2f28350e845ec230b7cb79ce7671cf26d3a680635e";
+
+#[test]
+fn transfer_matches_its_post_state() {
+    let report =
+        check::execute_test_suite_json(&fixture("transfer.json"), &CheckConfig::default(), None)
+            .unwrap();
+    let outcomes = &report.units["transfer"].per_test;
+    assert_eq!(outcomes.len(), 2);
+    assert!(outcomes
+        .iter()
+        .all(|outcome| outcome.success && outcome.gas_used == 21_000));
+}
+
+#[test]
+fn transfer_with_a_wrong_root_is_a_post_state_mismatch() {
+    let wrong = "0x00000000000000000000000000000000000000000000000000000000000000aa";
+    let json = fixture("transfer.json").replace(TRANSFER_ROOT, wrong);
+    match check::execute_test_suite_json(&json, &CheckConfig::default(), None) {
+        Err(CheckError::PostStateMismatch {
+            spec,
+            index,
+            expected,
+            actual,
+            ..
+        }) => {
+            assert_eq!(spec, "London");
+            assert_eq!(index, 0);
+            assert_eq!(expected, wrong.parse().unwrap());
+            assert_eq!(
+                actual,
+                b256!("db3a0973337986d3c47c5a2f28350e845ec230b7cb79ce7671cf26d3a680635e")
+            );
+        }
+        other => panic!("expected a post state mismatch, got {:?}", other),
+    }
+}
+
+#[test]
+fn plain_and_bincode_wrapped_suites_check_alike() {
+    let json = fixture("transfer.json");
+    let wrapped = bincode::serialize(&json).unwrap();
+    let config = CheckConfig::default();
+    let plain = check::execute_test_suite(json.as_bytes(), &config, None).unwrap();
+    let wrapped = check::execute_test_suite(&wrapped, &config, None).unwrap();
+    assert_eq!(plain.receipts(&[]), wrapped.receipts(&[]));
+    assert_eq!(plain.gas_used(), wrapped.gas_used());
+    let decoded = [json.as_bytes().to_vec(), bincode::serialize(&json).unwrap()]
+        .map(|data| suite::canonical_json(&check::decode_test_suite(&data).unwrap()).unwrap());
+    assert_eq!(decoded[0], decoded[1]);
+}
+
+#[test]
+fn undecodable_suite_names_both_formats() {
+    match check::decode_test_suite(b"garbage") {
+        Err(e @ CheckError::Deserialize(_)) => assert!(
+            e.to_string()
+                .contains("neither plain JSON nor a bincode-wrapped JSON string"),
+            "{}",
+            e
+        ),
+        other => panic!("expected a deserialize error, got {:?}", other.map(|_| ())),
+    }
+}
+
+#[test]
+fn chain_id_is_the_configured_one() {
+    let json = fixture("chain_id.json");
+    let goat = CheckConfig {
+        chain_id: 2345,
+        ..CheckConfig::default()
+    };
+    check::execute_test_suite_json(&json, &goat, None).unwrap();
+    assert!(matches!(
+        check::execute_test_suite_json(&json, &CheckConfig::default(), None),
+        Err(CheckError::PostStateMismatch { .. })
+    ));
+}
+
+#[test]
+fn gas_price_below_the_base_fee() {
+    let json = fixture("underpriced.json");
+    let strict = CheckConfig {
+        disable_base_fee: false,
+        ..CheckConfig::default()
+    };
+    check::execute_test_suite_json(&json, &strict, None).unwrap();
+    match check::execute_test_suite_json(&json, &CheckConfig::default(), None) {
+        Err(CheckError::MissingExpectedException {
+            spec,
+            index,
+            expected,
+        }) => {
+            assert_eq!((spec.as_str(), index), ("London", 0));
+            assert_eq!(
+                expected,
+                "TransactionException.INSUFFICIENT_MAX_FEE_PER_GAS"
+            );
+        }
+        other => panic!("expected a missing exception, got {:?}", other),
+    }
+}
+
+#[test]
+fn delegated_code_runs_as_the_authority() {
+    let report =
+        check::execute_test_suite_json(&fixture("eip7702.json"), &CheckConfig::default(), None)
+            .unwrap();
+    let outcome = &report.units["delegation"].per_test[0];
+    assert_eq!(outcome.spec, "Prague");
+    let receipt = outcome.receipt.as_ref().unwrap();
+    let authority = address!("7e5f4552091a69125d5dfcb7b8c2659029395bdf");
+    assert_eq!(receipt.logs.len(), 1);
+    assert_eq!(receipt.logs[0].address, authority);
+    assert_eq!(receipt.logs[0].data.data[12..], authority[..]);
+}
+
+#[test]
+fn index_past_a_transaction_list_is_out_of_range() {
+    match check::execute_test_suite_json(&fixture("bad_index.json"), &CheckConfig::default(), None)
+    {
+        Err(CheckError::IndexOutOfRange {
+            unit,
+            kind,
+            index,
+            len,
+        }) => assert_eq!(
+            (unit.as_str(), kind, index, len),
+            ("bad_index", "value", 1, 1)
+        ),
+        other => panic!("expected an index out of range, got {:?}", other),
+    }
+}
+
+#[test]
+fn p256verify_only_on_goat_chains() {
+    let json = fixture("p256verify.json");
+    let output_len = |chain_id| {
+        let config = CheckConfig {
+            chain_id,
+            ..CheckConfig::default()
+        };
+        let report = check::execute_test_suite_json(&json, &config, None).unwrap();
+        report.units["p256verify"].per_test[0].output_len
+    };
+    assert_eq!(output_len(2345), 32);
+    assert_eq!(output_len(48816), 32);
+    assert_eq!(output_len(1), 0);
+}
+
+/// The transfer fixture with its transaction edited by `edit`.
+fn transfer_with(edit: impl FnOnce(&mut serde_json::Value)) -> String {
+    let mut suite: serde_json::Value = serde_json::from_str(&fixture("transfer.json")).unwrap();
+    edit(&mut suite["transfer"]["transaction"]);
+    suite.to_string()
+}
+
+#[test]
+fn sender_must_belong_to_the_secret_key() {
+    let json = transfer_with(|tx| {
+        tx["sender"] = "0x0000000000000000000000000000000000000042".into();
+    });
+    match check::execute_test_suite_json(&json, &CheckConfig::default(), None) {
+        Err(CheckError::SenderMismatch {
+            unit,
+            sender,
+            recovered,
+        }) => {
+            assert_eq!(unit, "transfer");
+            assert_eq!(sender, address!("0000000000000000000000000000000000000042"));
+            assert_eq!(
+                recovered,
+                address!("a94f5374fce5edbc8e2a8697c15331677e6ebf0b")
+            );
+        }
+        other => panic!("expected a sender mismatch, got {:?}", other),
+    }
+}
+
+#[test]
+fn sender_without_a_secret_key() {
+    let zero_key = "0x0000000000000000000000000000000000000000000000000000000000000000";
+    let json = transfer_with(|tx| tx["secretKey"] = zero_key.into());
+    check::execute_test_suite_json(&json, &CheckConfig::default(), None).unwrap();
+
+    let json = transfer_with(|tx| {
+        tx["secretKey"] = zero_key.into();
+        tx.as_object_mut().unwrap().remove("sender");
+    });
+    assert!(matches!(
+        check::execute_test_suite_json(&json, &CheckConfig::default(), None),
+        Err(CheckError::SenderRecovery {
+            reason: check::KeyError::Zero,
+            ..
+        })
+    ));
+}
+
+#[test]
+fn withdrawals_from_shanghai_on() {
+    let json = fixture("withdrawals.json");
+    let report = check::execute_test_suite_json(&json, &CheckConfig::default(), None).unwrap();
+    assert_eq!(report.units["withdrawal"].per_test.len(), 2);
+
+    let london = check::execute_block_suite(json.as_bytes(), &CheckConfig::default()).unwrap();
+    assert_eq!(
+        london.state_root,
+        b256!("db3a0973337986d3c47c5a2f28350e845ec230b7cb79ce7671cf26d3a680635e")
+    );
+    let shanghai = CheckConfig {
+        specs: check::SpecFilter {
+            only: Some(vec!["shanghai".to_string()]),
+            skip: Vec::new(),
+        },
+        ..CheckConfig::default()
+    };
+    let shanghai = check::execute_block_suite(json.as_bytes(), &shanghai).unwrap();
+    assert_eq!(
+        shanghai.state_root,
+        b256!("79ae3d4a802ba812d6048474bbad8518509c21eebba7a162a15d9ba5a97bf967")
+    );
+    assert_eq!(shanghai.gas_used, 21_000);
+}
+
+#[test]
+fn beacon_root_is_stored_before_the_transaction() {
+    let json = fixture("beacon_root.json");
+    let root = b256!("43c56201b42464bb7b8c7cfdf48dee94b3f450cce7fde5b86911cd8d96387635");
+    let report = check::execute_test_suite_json(&json, &CheckConfig::default(), None).unwrap();
+    let receipt = report.units["beacon_root"].per_test[0]
+        .receipt
+        .clone()
+        .unwrap();
+    assert_eq!(receipt.logs[0].data.data[..], root[..]);
+
+    let block = check::execute_block_suite(json.as_bytes(), &CheckConfig::default()).unwrap();
+    assert_eq!(
+        block.state_root,
+        b256!("9de58df306d1d2d6b92a28f1bbf4f17331a745600541c9f5a8083a9f5862b021")
+    );
+}
+
+#[test]
+fn block_executes_units_on_one_state() {
+    let json = fixture("block.json");
+    let report = check::execute_block_suite(json.as_bytes(), &CheckConfig::default()).unwrap();
+    assert_eq!(report.gas_used, 42_000);
+    assert_eq!(
+        report
+            .receipts
+            .iter()
+            .map(|receipt| (receipt.nonce, receipt.cumulative_gas_used))
+            .collect::<Vec<_>>(),
+        [(0, 21_000), (1, 42_000)]
+    );
+    assert_eq!(
+        report.state_root,
+        b256!("3996abd6f9f33a7f29958074c1068606efd28257847e6d017dc70e705ce31a85")
+    );
+
+    let json = json.replace(
+        "0x3996abd6f9f33a7f29958074c1068606efd28257847e6d017dc70e705ce31a85",
+        TRANSFER_ROOT,
+    );
+    assert!(matches!(
+        check::execute_block_suite(json.as_bytes(), &CheckConfig::default()),
+        Err(CheckError::PostStateMismatch { .. })
+    ));
+}
+
+#[test]
+fn block_checks_transactions_like_units() {
+    let below_intrinsic_gas = transfer_with(|tx| tx["gasLimit"][0] = "0x5207".into());
+    assert!(matches!(
+        check::execute_block_suite(below_intrinsic_gas.as_bytes(), &CheckConfig::default()),
+        Err(CheckError::Rejected { .. })
+    ));
+
+    let strict = CheckConfig {
+        disable_base_fee: false,
+        ..CheckConfig::default()
+    };
+    let json = fixture("underpriced.json");
+    let report = check::execute_block_suite(json.as_bytes(), &strict).unwrap();
+    assert!(report.receipts.is_empty());
+    let json = json.replace("INSUFFICIENT_MAX_FEE_PER_GAS", "INTRINSIC_GAS_TOO_LOW");
+    assert!(matches!(
+        check::execute_block_suite(json.as_bytes(), &strict),
+        Err(CheckError::ExceptionMismatch { .. })
+    ));
+    let loose = CheckConfig {
+        loose_exceptions: true,
+        ..strict
+    };
+    check::execute_block_suite(json.as_bytes(), &loose).unwrap();
+}
+
+#[test]
+fn timeout_is_opt_in() {
+    let json = fixture("loop.json");
+    let report = check::execute_test_suite_json(&json, &CheckConfig::default(), None).unwrap();
+    let outcome = &report.units["loop"].per_test[0];
+    assert!(!outcome.success);
+    assert_eq!(outcome.gas_used, 10_000_000);
+
+    let bounded = CheckConfig {
+        test_timeout: Some(Duration::from_nanos(1)),
+        ..CheckConfig::default()
+    };
+    assert!(matches!(
+        check::execute_test_suite_json(&json, &bounded, None),
+        Err(CheckError::Timeout { .. })
+    ));
+    assert!(matches!(
+        check::execute_block_suite(json.as_bytes(), &bounded),
+        Err(CheckError::Timeout { .. })
+    ));
+}
+
+#[test]
+fn selfdestruct_before_and_after_cancun() {
+    let report = check::execute_test_suite_json(
+        &fixture("selfdestruct.json"),
+        &CheckConfig::default(),
+        None,
+    )
+    .unwrap();
+    for unit in ["destruct_preexisting", "create_and_destruct"] {
+        let specs: Vec<_> = report.units[unit]
+            .per_test
+            .iter()
+            .map(|outcome| outcome.spec.as_str())
+            .collect();
+        assert_eq!(specs, ["Shanghai", "Cancun"], "{}", unit);
+    }
+}
+
+#[test]
+fn selfdestruct_legacy_suites() {
+    let json = fixture("selfdestruct_legacy.json");
+    assert!(matches!(
+        check::execute_test_suite_json(&json, &CheckConfig::default(), None),
+        Err(CheckError::PostStateMismatch { .. })
+    ));
+    let legacy = CheckConfig {
+        selfdestruct_legacy_suites: true,
+        ..CheckConfig::default()
+    };
+    check::execute_test_suite_json(&json, &legacy, None).unwrap();
+}
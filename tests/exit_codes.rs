@@ -0,0 +1,73 @@
+//! Exit codes of the binary for failures induced from the command line.
+
+use std::process::Command;
+
+fn goat_prover(args: &[&str], envs: &[(&str, &str)]) -> i32 {
+    let output = Command::new(env!("CARGO_BIN_EXE_goat_prover"))
+        .args(args)
+        .env_clear()
+        .env("RUST_LOG", "off")
+        .envs(envs.iter().copied())
+        .output()
+        .unwrap();
+    output.status.code().unwrap()
+}
+
+fn temp_file(name: &str, content: &str) -> String {
+    let path = std::env::temp_dir().join(format!("exit-codes-{}-{}", std::process::id(), name));
+    std::fs::write(&path, content).unwrap();
+    path.to_string_lossy().into_owned()
+}
+
+#[test]
+fn unknown_subcommand_is_a_config_error() {
+    assert_eq!(goat_prover(&["chekc", "1.json"], &[]), 2);
+}
+
+#[test]
+fn config_errors() {
+    assert_eq!(goat_prover(&["--config"], &[]), 2);
+    assert_eq!(
+        goat_prover(&["check", "1.json"], &[("CHAIN_ID", "goat")]),
+        2
+    );
+}
+
+#[test]
+fn missing_suite_is_an_io_error() {
+    assert_eq!(goat_prover(&["check", "/nonexistent/1.json"], &[]), 6);
+}
+
+#[test]
+fn invalid_suite_is_a_check_error() {
+    let path = temp_file("1.json", "not a suite");
+    assert_eq!(goat_prover(&["check", &path], &[]), 4);
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn prove_file_accepts_plain_json_suites() {
+    let suite = concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/fixtures/check/transfer.json"
+    );
+    let outdir = std::env::temp_dir().join(format!("exit-codes-{}-prove-file", std::process::id()));
+    let outdir = outdir.to_string_lossy().into_owned();
+    let args = [
+        "prove-file",
+        "--suite",
+        suite,
+        "--elf",
+        "/nonexistent/evm",
+        "--block",
+        "7",
+        "--out",
+        &outdir,
+    ];
+    // The suite is decoded and checked, proving then fails on the missing ELF.
+    assert_eq!(goat_prover(&args, &[("VERIFY_PROOF", "false")]), 6);
+    let wrapped = std::fs::read(format!("{}/7.wrapped.json", outdir)).unwrap();
+    let json: String = bincode::deserialize(&wrapped).unwrap();
+    assert!(json.contains("\"transfer\""));
+    std::fs::remove_dir_all(outdir).unwrap();
+}
@@ -1,8 +1,10 @@
+use alerts::{Event, EventKind};
 use ethers::prelude::*;
 use k256::pkcs8::der::Encode;
 use log::{error, info};
 use serde::Deserialize;
-use std::{fs, sync::Arc};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+use std::{fs, sync::Arc, time::Duration};
 use tokio::sync::mpsc;
 
 #[derive(Deserialize)]
@@ -11,12 +13,112 @@ struct Config {
     sidechain: SidechainConfig,
     filter: FilterConfig,
     daconfig: da_service::DaServiceConfig,
+    #[serde(default)]
+    batch: BatchConfig,
+    #[serde(default)]
+    clock: ClockConfig,
+    #[serde(default)]
+    alerts: alerts::AlertsConfig,
+}
+
+/// How often the clock is checked again after startup.
+const CLOCK_CHECK_INTERVAL: Duration = Duration::from_secs(600);
+
+/// Limits of the difference between the local clock and the time of the
+/// Celestia network head. A synced node's head is only seconds old, so a
+/// local clock behind it is off. A clock ahead of it may also be a lagging
+/// node or a stalled network, which only warns.
+#[derive(Deserialize, Debug, Clone)]
+struct ClockConfig {
+    warn_secs: u64,
+    max_secs: u64,
+}
+
+impl Default for ClockConfig {
+    fn default() -> Self {
+        Self {
+            warn_secs: 60,
+            max_secs: 600,
+        }
+    }
+}
+
+impl ClockConfig {
+    /// Judge `skew`, the seconds the local clock is ahead of the head time.
+    /// Returns whether the skew is worth a warning.
+    fn judge(&self, skew: i64) -> anyhow::Result<bool> {
+        if skew < 0 && skew.unsigned_abs() > self.max_secs {
+            anyhow::bail!(
+                "local clock is {} secs behind the Celestia network head (limit {} secs), fix the clock or pass --ignore-clock-skew",
+                -skew,
+                self.max_secs
+            );
+        }
+        Ok(skew.unsigned_abs() > self.warn_secs)
+    }
+
+    async fn check(&self, da_service: &da_service::CelestiaService) -> anyhow::Result<()> {
+        let (height, head_time) = da_service.network_head().await?;
+        let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs() as i64;
+        let skew = now - head_time;
+        if self.judge(skew)? {
+            log::warn!(
+                "Local clock is {} secs off the time of Celestia header {}, a lagging node also shows as a clock ahead",
+                skew,
+                height
+            );
+        } else {
+            log::debug!(
+                "Clock skew: {} secs against Celestia header {}",
+                skew,
+                height
+            );
+        }
+        Ok(())
+    }
+}
+
+#[derive(Deserialize)]
+struct BatchConfig {
+    /// Blobs of a block are split past this size, a transaction larger than
+    /// it still goes in a blob of its own.
+    max_blob_size: usize,
+}
+
+impl Default for BatchConfig {
+    fn default() -> Self {
+        Self {
+            max_blob_size: 1_000_000,
+        }
+    }
+}
+
+/// The forwarded transactions of a block.
+#[derive(Debug)]
+struct BlockBatch {
+    number: u64,
+    hash: H256,
+    transactions: Vec<Transaction>,
 }
 
 #[derive(Deserialize)]
 struct EthereumConfig {
     rpc_url: String,
+    /// Websocket endpoint, required with `mode = "subscribe"`.
+    ws_url: Option<String>,
     start_height: u64,
+    #[serde(default)]
+    mode: SourceMode,
+}
+
+/// How new blocks are found: by height over `rpc_url` (default) or by a
+/// block subscription over `ws_url`.
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+enum SourceMode {
+    #[default]
+    Poll,
+    Subscribe,
 }
 
 #[derive(Deserialize)]
@@ -26,10 +128,51 @@ struct SidechainConfig {
 
 #[derive(Deserialize)]
 struct FilterConfig {
+    /// Comma-separated addresses whose transactions are forwarded, `create`
+    /// for contract creations. All transactions when empty.
     target_address: String,
 }
 
-pub mod da_service;
+/// The `[filter] target_address` list, parsed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct TargetFilter {
+    addresses: Vec<H160>,
+    creates: bool,
+}
+
+impl TargetFilter {
+    /// `None` for an empty list, no transaction is filtered out then.
+    fn parse(list: &str) -> anyhow::Result<Option<Self>> {
+        let mut filter = Self {
+            addresses: Vec::new(),
+            creates: false,
+        };
+        for entry in list.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+            if entry.eq_ignore_ascii_case("create") {
+                filter.creates = true;
+                continue;
+            }
+            let address = entry.parse::<H160>().map_err(|e| {
+                anyhow::anyhow!("invalid [filter] target_address {:?}: {}", entry, e)
+            })?;
+            filter.addresses.push(address);
+        }
+        let empty = filter.addresses.is_empty() && !filter.creates;
+        Ok((!empty).then_some(filter))
+    }
+
+    /// Whether `tx` is sent by or to a listed address, or is a contract
+    /// creation while `create` is listed.
+    fn matches(&self, tx: &Transaction) -> bool {
+        if self.addresses.contains(&tx.from) {
+            return true;
+        }
+        match tx.to {
+            Some(to) => self.addresses.contains(&to),
+            None => self.creates,
+        }
+    }
+}
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
@@ -44,71 +187,226 @@ async fn main() -> anyhow::Result<()> {
     let sidechain_provider = Provider::<Http>::try_from(config.sidechain.rpc_url.clone())?;
     let _sidechain_provider = Arc::new(sidechain_provider);
 
+    let chain_id = provider.get_chainid().await?;
+    let notifier = Arc::new(alerts::Notifier::new(
+        &config.alerts,
+        "tx_transfer",
+        &chain_id.to_string(),
+    )?);
+
     let da_service = da_service::CelestiaService::new(config.daconfig).await;
+    let ignore_clock_skew = std::env::args().any(|arg| arg == "--ignore-clock-skew");
+    let clock = (!ignore_clock_skew).then_some(config.clock);
+    match &clock {
+        Some(clock) => {
+            if let Err(e) = clock.check(&da_service).await {
+                alert(&notifier, EventKind::ClockSkew, &e).await;
+                return Err(e);
+            }
+        }
+        None => log::warn!("Clock skew check is disabled by --ignore-clock-skew"),
+    }
 
     let (tx, mut rx) = mpsc::channel(100);
 
     let provider_clone = provider.clone();
-    let _filter_target = config.filter.target_address.clone();
+    let target_filter = TargetFilter::parse(&config.filter.target_address)?;
+    match &target_filter {
+        Some(filter) => info!("Forwarding transactions matching {:?}", filter),
+        None => info!("Forwarding all transactions"),
+    }
+    let start_height = config.ethereum.start_height;
+    let ws_url =
+        match config.ethereum.mode {
+            SourceMode::Poll => None,
+            SourceMode::Subscribe => Some(config.ethereum.ws_url.clone().ok_or_else(|| {
+                anyhow::anyhow!("[ethereum] mode = \"subscribe\" requires ws_url")
+            })?),
+        };
 
+    let source_notifier = notifier.clone();
     tokio::spawn(async move {
-        // if let Err(e) = listen_ethereum_transactions(provider_clone, filter_target, tx).await {
-        //     error!("Error while listening to Ethereum transactions: {:?}", e);
-        // }
-        if let Err(e) =
-            process_blocks_from_height(provider_clone, config.ethereum.start_height, None, tx).await
-        {
+        let target = target_filter.as_ref();
+        let result = match ws_url {
+            Some(ws_url) => {
+                subscribe_blocks_from_height(provider_clone, &ws_url, start_height, target, tx)
+                    .await
+            }
+            None => process_blocks_from_height(provider_clone, start_height, target, tx).await,
+        };
+        if let Err(e) = result {
             error!("Error while listening to Ethereum transactions: {:?}", e);
+            alert(&source_notifier, EventKind::SourceFailed, &e).await;
         }
     });
 
-    while let Some(transaction) = rx.recv().await {
+    let max_blob_size = config.batch.max_blob_size;
+    let mut last_clock_check = Instant::now();
+    while let Some(batch) = rx.recv().await {
+        if let Some(clock) = &clock {
+            if last_clock_check.elapsed() > CLOCK_CHECK_INTERVAL {
+                if let Err(e) = clock.check(&da_service).await {
+                    error!("Clock check failed: {}", e);
+                    if let Some(notifier) = notifier.as_ref() {
+                        let mut event = Event::new(EventKind::ClockSkew, None);
+                        event.error = Some(format!("{:#}", e));
+                        notifier.notify(event);
+                    }
+                }
+                last_clock_check = Instant::now();
+            }
+        }
         // if let Err(e) = forward_to_sidechain(sidechain_provider.clone(), transaction).await {
         //     error!("Error while forwarding transaction: {:?}", e);
         // }
-        if let Err(e) = forward_to_da(da_service.clone(), transaction).await {
+        let number = batch.number;
+        if let Err(e) = forward_to_da(da_service.clone(), batch, max_blob_size).await {
             error!("Error while forwarding transaction: {:?}", e);
+            if let Some(notifier) = notifier.as_ref() {
+                let mut event = Event::new(EventKind::BlobFailed, Some(number));
+                event.error = Some(format!("{:#}", e));
+                notifier.notify(event);
+            }
         }
     }
 
+    if let Some(notifier) = notifier.as_ref() {
+        notifier.flush().await;
+    }
     Ok(())
 }
 
-#[allow(dead_code)]
-async fn listen_ethereum_transactions(
+/// Notify the webhook of `kind` and wait for the delivery, the process may
+/// be about to exit.
+async fn alert(notifier: &Option<alerts::Notifier>, kind: EventKind, e: &anyhow::Error) {
+    if let Some(notifier) = notifier {
+        let mut event = Event::new(kind, None);
+        event.error = Some(format!("{:#}", e));
+        notifier.notify(event);
+        notifier.flush().await;
+    }
+}
+
+/// Forward the transactions of `block` that pass the target filter, the same
+/// in both source modes.
+async fn forward_block(
+    block: Block<Transaction>,
+    target_address: Option<&TargetFilter>,
+    tx_sender: &mpsc::Sender<BlockBatch>,
+) -> anyhow::Result<()> {
+    let transactions: Vec<_> = block
+        .transactions
+        .into_iter()
+        .filter(|tx| target_address.map_or(true, |filter| filter.matches(tx)))
+        .collect();
+    if transactions.is_empty() {
+        return Ok(());
+    }
+    for tx in &transactions {
+        info!("Forwarding transaction: {:?}", tx);
+    }
+    let batch = BlockBatch {
+        number: block.number.unwrap_or_default().as_u64(),
+        hash: block.hash.unwrap_or_default(),
+        transactions,
+    };
+    tx_sender
+        .send(batch)
+        .await
+        .map_err(|e| anyhow::anyhow!(e))?;
+    Ok(())
+}
+
+/// Forward the blocks from `start_height` as the subscription over `ws_url`
+/// announces them. Blocks the subscription skipped are fetched by height,
+/// and when it drops the blocks up to the head are polled over `provider`
+/// before reconnecting with backoff, so none are missed.
+async fn subscribe_blocks_from_height(
     provider: Arc<Provider<Http>>,
-    target_address: String,
-    tx_sender: mpsc::Sender<Transaction>,
+    ws_url: &str,
+    start_height: u64,
+    target_address: Option<&TargetFilter>,
+    tx_sender: mpsc::Sender<BlockBatch>,
 ) -> anyhow::Result<()> {
-    let block_stream = provider.watch_blocks().await?;
-    let mut block_stream = block_stream.stream();
-
-    while let Some(block_hash) = block_stream.next().await {
-        info!("Received new block: {:?}", block_hash);
-        if let Ok(Some(block)) = provider.get_block_with_txs(block_hash).await {
-            info!("Received block with transactions: {:?}", block);
-            for tx in block.transactions {
-                if tx
-                    .to
-                    .map(|to| to == target_address.parse().unwrap())
-                    .unwrap_or(false)
-                {
-                    info!("Filtered transaction: {:?}", tx);
-                    tx_sender.send(tx).await.map_err(|e| anyhow::anyhow!(e))?;
+    let mut next_height = start_height;
+    let mut backoff = Duration::from_secs(1);
+    loop {
+        match Provider::<Ws>::connect(ws_url).await {
+            Ok(ws_provider) => match ws_provider.subscribe_blocks().await {
+                Ok(mut headers) => {
+                    info!("Subscribed to blocks at {}", ws_url);
+                    backoff = Duration::from_secs(1);
+                    'headers: while let Some(header) = headers.next().await {
+                        let Some(number) = header.number else {
+                            continue;
+                        };
+                        while next_height <= number.as_u64() {
+                            match ws_provider.get_block_with_txs(next_height).await {
+                                Ok(Some(block)) => {
+                                    info!(
+                                        "Processing block number: {} txs: {}",
+                                        next_height,
+                                        block.transactions.len(),
+                                    );
+                                    forward_block(block, target_address, &tx_sender).await?;
+                                    next_height += 1;
+                                }
+                                Ok(None) => break,
+                                Err(e) => {
+                                    info!(
+                                        "Error fetching block at height {}: {:?}",
+                                        next_height, e
+                                    );
+                                    break 'headers;
+                                }
+                            }
+                        }
+                    }
+                    info!("Block subscription dropped at height {}", next_height);
                 }
-            }
+                Err(e) => error!("Failed to subscribe to blocks at {}: {:?}", ws_url, e),
+            },
+            Err(e) => error!("Failed to connect to {}: {:?}", ws_url, e),
         }
+
+        next_height = catch_up(&provider, next_height, target_address, &tx_sender).await?;
+        tokio::time::sleep(backoff).await;
+        backoff = (backoff * 2).min(Duration::from_secs(60));
     }
+}
 
-    Ok(())
+/// Poll the blocks from `height` up to the head, returning the next height.
+async fn catch_up(
+    provider: &Provider<Http>,
+    mut height: u64,
+    target_address: Option<&TargetFilter>,
+    tx_sender: &mpsc::Sender<BlockBatch>,
+) -> anyhow::Result<u64> {
+    loop {
+        match provider.get_block_with_txs(height).await {
+            Ok(Some(block)) => {
+                info!(
+                    "Polling block number: {} txs: {}",
+                    height,
+                    block.transactions.len(),
+                );
+                forward_block(block, target_address, tx_sender).await?;
+                height += 1;
+            }
+            Ok(None) => return Ok(height),
+            Err(e) => {
+                info!("Error fetching block at height {}: {:?}", height, e);
+                return Ok(height);
+            }
+        }
+    }
 }
 
-#[allow(dead_code)]
-pub async fn process_blocks_from_height(
+async fn process_blocks_from_height(
     provider: Arc<Provider<Http>>,
     start_height: u64,
-    target_address: Option<H160>,
-    tx_sender: mpsc::Sender<Transaction>,
+    target_address: Option<&TargetFilter>,
+    tx_sender: mpsc::Sender<BlockBatch>,
 ) -> anyhow::Result<()> {
     let mut current_height = start_height;
 
@@ -120,15 +418,7 @@ pub async fn process_blocks_from_height(
                     current_height,
                     block.transactions.len(),
                 );
-                for tx in block.transactions {
-                    if let Some(target) = target_address {
-                        if tx.to != Some(target) {
-                            continue;
-                        }
-                    }
-                    info!("Forwarding transaction: {:?}", tx);
-                    tx_sender.send(tx).await.map_err(|e| anyhow::anyhow!(e))?;
-                }
+                forward_block(block, target_address, &tx_sender).await?;
                 current_height += 1;
             }
             Ok(None) => {
@@ -165,25 +455,287 @@ async fn forward_to_sidechain(
     Ok(())
 }
 
-#[allow(dead_code)]
+/// Submit the transactions of `batch` to Celestia in as few blobs of at most
+/// `max_blob_size` bytes as they fit in, see `encode_batch`.
 async fn forward_to_da(
     provider: da_service::CelestiaService,
-    transaction: Transaction,
+    batch: BlockBatch,
+    max_blob_size: usize,
 ) -> anyhow::Result<()> {
-    // let tx_request = TransactionRequest::new()
-    //     .from(transaction.from)
-    //     .to(transaction.to.unwrap())
-    //     .value(transaction.value)
-    //     .data(transaction.input)
-    //     .gas(21000)
-    //     .gas_price(1_000_000_000u64);
-
-    let block_json = serde_json::to_string(&transaction)?;
-    let pending_tx = provider
-        .send_transaction(block_json.as_bytes())
-        .await
-        .unwrap();
-    info!("Forwarded transaction with hash: {:?}", pending_tx);
+    let entries = batch
+        .transactions
+        .iter()
+        .map(serde_json::to_vec)
+        .collect::<Result<Vec<_>, _>>()?;
+    let blobs = encode_batch(batch.number, batch.hash, &entries, max_blob_size);
+    for (i, blob) in blobs.iter().enumerate() {
+        let submission = provider.send_transaction(blob).await?;
+        info!(
+            "Forwarded block {} blob {}/{} of {} bytes to DA height:{} commitment:{}",
+            batch.number,
+            i + 1,
+            blobs.len(),
+            blob.len(),
+            submission.height,
+            submission.commitment
+        );
+    }
 
+    let gas = |blobs: &[Vec<u8>]| -> usize {
+        blobs
+            .iter()
+            .map(|blob| da_service::get_gas_limit_for_bytes(blob.len()))
+            .sum()
+    };
+    info!(
+        "Block {}: {} txs in {} blobs of {} bytes, gas limit {} instead of {} for a blob per tx",
+        batch.number,
+        entries.len(),
+        blobs.len(),
+        blobs.iter().map(Vec::len).sum::<usize>(),
+        gas(&blobs),
+        gas(&entries),
+    );
     Ok(())
 }
+
+/// Block number (u64), block hash and entry count (u32) of a batch blob, big
+/// endian.
+const BATCH_HEADER_SIZE: usize = 8 + 32 + 4;
+
+/// Blobs of the JSON `entries` of block `number`, each the header followed by
+/// its entries prefixed with their u32 big endian length. A blob takes
+/// entries in order while it stays within `max_blob_size`.
+fn encode_batch(
+    number: u64,
+    hash: H256,
+    entries: &[Vec<u8>],
+    max_blob_size: usize,
+) -> Vec<Vec<u8>> {
+    let mut groups: Vec<&[Vec<u8>]> = Vec::new();
+    let mut start = 0;
+    let mut size = BATCH_HEADER_SIZE;
+    for (i, entry) in entries.iter().enumerate() {
+        let entry_size = 4 + entry.len();
+        if i > start && size + entry_size > max_blob_size {
+            groups.push(&entries[start..i]);
+            start = i;
+            size = BATCH_HEADER_SIZE;
+        }
+        size += entry_size;
+    }
+    groups.push(&entries[start..]);
+
+    groups
+        .into_iter()
+        .map(|group| {
+            let mut blob = Vec::with_capacity(
+                BATCH_HEADER_SIZE + group.iter().map(|e| 4 + e.len()).sum::<usize>(),
+            );
+            blob.extend_from_slice(&number.to_be_bytes());
+            blob.extend_from_slice(hash.as_bytes());
+            blob.extend_from_slice(&(group.len() as u32).to_be_bytes());
+            for entry in group {
+                blob.extend_from_slice(&(entry.len() as u32).to_be_bytes());
+                blob.extend_from_slice(entry);
+            }
+            blob
+        })
+        .collect()
+}
+
+/// Block number, block hash and entries of a blob of `encode_batch`.
+#[allow(dead_code)]
+fn decode_batch(blob: &[u8]) -> anyhow::Result<(u64, H256, Vec<Vec<u8>>)> {
+    if blob.len() < BATCH_HEADER_SIZE {
+        anyhow::bail!("batch blob of {} bytes has no header", blob.len());
+    }
+    let (header, mut rest) = blob.split_at(BATCH_HEADER_SIZE);
+    let number = u64::from_be_bytes(header[..8].try_into()?);
+    let hash = H256::from_slice(&header[8..40]);
+    let count = u32::from_be_bytes(header[40..].try_into()?);
+    let mut entries = Vec::new();
+    for i in 0..count {
+        let len = match rest.get(..4) {
+            Some(len) => u32::from_be_bytes(len.try_into()?) as usize,
+            None => anyhow::bail!("batch blob ends at the length of entry {}", i),
+        };
+        let Some(entry) = rest.get(4..4 + len) else {
+            anyhow::bail!("batch blob ends within entry {} of {} bytes", i, len);
+        };
+        entries.push(entry.to_vec());
+        rest = &rest[4 + len..];
+    }
+    if !rest.is_empty() {
+        anyhow::bail!(
+            "{} bytes after the {} entries of the batch blob",
+            rest.len(),
+            count
+        );
+    }
+    Ok((number, hash, entries))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TARGET: &str = "0x00000000000000000000000000000000000000aa";
+
+    fn tx(from: u64, to: Option<u64>) -> Transaction {
+        Transaction {
+            from: H160::from_low_u64_be(from),
+            to: to.map(H160::from_low_u64_be),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn empty_list_is_no_filter() {
+        assert_eq!(TargetFilter::parse("").unwrap(), None);
+        assert_eq!(TargetFilter::parse(" , ").unwrap(), None);
+    }
+
+    #[test]
+    fn malformed_address_is_an_error() {
+        let e = TargetFilter::parse(&format!("{},0xzz", TARGET)).unwrap_err();
+        assert!(e.to_string().contains("\"0xzz\""), "{}", e);
+    }
+
+    #[test]
+    fn matches_sender_and_recipient() {
+        let filter = TargetFilter::parse(TARGET).unwrap().unwrap();
+        assert!(filter.matches(&tx(0xaa, Some(1))));
+        assert!(filter.matches(&tx(1, Some(0xaa))));
+        assert!(!filter.matches(&tx(1, Some(2))));
+        assert!(!filter.matches(&tx(1, None)));
+    }
+
+    #[test]
+    fn create_matches_contract_creations() {
+        let filter = TargetFilter::parse(&format!("create, {}", TARGET))
+            .unwrap()
+            .unwrap();
+        assert!(filter.matches(&tx(1, None)));
+        assert!(filter.matches(&tx(1, Some(0xaa))));
+        assert!(!filter.matches(&tx(1, Some(2))));
+
+        let filter = TargetFilter::parse("CREATE").unwrap().unwrap();
+        assert!(filter.matches(&tx(1, None)));
+        assert!(!filter.matches(&tx(1, Some(2))));
+    }
+
+    fn decode_all(blobs: &[Vec<u8>]) -> Vec<Vec<u8>> {
+        let mut entries = Vec::new();
+        for blob in blobs {
+            let (number, hash, blob_entries) = decode_batch(blob).unwrap();
+            assert_eq!((number, hash), (7, H256::repeat_byte(0x11)));
+            entries.extend(blob_entries);
+        }
+        entries
+    }
+
+    #[test]
+    fn clock_behind_the_head_fails() {
+        let clock = ClockConfig::default();
+        assert!(!clock.judge(0).unwrap());
+        assert!(!clock.judge(-60).unwrap());
+        assert!(clock.judge(-61).unwrap());
+        assert!(clock.judge(-600).unwrap());
+        assert!(clock.judge(-601).is_err());
+        // Ahead of the head may be node lag, only warned about.
+        assert!(clock.judge(3600).unwrap());
+    }
+
+    #[test]
+    fn batch_round_trip() {
+        let hash = H256::repeat_byte(0x11);
+        let entries = vec![
+            b"{}".to_vec(),
+            vec![],
+            serde_json::to_vec(&tx(1, None)).unwrap(),
+        ];
+        let blobs = encode_batch(7, hash, &entries, 1_000_000);
+        assert_eq!(blobs.len(), 1);
+        assert_eq!(decode_batch(&blobs[0]).unwrap(), (7, hash, entries));
+
+        // A block without entries still encodes to a header.
+        let blobs = encode_batch(7, hash, &[], 1_000_000);
+        assert_eq!(blobs.len(), 1);
+        assert_eq!(blobs[0].len(), BATCH_HEADER_SIZE);
+        assert_eq!(decode_batch(&blobs[0]).unwrap(), (7, hash, vec![]));
+    }
+
+    #[test]
+    fn batch_splits_at_max_blob_size() {
+        let hash = H256::repeat_byte(0x11);
+        let entries = vec![vec![1; 10], vec![2; 10], vec![3; 10]];
+        // Exactly two entries fit.
+        let max_blob_size = BATCH_HEADER_SIZE + 2 * 14;
+        let blobs = encode_batch(7, hash, &entries, max_blob_size);
+        assert_eq!(blobs.len(), 2);
+        assert_eq!(blobs[0].len(), max_blob_size);
+        assert!(blobs.iter().all(|blob| blob.len() <= max_blob_size));
+        assert_eq!(decode_all(&blobs), entries);
+
+        // One byte less and every entry needs a blob of its own.
+        let blobs = encode_batch(7, hash, &entries, max_blob_size - 1);
+        assert_eq!(blobs.len(), 3);
+        assert_eq!(decode_all(&blobs), entries);
+    }
+
+    #[test]
+    fn oversized_entry_gets_a_blob_of_its_own() {
+        let hash = H256::repeat_byte(0x11);
+        let entries = vec![vec![1; 10], vec![2; 100], vec![3; 10], vec![4; 10]];
+        let max_blob_size = BATCH_HEADER_SIZE + 2 * 14;
+        let blobs = encode_batch(7, hash, &entries, max_blob_size);
+        let sizes: Vec<_> = blobs
+            .iter()
+            .map(|blob| decode_batch(blob).unwrap().2.len())
+            .collect();
+        assert_eq!(sizes, vec![1, 1, 2]);
+        assert_eq!(blobs[1].len(), BATCH_HEADER_SIZE + 104);
+        assert_eq!(decode_all(&blobs), entries);
+    }
+
+    #[test]
+    fn truncated_batch_is_an_error() {
+        let blobs = encode_batch(7, H256::zero(), &[vec![1; 10]], 1_000_000);
+        let blob = &blobs[0];
+        assert!(decode_batch(&blob[..BATCH_HEADER_SIZE - 1]).is_err());
+        assert!(decode_batch(&blob[..BATCH_HEADER_SIZE + 2]).is_err());
+        assert!(decode_batch(&blob[..blob.len() - 1]).is_err());
+        let mut longer = blob.clone();
+        longer.push(0);
+        assert!(decode_batch(&longer).is_err());
+    }
+
+    #[tokio::test]
+    async fn forward_block_filters_transactions() {
+        let block = Block {
+            number: Some(7.into()),
+            transactions: vec![tx(1, Some(0xaa)), tx(1, Some(2)), tx(1, None)],
+            ..Default::default()
+        };
+        let (sender, mut receiver) = mpsc::channel(4);
+
+        forward_block(block.clone(), None, &sender).await.unwrap();
+        let batch = receiver.recv().await.unwrap();
+        assert_eq!((batch.number, batch.transactions.len()), (7, 3));
+
+        let filter = TargetFilter::parse(TARGET).unwrap();
+        forward_block(block.clone(), filter.as_ref(), &sender)
+            .await
+            .unwrap();
+        let batch = receiver.recv().await.unwrap();
+        assert_eq!(batch.transactions, vec![tx(1, Some(0xaa))]);
+
+        // A block without a matching transaction sends no batch.
+        let filter = TargetFilter::parse("0x00000000000000000000000000000000000000bb").unwrap();
+        forward_block(block, filter.as_ref(), &sender)
+            .await
+            .unwrap();
+        assert!(receiver.try_recv().is_err());
+    }
+}